@@ -1,6 +1,6 @@
 use crate::LineEnding;
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum CharCategory {
     Whitespace,
     Eol,
@@ -89,10 +89,33 @@ pub fn toggle_line_comments(doc: &Rope, selection: &Selection, token: Option<&st
     Transaction::change(doc, changes.into_iter())
 }
 
+/// If `line` is a line comment, returns the char index within the line at which its comment token
+/// starts.
+pub fn line_comment_start(line: RopeSlice, token: &str) -> Option<usize> {
+    let start = find_first_non_whitespace_char(line)?;
+    let end = std::cmp::min(start + token.chars().count(), line.len_chars());
+
+    if line.slice(start..end) == token {
+        Some(start)
+    } else {
+        None
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
+    #[test]
+    fn test_line_comment_start() {
+        let text = Rope::from("  // foo\nlet a = 1; // bar\n/\n");
+        let text = text.slice(..);
+
+        assert_eq!(line_comment_start(text.line(0), "//"), Some(2));
+        assert_eq!(line_comment_start(text.line(1), "//"), None);
+        assert_eq!(line_comment_start(text.line(2), "//"), None);
+    }
+
     #[test]
     fn test_find_line_comment() {
         use crate::State;
@@ -1,4 +1,8 @@
-#[derive(Debug, Eq, PartialEq)]
+use serde::Deserialize;
+
+/// Ordered from the most severe.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Severity {
     Error,
     Warning,
@@ -53,10 +53,147 @@ pub fn compare_ropes(old: &Rope, new: &Rope) -> Transaction {
     )
 }
 
+/// How a line of a document differs from the same document at another revision.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineDiff {
+    Added,
+    Modified,
+    /// Lines were removed before this one, or after it if it's the last line.
+    Removed,
+}
+
+/// Lines that differ between two revisions of a document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    /// The lines in the old revision.
+    pub before: std::ops::Range<usize>,
+    /// The lines in the new revision.
+    pub after: std::ops::Range<usize>,
+}
+
+impl Hunk {
+    /// The lines of `doc` the hunk is shown on: its lines, or for removed lines the line after
+    /// them, or the last line.
+    pub fn shown_lines(&self, doc: &Rope) -> std::ops::Range<usize> {
+        if !self.after.is_empty() {
+            return self.after.clone();
+        }
+
+        // the rope has an empty last line after a trailing line ending, `similar` doesn't
+        let mut last_line = doc.len_lines().saturating_sub(1);
+        if last_line > 0 && doc.line(last_line).len_chars() == 0 {
+            last_line -= 1;
+        }
+        let line = self.after.start.min(last_line);
+        line..line + 1
+    }
+
+    pub fn line_diff(&self) -> LineDiff {
+        if self.before.is_empty() {
+            LineDiff::Added
+        } else if self.after.is_empty() {
+            LineDiff::Removed
+        } else {
+            LineDiff::Modified
+        }
+    }
+}
+
+/// Compares `base` and `doc` line by line, returning the hunks of lines that differ, ordered by
+/// line.
+pub fn compare_lines(base: &Rope, doc: &Rope) -> Vec<Hunk> {
+    let base_converted = base.to_string();
+    let doc_converted = doc.to_string();
+
+    let mut config = similar::TextDiff::configure();
+    config.timeout(std::time::Duration::from_secs(1));
+
+    let diff = config.diff_lines(&base_converted, &doc_converted);
+    diff.ops()
+        .iter()
+        .map(|op| op.as_tag_tuple())
+        .filter(|(tag, _, _)| *tag != similar::DiffTag::Equal)
+        .map(|(_, before, after)| Hunk { before, after })
+        .collect()
+}
+
+/// How each line of `doc` that differs in `hunks` does, ordered by line.
+pub fn line_diffs(doc: &Rope, hunks: &[Hunk]) -> Vec<(usize, LineDiff)> {
+    let mut lines = Vec::new();
+    for hunk in hunks {
+        let diff = hunk.line_diff();
+        lines.extend(hunk.shown_lines(doc).map(|line| (line, diff)));
+    }
+    lines
+}
+
+/// Formats the changes from `old` to `new` as a unified diff, with `old_name` and `new_name` in
+/// its header.
+pub fn unified_diff(old: &Rope, new: &Rope, old_name: &str, new_name: &str) -> String {
+    let old = old.to_string();
+    let new = new.to_string();
+
+    let mut config = similar::TextDiff::configure();
+    config.timeout(std::time::Duration::from_secs(1));
+
+    config
+        .diff_lines(&old, &new)
+        .unified_diff()
+        .header(old_name, new_name)
+        .to_string()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_compare_lines() {
+        let base = Rope::from("one\ntwo\nthree\nfour\n");
+        let doc = Rope::from("one\n2\nthree\nnew\n");
+        let hunks = compare_lines(&base, &doc);
+        assert_eq!(
+            hunks,
+            vec![
+                Hunk {
+                    before: 1..2,
+                    after: 1..2
+                },
+                Hunk {
+                    before: 3..4,
+                    after: 3..4
+                }
+            ]
+        );
+        assert_eq!(
+            line_diffs(&doc, &hunks),
+            vec![(1, LineDiff::Modified), (3, LineDiff::Modified)]
+        );
+
+        let doc = Rope::from("zero\none\nthree\nfour\n");
+        let hunks = compare_lines(&base, &doc);
+        assert_eq!(
+            line_diffs(&doc, &hunks),
+            vec![(0, LineDiff::Added), (2, LineDiff::Removed)]
+        );
+
+        // removing the last lines marks the new last line
+        let doc = Rope::from("one\ntwo\n");
+        let hunks = compare_lines(&base, &doc);
+        assert_eq!(line_diffs(&doc, &hunks), vec![(1, LineDiff::Removed)]);
+    }
+
+    #[test]
+    fn test_unified_diff() {
+        let old = Rope::from("one\ntwo\nthree\n");
+        let new = Rope::from("one\n2\nthree\n");
+        assert_eq!(
+            unified_diff(&old, &new, "a", "b"),
+            "--- a\n+++ b\n@@ -1,3 +1,3 @@\n one\n-two\n+2\n three\n"
+        );
+        assert_eq!(unified_diff(&old, &old, "a", "b"), "");
+    }
+
     quickcheck::quickcheck! {
         fn test_compare_ropes(a: String, b: String) -> bool {
             let mut old = Rope::from(a);
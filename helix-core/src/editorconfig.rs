@@ -0,0 +1,270 @@
+//! [EditorConfig](https://editorconfig.org) files, setting the indentation and line endings of the
+//! files below them.
+
+use crate::LineEnding;
+use regex::Regex;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndentStyle {
+    Tab,
+    Space,
+}
+
+/// The settings of a file. Unset properties are left to the editor.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct EditorConfig {
+    pub indent_style: Option<IndentStyle>,
+    pub indent_size: Option<usize>,
+    pub tab_width: Option<usize>,
+    pub line_ending: Option<LineEnding>,
+    pub trim_trailing_whitespace: Option<bool>,
+    pub insert_final_newline: Option<bool>,
+}
+
+impl EditorConfig {
+    /// The settings of the file at `path`, from the `.editorconfig` files of its directory and
+    /// the ones above, up to one marked `root = true`. Closer files take precedence.
+    pub fn find(path: &Path) -> Self {
+        let mut files = Vec::new();
+        for dir in path.ancestors().skip(1) {
+            if let Ok(contents) = std::fs::read_to_string(dir.join(".editorconfig")) {
+                let is_root = is_root(&contents);
+                files.push((dir, contents));
+                if is_root {
+                    break;
+                }
+            }
+        }
+
+        let mut config = Self::default();
+        for (dir, contents) in files.iter().rev() {
+            if let Ok(relative) = path.strip_prefix(dir) {
+                let relative: Vec<_> = relative
+                    .components()
+                    .map(|component| component.as_os_str().to_string_lossy())
+                    .collect();
+                config.apply(contents, &relative.join("/"));
+            }
+        }
+        config
+    }
+
+    /// Applies the properties of the sections of `contents` matching `path`, relative to the
+    /// directory of the file.
+    fn apply(&mut self, contents: &str, path: &str) {
+        // properties before the first section, like `root`, don't apply to files
+        let mut matches = false;
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            if let Some(section) = line.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+                matches = matches!(glob_to_regex(section), Some(regex) if regex.is_match(path));
+                continue;
+            }
+            if !matches {
+                continue;
+            }
+
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (
+                    key.trim().to_ascii_lowercase(),
+                    value.trim().to_ascii_lowercase(),
+                ),
+                None => continue,
+            };
+            let unset = value == "unset";
+            let boolean = match value.as_str() {
+                "true" => Some(true),
+                "false" => Some(false),
+                _ => None,
+            };
+            match key.as_str() {
+                "indent_style" => {
+                    self.indent_style = match value.as_str() {
+                        "tab" => Some(IndentStyle::Tab),
+                        "space" => Some(IndentStyle::Space),
+                        _ if unset => None,
+                        _ => self.indent_style,
+                    }
+                }
+                // `indent_size = tab` means the tab width, the same as leaving it unset
+                "indent_size" if unset || value == "tab" => self.indent_size = None,
+                "indent_size" => self.indent_size = value.parse().ok().or(self.indent_size),
+                "tab_width" if unset => self.tab_width = None,
+                "tab_width" => self.tab_width = value.parse().ok().or(self.tab_width),
+                "end_of_line" => {
+                    self.line_ending = match value.as_str() {
+                        "lf" => Some(LineEnding::LF),
+                        "crlf" => Some(LineEnding::Crlf),
+                        "cr" => Some(LineEnding::CR),
+                        _ if unset => None,
+                        _ => self.line_ending,
+                    }
+                }
+                "trim_trailing_whitespace" if unset => self.trim_trailing_whitespace = None,
+                "trim_trailing_whitespace" => {
+                    self.trim_trailing_whitespace = boolean.or(self.trim_trailing_whitespace)
+                }
+                "insert_final_newline" if unset => self.insert_final_newline = None,
+                "insert_final_newline" => {
+                    self.insert_final_newline = boolean.or(self.insert_final_newline)
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Whether the preamble of `contents` has `root = true`.
+fn is_root(contents: &str) -> bool {
+    contents
+        .lines()
+        .map(str::trim)
+        .take_while(|line| !line.starts_with('['))
+        .filter_map(|line| line.split_once('='))
+        .any(|(key, value)| {
+            key.trim().eq_ignore_ascii_case("root") && value.trim().eq_ignore_ascii_case("true")
+        })
+}
+
+/// Translates an EditorConfig glob into a regex matching paths relative to the directory of the
+/// file. Globs without a `/` match file names in any directory below.
+fn glob_to_regex(glob: &str) -> Option<Regex> {
+    let mut regex = String::from("^");
+    let glob = match glob.strip_prefix('/') {
+        Some(glob) => glob,
+        None if !glob.contains('/') => {
+            regex.push_str("(?:.*/)?");
+            glob
+        }
+        None => glob,
+    };
+
+    let chars: Vec<char> = glob.chars().collect();
+    let mut i = 0;
+    let mut braces = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                regex.push_str(".*");
+                i += 1;
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            '[' => match chars[i..].iter().position(|&c| c == ']') {
+                Some(len) => {
+                    let class: String = chars[i + 1..i + len].iter().collect();
+                    match class.strip_prefix('!') {
+                        Some(class) => regex.push_str(&format!("[^{}]", class)),
+                        None => regex.push_str(&format!("[{}]", class)),
+                    }
+                    i += len;
+                }
+                None => regex.push_str(r"\["),
+            },
+            '{' => {
+                let close = chars[i..].iter().position(|&c| c == '}');
+                let range = close.and_then(|len| {
+                    let inner: String = chars[i + 1..i + len].iter().collect();
+                    let (start, end) = inner.split_once("..")?;
+                    Some((len, start.parse::<i64>().ok()?, end.parse::<i64>().ok()?))
+                });
+                match (range, close) {
+                    (Some((len, start, end)), _) => {
+                        let (start, end) = (start.min(end), start.max(end));
+                        if end - start > 1000 {
+                            // too many to list, any number will do
+                            regex.push_str(r"[+-]?\d+");
+                        } else {
+                            let numbers: Vec<String> =
+                                (start..=end).map(|n| n.to_string()).collect();
+                            regex.push_str(&format!("(?:{})", numbers.join("|")));
+                        }
+                        i += len;
+                    }
+                    (None, Some(_)) => {
+                        braces += 1;
+                        regex.push_str("(?:");
+                    }
+                    (None, None) => regex.push_str(r"\{"),
+                }
+            }
+            '}' if braces > 0 => {
+                braces -= 1;
+                regex.push(')');
+            }
+            ',' if braces > 0 => regex.push('|'),
+            '\\' if i + 1 < chars.len() => {
+                i += 1;
+                regex.push_str(&regex::escape(&chars[i].to_string()));
+            }
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+        i += 1;
+    }
+    regex.push('$');
+    Regex::new(&regex).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn matches(glob: &str, path: &str) -> bool {
+        glob_to_regex(glob).unwrap().is_match(path)
+    }
+
+    #[test]
+    fn test_globs() {
+        assert!(matches("*", "src/main.rs"));
+        assert!(matches("*.rs", "src/main.rs"));
+        assert!(!matches("*.rs", "src/main.rsx"));
+        assert!(matches("*.{js,ts}", "index.ts"));
+        assert!(matches("src/*.rs", "src/main.rs"));
+        assert!(!matches("src/*.rs", "src/bin/main.rs"));
+        assert!(matches("src/**.rs", "src/bin/main.rs"));
+        assert!(matches("/Makefile", "Makefile"));
+        assert!(!matches("/Makefile", "docs/Makefile"));
+        assert!(matches("file{1..3}.txt", "file2.txt"));
+        assert!(!matches("file{1..3}.txt", "file4.txt"));
+        assert!(matches("[!a]?.c", "bc.c"));
+    }
+
+    #[test]
+    fn test_apply() {
+        let contents = "root = true\n\
+            \n\
+            [*]\n\
+            indent_style = space\n\
+            indent_size = 4\n\
+            end_of_line = lf\n\
+            \n\
+            # comment\n\
+            [Makefile]\n\
+            indent_style = tab\n\
+            indent_size = unset\n\
+            \n\
+            [*.md]\n\
+            trim_trailing_whitespace = false\n";
+        assert!(is_root(contents));
+
+        let mut config = EditorConfig::default();
+        config.apply(contents, "src/Makefile");
+        assert_eq!(
+            config,
+            EditorConfig {
+                indent_style: Some(IndentStyle::Tab),
+                line_ending: Some(LineEnding::LF),
+                ..Default::default()
+            }
+        );
+
+        let mut config = EditorConfig::default();
+        config.apply(contents, "README.md");
+        assert_eq!(config.indent_size, Some(4));
+        assert_eq!(config.trim_trailing_whitespace, Some(false));
+    }
+}
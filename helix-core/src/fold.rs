@@ -0,0 +1,202 @@
+//! Folds: ranges of lines of which only the first one is shown. The ranges that can be folded come
+//! from the syntax tree, or from the indentation when there is none.
+
+use crate::{chars::char_is_line_ending, movement::Direction, RopeSlice, Syntax};
+use std::cmp::Reverse;
+use std::ops::{Range, RangeInclusive};
+
+/// Lines `start..=end`, of which the lines after `start` are hidden when folded.
+pub type Fold = RangeInclusive<usize>;
+
+/// The ranges of lines that can be folded, sorted by their first line. Of the ranges starting on
+/// the same line, only the largest is kept.
+pub fn fold_ranges(syntax: Option<&Syntax>, text: RopeSlice, tab_width: usize) -> Vec<Fold> {
+    let mut ranges = match syntax {
+        Some(syntax) => syntax_ranges(syntax, text),
+        None => indent_ranges(text, tab_width),
+    };
+    ranges.sort_by_key(|range| (*range.start(), Reverse(*range.end())));
+    ranges.dedup_by_key(|range| *range.start());
+    ranges
+}
+
+/// The named nodes spanning several lines, except the root.
+fn syntax_ranges(syntax: &Syntax, text: RopeSlice) -> Vec<Fold> {
+    let mut ranges = Vec::new();
+    let mut cursor = syntax.tree().walk();
+    'walk: loop {
+        let node = cursor.node();
+        if node.is_named() && node.parent().is_some() && node.end_byte() > node.start_byte() {
+            let start = text.byte_to_line(node.start_byte());
+            let end = text.byte_to_line(node.end_byte() - 1);
+            if end > start {
+                ranges.push(start..=end);
+            }
+        }
+
+        if cursor.goto_first_child() {
+            continue;
+        }
+        while !cursor.goto_next_sibling() {
+            if !cursor.goto_parent() {
+                break 'walk;
+            }
+        }
+    }
+    ranges
+}
+
+/// The lines followed by more indented ones, up to the last of those. Blank lines don't end a
+/// range, but aren't included at its end.
+fn indent_ranges(text: RopeSlice, tab_width: usize) -> Vec<Fold> {
+    let indents: Vec<Option<usize>> = text
+        .lines()
+        .map(|line| {
+            let mut indent = 0;
+            for ch in line.chars() {
+                match ch {
+                    ' ' => indent += 1,
+                    '\t' => indent += tab_width - indent % tab_width,
+                    ch if char_is_line_ending(ch) => return None,
+                    _ => return Some(indent),
+                }
+            }
+            None
+        })
+        .collect();
+
+    let mut ranges = Vec::new();
+    for (start, indent) in indents.iter().enumerate() {
+        let indent = match indent {
+            Some(indent) => *indent,
+            None => continue,
+        };
+        let mut end = start;
+        for (line, other) in indents.iter().enumerate().skip(start + 1) {
+            match other {
+                Some(other) if *other <= indent => break,
+                Some(_) => end = line,
+                None => {}
+            }
+        }
+        if end > start {
+            ranges.push(start..=end);
+        }
+    }
+    ranges
+}
+
+/// The smallest of `ranges` containing `line`.
+pub fn innermost(ranges: &[Fold], line: usize) -> Option<&Fold> {
+    ranges
+        .iter()
+        .filter(|range| range.contains(&line))
+        .min_by_key(|range| range.end() - range.start())
+}
+
+/// Whether `line` is hidden by one of the `folds`.
+pub fn is_hidden(folds: &[Fold], line: usize) -> bool {
+    folds
+        .iter()
+        .any(|fold| *fold.start() < line && line <= *fold.end())
+}
+
+/// The runs of consecutive lines from `first` to `last` that the `folds` don't hide, in order.
+pub fn shown_lines(folds: &[Fold], first: usize, last: usize) -> Vec<Range<usize>> {
+    let mut hidden: Vec<_> = folds
+        .iter()
+        .map(|fold| *fold.start() + 1..*fold.end() + 1)
+        .collect();
+    hidden.sort_unstable_by_key(|range| range.start);
+
+    let mut runs = Vec::new();
+    let mut line = first;
+    for range in hidden {
+        if range.start > last {
+            break;
+        }
+        if range.start > line {
+            runs.push(line..range.start);
+        }
+        line = line.max(range.end);
+    }
+    if line <= last {
+        runs.push(line..last + 1);
+    }
+    runs
+}
+
+/// The closest line to `line` in the direction `dir` that isn't hidden by the `folds`, which is
+/// `line` itself if it's shown. `None` if the folds hide everything up to the end of the text.
+pub fn visible_line(
+    folds: &[Fold],
+    mut line: usize,
+    dir: Direction,
+    last_line: usize,
+) -> Option<usize> {
+    // folds can hide the first line of others, so this may take several steps
+    for _ in 0..=folds.len() {
+        let hiding = folds
+            .iter()
+            .filter(|fold| *fold.start() < line && line <= *fold.end());
+        line = match dir {
+            Direction::Forward => match hiding.map(|fold| *fold.end() + 1).max() {
+                Some(next) if next > last_line => return None,
+                Some(next) => next,
+                None => return Some(line),
+            },
+            Direction::Backward => match hiding.map(|fold| *fold.start()).min() {
+                Some(prev) => prev,
+                None => return Some(line),
+            },
+        };
+    }
+    Some(line)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Rope;
+
+    #[test]
+    fn test_indent_ranges() {
+        let text = Rope::from("def f():\n    if x:\n        y\n\n    z\n\nw\n");
+        assert_eq!(fold_ranges(None, text.slice(..), 4), vec![0..=4, 1..=2]);
+    }
+
+    #[test]
+    fn test_innermost() {
+        let ranges = vec![0..=4, 1..=2];
+        assert_eq!(innermost(&ranges, 2), Some(&(1..=2)));
+        assert_eq!(innermost(&ranges, 3), Some(&(0..=4)));
+        assert_eq!(innermost(&ranges, 5), None);
+    }
+
+    #[test]
+    fn test_shown_lines() {
+        assert_eq!(shown_lines(&[], 3, 9), vec![3..10]);
+        // nested and overlapping folds are merged
+        let folds = vec![6..=9, 2..=4, 3..=5, 12..=20];
+        assert_eq!(shown_lines(&folds, 0, 14), vec![0..3, 6..7, 10..13]);
+        // folds hiding the start
+        assert_eq!(shown_lines(&folds, 4, 8), vec![6..7]);
+        assert_eq!(shown_lines(&folds, 7, 8), vec![]);
+    }
+
+    #[test]
+    fn test_visible_line() {
+        let folds = vec![2..=4, 6..=9];
+        assert!(is_hidden(&folds, 3));
+        assert!(!is_hidden(&folds, 2));
+        assert_eq!(visible_line(&folds, 1, Direction::Forward, 9), Some(1));
+        assert_eq!(visible_line(&folds, 3, Direction::Forward, 9), Some(5));
+        assert_eq!(visible_line(&folds, 3, Direction::Backward, 9), Some(2));
+        // the last fold hides the end of the text
+        assert_eq!(visible_line(&folds, 7, Direction::Forward, 9), None);
+        // nested folds
+        let folds = vec![0..=5, 2..=4];
+        assert_eq!(visible_line(&folds, 3, Direction::Backward, 9), Some(0));
+        assert_eq!(visible_line(&folds, 3, Direction::Forward, 9), Some(6));
+    }
+}
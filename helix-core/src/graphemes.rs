@@ -29,6 +29,18 @@ pub fn grapheme_width(g: &str) -> usize {
     }
 }
 
+/// The display width of `g` drawn at column `col`. Tabs reach up to the next tab stop, every
+/// `tab_width` columns.
+#[must_use]
+pub fn grapheme_width_at(g: &str, col: usize, tab_width: usize) -> usize {
+    if g == "\t" {
+        let tab_width = tab_width.max(1);
+        tab_width - col % tab_width
+    } else {
+        grapheme_width(g)
+    }
+}
+
 #[must_use]
 pub fn nth_prev_grapheme_boundary(slice: RopeSlice, char_idx: usize, n: usize) -> usize {
     // Bounds check
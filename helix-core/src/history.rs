@@ -1,4 +1,4 @@
-use crate::{ChangeSet, Rope, State, Transaction};
+use crate::{Annotation, ChangeSet, Operation, Rope, State, Transaction};
 use once_cell::sync::Lazy;
 use regex::Regex;
 use std::num::NonZeroUsize;
@@ -25,6 +25,11 @@ use std::time::{Duration, Instant};
 // Commiting a new revision to the history will update the last child of the
 // current revision, and push a new revision to the end of the vector.
 //
+// Transactions annotated as compatible (see [Annotation::groups_with]) are
+// composed into the current revision instead of committing a new one, as long
+// as the current revision is the latest and the history wasn't sealed since,
+// so that for example typing a word is a single undo step.
+//
 // Revisions are commited with a timestamp. :earlier and :later can be used
 // to jump to the closest revision to a moment in time relative to the timestamp
 // of the current revision plus (:later) or minus (:earlier) the duration
@@ -43,6 +48,9 @@ use std::time::{Duration, Instant};
 pub struct History {
     revisions: Vec<Revision>,
     current: usize,
+    /// Whether the next revision starts a new undo step, even if it's annotated like the
+    /// current one.
+    sealed: bool,
 }
 
 // A single point in history. See [History] for more information.
@@ -69,6 +77,7 @@ impl Default for History {
                 timestamp: Instant::now(),
             }],
             current: 0,
+            sealed: true,
         }
     }
 }
@@ -89,6 +98,37 @@ impl History {
             // Store the current cursor position
             .with_selection(original.selection.clone());
 
+        if self.groups_with_current(transaction.annotation()) {
+            let revision = &mut self.revisions[self.current];
+            let mut grouped = Transaction::from(
+                revision
+                    .transaction
+                    .changes()
+                    .clone()
+                    .compose(transaction.changes().clone()),
+            );
+            if let Some(selection) = transaction.selection() {
+                grouped = grouped.with_selection(selection.clone());
+            }
+            if let Some(annotation) = transaction.annotation() {
+                grouped = grouped.with_annotation(annotation);
+            }
+            // undo the new changes first, then the ones before
+            let mut grouped_inversion = Transaction::from(
+                inversion
+                    .changes()
+                    .clone()
+                    .compose(revision.inversion.changes().clone()),
+            );
+            if let Some(selection) = revision.inversion.selection() {
+                grouped_inversion = grouped_inversion.with_selection(selection.clone());
+            }
+            revision.transaction = grouped;
+            revision.inversion = grouped_inversion;
+            revision.timestamp = timestamp;
+            return;
+        }
+
         let new_current = self.revisions.len();
         self.revisions[self.current].last_child = NonZeroUsize::new(new_current);
         self.revisions.push(Revision {
@@ -99,6 +139,24 @@ impl History {
             timestamp,
         });
         self.current = new_current;
+        self.sealed = false;
+    }
+
+    /// Whether a transaction annotated `annotation` is composed into the current revision.
+    fn groups_with_current(&self, annotation: Option<Annotation>) -> bool {
+        let current = &self.revisions[self.current];
+        !self.sealed
+            && !self.at_root()
+            && current.last_child.is_none()
+            && matches!(
+                (current.transaction.annotation(), annotation),
+                (Some(previous), Some(annotation)) if annotation.groups_with(previous)
+            )
+    }
+
+    /// Ends the current undo step: the next revision is committed on its own.
+    pub fn seal(&mut self) {
+        self.sealed = true;
     }
 
     #[inline]
@@ -111,6 +169,28 @@ impl History {
         self.current == 0
     }
 
+    /// Returns the position right after the last change of the current revision, or `None` at
+    /// the root.
+    pub fn last_edit_pos(&self) -> Option<usize> {
+        if self.at_root() {
+            return None;
+        }
+
+        let mut pos = 0;
+        let mut last_edit = None;
+        for operation in self.revisions[self.current].transaction.changes().changes() {
+            match operation {
+                Operation::Retain(n) => pos += n,
+                Operation::Delete(_) => last_edit = Some(pos),
+                Operation::Insert(s) => {
+                    pos += s.chars().count();
+                    last_edit = Some(pos);
+                }
+            }
+        }
+        last_edit
+    }
+
     pub fn undo(&mut self) -> Option<&Transaction> {
         if self.at_root() {
             return None;
@@ -118,6 +198,7 @@ impl History {
 
         let current_revision = &self.revisions[self.current];
         self.current = current_revision.parent;
+        self.sealed = true;
         Some(&current_revision.inversion)
     }
 
@@ -125,6 +206,7 @@ impl History {
         let current_revision = &self.revisions[self.current];
         let last_child = current_revision.last_child?;
         self.current = last_child.get();
+        self.sealed = true;
 
         Some(&self.revisions[last_child.get()].transaction)
     }
@@ -163,6 +245,7 @@ impl History {
         let up = self.path_up(self.current, lca);
         let down = self.path_up(to, lca);
         self.current = to;
+        self.sealed = true;
         let up_txns = up.iter().map(|&n| self.revisions[n].inversion.clone());
         let down_txns = down
             .iter()
@@ -365,6 +448,77 @@ mod test {
         assert_eq!("hello", state.doc);
     }
 
+    #[test]
+    fn test_grouping() {
+        let mut history = History::default();
+        let mut state = State::new(Rope::from("hi"));
+
+        fn commit(history: &mut History, state: &mut State, text: &str, annotation: Annotation) {
+            let pos = state.doc.len_chars();
+            let transaction =
+                Transaction::change(&state.doc, vec![(pos, pos, Some(text.into()))].into_iter())
+                    .with_annotation(annotation);
+            history.commit_revision(&transaction, state);
+            transaction.apply(&mut state.doc);
+        }
+
+        fn undo(history: &mut History, state: &mut State) {
+            if let Some(transaction) = history.undo() {
+                transaction.apply(&mut state.doc);
+            }
+        }
+
+        // typing is one undo step
+        commit(&mut history, &mut state, " t", Annotation::InsertChar);
+        commit(&mut history, &mut state, "here", Annotation::InsertChar);
+        assert_eq!(history.current_revision(), 1);
+        // pasting isn't grouped, even with another paste
+        commit(&mut history, &mut state, "!", Annotation::Paste);
+        commit(&mut history, &mut state, "!", Annotation::Paste);
+        assert_eq!(history.current_revision(), 3);
+        // nor after sealing
+        history.seal();
+        commit(&mut history, &mut state, " you", Annotation::InsertChar);
+        commit(&mut history, &mut state, "!", Annotation::InsertChar);
+        assert_eq!("hi there!! you!", state.doc);
+        assert_eq!(history.current_revision(), 4);
+
+        undo(&mut history, &mut state);
+        assert_eq!("hi there!!", state.doc);
+        undo(&mut history, &mut state);
+        undo(&mut history, &mut state);
+        assert_eq!("hi there", state.doc);
+        undo(&mut history, &mut state);
+        assert_eq!("hi", state.doc);
+
+        // undoing seals: the redone revision isn't extended
+        if let Some(transaction) = history.redo() {
+            transaction.apply(&mut state.doc);
+        }
+        commit(&mut history, &mut state, "!", Annotation::InsertChar);
+        assert_eq!(history.current_revision(), 5);
+    }
+
+    #[test]
+    fn test_last_edit_pos() {
+        let mut history = History::default();
+        let mut state = State::new(Rope::from("hello world"));
+        assert_eq!(history.last_edit_pos(), None);
+
+        let transaction = Transaction::change(
+            &state.doc,
+            vec![(0, 0, Some("> ".into())), (6, 11, Some("all".into()))].into_iter(),
+        );
+        history.commit_revision(&transaction, &state);
+        transaction.apply(&mut state.doc);
+        assert_eq!("> hello all", state.doc);
+        assert_eq!(history.last_edit_pos(), Some(11));
+
+        let transaction = Transaction::change(&state.doc, vec![(2, 8, None)].into_iter());
+        history.commit_revision(&transaction, &state);
+        assert_eq!(history.last_edit_pos(), Some(2));
+    }
+
     #[test]
     fn test_earlier_later() {
         let mut history = History::default();
@@ -96,8 +96,9 @@ fn calculate_indentation(query: &IndentQuery, node: Option<Node>, newline: bool)
     increment.max(0) as usize
 }
 
-#[allow(dead_code)]
-fn suggested_indent_for_line(
+/// Indent level the line `line_num` should have according to the syntax tree. Blank lines get no
+/// indentation.
+pub fn suggested_indent_for_line(
     language_config: &LanguageConfiguration,
     syntax: Option<&Syntax>,
     text: RopeSlice,
@@ -266,11 +267,18 @@ where
                 comment_token: None,
                 auto_format: false,
                 language_server: None,
+                debugger: None,
+                formatter: None,
                 indent: Some(IndentationConfiguration {
                     tab_width: 4,
                     unit: String::from("    "),
+                    enforce: false,
                 }),
+                rulers: None,
+                trim_trailing_whitespace: None,
+                text_width: None,
                 indent_query: OnceCell::new(),
+                textobject_query: OnceCell::new(),
             }],
         });
 
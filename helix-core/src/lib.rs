@@ -3,6 +3,8 @@ pub mod chars;
 pub mod comment;
 pub mod diagnostic;
 pub mod diff;
+pub mod editorconfig;
+pub mod fold;
 pub mod graphemes;
 pub mod history;
 pub mod indent;
@@ -12,14 +14,19 @@ pub mod match_brackets;
 pub mod movement;
 pub mod object;
 mod position;
+pub mod reflow;
 pub mod register;
 pub mod search;
 pub mod selection;
+pub mod snippet;
+pub mod spelling;
 mod state;
 pub mod surround;
 pub mod syntax;
+pub mod tags;
 pub mod textobject;
 mod transaction;
+pub mod wrap;
 
 pub mod unicode {
     pub use unicode_general_category as category;
@@ -34,28 +41,23 @@ pub fn find_first_non_whitespace_char(line: RopeSlice) -> Option<usize> {
     line.chars().position(|ch| !ch.is_whitespace())
 }
 
-pub fn find_root(root: Option<&str>) -> Option<std::path::PathBuf> {
+/// The root of the project containing `root`, or the working directory when `None`: the closest
+/// ancestor containing the first of the `markers`, like `.git` or `Cargo.toml`, found in any of
+/// them. `None` outside of any project.
+pub fn find_root(root: Option<&std::path::Path>, markers: &[String]) -> Option<std::path::PathBuf> {
     let current_dir = std::env::current_dir().expect("unable to determine current directory");
 
     let root = match root {
-        Some(root) => {
-            let root = std::path::Path::new(root);
-            if root.is_absolute() {
-                root.to_path_buf()
-            } else {
-                current_dir.join(root)
-            }
-        }
+        Some(root) if root.is_absolute() => root.to_path_buf(),
+        Some(root) => current_dir.join(root),
         None => current_dir,
     };
 
-    for ancestor in root.ancestors() {
-        // TODO: also use defined roots if git isn't found
-        if ancestor.join(".git").is_dir() {
-            return Some(ancestor.to_path_buf());
-        }
-    }
-    None
+    markers.iter().find_map(|marker| {
+        root.ancestors()
+            .find(|ancestor| ancestor.join(marker).exists())
+            .map(|ancestor| ancestor.to_path_buf())
+    })
 }
 
 #[cfg(not(embed_runtime))]
@@ -98,6 +100,25 @@ pub fn cache_dir() -> std::path::PathBuf {
     path
 }
 
+/// The file the editor logs to.
+pub fn log_file() -> std::path::PathBuf {
+    cache_dir().join("helix.log")
+}
+
+/// The tutorial opened by `hx --tutor` and `:tutor`.
+pub fn tutor_file() -> std::path::PathBuf {
+    runtime_dir().join("tutor.txt")
+}
+
+/// Directory for state that persists across sessions, like the prompt history.
+pub fn state_dir() -> std::path::PathBuf {
+    // TODO: allow env var override
+    let strategy = choose_base_strategy().expect("Unable to find the data directory!");
+    let mut path = strategy.data_dir();
+    path.push("helix");
+    path
+}
+
 pub use etcetera::home_dir;
 
 use etcetera::base_strategy::{choose_base_strategy, BaseStrategy};
@@ -110,7 +131,7 @@ pub use tendril::StrTendril as Tendril;
 pub use {regex, tree_sitter};
 
 pub use graphemes::RopeGraphemes;
-pub use position::{coords_at_pos, pos_at_coords, Position};
+pub use position::{coords_at_pos, pos_at_coords, visual_coords_at_pos, Position};
 pub use selection::{Range, Selection};
 pub use smallvec::SmallVec;
 pub use syntax::Syntax;
@@ -119,4 +140,48 @@ pub use diagnostic::Diagnostic;
 pub use state::State;
 
 pub use line_ending::{LineEnding, DEFAULT_LINE_ENDING};
-pub use transaction::{Assoc, Change, ChangeSet, Operation, Transaction};
+pub use transaction::{Annotation, Assoc, Change, ChangeSet, Operation, Transaction};
+
+#[cfg(test)]
+mod test {
+    use super::find_root;
+    use std::fs;
+
+    #[test]
+    fn find_root_by_marker_priority() {
+        let dir = std::env::temp_dir().join(format!("helix-core-test-{}", std::process::id()));
+        let project = dir.join("project");
+        let crate_dir = project.join("crate");
+        let src = crate_dir.join("src");
+        fs::create_dir_all(project.join(".helix-marker")).unwrap();
+        fs::create_dir_all(&src).unwrap();
+        fs::write(crate_dir.join("Marker.toml"), "").unwrap();
+        let markers = |markers: &[&str]| -> Vec<String> {
+            markers.iter().map(|marker| marker.to_string()).collect()
+        };
+
+        // the first marker found wins over a closer one found later
+        assert_eq!(
+            find_root(Some(&src), &markers(&[".helix-marker", "Marker.toml"])),
+            Some(project.clone())
+        );
+        assert_eq!(
+            find_root(Some(&src), &markers(&["Marker.toml", ".helix-marker"])),
+            Some(crate_dir.clone())
+        );
+        // markers are looked up in the directory itself too
+        assert_eq!(
+            find_root(Some(&crate_dir), &markers(&["Marker.toml"])),
+            Some(crate_dir)
+        );
+        assert_eq!(find_root(Some(&src), &markers(&["not-a-marker"])), None);
+        assert_eq!(find_root(Some(&src), &[]), None);
+        // without a directory, the working directory is used
+        assert_eq!(
+            find_root(None, &markers(&["Cargo.toml"])),
+            Some(std::env::current_dir().unwrap())
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
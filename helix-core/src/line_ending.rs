@@ -1,4 +1,4 @@
-use crate::{Rope, RopeSlice};
+use crate::{graphemes::prev_grapheme_boundary, Rope, RopeSlice};
 
 #[cfg(target_os = "windows")]
 pub const DEFAULT_LINE_ENDING: LineEnding = LineEnding::Crlf;
@@ -159,6 +159,25 @@ pub fn line_end_char_index(slice: &RopeSlice, line: usize) -> usize {
             .unwrap_or(0)
 }
 
+/// Returns the char index of the start of the last grapheme of the given line, not counting its
+/// line ending. This is the start of the line for empty lines.
+pub fn line_end_grapheme_index(slice: &RopeSlice, line: usize) -> usize {
+    let start = slice.line_to_char(line);
+    let end = line_end_char_index(slice, line);
+    prev_grapheme_boundary(*slice, end).max(start)
+}
+
+/// Returns the index of the last line of the given RopeSlice, not counting the empty line after
+/// a final line ending.
+pub fn last_line_index(slice: &RopeSlice) -> usize {
+    let last = slice.len_lines() - 1;
+    if last > 0 && slice.line(last).len_chars() == 0 {
+        last - 1
+    } else {
+        last
+    }
+}
+
 /// Returns the char index of the end of the given RopeSlice, not including
 /// any final line ending.
 pub fn rope_end_without_line_ending(slice: &RopeSlice) -> usize {
@@ -255,4 +274,26 @@ mod line_ending_tests {
         assert_eq!(line_end_char_index(s, 2), 15);
         assert_eq!(line_end_char_index(s, 3), 25);
     }
+
+    #[test]
+    fn line_end_grapheme_index_rope_slice() {
+        let r = Rope::from_str("Hello\r\nwe\u{301}\n\r\nlast e\u{301}");
+        let s = &r.slice(..);
+        assert_eq!(line_end_grapheme_index(s, 0), 4);
+        // the combining accent belongs to the last grapheme
+        assert_eq!(line_end_grapheme_index(s, 1), 8);
+        // empty lines
+        assert_eq!(line_end_grapheme_index(s, 2), 11);
+        // no line ending
+        assert_eq!(line_end_grapheme_index(s, 3), 18);
+    }
+
+    #[test]
+    fn last_line_index_rope_slice() {
+        assert_eq!(last_line_index(&Rope::from_str("").slice(..)), 0);
+        assert_eq!(last_line_index(&Rope::from_str("a").slice(..)), 0);
+        assert_eq!(last_line_index(&Rope::from_str("a\r\n").slice(..)), 0);
+        assert_eq!(last_line_index(&Rope::from_str("a\nb").slice(..)), 1);
+        assert_eq!(last_line_index(&Rope::from_str("a\n\n").slice(..)), 1);
+    }
 }
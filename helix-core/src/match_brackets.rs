@@ -3,6 +3,13 @@ use crate::{Rope, Syntax};
 const PAIRS: &[(char, char)] = &[('(', ')'), ('{', '}'), ('[', ']'), ('<', '>')];
 // limit matching pairs to only ( ) { } [ ] < >
 
+/// The pairs matched without a syntax tree, where `<` and `>` are too often comparisons.
+const SCAN_PAIRS: &[(char, char)] = &[('(', ')'), ('{', '}'), ('[', ']')];
+
+/// How many chars are scanned for a match without a syntax tree, so a stray bracket in a large
+/// document stays cheap.
+const MAX_SCAN: usize = 10_000;
+
 #[must_use]
 pub fn find(syntax: &Syntax, doc: &Rope, pos: usize) -> Option<usize> {
     let tree = syntax.tree();
@@ -45,3 +52,110 @@ pub fn find(syntax: &Syntax, doc: &Rope, pos: usize) -> Option<usize> {
 
     None
 }
+
+/// Finds the bracket matching the one at `pos` by counting the brackets in between, for documents
+/// without a syntax tree.
+#[must_use]
+pub fn find_by_scan(doc: &Rope, pos: usize) -> Option<usize> {
+    let ch = doc.get_char(pos)?;
+    let (open, close, forward) = SCAN_PAIRS.iter().find_map(|&(open, close)| {
+        if ch == open {
+            Some((open, close, true))
+        } else if ch == close {
+            Some((open, close, false))
+        } else {
+            None
+        }
+    })?;
+
+    let mut depth = 0usize;
+    if forward {
+        for (i, c) in doc.chars_at(pos + 1).take(MAX_SCAN).enumerate() {
+            if c == open {
+                depth += 1;
+            } else if c == close {
+                if depth == 0 {
+                    return Some(pos + 1 + i);
+                }
+                depth -= 1;
+            }
+        }
+    } else {
+        let mut chars = doc.chars_at(pos);
+        for i in 0..MAX_SCAN.min(pos) {
+            let c = chars.prev()?;
+            if c == close {
+                depth += 1;
+            } else if c == open {
+                if depth == 0 {
+                    return Some(pos - 1 - i);
+                }
+                depth -= 1;
+            }
+        }
+    }
+    None
+}
+
+/// The bracket matching the one at `pos`, from the syntax tree if there is one. Brackets the tree
+/// doesn't pair, like the ones in comments and strings, are matched by scanning.
+#[must_use]
+pub fn find_matching(syntax: Option<&Syntax>, doc: &Rope, pos: usize) -> Option<usize> {
+    syntax
+        .and_then(|syntax| find(syntax, doc, pos))
+        .or_else(|| find_by_scan(doc, pos))
+}
+
+/// The bracket matching the one at the cursor `pos`, or else the one just before it, like a
+/// closing bracket just typed.
+#[must_use]
+pub fn find_highlight(syntax: Option<&Syntax>, doc: &Rope, pos: usize) -> Option<usize> {
+    let find = |pos| find_matching(syntax, doc, pos);
+    find(pos).or_else(|| pos.checked_sub(1).and_then(find))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_find_by_scan() {
+        let doc = Rope::from("fn f(a: [u8; 2]) { g(a[0]) }");
+        assert_eq!(find_by_scan(&doc, 4), Some(15));
+        assert_eq!(find_by_scan(&doc, 15), Some(4));
+        assert_eq!(find_by_scan(&doc, 17), Some(27));
+        assert_eq!(find_by_scan(&doc, 27), Some(17));
+        assert_eq!(find_by_scan(&doc, 0), None);
+        // unbalanced
+        assert_eq!(find_by_scan(&Rope::from("(a"), 0), None);
+    }
+
+    #[test]
+    fn test_find_matching_outside_of_the_tree() {
+        use crate::syntax::{get_language, HighlightConfiguration};
+        use std::sync::Arc;
+
+        let language = get_language(&crate::RUNTIME_DIR, "Rust").unwrap();
+        let config = HighlightConfiguration::new(language, "", "", "").unwrap();
+        let doc = Rope::from("fn f(a: u8) { g(\"[b]\") } // (c)");
+        let syntax = Syntax::new(&doc, Arc::new(config));
+
+        // pairs of the tree
+        assert_eq!(find_matching(Some(&syntax), &doc, 4), Some(10));
+        assert_eq!(find_matching(Some(&syntax), &doc, 23), Some(12));
+        // in strings and comments
+        assert_eq!(find_matching(Some(&syntax), &doc, 17), Some(19));
+        assert_eq!(find(&syntax, &doc, 28), None);
+        assert_eq!(find_matching(Some(&syntax), &doc, 28), Some(30));
+        assert_eq!(find_matching(Some(&syntax), &doc, 30), Some(28));
+    }
+
+    #[test]
+    fn test_find_highlight_adjacent() {
+        let doc = Rope::from("(a) b");
+        assert_eq!(find_highlight(None, &doc, 0), Some(2));
+        // right after the closing bracket
+        assert_eq!(find_highlight(None, &doc, 3), Some(0));
+        assert_eq!(find_highlight(None, &doc, 4), None);
+    }
+}
@@ -6,8 +6,10 @@ use crate::{
     chars::{categorize_char, char_is_line_ending, CharCategory},
     coords_at_pos,
     graphemes::{nth_next_grapheme_boundary, nth_prev_grapheme_boundary},
-    line_ending::{get_line_ending, line_end_char_index},
-    pos_at_coords, Position, Range, RopeSlice,
+    line_ending::{get_line_ending, last_line_index, line_end_char_index},
+    pos_at_coords,
+    wrap::{line_breaks, pos_at_visual_coords, visual_coords_at_pos, SoftWrap},
+    Position, Range, RopeSlice,
 };
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -63,10 +65,7 @@ pub fn move_vertically(
 
     let new_line = match dir {
         Direction::Backward => row.saturating_sub(count),
-        Direction::Forward => std::cmp::min(
-            row.saturating_add(count),
-            slice.len_lines().saturating_sub(2),
-        ),
+        Direction::Forward => std::cmp::min(row.saturating_add(count), last_line_index(&slice)),
     };
 
     // Length of the line sans line-ending.
@@ -89,6 +88,69 @@ pub fn move_vertically(
     range
 }
 
+/// Like [`move_vertically`], but moves across the rows of soft wrapped lines as they are
+/// displayed in a view `width` columns wide.
+#[allow(clippy::too_many_arguments)]
+pub fn move_vertically_visual(
+    slice: RopeSlice,
+    range: Range,
+    dir: Direction,
+    count: usize,
+    behaviour: Movement,
+    width: usize,
+    tab_width: usize,
+    soft_wrap: SoftWrap,
+) -> Range {
+    let breaks_of = |line| line_breaks(slice.line(line), width, tab_width, soft_wrap);
+
+    let mut line = slice.char_to_line(range.head);
+    let mut breaks = breaks_of(line);
+    let Position { mut row, col } = visual_coords_at_pos(
+        slice.line(line),
+        &breaks,
+        range.head - slice.line_to_char(line),
+        tab_width,
+    );
+
+    let horiz = range.horiz.unwrap_or(col as u32);
+    let last_line = last_line_index(&slice);
+
+    for _ in 0..count {
+        match dir {
+            Direction::Forward if row + 1 < breaks.len() => row += 1,
+            Direction::Forward if line < last_line => {
+                line += 1;
+                breaks = breaks_of(line);
+                row = 0;
+            }
+            Direction::Backward if row > 0 => row -= 1,
+            Direction::Backward if line > 0 => {
+                line -= 1;
+                breaks = breaks_of(line);
+                row = breaks.len() - 1;
+            }
+            _ => break,
+        }
+    }
+
+    let pos = slice.line_to_char(line)
+        + pos_at_visual_coords(
+            slice.line(line),
+            &breaks,
+            Position::new(row, horiz as usize),
+            tab_width,
+        );
+
+    let anchor = match behaviour {
+        Movement::Extend => range.anchor,
+        Movement::Move => pos,
+    };
+
+    let mut range = Range::new(anchor, pos);
+    range.horiz = Some(horiz);
+    range
+}
+
 pub fn move_next_word_start(slice: RopeSlice, range: Range, count: usize) -> Range {
     word_move(slice, range, count, WordMotionTarget::NextWordStart)
 }
@@ -329,6 +391,52 @@ mod test {
         );
     }
 
+    #[test]
+    fn vertical_moves_reach_the_last_line() {
+        // without a final line ending
+        let text = Rope::from("abcd\r\nefg\r\nwrs");
+        let slice = text.slice(..);
+        let range = Range::point(pos_at_coords(slice, (0, 1).into()));
+        let range = move_vertically(slice, range, Direction::Forward, 5, Movement::Move);
+        assert_eq!(coords_at_pos(slice, range.head), (2, 1).into());
+
+        // the empty line after a final line ending isn't one
+        let text = Rope::from("abcd\nefg\n");
+        let slice = text.slice(..);
+        let range = Range::point(pos_at_coords(slice, (0, 1).into()));
+        let range = move_vertically(slice, range, Direction::Forward, 5, Movement::Move);
+        assert_eq!(coords_at_pos(slice, range.head), (1, 1).into());
+    }
+
+    #[test]
+    fn vertical_moves_through_wrapped_rows() {
+        let text = Rope::from("hello world foo\nbar\n");
+        let slice = text.slice(..);
+        let mut range = Range::point(pos_at_coords(slice, (0, 1).into()));
+
+        let moves_and_expected_coordinates = [
+            // "hello " | "world " | "foo"
+            ((Direction::Forward, 1usize), (0, 7)),
+            ((Direction::Forward, 1usize), (0, 13)),
+            ((Direction::Forward, 1usize), (1, 1)),
+            ((Direction::Backward, 2usize), (0, 7)),
+        ];
+
+        for &((direction, amount), coordinates) in moves_and_expected_coordinates.iter() {
+            range = move_vertically_visual(
+                slice,
+                range,
+                direction,
+                amount,
+                Movement::Move,
+                8,
+                4,
+                SoftWrap::Word,
+            );
+            assert_eq!(coords_at_pos(slice, range.head), coordinates.into());
+        }
+    }
+
     #[test]
     fn horizontal_moves_through_single_line_in_single_line_text() {
         let text = Rope::from(SINGLE_LINE_SAMPLE);
@@ -1,31 +1,108 @@
 use crate::{Range, RopeSlice, Selection, Syntax};
+use tree_sitter::Node;
+
+/// Byte range covered by the inclusive `range`.
+fn byte_range(text: RopeSlice, range: Range) -> (usize, usize) {
+    let from = text.char_to_byte(range.from());
+    let to = text.char_to_byte((range.to() + 1).min(text.len_chars()));
+    (from, to)
+}
+
+/// Selects `node`, keeping the direction of `range`.
+fn select_node(text: RopeSlice, node: Node, range: Range) -> Range {
+    let from = text.byte_to_char(node.start_byte());
+    let to = text
+        .byte_to_char(node.end_byte())
+        .saturating_sub(1)
+        .max(from);
+
+    if range.head < range.anchor {
+        Range::new(to, from)
+    } else {
+        Range::new(from, to)
+    }
+}
+
+/// The largest node spanning exactly the bytes `from..to`, if any, or else the smallest node
+/// containing them.
+fn node_for_range(root: Node, from: usize, to: usize) -> Option<Node> {
+    let mut node = root.descendant_for_byte_range(from, to)?;
+    while let Some(parent) = node.parent() {
+        if parent.start_byte() != node.start_byte() || parent.end_byte() != node.end_byte() {
+            break;
+        }
+        node = parent;
+    }
+    Some(node)
+}
 
-// TODO: to contract_selection we'd need to store the previous ranges before expand.
-// Maybe just contract to the first child node?
 pub fn expand_selection(syntax: &Syntax, text: RopeSlice, selection: &Selection) -> Selection {
-    let tree = syntax.tree();
+    let root = syntax.tree().root_node();
 
     selection.transform(|range| {
-        let from = text.char_to_byte(range.from());
-        let to = text.char_to_byte(range.to());
-
-        // find parent of a descendant that matches the range
-        let parent = match tree
-            .root_node()
-            .descendant_for_byte_range(from, to)
-            .and_then(|node| node.parent())
-        {
-            Some(parent) => parent,
+        let (from, to) = byte_range(text, range);
+
+        let node = match node_for_range(root, from, to) {
+            Some(node) => node,
             None => return range,
         };
 
-        let from = text.byte_to_char(parent.start_byte());
-        let to = text.byte_to_char(parent.end_byte());
-
-        if range.head < range.anchor {
-            Range::new(to, from)
+        // the selection already spans the node, select its parent
+        let node = if node.start_byte() == from && node.end_byte() == to {
+            match node.parent() {
+                Some(parent) => parent,
+                None => return range,
+            }
         } else {
-            Range::new(from, to)
+            node
+        };
+
+        select_node(text, node, range)
+    })
+}
+
+/// Shrinks each selection spanning a node to the node's first child. Selections that don't span
+/// a node are kept.
+pub fn shrink_selection(syntax: &Syntax, text: RopeSlice, selection: &Selection) -> Selection {
+    let root = syntax.tree().root_node();
+
+    selection.transform(|range| {
+        let (from, to) = byte_range(text, range);
+
+        match node_for_range(root, from, to) {
+            Some(node) if node.start_byte() == from && node.end_byte() == to => node
+                .named_child(0)
+                .or_else(|| node.child(0))
+                .map_or(range, |child| select_node(text, child, range)),
+            _ => range,
+        }
+    })
+}
+
+/// Moves each selection to the next named node at the same level of the syntax tree, or the
+/// previous one if `forward` is false. Selections without such a sibling are kept.
+pub fn select_sibling(
+    syntax: &Syntax,
+    text: RopeSlice,
+    selection: &Selection,
+    forward: bool,
+) -> Selection {
+    let root = syntax.tree().root_node();
+
+    selection.transform(|range| {
+        let (from, to) = byte_range(text, range);
+
+        let sibling = node_for_range(root, from, to).and_then(|node| {
+            if forward {
+                node.next_named_sibling()
+            } else {
+                node.prev_named_sibling()
+            }
+        });
+
+        match sibling {
+            Some(sibling) => select_node(text, sibling, range),
+            None => range,
         }
     })
 }
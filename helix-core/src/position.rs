@@ -1,6 +1,7 @@
 use crate::{
     chars::char_is_line_ending,
     graphemes::{nth_next_grapheme_boundary, RopeGraphemes},
+    wrap::visual_width,
     RopeSlice,
 };
 
@@ -59,6 +60,16 @@ pub fn coords_at_pos(text: RopeSlice, pos: usize) -> Position {
     Position::new(line, col)
 }
 
+/// Convert a character index to (line, column) coordinates, where the column is the display
+/// column the character is drawn at: wide graphemes, like CJK characters or emoji, take up two
+/// columns and tabs `tab_width`.
+pub fn visual_coords_at_pos(text: RopeSlice, pos: usize, tab_width: usize) -> Position {
+    let line = text.char_to_line(pos);
+    let line_start = text.line_to_char(line);
+    let col = visual_width(text.slice(line_start..pos), tab_width);
+    Position::new(line, col)
+}
+
 /// Convert (line, column) coordinates to a character index.
 pub fn pos_at_coords(text: RopeSlice, coords: Position) -> usize {
     let Position { row, col } = coords;
@@ -104,6 +115,19 @@ mod test {
         assert_eq!(coords_at_pos(slice, 5), (0, 3).into());
     }
 
+    #[test]
+    fn test_visual_coords_at_pos() {
+        let text = Rope::from("ab\n\tc\n世界x\n😀a̐b");
+        let slice = text.slice(..);
+        assert_eq!(visual_coords_at_pos(slice, 1, 4), (0, 1).into());
+        assert_eq!(visual_coords_at_pos(slice, 4, 4), (1, 4).into()); // position on c
+        assert_eq!(visual_coords_at_pos(slice, 4, 2), (1, 2).into());
+        assert_eq!(visual_coords_at_pos(slice, 7, 4), (2, 2).into()); // position on 界
+        assert_eq!(visual_coords_at_pos(slice, 8, 4), (2, 4).into()); // position on x
+        assert_eq!(visual_coords_at_pos(slice, 11, 4), (3, 2).into()); // position on a̐
+        assert_eq!(visual_coords_at_pos(slice, 13, 4), (3, 3).into()); // position on b
+    }
+
     #[test]
     fn test_pos_at_coords() {
         let text = Rope::from("ḧëḷḷö\nẅöṛḷḋ");
@@ -0,0 +1,99 @@
+//! Hard wrapping of prose and comments to a maximum line width.
+
+use crate::unicode::width::UnicodeWidthStr;
+
+/// Splits `line` into its prefix, the indentation followed by the comment token and the spaces
+/// after it if the line is a comment, and its content.
+fn split_prefix<'a>(line: &'a str, comment_token: Option<&str>) -> (&'a str, &'a str) {
+    let content = line.trim_start();
+    let mut prefix_len = line.len() - content.len();
+
+    if let Some(token) = comment_token {
+        if let Some(rest) = content.strip_prefix(token) {
+            let rest_trimmed = rest.trim_start();
+            prefix_len += token.len() + rest.len() - rest_trimmed.len();
+        }
+    }
+
+    line.split_at(prefix_len)
+}
+
+/// Fills lines starting with `prefix` with as many `words` as fit in `width`.
+fn fill(prefix: &str, words: &[&str], width: usize, lines: &mut Vec<String>) {
+    let mut line = String::from(prefix);
+    let mut empty = true;
+    for word in words {
+        if !empty && line.width() + 1 + word.width() > width {
+            lines.push(std::mem::replace(&mut line, String::from(prefix)));
+            empty = true;
+        }
+        if !empty {
+            line.push(' ');
+        }
+        line.push_str(word);
+        empty = false;
+    }
+    lines.push(line);
+}
+
+/// Rewraps the paragraphs of `text` so that lines are at most `width` columns wide, unless a
+/// single word is longer. Paragraphs are separated by blank lines. The indentation and comment
+/// token of each paragraph's first line are repeated on its continuation lines.
+pub fn reflow(text: &str, width: usize, comment_token: Option<&str>, line_ending: &str) -> String {
+    let mut lines = Vec::new();
+    // prefix and words of the paragraph being filled
+    let mut paragraph: Option<(&str, Vec<&str>)> = None;
+
+    for line in text.lines() {
+        let (prefix, content) = split_prefix(line, comment_token);
+        if content.trim().is_empty() {
+            if let Some((prefix, words)) = paragraph.take() {
+                fill(prefix, &words, width, &mut lines);
+            }
+            lines.push(line.trim_end().to_owned());
+            continue;
+        }
+
+        paragraph
+            .get_or_insert_with(|| (prefix, Vec::new()))
+            .1
+            .extend(content.split_whitespace());
+    }
+    if let Some((prefix, words)) = paragraph {
+        fill(prefix, &words, width, &mut lines);
+    }
+
+    let mut reflowed = lines.join(line_ending);
+    if text.ends_with('\n') {
+        reflowed.push_str(line_ending);
+    }
+    reflowed
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_reflow() {
+        assert_eq!(
+            reflow("one two three four five\n", 10, None, "\n"),
+            "one two\nthree four\nfive\n"
+        );
+        // paragraphs are kept apart and lines joined
+        assert_eq!(
+            reflow("one\ntwo\n\nthree", 80, None, "\n"),
+            "one two\n\nthree"
+        );
+        // indentation and comment tokens are repeated
+        assert_eq!(
+            reflow("    // one two three\n    // four", 17, Some("//"), "\n"),
+            "    // one two\n    // three four"
+        );
+        // long words aren't broken up
+        assert_eq!(
+            reflow("a verylongword b", 4, None, "\n"),
+            "a\nverylongword\nb"
+        );
+    }
+}
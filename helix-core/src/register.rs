@@ -60,4 +60,15 @@ impl Registers {
     pub fn read(&self, name: char) -> Option<&[String]> {
         self.get(name).map(|reg| reg.read())
     }
+
+    /// The registers holding values, ordered by name.
+    pub fn iter(&self) -> impl Iterator<Item = &Register> {
+        let mut registers: Vec<_> = self
+            .inner
+            .values()
+            .filter(|reg| !reg.read().is_empty())
+            .collect();
+        registers.sort_by_key(|reg| reg.name());
+        registers.into_iter()
+    }
 }
@@ -1,4 +1,5 @@
-use crate::RopeSlice;
+use crate::{chars::char_is_word, regex::Regex, RopeSlice};
+use std::ops::Range;
 
 pub fn find_nth_next(
     text: RopeSlice,
@@ -61,3 +62,243 @@ pub fn find_nth_prev(
 
     Some(pos)
 }
+
+/// How many bytes of text, rounded up to whole lines, are searched at once.
+const WINDOW_LEN: usize = 64 * 1024;
+/// How far past its end a window extends, so that matches starting near its end aren't cut off.
+/// Longer matches may be shortened or missed.
+const OVERLAP_LEN: usize = 4 * 1024;
+
+/// An iterator over the byte ranges of the matches of a regex in a rope, see [`find_iter`].
+///
+/// The text is copied into a string a window of lines at a time rather than as a whole. Each
+/// window overlaps the next one, and matches starting in the overlap are left to the next window.
+/// The char on either side of a window is copied with it, so that `^`, `$`, `\A`, `\z` and `\b`
+/// only match where they would in the whole range.
+#[derive(Debug)]
+pub struct Matches<'r, 't> {
+    regex: &'r Regex,
+    text: RopeSlice<'t>,
+    /// The start of the searched range, where the text is taken to begin.
+    start: usize,
+    /// The end of the searched range.
+    end: usize,
+    /// Where the next match may start.
+    pos: usize,
+    /// The end of the last match, an empty match may not be found there again.
+    last_match: Option<usize>,
+
+    window: String,
+    /// Where the window, including the char before it, starts.
+    window_start: usize,
+    /// Where the next window starts, matches starting from here are found in it.
+    next_window: usize,
+}
+
+impl<'r, 't> Matches<'r, 't> {
+    /// Searches the byte `range` of `text` from its byte `pos`.
+    fn new(regex: &'r Regex, text: RopeSlice<'t>, range: Range<usize>, pos: usize) -> Self {
+        let mut matches = Self {
+            regex,
+            text,
+            start: range.start,
+            end: range.end,
+            pos,
+            last_match: None,
+            window: String::new(),
+            window_start: pos,
+            next_window: pos,
+        };
+        matches.load_window(pos);
+        matches
+    }
+
+    /// Copies the lines following the byte `start`, up to the end of the range, into the window.
+    fn load_window(&mut self, start: usize) {
+        let (text, range_end) = (self.text, self.end);
+        let line_end = |pos: usize| {
+            let line = text.byte_to_line(pos.min(range_end));
+            text.line_to_byte(line + 1).min(range_end)
+        };
+        self.next_window = line_end(start + WINDOW_LEN);
+        let end = if self.next_window == self.end {
+            self.end
+        } else {
+            line_end(self.next_window + OVERLAP_LEN)
+        };
+
+        // the chars around the window, within the range
+        let mut start = text.byte_to_char(start);
+        if text.char_to_byte(start) > self.start {
+            start -= 1;
+        }
+        let mut end = text.byte_to_char(end);
+        if text.char_to_byte(end) < range_end {
+            end += 1;
+        }
+
+        self.window_start = text.char_to_byte(start);
+        self.window = text.slice(start..end).into();
+    }
+
+    fn is_last_window(&self) -> bool {
+        self.window_start + self.window.len() == self.end
+    }
+}
+
+impl<'r, 't> Iterator for Matches<'r, 't> {
+    type Item = Range<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.pos > self.end {
+                return None;
+            }
+            if self.pos >= self.next_window && !self.is_last_window() {
+                self.load_window(self.next_window);
+                continue;
+            }
+
+            let mat = self
+                .regex
+                .find_at(&self.window, self.pos - self.window_start)
+                .map(|mat| self.window_start + mat.start()..self.window_start + mat.end());
+            match mat {
+                Some(mat) if self.is_last_window() || mat.start < self.next_window => {
+                    if mat.is_empty() && self.last_match == Some(mat.end) {
+                        // step over the char after the empty match
+                        self.pos = if mat.end < self.end {
+                            let text = self.text;
+                            text.char_to_byte(text.byte_to_char(mat.end) + 1)
+                        } else {
+                            mat.end + 1
+                        };
+                        continue;
+                    }
+                    self.pos = mat.end;
+                    self.last_match = Some(mat.end);
+                    return Some(mat);
+                }
+                // nothing before the next window starts
+                _ if !self.is_last_window() => {
+                    self.pos = self.pos.max(self.next_window);
+                }
+                _ => return None,
+            }
+        }
+    }
+}
+
+/// The byte ranges of the successive non-overlapping matches of `regex` in the byte `range` of
+/// `text`, as if the range were the whole text.
+pub fn find_iter<'r, 't>(
+    regex: &'r Regex,
+    text: RopeSlice<'t>,
+    range: Range<usize>,
+) -> Matches<'r, 't> {
+    let start = range.start;
+    Matches::new(regex, text, range, start)
+}
+
+/// The byte range of the first match of `regex` in `text` that starts at or after the byte
+/// `start`. Unlike searching a range from `start`, the text before it is taken into account,
+/// for `^` or `\b`.
+pub fn find_at(regex: &Regex, text: RopeSlice, start: usize) -> Option<Range<usize>> {
+    Matches::new(regex, text, 0..text.len_bytes(), start).next()
+}
+
+/// The char ranges of the whole-word occurrences, within the lines `lines`, of the word at the
+/// char `pos`. Empty if `pos` isn't on a word.
+pub fn word_occurrences(text: RopeSlice, pos: usize, lines: Range<usize>) -> Vec<Range<usize>> {
+    if !matches!(text.get_char(pos), Some(c) if char_is_word(c)) {
+        return Vec::new();
+    }
+    let mut start = pos;
+    let mut chars = text.chars_at(pos);
+    while matches!(chars.prev(), Some(c) if char_is_word(c)) {
+        start -= 1;
+    }
+    let end = pos + text.chars_at(pos).take_while(|&c| char_is_word(c)).count();
+    let word = text.slice(start..end).to_string();
+
+    let regex = match Regex::new(&format!(r"\b{}\b", crate::regex::escape(&word))) {
+        Ok(regex) => regex,
+        Err(_) => return Vec::new(),
+    };
+    let lines = lines.start.min(text.len_lines())..lines.end.min(text.len_lines());
+    let range = text.line_to_byte(lines.start)..text.line_to_byte(lines.end);
+    find_iter(&regex, text, range)
+        .map(|mat| text.byte_to_char(mat.start)..text.byte_to_char(mat.end))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Rope;
+
+    fn assert_same_matches(regex: &str, text: &str) {
+        let regex = Regex::new(regex).unwrap();
+        let rope = Rope::from(text);
+        let expected: Vec<_> = regex.find_iter(text).map(|mat| mat.range()).collect();
+        let found: Vec<_> = find_iter(&regex, rope.slice(..), 0..text.len()).collect();
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn test_find_iter() {
+        assert_same_matches("fo+", "foo bar\nfoooo\nbaz fo");
+        assert_same_matches("(?m)^b", "foo bar\nfoooo\nbaz fo");
+        assert_same_matches("a*", "aab ä\na");
+        assert_same_matches(r"\d+\n", "12\n34\n\n5");
+    }
+
+    #[test]
+    fn test_find_iter_across_windows() {
+        // long enough for several windows, with matches spanning lines on window boundaries
+        let text = "lorem ipsum\ndolor sit amet\n".repeat(WINDOW_LEN / 10);
+        assert_same_matches(r"amet\ndolor", &text);
+        assert_same_matches(r"(?m)^\w+", &text);
+        assert_same_matches(r"\bsit\b", &text);
+        assert_same_matches("x*", &text[..WINDOW_LEN * 2]);
+        // anchored to the whole text, not to the start or end of a window
+        assert_same_matches(r"^\w+", &text);
+        assert_same_matches(r"\A\w+", &text);
+        assert_same_matches(r"\w+\n$", &text);
+        assert_same_matches(r"\w+\n\z", &text);
+    }
+
+    #[test]
+    fn test_find_iter_range() {
+        let regex = Regex::new(r"\bo\w*").unwrap();
+        let text = Rope::from("foo bar\nonce only");
+        let matches: Vec<_> = find_iter(&regex, text.slice(..), 1..13).collect();
+        // the range is searched as a whole text, so `oo` starts a word
+        assert_eq!(matches, vec![1..3, 8..12]);
+    }
+
+    #[test]
+    fn test_find_at() {
+        let regex = Regex::new(r"\bo\w*").unwrap();
+        let text = Rope::from("foo bar\nonce only");
+        assert_eq!(find_at(&regex, text.slice(..), 1), Some(8..12));
+        assert_eq!(find_at(&regex, text.slice(..), 9), Some(13..17));
+        assert_eq!(find_at(&regex, text.slice(..), 14), None);
+
+        // the search doesn't start a new text
+        let regex = Regex::new(r"\Ao|^o").unwrap();
+        assert_eq!(find_at(&regex, text.slice(..), 8), None);
+        let regex = Regex::new(r"(?m)^o").unwrap();
+        assert_eq!(find_at(&regex, text.slice(..), 8), Some(8..9));
+    }
+
+    #[test]
+    fn test_word_occurrences() {
+        let text = Rope::from("let foo = 1;\nfoo + foobar + foo\n");
+        let text = text.slice(..);
+        assert_eq!(word_occurrences(text, 5, 0..2), vec![4..7, 13..16, 28..31]);
+        assert_eq!(word_occurrences(text, 5, 1..2), vec![13..16, 28..31]);
+        // not on a word
+        assert_eq!(word_occurrences(text, 3, 0..2), Vec::<Range<usize>>::new());
+    }
+}
@@ -2,7 +2,11 @@
 //! single selection range.
 //!
 //! All positioning is done via `char` offsets into the buffer.
-use crate::{Assoc, ChangeSet, RopeSlice};
+use crate::{
+    chars::{char_is_line_ending, char_is_whitespace},
+    line_ending::line_end_char_index,
+    search, Assoc, ChangeSet, RopeSlice,
+};
 use smallvec::{smallvec, SmallVec};
 use std::borrow::Cow;
 
@@ -224,51 +228,31 @@ impl Selection {
         Self::single(pos, pos)
     }
 
-    fn normalize(mut ranges: SmallVec<[Range; 1]>, mut primary_index: usize) -> Self {
-        let primary = ranges[primary_index];
-        ranges.sort_unstable_by_key(Range::from);
-        primary_index = ranges.iter().position(|&range| range == primary).unwrap();
+    /// Sorts the ranges and merges the overlapping ones. A merged range goes the way of the
+    /// primary range if it's part of it, else the way of its first range.
+    fn normalize(ranges: SmallVec<[Range; 1]>, primary_index: usize) -> Self {
+        // sort stably, so that ranges with the same bounds (like copies of a range) are merged in
+        // a deterministic order
+        let mut ranges: SmallVec<[(usize, Range); 1]> = ranges.into_iter().enumerate().collect();
+        ranges.sort_by_key(|(_, range)| (range.from(), range.to()));
 
-        let mut result = SmallVec::with_capacity(ranges.len()); // approx
+        let mut result: SmallVec<[Range; 1]> = SmallVec::with_capacity(ranges.len()); // approx
+        let mut new_primary_index = 0;
 
-        // TODO: we could do with one vec by removing elements as we mutate
-
-        let mut i = 0;
-
-        for range in ranges.into_iter() {
-            // if previous value exists
-            if let Some(prev) = result.last_mut() {
-                // and we overlap it
-
-                // TODO: we used to simply check range.from() <(=) prev.to()
-                // avoiding two comparisons
-                if range.overlaps(prev) {
-                    let from = prev.from();
-                    let to = std::cmp::max(range.to(), prev.to());
-
-                    if i <= primary_index {
-                        primary_index -= 1
-                    }
-
-                    // merge into previous
-                    if range.anchor > range.head {
-                        prev.anchor = to;
-                        prev.head = from;
-                    } else {
-                        prev.anchor = from;
-                        prev.head = to;
-                    }
-                    continue;
-                }
+        for (i, range) in ranges {
+            let is_primary = i == primary_index;
+            match result.last_mut() {
+                Some(prev) if range.overlaps(prev) => merge_into(prev, range, is_primary),
+                _ => result.push(range),
+            }
+            if is_primary {
+                new_primary_index = result.len() - 1;
             }
-
-            result.push(range);
-            i += 1
         }
 
         Self {
             ranges: result,
-            primary_index,
+            primary_index: new_primary_index,
         }
     }
 
@@ -300,6 +284,40 @@ impl Selection {
         )
     }
 
+    /// Merges every range into one, from the start of the first to the end of the last.
+    pub fn merge_ranges(&self) -> Self {
+        let from = self.ranges.first().unwrap().from();
+        let to = self.ranges.last().unwrap().to();
+        let primary = self.primary();
+        if primary.anchor > primary.head {
+            Self::single(to, from)
+        } else {
+            Self::single(from, to)
+        }
+    }
+
+    /// Merges the ranges following each other without a gap.
+    pub fn merge_consecutive_ranges(&self) -> Self {
+        let mut result: SmallVec<[Range; 1]> = SmallVec::with_capacity(self.len());
+        let mut primary_index = 0;
+
+        for (i, &range) in self.ranges.iter().enumerate() {
+            let is_primary = i == self.primary_index;
+            match result.last_mut() {
+                Some(prev) if range.from() <= prev.to() + 1 => merge_into(prev, range, is_primary),
+                _ => result.push(range),
+            }
+            if is_primary {
+                primary_index = result.len() - 1;
+            }
+        }
+
+        Self {
+            ranges: result,
+            primary_index,
+        }
+    }
+
     pub fn fragments<'a>(&'a self, text: RopeSlice<'a>) -> impl Iterator<Item = Cow<str>> + 'a {
         self.ranges.iter().map(move |range| range.fragment(text))
     }
@@ -315,6 +333,18 @@ impl Selection {
     }
 }
 
+/// Extends `prev` over `range`, going the way of `range` if `is_primary`, else the way of `prev`.
+fn merge_into(prev: &mut Range, range: Range, is_primary: bool) {
+    let from = prev.from();
+    let to = std::cmp::max(range.to(), prev.to());
+    let direction = if is_primary { range } else { *prev };
+    *prev = if direction.anchor > direction.head {
+        Range::new(to, from)
+    } else {
+        Range::new(from, to)
+    };
+}
+
 impl<'a> IntoIterator for &'a Selection {
     type Item = &'a Range;
     type IntoIter = std::slice::Iter<'a, Range>;
@@ -333,7 +363,11 @@ pub fn keep_matches(
 ) -> Option<Selection> {
     let result: SmallVec<_> = selection
         .iter()
-        .filter(|range| regex.is_match(&range.fragment(text)))
+        .filter(|range| {
+            let start = text.char_to_byte(range.from());
+            let end = text.char_to_byte(range.to() + 1);
+            search::find_iter(regex, text, start..end).next().is_some()
+        })
         .copied()
         .collect();
 
@@ -352,17 +386,14 @@ pub fn select_on_matches(
     let mut result = SmallVec::with_capacity(selection.len());
 
     for sel in selection {
-        // TODO: can't avoid occasional allocations since Regex can't operate on chunks yet
-        let fragment = sel.fragment(text);
-
-        let sel_start = sel.from();
-        let start_byte = text.char_to_byte(sel_start);
+        let start_byte = text.char_to_byte(sel.from());
+        let end_byte = text.char_to_byte(sel.to() + 1);
 
-        for mat in regex.find_iter(&fragment) {
+        for mat in search::find_iter(regex, text, start_byte..end_byte) {
             // TODO: retain range direction
 
-            let start = text.byte_to_char(start_byte + mat.start());
-            let end = text.byte_to_char(start_byte + mat.end());
+            let start = text.byte_to_char(mat.start);
+            let end = text.byte_to_char(mat.end);
             result.push(Range::new(start, end.saturating_sub(1)));
         }
     }
@@ -384,22 +415,20 @@ pub fn split_on_matches(
     let mut result = SmallVec::with_capacity(selection.len());
 
     for sel in selection {
-        // TODO: can't avoid occasional allocations since Regex can't operate on chunks yet
-        let fragment = sel.fragment(text);
-
         let sel_start = sel.from();
         let sel_end = sel.to();
 
         let start_byte = text.char_to_byte(sel_start);
+        let end_byte = text.char_to_byte(sel_end + 1);
 
         let mut start = sel_start;
 
-        for mat in regex.find_iter(&fragment) {
+        for mat in search::find_iter(regex, text, start_byte..end_byte) {
             // TODO: retain range direction
 
-            let end = text.byte_to_char(start_byte + mat.start());
+            let end = text.byte_to_char(mat.start);
             result.push(Range::new(start, end.saturating_sub(1)));
-            start = text.byte_to_char(start_byte + mat.end());
+            start = text.byte_to_char(mat.end);
         }
 
         if start <= sel_end {
@@ -411,6 +440,100 @@ pub fn split_on_matches(
     Selection::new(result, 0)
 }
 
+/// Splits each range spanning several lines into one range per line, without its line ending.
+/// Empty lines get a cursor on their line ending, and ranges within a single line are kept as
+/// they are.
+pub fn split_on_newline(text: RopeSlice, selection: &Selection) -> Selection {
+    let mut result = SmallVec::with_capacity(selection.len());
+    let mut primary_index = 0;
+
+    for (i, range) in selection.iter().enumerate() {
+        let (from, to) = (range.from(), range.to());
+        let first_line = text.char_to_line(from);
+        let last_line = text.char_to_line(to);
+        if i == selection.primary_index() {
+            primary_index = result.len();
+        }
+        if first_line == last_line {
+            result.push(*range);
+            continue;
+        }
+
+        let start_len = result.len();
+        for line in first_line..=last_line {
+            let line_start = text.line_to_char(line);
+            let line_end = line_end_char_index(&text, line);
+            let start = from.max(line_start);
+            let end = (to + 1).min(line_end);
+            let (start, end) = if end > start {
+                (start, end - 1)
+            } else if line_start == line_end {
+                (line_start, line_start)
+            } else {
+                // only the line ending of this line is selected
+                continue;
+            };
+
+            if i == selection.primary_index() && (start..=end).contains(&range.head) {
+                primary_index = result.len();
+            }
+            // keep the direction of the range
+            if range.head < range.anchor {
+                result.push(Range::new(end, start));
+            } else {
+                result.push(Range::new(start, end));
+            }
+        }
+        if result.len() == start_len {
+            result.push(*range);
+        }
+    }
+
+    Selection::new(result, primary_index)
+}
+
+/// Shrinks each range to exclude its leading and trailing whitespace and line endings, dropping
+/// the ranges that are only whitespace. Returns `None` if every range is.
+pub fn trim(text: RopeSlice, selection: &Selection) -> Option<Selection> {
+    // an empty document has no characters to keep
+    if text.len_chars() == 0 {
+        return None;
+    }
+    let is_whitespace = |pos: &usize| {
+        let ch = text.char(*pos);
+        char_is_whitespace(ch) || char_is_line_ending(ch)
+    };
+
+    let mut result = SmallVec::with_capacity(selection.len());
+    let mut primary_index = 0;
+
+    for (i, range) in selection.iter().enumerate() {
+        let (from, to) = (
+            range.from(),
+            range.to().min(text.len_chars().saturating_sub(1)),
+        );
+        let start = (from..=to).find(|pos| !is_whitespace(pos));
+        let end = (from..=to).rev().find(|pos| !is_whitespace(pos));
+        if let (Some(start), Some(end)) = (start, end) {
+            // keep the direction of the range
+            if range.head < range.anchor {
+                result.push(Range::new(end, start));
+            } else {
+                result.push(Range::new(start, end));
+            }
+        }
+        // a dropped primary range passes on to the range before it
+        if i == selection.primary_index() {
+            primary_index = result.len().saturating_sub(1);
+        }
+    }
+
+    if result.is_empty() {
+        return None;
+    }
+    Some(Selection::new(result, primary_index))
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -503,6 +626,30 @@ mod test {
         assert_eq!(range.contains(6), false);
     }
 
+    #[test]
+    fn test_merge_ranges() {
+        let sel = Selection::new(
+            smallvec![
+                Range::new(0, 1),
+                Range::new(3, 2),
+                Range::new(4, 5),
+                Range::new(8, 9)
+            ],
+            1,
+        );
+
+        assert_eq!(sel.merge_ranges().ranges(), &[Range::new(9, 0)]);
+
+        let merged = sel.merge_consecutive_ranges();
+        assert_eq!(merged.ranges(), &[Range::new(5, 0), Range::new(8, 9)],);
+        assert_eq!(merged.primary_index(), 0);
+
+        // copies of a range merge the same whatever their order
+        let sel = Selection::new(smallvec![Range::new(2, 0), Range::new(0, 2)], 1);
+        assert_eq!(sel.ranges(), &[Range::new(0, 2)]);
+        assert_eq!(sel.primary_index(), 0);
+    }
+
     #[test]
     fn test_split_on_matches() {
         use crate::regex::Regex;
@@ -529,4 +676,53 @@ mod test {
             &["abcd", "efg", "rs", "xyz", "1"]
         );
     }
+
+    #[test]
+    fn test_trim() {
+        let text = Rope::from("  abc  \n\t\n de f\n");
+        let selection = Selection::new(
+            smallvec![Range::new(0, 7), Range::new(8, 9), Range::new(15, 10)],
+            1,
+        );
+
+        let result = trim(text.slice(..), &selection).unwrap();
+
+        assert_eq!(result.ranges(), &[Range::new(2, 4), Range::new(14, 11)]);
+        assert_eq!(result.primary_index(), 0);
+        assert_eq!(
+            result.fragments(text.slice(..)).collect::<Vec<_>>(),
+            &["abc", "de f"]
+        );
+
+        assert!(trim(text.slice(..), &Selection::single(8, 9)).is_none());
+        assert!(trim(Rope::new().slice(..), &Selection::point(0)).is_none());
+        // a cursor past the last character keeps nothing either
+        let text = Rope::from("ab");
+        assert!(trim(text.slice(..), &Selection::point(2)).is_none());
+    }
+
+    #[test]
+    fn test_split_on_newline() {
+        let text = Rope::from("abc\n\ndef\nghi\njkl\n");
+        let selection = Selection::new(smallvec![Range::new(1, 9), Range::new(14, 11)], 1);
+
+        let result = split_on_newline(text.slice(..), &selection);
+
+        assert_eq!(
+            result.ranges(),
+            &[
+                Range::new(1, 2),
+                Range::new(4, 4),
+                Range::new(5, 7),
+                Range::new(9, 9),
+                Range::new(11, 11),
+                Range::new(14, 13),
+            ]
+        );
+        assert_eq!(result.primary_index(), 4);
+        assert_eq!(
+            result.fragments(text.slice(..)).collect::<Vec<_>>(),
+            &["bc", "\n", "def", "g", "i", "jk"]
+        );
+    }
 }
@@ -0,0 +1,208 @@
+//! Snippets in the LSP snippet syntax: `$1`, `${2:placeholder}`, `${3|one,two|}`, variables like
+//! `${TM_FILENAME:default}` and `$0` for the final cursor position.
+
+use crate::{Range, Selection, SmallVec};
+use std::collections::BTreeMap;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Snippet {
+    /// The text to insert, with every tabstop replaced by its placeholder.
+    pub text: String,
+    /// Char ranges of the tabstops relative to the start of `text`, in the order they're
+    /// visited: ascending, with `$0` last. A tabstop that appears several times has several
+    /// ranges.
+    pub tabstops: Vec<Vec<std::ops::Range<usize>>>,
+}
+
+impl Snippet {
+    /// Selections of each tabstop once the snippet is inserted at `pos`.
+    pub fn selections(&self, pos: usize) -> Vec<Selection> {
+        self.tabstops
+            .iter()
+            .map(|ranges| {
+                let ranges: SmallVec<[Range; 1]> = ranges
+                    .iter()
+                    .map(|range| {
+                        if range.is_empty() {
+                            Range::point(pos + range.start)
+                        } else {
+                            Range::new(pos + range.start, pos + range.end - 1)
+                        }
+                    })
+                    .collect();
+                Selection::new(ranges, 0)
+            })
+            .collect()
+    }
+}
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    text: String,
+    /// Length of `text` in chars.
+    len: usize,
+    tabstops: BTreeMap<usize, Vec<std::ops::Range<usize>>>,
+}
+
+impl<'a> Parser<'a> {
+    fn push(&mut self, ch: char) {
+        self.text.push(ch);
+        self.len += 1;
+    }
+
+    fn number(&mut self) -> Option<usize> {
+        let mut number = None;
+        while let Some(digit) = self.chars.peek().and_then(|ch| ch.to_digit(10)) {
+            self.chars.next();
+            number = Some(number.unwrap_or(0) * 10 + digit as usize);
+        }
+        number
+    }
+
+    fn name(&mut self) -> String {
+        let mut name = String::new();
+        while let Some(&ch) = self.chars.peek() {
+            if !(ch.is_ascii_alphanumeric() || ch == '_') {
+                break;
+            }
+            name.push(ch);
+            self.chars.next();
+        }
+        name
+    }
+
+    /// Parses text until the unescaped `end`, which is consumed.
+    fn parse_until(&mut self, end: Option<char>) {
+        while let Some(ch) = self.chars.next() {
+            match ch {
+                _ if Some(ch) == end => return,
+                '\\' => match self.chars.peek() {
+                    Some(&next) if matches!(next, '$' | '}' | '\\') => {
+                        self.chars.next();
+                        self.push(next);
+                    }
+                    _ => self.push('\\'),
+                },
+                '$' => self.dollar(),
+                _ => self.push(ch),
+            }
+        }
+    }
+
+    /// Parses what follows a `$`.
+    fn dollar(&mut self) {
+        let start = self.len;
+
+        if let Some(tabstop) = self.number() {
+            self.tabstops.entry(tabstop).or_default().push(start..start);
+            return;
+        }
+
+        if self.chars.peek() != Some(&'{') {
+            // variables without a default are empty, a lone `$` is literal
+            if self.name().is_empty() {
+                self.push('$');
+            }
+            return;
+        }
+        self.chars.next();
+
+        match self.number() {
+            Some(tabstop) => {
+                match self.chars.next() {
+                    Some(':') => self.parse_until(Some('}')),
+                    Some('|') => self.choice(),
+                    _ => (),
+                }
+                self.tabstops
+                    .entry(tabstop)
+                    .or_default()
+                    .push(start..self.len);
+            }
+            None => {
+                self.name();
+                if self.chars.next() == Some(':') {
+                    self.parse_until(Some('}'));
+                }
+            }
+        }
+    }
+
+    /// Inserts the first option of a `${1|one,two|}` choice.
+    fn choice(&mut self) {
+        let mut first = true;
+        while let Some(ch) = self.chars.next() {
+            match ch {
+                '|' => {
+                    if self.chars.peek() == Some(&'}') {
+                        self.chars.next();
+                    }
+                    return;
+                }
+                ',' => first = false,
+                '\\' => {
+                    if let Some(next) = self.chars.next() {
+                        if first {
+                            self.push(next);
+                        }
+                    }
+                }
+                _ if first => self.push(ch),
+                _ => (),
+            }
+        }
+    }
+}
+
+pub fn parse(input: &str) -> Snippet {
+    let mut parser = Parser {
+        chars: input.chars().peekable(),
+        text: String::new(),
+        len: 0,
+        tabstops: BTreeMap::new(),
+    };
+    parser.parse_until(None);
+
+    // the cursor ends up after the snippet unless `$0` says otherwise
+    let end = parser.len;
+    let last = parser
+        .tabstops
+        .remove(&0)
+        .unwrap_or_else(|| std::iter::once(end..end).collect());
+    let mut tabstops: Vec<_> = parser.tabstops.into_values().collect();
+    tabstops.push(last);
+
+    Snippet {
+        text: parser.text,
+        tabstops,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let snippet = parse("fn ${1:name}($2) {\n    $0\n}");
+        assert_eq!(snippet.text, "fn name() {\n    \n}");
+        assert_eq!(snippet.tabstops.len(), 3);
+        assert_eq!(snippet.tabstops.concat(), vec![3..7, 8..8, 16..16]);
+
+        // nested placeholders, mirrors, choices, variables and escapes
+        let snippet = parse("${1:a ${2:b}} $1 ${3|x,y|} ${TM_FILENAME:file} \\$1 $");
+        assert_eq!(snippet.text, "a b  x file $1 $");
+        assert_eq!(snippet.tabstops[0], vec![0..3, 4..4]);
+        assert_eq!(snippet.tabstops.len(), 4);
+        assert_eq!(snippet.tabstops[1..].concat(), vec![2..3, 5..6, 16..16]);
+    }
+
+    #[test]
+    fn test_selections() {
+        let snippet = parse("${1:foo} $2");
+        let selections = snippet.selections(10);
+        assert_eq!(selections[0].primary(), Range::new(10, 12));
+        assert_eq!(selections[1].primary(), Range::point(14));
+        assert_eq!(selections[2].primary(), Range::point(14));
+    }
+}
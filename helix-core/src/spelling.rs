@@ -0,0 +1,271 @@
+//! Spell checking of prose, and of the comments and strings of code.
+
+use crate::{RopeSlice, Syntax};
+use std::collections::HashSet;
+use std::ops::Range;
+use std::path::Path;
+
+/// The file words added to the dictionary are saved to.
+pub fn user_dictionary() -> std::path::PathBuf {
+    crate::config_dir().join("dictionary")
+}
+
+/// The words considered correctly spelled.
+#[derive(Debug, Default)]
+pub struct Dictionary {
+    words: HashSet<String>,
+}
+
+impl Dictionary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the words listed in the file at `path`, one per line.
+    pub fn load(&mut self, path: &Path) -> std::io::Result<()> {
+        let contents = std::fs::read_to_string(path)?;
+        for word in contents.lines() {
+            self.insert(word.trim());
+        }
+        Ok(())
+    }
+
+    pub fn insert(&mut self, word: &str) {
+        if !word.is_empty() {
+            self.words.insert(word.to_owned());
+        }
+    }
+
+    /// Whether `word` is spelled correctly. Capitalized words match their lowercase form, and
+    /// possessives their stem.
+    pub fn contains(&self, word: &str) -> bool {
+        self.words.contains(word)
+            || self.words.contains(&word.to_lowercase())
+            || matches!(word.strip_suffix("'s"), Some(stem) if self.contains(stem))
+    }
+
+    /// Up to `limit` words of the dictionary at most two edits away from `word`, closest first.
+    pub fn suggestions(&self, word: &str, limit: usize) -> Vec<String> {
+        let lowercase = word.to_lowercase();
+        let mut candidates: Vec<(usize, String)> = self
+            .words
+            .iter()
+            .filter_map(|candidate| {
+                let candidate = candidate.to_lowercase();
+                let distance = edit_distance(&lowercase, &candidate, 2)?;
+                Some((distance, candidate))
+            })
+            .collect();
+        candidates.sort();
+        candidates.dedup();
+
+        let capitalized = matches!(word.chars().next(), Some(first) if first.is_uppercase());
+        candidates
+            .into_iter()
+            .take(limit)
+            .map(|(_, candidate)| {
+                if !capitalized {
+                    return candidate;
+                }
+                let mut chars = candidate.chars();
+                chars
+                    .next()
+                    .map(|first| first.to_uppercase().chain(chars).collect())
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
+}
+
+/// The Levenshtein distance between `a` and `b`, if it's at most `max`.
+fn edit_distance(a: &str, b: &str, max: usize) -> Option<usize> {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    if a.len().max(b.len()) - a.len().min(b.len()) > max {
+        return None;
+    }
+
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        // every later row is at least the minimum of this one
+        if current.iter().min() > Some(&max) {
+            return None;
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    Some(previous[b.len()]).filter(|&distance| distance <= max)
+}
+
+/// Char ranges of the words of `text` to check, with the words. Identifiers, like `snake_case`,
+/// `camelCase` or `x2`, acronyms and single letters are left out.
+fn words(text: &str) -> Vec<(Range<usize>, &str)> {
+    let is_token_char = |ch: char| ch.is_alphanumeric() || ch == '_' || ch == '\'';
+    let chars: Vec<char> = text.chars().collect();
+    // byte offset of each char, and of the end
+    let offsets: Vec<usize> = text
+        .char_indices()
+        .map(|(offset, _)| offset)
+        .chain(std::iter::once(text.len()))
+        .collect();
+
+    let mut words = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] == '\\' {
+            // skip escape sequences like `\n`
+            i += 2;
+            continue;
+        }
+        if !is_token_char(chars[i]) {
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        while i < chars.len() && is_token_char(chars[i]) {
+            i += 1;
+        }
+
+        // apostrophes around a word are quotes
+        let (mut from, mut to) = (start, i);
+        while from < to && chars[from] == '\'' {
+            from += 1;
+        }
+        while to > from && chars[to - 1] == '\'' {
+            to -= 1;
+        }
+
+        let word = &chars[from..to];
+        let is_checked = word.len() > 1
+            && word.iter().all(|&ch| ch.is_alphabetic() || ch == '\'')
+            && !word[1..].iter().any(|ch| ch.is_uppercase());
+        if is_checked {
+            words.push((from..to, &text[offsets[from]..offsets[to]]));
+        }
+    }
+
+    words
+}
+
+/// Byte ranges to check within the byte `range` of `text`: the comments and strings of the syntax
+/// tree, or the whole range without one.
+pub fn checked_ranges(
+    syntax: Option<&Syntax>,
+    text: RopeSlice,
+    range: Range<usize>,
+) -> Vec<Range<usize>> {
+    let range = range.start.min(text.len_bytes())..range.end.min(text.len_bytes());
+    let syntax = match syntax {
+        Some(syntax) => syntax,
+        None => return std::iter::once(range).collect(),
+    };
+
+    let mut ranges = Vec::new();
+    let mut cursor = syntax.tree().walk();
+    loop {
+        let node = cursor.node();
+        let kind = node.kind();
+        // nodes outside of the range are skipped along with their children
+        let inside = node.end_byte() > range.start && node.start_byte() < range.end;
+        if inside && (kind.contains("comment") || kind.contains("string")) {
+            ranges.push(node.start_byte().max(range.start)..node.end_byte().min(range.end));
+        } else if inside && cursor.goto_first_child() {
+            continue;
+        }
+
+        while !cursor.goto_next_sibling() {
+            if !cursor.goto_parent() {
+                return ranges;
+            }
+        }
+    }
+}
+
+/// Char ranges of the misspelled words in the byte `ranges` of `text`.
+pub fn misspelled(
+    dictionary: &Dictionary,
+    text: RopeSlice,
+    ranges: &[Range<usize>],
+) -> Vec<Range<usize>> {
+    let mut misspelled = Vec::new();
+    for range in ranges {
+        let start = text.byte_to_char(range.start);
+        let end = text.byte_to_char(range.end);
+        let fragment = String::from(text.slice(start..end));
+        for (word_range, word) in words(&fragment) {
+            if !dictionary.contains(word) {
+                misspelled.push(start + word_range.start..start + word_range.end);
+            }
+        }
+    }
+    misspelled
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Rope;
+
+    fn dictionary() -> Dictionary {
+        let mut dictionary = Dictionary::new();
+        for word in ["the", "quick", "brown", "fox", "don't", "London", "quack"] {
+            dictionary.insert(word);
+        }
+        dictionary
+    }
+
+    #[test]
+    fn test_words() {
+        assert_eq!(
+            words("'Quick' fox_2, camelCase don't HTTP a \\tab x2 ünïcode"),
+            vec![
+                (1..6, "Quick"),
+                (25..30, "don't"),
+                (40..42, "ab"),
+                (46..53, "ünïcode")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_contains() {
+        let dictionary = dictionary();
+        assert!(dictionary.contains("The"));
+        assert!(dictionary.contains("London"));
+        assert!(dictionary.contains("fox's"));
+        assert!(!dictionary.contains("london"));
+        assert!(!dictionary.contains("qiuck"));
+    }
+
+    #[test]
+    fn test_suggestions() {
+        let dictionary = dictionary();
+        assert_eq!(dictionary.suggestions("quik", 5), vec!["quick", "quack"]);
+        assert_eq!(dictionary.suggestions("Teh", 1), vec!["The"]);
+        assert!(dictionary.suggestions("elephant", 5).is_empty());
+    }
+
+    #[test]
+    fn test_misspelled() {
+        let dictionary = dictionary();
+        let text = Rope::from("the qiuck brown fox\nteh fox");
+        let ranges = checked_ranges(None, text.slice(..), 0..text.len_bytes());
+        assert_eq!(
+            misspelled(&dictionary, text.slice(..), &ranges),
+            vec![4..9, 20..23]
+        );
+        let ranges = checked_ranges(None, text.slice(..), 20..40);
+        assert_eq!(ranges, vec![20..27]);
+        assert_eq!(
+            misspelled(&dictionary, text.slice(..), &ranges),
+            vec![20..23]
+        );
+    }
+}
@@ -51,10 +51,35 @@ pub struct LanguageConfiguration {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub language_server: Option<LanguageServerConfiguration>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub debugger: Option<DebugAdapterConfiguration>,
+    /// A program formatting the document given on stdin, used instead of the language server.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub formatter: Option<FormatterConfiguration>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub indent: Option<IndentationConfiguration>,
+    /// Overrides the editor's rulers for this language.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rulers: Option<Vec<u16>>,
+    /// Overrides the editor's `trim-trailing-whitespace` for this language.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trim_trailing_whitespace: Option<bool>,
+    /// Overrides the editor's `text-width` for this language.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text_width: Option<usize>,
 
     #[serde(skip)]
     pub(crate) indent_query: OnceCell<Option<IndentQuery>>,
+    #[serde(skip)]
+    pub(crate) textobject_query: OnceCell<Option<TextObjectQuery>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct FormatterConfiguration {
+    pub command: String,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -66,11 +91,46 @@ pub struct LanguageServerConfiguration {
     pub args: Vec<String>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct DebugAdapterConfiguration {
+    /// The adapter ID sent to the adapter, e.g. `lldb`.
+    pub name: String,
+    pub command: String,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub templates: Vec<DebugTemplate>,
+}
+
+/// A debug configuration to start a session with.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct DebugTemplate {
+    pub name: String,
+    /// Only `launch` is supported.
+    #[serde(default = "default_debug_request")]
+    pub request: String,
+    /// The arguments of the request. `{0}`, `{1}`, ... in strings are replaced by the arguments
+    /// given when starting the session.
+    #[serde(default)]
+    pub args: HashMap<String, toml::Value>,
+}
+
+fn default_debug_request() -> String {
+    "launch".to_owned()
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct IndentationConfiguration {
     pub tab_width: usize,
     pub unit: String,
+    /// Always indent with `unit` instead of the indentation detected in the file.
+    #[serde(default)]
+    pub enforce: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -180,6 +240,15 @@ impl LanguageConfiguration {
         }
     }
 
+    pub fn language_id(&self) -> &str {
+        &self.language_id
+    }
+
+    /// Whether the tree-sitter grammar of the language can be loaded.
+    pub fn has_grammar(&self) -> bool {
+        get_language(&crate::RUNTIME_DIR, &self.language_id).is_ok()
+    }
+
     pub fn reconfigure(&self, scopes: &[String]) {
         if let Some(Some(config)) = self.highlight_config.get() {
             config.configure(scopes);
@@ -207,11 +276,66 @@ impl LanguageConfiguration {
             .as_ref()
     }
 
+    pub fn textobject_query(&self) -> Option<&TextObjectQuery> {
+        self.textobject_query
+            .get_or_init(|| {
+                let language = self.language_id.to_ascii_lowercase();
+
+                let query_source = read_query(&language, "textobjects.scm");
+                if query_source.is_empty() {
+                    return None;
+                }
+                let grammar = get_language(&crate::RUNTIME_DIR, &self.language_id).ok()?;
+                let query = Query::new(grammar, &query_source).ok()?;
+                Some(TextObjectQuery { query })
+            })
+            .as_ref()
+    }
+
     pub fn scope(&self) -> &str {
         &self.scope
     }
 }
 
+/// Tree-sitter query capturing text objects such as `@function.around` or `@class.inside`.
+#[derive(Debug)]
+pub struct TextObjectQuery {
+    pub query: Query,
+}
+
+impl TextObjectQuery {
+    /// Returns the nodes within `node` captured as `capture_name`, in document order.
+    pub fn capture_nodes<'tree>(
+        &self,
+        capture_name: &str,
+        node: Node<'tree>,
+        slice: RopeSlice<'tree>,
+    ) -> Vec<Node<'tree>> {
+        let index = match self
+            .query
+            .capture_names()
+            .iter()
+            .position(|name| name == capture_name)
+        {
+            Some(index) => index as u32,
+            None => return Vec::new(),
+        };
+
+        let mut cursor = QueryCursor::new();
+        cursor
+            .captures(&self.query, node, |node: Node| node_to_bytes(node, slice))
+            .filter_map(|(query_match, i)| {
+                let capture = query_match.captures[i];
+                if capture.index == index {
+                    Some(capture.node)
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
 #[derive(Debug)]
 pub struct Loader {
     // highlight_names ?
@@ -292,7 +416,7 @@ thread_local! {
     })
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Syntax {
     config: Arc<HighlightConfiguration>,
 
@@ -453,6 +577,34 @@ impl Syntax {
         result.sort_layers();
         result
     }
+
+    /// Collects the highlight events of the byte `range` of `source`, including the injected
+    /// languages found with `loader`. Stops with `Error::Cancelled` once `cancellation_flag` is
+    /// set.
+    pub fn highlights(
+        &self,
+        source: RopeSlice,
+        range: std::ops::Range<usize>,
+        cancellation_flag: Option<&AtomicUsize>,
+        loader: &Loader,
+        scopes: &[String],
+    ) -> Result<Vec<HighlightEvent>, Error> {
+        self.highlight_iter(source, Some(range), cancellation_flag, |language| {
+            loader
+                .language_config_for_scope(&format!("source.{}", language))
+                .and_then(|language_config| {
+                    let config = language_config.highlight_config(scopes)?;
+                    let config_ref = config.as_ref();
+                    // SAFETY: the referenced `HighlightConfiguration` behind the `Arc` is owned
+                    // by the loader, which outlives the highlight.
+                    let config_ref =
+                        unsafe { mem::transmute::<_, &'static HighlightConfiguration>(config_ref) };
+                    Some(config_ref)
+                })
+        })
+        .collect()
+    }
+
     // on_tokenize
     // on_change_highlighting
 
@@ -474,7 +626,7 @@ impl Syntax {
     // buffer_range_for_scope_at_pos
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct LanguageLayer {
     // mode
     // grammar
@@ -761,7 +913,7 @@ pub enum Error {
 }
 
 /// Represents a single step in rendering a syntax-highlighted document.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum HighlightEvent {
     Source { start: usize, end: usize },
     HighlightStart(Highlight),
@@ -0,0 +1,203 @@
+//! [ctags](https://en.wikipedia.org/wiki/Ctags) files, mapping the symbols of a project to where
+//! they're defined.
+
+use crate::RopeSlice;
+use std::path::{Path, PathBuf};
+
+/// The names a tags file is looked up by, in order.
+const FILE_NAMES: &[&str] = &["tags", ".tags", "TAGS"];
+
+/// Where a tag is in its file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Address {
+    /// A 0-based line.
+    Line(usize),
+    /// A line matching the pattern, usually all of its text with `^` and `$` anchors.
+    Pattern(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tag {
+    pub name: String,
+    pub file: PathBuf,
+    pub address: Address,
+    /// The kind of symbol, like `f` or `function`, if the tags file has it.
+    pub kind: Option<String>,
+}
+
+impl Tag {
+    /// The 0-based line of the tag in `text`, the contents of its file.
+    pub fn line(&self, text: RopeSlice) -> Option<usize> {
+        let pattern = match &self.address {
+            Address::Line(line) => return Some((*line).min(text.len_lines().saturating_sub(1))),
+            Address::Pattern(pattern) => pattern,
+        };
+        let (pattern, start) = match pattern.strip_prefix('^') {
+            Some(pattern) => (pattern, true),
+            None => (pattern.as_str(), false),
+        };
+        let (pattern, end) = match pattern.strip_suffix('$') {
+            Some(pattern) => (pattern, true),
+            None => (pattern, false),
+        };
+
+        text.lines().position(|line| {
+            let line = String::from(line);
+            let line = line.trim_end_matches(&['\n', '\r'][..]);
+            match (start, end) {
+                (true, true) => line == pattern,
+                (true, false) => line.starts_with(pattern),
+                (false, true) => line.ends_with(pattern),
+                (false, false) => line.contains(pattern),
+            }
+        })
+    }
+}
+
+/// The tags file of the closest of `dir` and its ancestors having one.
+pub fn find_file(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .flat_map(|dir| FILE_NAMES.iter().map(move |name| dir.join(name)))
+        .find(|path| path.is_file())
+}
+
+/// Reads the tags of the file at `path`.
+pub fn load(path: &Path) -> std::io::Result<Vec<Tag>> {
+    let contents = std::fs::read_to_string(path)?;
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    Ok(parse(&contents, dir))
+}
+
+/// Parses the lines of a tags file, `name<tab>file<tab>address[;"<tab>fields]`, with the files
+/// relative to `dir`. Malformed lines and the `!_TAG_` metadata are skipped.
+pub fn parse(contents: &str, dir: &Path) -> Vec<Tag> {
+    contents
+        .lines()
+        .filter(|line| !line.starts_with("!_TAG_"))
+        .filter_map(|line| parse_line(line, dir))
+        .collect()
+}
+
+fn parse_line(line: &str, dir: &Path) -> Option<Tag> {
+    let mut parts = line.splitn(3, '\t');
+    let name = parts.next()?;
+    let file = parts.next()?;
+    let rest = parts.next()?;
+
+    let (address, fields) = match rest.rfind(";\"") {
+        Some(i) => (&rest[..i], Some(&rest[i + 2..])),
+        None => (rest, None),
+    };
+    let address = if let Ok(line) = address.trim().parse::<usize>() {
+        Address::Line(line.saturating_sub(1))
+    } else {
+        let address = address.trim();
+        let delimiter = address.chars().next().filter(|&c| c == '/' || c == '?')?;
+        let pattern = address
+            .strip_prefix(delimiter)?
+            .strip_suffix(delimiter)
+            .unwrap_or_else(|| &address[1..]);
+        Address::Pattern(unescape(pattern, delimiter))
+    };
+
+    let kind = fields
+        .into_iter()
+        .flat_map(|fields| fields.split('\t'))
+        .map(str::trim)
+        .find_map(|field| match field.strip_prefix("kind:") {
+            Some(kind) => Some(kind),
+            // a field without a name is the kind
+            None if !field.is_empty() && !field.contains(':') => Some(field),
+            None => None,
+        })
+        .map(str::to_owned);
+
+    Some(Tag {
+        name: name.to_owned(),
+        file: dir.join(file),
+        address,
+        kind,
+    })
+}
+
+/// Removes the backslashes escaping the pattern delimiter and backslashes.
+fn unescape(pattern: &str, delimiter: char) -> String {
+    let mut unescaped = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => match chars.next() {
+                Some(next) if next == delimiter || next == '\\' => unescaped.push(next),
+                Some(next) => {
+                    unescaped.push('\\');
+                    unescaped.push(next);
+                }
+                None => unescaped.push('\\'),
+            },
+            ch => unescaped.push(ch),
+        }
+    }
+    unescaped
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Rope;
+
+    #[test]
+    fn test_parse() {
+        let contents = "!_TAG_FILE_FORMAT\t2\t/extended format/\n\
+            main\tsrc/main.rs\t/^fn main() {$/;\"\tf\n\
+            Config\tsrc/config.rs\t12;\"\tkind:struct\tline:12\n\
+            path\tsrc/lib.rs\t/^let path = \"a\\/b\";$/\n";
+        let tags = parse(contents, Path::new("/project"));
+        assert_eq!(
+            tags,
+            vec![
+                Tag {
+                    name: "main".to_owned(),
+                    file: PathBuf::from("/project/src/main.rs"),
+                    address: Address::Pattern("^fn main() {$".to_owned()),
+                    kind: Some("f".to_owned()),
+                },
+                Tag {
+                    name: "Config".to_owned(),
+                    file: PathBuf::from("/project/src/config.rs"),
+                    address: Address::Line(11),
+                    kind: Some("struct".to_owned()),
+                },
+                Tag {
+                    name: "path".to_owned(),
+                    file: PathBuf::from("/project/src/lib.rs"),
+                    address: Address::Pattern("^let path = \"a/b\";$".to_owned()),
+                    kind: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_line() {
+        let text = Rope::from("use std::io;\n\nfn main() {\n}\n");
+        let tag = |address| Tag {
+            name: "main".to_owned(),
+            file: PathBuf::new(),
+            address,
+            kind: None,
+        };
+        assert_eq!(
+            tag(Address::Pattern("^fn main() {$".to_owned())).line(text.slice(..)),
+            Some(2)
+        );
+        assert_eq!(
+            tag(Address::Pattern("^fn main(".to_owned())).line(text.slice(..)),
+            Some(2)
+        );
+        assert_eq!(
+            tag(Address::Pattern("^fn other() {$".to_owned())).line(text.slice(..)),
+            None
+        );
+        assert_eq!(tag(Address::Line(1)).line(text.slice(..)), Some(1));
+    }
+}
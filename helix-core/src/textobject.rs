@@ -1,8 +1,10 @@
 use ropey::RopeSlice;
+use tree_sitter::Node;
 
 use crate::chars::{categorize_char, char_is_line_ending, char_is_whitespace, CharCategory};
 use crate::movement::{self, Direction};
 use crate::surround;
+use crate::syntax::LanguageConfiguration;
 use crate::Range;
 
 fn this_word_end_pos(slice: RopeSlice, pos: usize) -> usize {
@@ -112,6 +114,52 @@ pub fn textobject_surround(
         .unwrap_or(range)
 }
 
+/// Selects the `count`th next or previous `object_name` (`function`, `class`...) captured by the
+/// language's textobjects query as `@<object_name>.around`, starting at the cursor. The head ends
+/// up on the side of the object facing `dir`. Returns `range` if there is none.
+pub fn goto_treesitter_object(
+    slice: RopeSlice,
+    range: Range,
+    object_name: &str,
+    dir: Direction,
+    root: Node,
+    lang_config: &LanguageConfiguration,
+    count: usize,
+) -> Range {
+    let query = match lang_config.textobject_query() {
+        Some(query) => query,
+        None => return range,
+    };
+    let capture_name = format!("{}.around", object_name);
+    let nodes = query.capture_nodes(&capture_name, root, slice);
+
+    (0..count).fold(range, |range, _| {
+        let byte_pos = slice.char_to_byte(range.head);
+        let node = match dir {
+            Direction::Forward => nodes
+                .iter()
+                .filter(|node| node.start_byte() > byte_pos)
+                .min_by_key(|node| node.start_byte()),
+            Direction::Backward => nodes
+                .iter()
+                .filter(|node| node.start_byte() < byte_pos)
+                .max_by_key(|node| node.start_byte()),
+        };
+
+        match node {
+            Some(node) => {
+                let start = slice.byte_to_char(node.start_byte());
+                let end = slice.byte_to_char(node.end_byte()).saturating_sub(1);
+                match dir {
+                    Direction::Forward => Range::new(start, end),
+                    Direction::Backward => Range::new(end, start),
+                }
+            }
+            None => range,
+        }
+    })
+}
+
 #[cfg(test)]
 mod test {
     use super::TextObject::*;
@@ -315,4 +363,58 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn test_goto_treesitter_object() {
+        // set runtime path so we can find the queries
+        let mut runtime = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        runtime.push("../runtime");
+        std::env::set_var("HELIX_RUNTIME", runtime.to_str().unwrap());
+
+        let lang_config: LanguageConfiguration = toml::from_str(
+            r#"
+            name = "Rust"
+            scope = "source.rust"
+            file-types = ["rs"]
+            roots = []
+            "#,
+        )
+        .unwrap();
+
+        let doc = Rope::from("fn a() {}\n\nstruct B {}\n\nfn c() {\n    1\n}\n");
+        let slice = doc.slice(..);
+        let grammar = crate::syntax::get_language(&crate::RUNTIME_DIR, "Rust").unwrap();
+        let mut parser = tree_sitter::Parser::new();
+        parser.set_language(grammar).unwrap();
+        let tree = parser.parse(doc.to_string(), None).unwrap();
+        let root = tree.root_node();
+
+        let goto = |pos, object, dir, count| {
+            goto_treesitter_object(
+                slice,
+                Range::point(pos),
+                object,
+                dir,
+                root,
+                &lang_config,
+                count,
+            )
+        };
+
+        assert_eq!(
+            goto(0, "function", Direction::Forward, 1),
+            Range::new(24, 39)
+        );
+        assert_eq!(
+            goto(30, "function", Direction::Backward, 1),
+            Range::new(39, 24)
+        );
+        assert_eq!(
+            goto(30, "function", Direction::Backward, 2),
+            Range::new(8, 0)
+        );
+        assert_eq!(goto(0, "class", Direction::Forward, 1), Range::new(11, 21));
+        // nothing to go to
+        assert_eq!(goto(30, "class", Direction::Forward, 1), Range::point(30));
+    }
 }
@@ -414,13 +414,39 @@ impl ChangeSet {
     }
 }
 
+/// What a transaction does, deciding whether the history groups it with the previous one into a
+/// single undo step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Annotation {
+    /// Typing a char.
+    InsertChar,
+    /// Deleting a char while typing, like with backspace.
+    DeleteChar,
+    Paste,
+    /// Formatting the document, by a language server or an external formatter.
+    Format,
+}
+
+impl Annotation {
+    /// Whether a transaction annotated `self` following one annotated `previous` is part of the
+    /// same undo step: typing, and formatting several times in a row.
+    pub fn groups_with(self, previous: Annotation) -> bool {
+        use Annotation::*;
+        matches!(
+            (previous, self),
+            (InsertChar | DeleteChar, InsertChar | DeleteChar) | (Format, Format)
+        )
+    }
+}
+
 /// Transaction represents a single undoable unit of changes. Several changes can be grouped into
 /// a single transaction.
 #[derive(Debug, Default, Clone)]
 pub struct Transaction {
     changes: ChangeSet,
     selection: Option<Selection>,
-    // effects, annotations
+    annotation: Option<Annotation>,
+    // effects
     // scroll_into_view
 }
 
@@ -430,6 +456,7 @@ impl Transaction {
         Self {
             changes: ChangeSet::new(doc),
             selection: None,
+            annotation: None,
         }
     }
 
@@ -462,6 +489,7 @@ impl Transaction {
         Self {
             changes,
             selection: None,
+            annotation: self.annotation,
         }
     }
 
@@ -470,6 +498,16 @@ impl Transaction {
         self
     }
 
+    /// What the transaction does, if it's grouped with others in the history.
+    pub fn annotation(&self) -> Option<Annotation> {
+        self.annotation
+    }
+
+    pub fn with_annotation(mut self, annotation: Annotation) -> Self {
+        self.annotation = Some(annotation);
+        self
+    }
+
     /// Generate a transaction from a set of changes.
     pub fn change<I>(doc: &Rope, changes: I) -> Self
     where
@@ -530,6 +568,7 @@ impl From<ChangeSet> for Transaction {
         Self {
             changes,
             selection: None,
+            annotation: None,
         }
     }
 }
@@ -0,0 +1,185 @@
+//! Soft wrapping: displaying lines that are longer than the view width on multiple rows.
+
+use crate::{
+    graphemes::{grapheme_width_at, prev_grapheme_boundary, RopeGraphemes},
+    line_ending::get_line_ending,
+    LineEnding, Position, RopeSlice,
+};
+use serde::Deserialize;
+use std::borrow::Cow;
+
+/// How lines that don't fit into the view are displayed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SoftWrap {
+    /// Lines are not wrapped, the view scrolls horizontally instead.
+    None,
+    /// Lines are wrapped at the last column that fits.
+    Char,
+    /// Lines are wrapped at the last word boundary that fits, falling back to the last column
+    /// for words longer than the view.
+    Word,
+}
+
+impl Default for SoftWrap {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
+/// Display width of `text` drawn from the start of a row, it shouldn't contain line endings.
+pub fn visual_width(text: RopeSlice, tab_width: usize) -> usize {
+    RopeGraphemes::new(text).fold(0, |col, grapheme| {
+        col + grapheme_width_at(&Cow::from(grapheme), col, tab_width)
+    })
+}
+
+/// Returns the char offsets (relative to the start of `line`) at which each row of `line`
+/// starts when wrapped to `width` columns. The first row always starts at 0.
+pub fn line_breaks(line: RopeSlice, width: usize, tab_width: usize, mode: SoftWrap) -> Vec<usize> {
+    let mut breaks = vec![0];
+
+    if mode == SoftWrap::None || width == 0 {
+        return breaks;
+    }
+
+    let mut col = 0;
+    let mut offset = 0;
+    // offset and column of the last word start seen on the current row
+    let mut word_start = None;
+    let mut prev_whitespace = false;
+
+    for grapheme in RopeGraphemes::new(line) {
+        if LineEnding::from_rope_slice(&grapheme).is_some() {
+            break;
+        }
+
+        let len = grapheme.len_chars();
+        let grapheme = Cow::from(grapheme);
+        let whitespace = grapheme.chars().all(char::is_whitespace);
+        let width_of_grapheme = grapheme_width_at(&grapheme, col, tab_width);
+
+        if mode == SoftWrap::Word && prev_whitespace && !whitespace {
+            word_start = Some((offset, col));
+        }
+
+        if col + width_of_grapheme > width && col > 0 {
+            let row_start = *breaks.last().unwrap();
+            if let Some((word_offset, word_col)) = word_start
+                .take()
+                .filter(|&(word_offset, _)| word_offset > row_start)
+            {
+                breaks.push(word_offset);
+                col -= word_col;
+            }
+            // the word alone doesn't fit either
+            if col + width_of_grapheme > width && col > 0 {
+                breaks.push(offset);
+                col = 0;
+            }
+        }
+
+        col += width_of_grapheme;
+        offset += len;
+        prev_whitespace = whitespace;
+    }
+
+    breaks
+}
+
+/// Converts a char offset within `line` into the row of the wrapped line it ends up on and the
+/// column within that row.
+pub fn visual_coords_at_pos(
+    line: RopeSlice,
+    breaks: &[usize],
+    pos: usize,
+    tab_width: usize,
+) -> Position {
+    let row = breaks.iter().rposition(|&start| start <= pos).unwrap_or(0);
+    let col = visual_width(line.slice(breaks[row]..pos), tab_width);
+    Position::new(row, col)
+}
+
+/// Converts a row and column of the wrapped `line` back into a char offset within the line. The
+/// column is clamped to the end of the row.
+pub fn pos_at_visual_coords(
+    line: RopeSlice,
+    breaks: &[usize],
+    coords: Position,
+    tab_width: usize,
+) -> usize {
+    let row = coords.row.min(breaks.len() - 1);
+    let start = breaks[row];
+    let last_row = row + 1 == breaks.len();
+    let end = match breaks.get(row + 1) {
+        Some(&next) => next,
+        None => line.len_chars() - get_line_ending(&line).map_or(0, |le| le.len_chars()),
+    };
+
+    let mut pos = start;
+    let mut col = 0;
+    for grapheme in RopeGraphemes::new(line.slice(start..end)) {
+        let width = grapheme_width_at(&Cow::from(grapheme), col, tab_width);
+        if col + width > coords.col {
+            break;
+        }
+        col += width;
+        pos += grapheme.len_chars();
+    }
+
+    // the end of a wrapped row is the start of the next one
+    if pos == end && !last_row {
+        prev_grapheme_boundary(line, pos)
+    } else {
+        pos
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Rope;
+
+    #[test]
+    fn test_line_breaks() {
+        let text = Rope::from("hello world foo\n");
+        let line = text.slice(..);
+
+        assert_eq!(line_breaks(line, 80, 4, SoftWrap::Char), vec![0]);
+        assert_eq!(line_breaks(line, 5, 4, SoftWrap::None), vec![0]);
+        assert_eq!(line_breaks(line, 5, 4, SoftWrap::Char), vec![0, 5, 10]);
+        assert_eq!(line_breaks(line, 8, 4, SoftWrap::Word), vec![0, 6, 12]);
+        // words longer than the width are broken up
+        assert_eq!(
+            line_breaks(line, 3, 4, SoftWrap::Word),
+            vec![0, 3, 6, 9, 12]
+        );
+        // tabs reach up to the next tab stop
+        let text = Rope::from("\tab");
+        assert_eq!(
+            line_breaks(text.slice(..), 5, 4, SoftWrap::Char),
+            vec![0, 2]
+        );
+        let text = Rope::from("a\tb");
+        assert_eq!(line_breaks(text.slice(..), 5, 4, SoftWrap::Char), vec![0]);
+        assert_eq!(visual_width(text.slice(..), 4), 5);
+        assert_eq!(visual_width(text.slice(..), 2), 3);
+    }
+
+    #[test]
+    fn test_visual_coords() {
+        let text = Rope::from("hello world foo\n");
+        let line = text.slice(..);
+        let breaks = line_breaks(line, 8, 4, SoftWrap::Word);
+
+        assert_eq!(visual_coords_at_pos(line, &breaks, 0, 4), (0, 0).into());
+        assert_eq!(visual_coords_at_pos(line, &breaks, 7, 4), (1, 1).into());
+        assert_eq!(visual_coords_at_pos(line, &breaks, 15, 4), (2, 3).into());
+
+        assert_eq!(pos_at_visual_coords(line, &breaks, (1, 1).into(), 4), 7);
+        // clamped to the last char of a wrapped row
+        assert_eq!(pos_at_visual_coords(line, &breaks, (0, 20).into(), 4), 5);
+        // last row may reach the line ending
+        assert_eq!(pos_at_visual_coords(line, &breaks, (2, 20).into(), 4), 15);
+    }
+}
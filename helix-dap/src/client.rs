@@ -0,0 +1,325 @@
+use crate::{
+    requests,
+    transport::{Outgoing, Payload, Request as RawRequest, Response, Transport},
+    DebuggerCapabilities, Error, Result, SourceBreakpoint, StackFrame,
+};
+
+use serde_json::Value;
+use std::future::Future;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+use std::time::Duration;
+use tokio::{
+    io::{BufReader, BufWriter},
+    process::{Child, Command},
+    sync::mpsc::{channel, UnboundedReceiver, UnboundedSender},
+};
+
+/// How long to wait for a response. Adapters may build the program on launch, so this is more
+/// generous than for language servers.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// A running debug adapter and the state of the session.
+#[derive(Debug)]
+pub struct Client {
+    id: usize,
+    _process: Child,
+    server_tx: UnboundedSender<Outgoing>,
+    /// Shared with requests made once others resolve.
+    request_counter: Arc<AtomicU64>,
+    capabilities: Option<DebuggerCapabilities>,
+
+    /// The thread that stopped last, which stepping commands apply to.
+    pub thread_id: Option<usize>,
+    /// The innermost frame of the stopped thread.
+    pub active_frame: Option<StackFrame>,
+}
+
+impl Client {
+    pub fn start(
+        cmd: &str,
+        args: &[String],
+        id: usize,
+    ) -> Result<(Self, UnboundedReceiver<(usize, Payload)>)> {
+        let mut process = Command::new(cmd)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            // make sure the process is reaped on drop
+            .kill_on_drop(true)
+            .spawn()?;
+
+        let writer = BufWriter::new(process.stdin.take().expect("Failed to open stdin"));
+        let reader = BufReader::new(process.stdout.take().expect("Failed to open stdout"));
+        let stderr = BufReader::new(process.stderr.take().expect("Failed to open stderr"));
+
+        let (server_rx, server_tx) = Transport::start(reader, writer, stderr, id);
+
+        let client = Self {
+            id,
+            _process: process,
+            server_tx,
+            request_counter: Arc::new(AtomicU64::new(1)),
+            capabilities: None,
+            thread_id: None,
+            active_frame: None,
+        };
+
+        Ok((client, server_rx))
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    fn next_seq(&self) -> u64 {
+        self.request_counter.fetch_add(1, Ordering::Relaxed)
+    }
+
+    pub fn capabilities(&self) -> &DebuggerCapabilities {
+        self.capabilities
+            .as_ref()
+            .expect("debug adapter not yet initialized!")
+    }
+
+    /// Sends a request to the adapter, the future resolves into the response body.
+    fn call<R: crate::Request>(
+        &self,
+        arguments: R::Arguments,
+    ) -> impl Future<Output = Result<R::Result>> {
+        request::<R>(self.server_tx.clone(), self.next_seq(), arguments)
+    }
+
+    /// Replies to a request from the adapter.
+    pub fn reply(
+        &self,
+        request: &RawRequest,
+        result: std::result::Result<Value, String>,
+    ) -> Result<()> {
+        let (success, message, body) = match result {
+            Ok(body) => (true, None, Some(body)),
+            Err(message) => (false, Some(message), None),
+        };
+        let response = Response {
+            seq: self.next_seq(),
+            request_seq: request.seq,
+            success,
+            command: request.command.clone(),
+            message,
+            body,
+        };
+        self.server_tx
+            .send(Outgoing::Response(response))
+            .map_err(|e| Error::Other(e.into()))
+    }
+
+    pub async fn initialize(&mut self, adapter_id: String) -> Result<()> {
+        let arguments = requests::InitializeArguments {
+            client_id: "helix".to_owned(),
+            adapter_id,
+            lines_start_at1: true,
+            columns_start_at1: true,
+            path_format: "path".to_owned(),
+        };
+
+        let capabilities = self.call::<requests::Initialize>(arguments).await?;
+        self.capabilities = Some(capabilities.unwrap_or_default());
+        Ok(())
+    }
+
+    pub fn launch(&self, arguments: Value) -> impl Future<Output = Result<Value>> {
+        self.call::<requests::Launch>(arguments)
+    }
+
+    /// Tells the adapter the breakpoints are set. Adapters that don't need it resolve right away.
+    pub fn configuration_done(&self) -> impl Future<Output = Result<Value>> {
+        let needed = self.capabilities().supports_configuration_done_request;
+        let request = self.call::<requests::ConfigurationDone>(());
+        async move {
+            if needed {
+                request.await
+            } else {
+                Ok(Value::Null)
+            }
+        }
+    }
+
+    /// Replaces all breakpoints of the file at `path`.
+    pub fn set_breakpoints(
+        &self,
+        path: PathBuf,
+        breakpoints: Vec<SourceBreakpoint>,
+    ) -> impl Future<Output = Result<Vec<crate::Breakpoint>>> {
+        let arguments = requests::SetBreakpointsArguments {
+            source: crate::Source {
+                name: path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned()),
+                path: Some(path),
+            },
+            breakpoints,
+        };
+        let request = self.call::<requests::SetBreakpoints>(arguments);
+        async move { Ok(request.await?.breakpoints) }
+    }
+
+    pub fn continue_thread(&self, thread_id: usize) -> impl Future<Output = Result<Value>> {
+        self.call::<requests::Continue>(requests::ThreadArguments { thread_id })
+    }
+
+    pub fn next(&self, thread_id: usize) -> impl Future<Output = Result<Value>> {
+        self.call::<requests::Next>(requests::ThreadArguments { thread_id })
+    }
+
+    pub fn step_in(&self, thread_id: usize) -> impl Future<Output = Result<Value>> {
+        self.call::<requests::StepIn>(requests::ThreadArguments { thread_id })
+    }
+
+    pub fn step_out(&self, thread_id: usize) -> impl Future<Output = Result<Value>> {
+        self.call::<requests::StepOut>(requests::ThreadArguments { thread_id })
+    }
+
+    pub fn pause(&self, thread_id: usize) -> impl Future<Output = Result<Value>> {
+        self.call::<requests::Pause>(requests::ThreadArguments { thread_id })
+    }
+
+    pub fn threads(&self) -> impl Future<Output = Result<Vec<crate::Thread>>> {
+        let request = self.call::<requests::Threads>(());
+        async move { Ok(request.await?.threads) }
+    }
+
+    pub fn stack_trace(&self, thread_id: usize) -> impl Future<Output = Result<Vec<StackFrame>>> {
+        let request = self.call::<requests::StackTrace>(requests::ThreadArguments { thread_id });
+        async move { Ok(request.await?.stack_frames) }
+    }
+
+    pub fn scopes(&self, frame_id: usize) -> impl Future<Output = Result<Vec<crate::Scope>>> {
+        let request = self.call::<requests::Scopes>(requests::ScopesArguments { frame_id });
+        async move { Ok(request.await?.scopes) }
+    }
+
+    pub fn variables(
+        &self,
+        variables_reference: usize,
+    ) -> impl Future<Output = Result<Vec<crate::Variable>>> {
+        let request = self.call::<requests::Variables>(requests::VariablesArguments {
+            variables_reference,
+        });
+        async move { Ok(request.await?.variables) }
+    }
+
+    /// The variables of the scopes of the frame `frame_id`, leaving out expensive scopes like
+    /// globals.
+    pub fn frame_variables(
+        &self,
+        frame_id: usize,
+    ) -> impl Future<Output = Result<Vec<crate::Variable>>> {
+        let scopes = self.scopes(frame_id);
+        let server_tx = self.server_tx.clone();
+        let request_counter = self.request_counter.clone();
+        async move {
+            let mut variables = Vec::new();
+            for scope in scopes.await? {
+                if scope.expensive {
+                    continue;
+                }
+                let response = request::<requests::Variables>(
+                    server_tx.clone(),
+                    request_counter.fetch_add(1, Ordering::Relaxed),
+                    requests::VariablesArguments {
+                        variables_reference: scope.variables_reference,
+                    },
+                )
+                .await?;
+                variables.extend(response.variables);
+            }
+            Ok(variables)
+        }
+    }
+
+    /// Ends the session, terminating the debugged program.
+    pub fn disconnect(&self) -> impl Future<Output = Result<Value>> {
+        self.call::<requests::Disconnect>(requests::DisconnectArguments {
+            terminate_debuggee: true,
+        })
+    }
+}
+
+/// Sends the request numbered `seq` to the adapter, the future resolves into the response body.
+async fn request<R: crate::Request>(
+    server_tx: UnboundedSender<Outgoing>,
+    seq: u64,
+    arguments: R::Arguments,
+) -> Result<R::Result> {
+    let arguments = serde_json::to_value(arguments)?;
+    let request = RawRequest {
+        seq,
+        command: R::COMMAND.to_string(),
+        arguments: match arguments {
+            Value::Null => None,
+            arguments => Some(arguments),
+        },
+    };
+
+    let (tx, mut rx) = channel(1);
+    server_tx
+        .send(Outgoing::Request {
+            chan: tx,
+            value: request,
+        })
+        .map_err(|e| Error::Other(e.into()))?;
+
+    let body = tokio::time::timeout(REQUEST_TIMEOUT, rx.recv())
+        .await
+        .map_err(|_| Error::Timeout)?
+        .ok_or(Error::StreamClosed)??;
+    Ok(serde_json::from_value(body)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Event;
+
+    #[tokio::test]
+    async fn test_exit_tagged_with_session() {
+        // an adapter that exits right away ends its own session only
+        let (_client, mut events) = Client::start("true", &[], 7).unwrap();
+        let (id, payload) = events.recv().await.unwrap();
+        assert_eq!(id, 7);
+        assert!(
+            matches!(payload, Payload::Event(Event { ref event, .. }) if event == "terminated")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_frame_variables() {
+        let message = |json: &str| format!("Content-Length: {}\r\n\r\n{}", json.len(), json);
+        let scopes = message(
+            r#"{"seq": 1, "type": "response", "request_seq": 1, "success": true,
+                "command": "scopes", "body": {"scopes": [
+                    {"name": "Globals", "variablesReference": 1, "expensive": true},
+                    {"name": "Locals", "variablesReference": 2}]}}"#,
+        );
+        let variables = message(
+            r#"{"seq": 2, "type": "response", "request_seq": 2, "success": true,
+                "command": "variables", "body": {"variables": [{"name": "x", "value": "1"}]}}"#,
+        );
+        // the variables are only asked for once the scopes are answered, the line read last ends
+        // with the header of that request
+        let script = format!(
+            "read header; read blank; printf '%s' '{}'; read request; printf '%s' '{}'; sleep 5",
+            scopes, variables
+        );
+        let (client, _events) = Client::start("sh", &["-c".to_owned(), script], 0).unwrap();
+
+        let variables = client.frame_variables(0).await.unwrap();
+        assert_eq!(variables.len(), 1);
+        assert_eq!(variables[0].name, "x");
+    }
+}
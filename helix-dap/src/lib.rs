@@ -0,0 +1,27 @@
+mod client;
+mod transport;
+mod types;
+
+pub use client::Client;
+pub use transport::{Event, Payload, Request as RawRequest, Response};
+pub use types::*;
+
+use thiserror::Error;
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("debug adapter error: {0}")]
+    Protocol(String),
+    #[error("failed to parse: {0}")]
+    Parse(#[from] serde_json::Error),
+    #[error("IO Error: {0}")]
+    IO(#[from] std::io::Error),
+    #[error("request timed out")]
+    Timeout,
+    #[error("debug adapter closed the stream")]
+    StreamClosed,
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
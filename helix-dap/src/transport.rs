@@ -0,0 +1,282 @@
+use crate::{Error, Result};
+use anyhow::Context;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use tokio::{
+    io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader, BufWriter},
+    process::{ChildStderr, ChildStdin, ChildStdout},
+    sync::mpsc::{unbounded_channel, Sender, UnboundedReceiver, UnboundedSender},
+};
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Request {
+    pub seq: u64,
+    pub command: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub arguments: Option<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Response {
+    pub seq: u64,
+    #[serde(rename = "request_seq")]
+    pub request_seq: u64,
+    pub success: bool,
+    pub command: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Event {
+    pub seq: u64,
+    pub event: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<Value>,
+}
+
+/// A message of the protocol, in either direction.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Payload {
+    Request(Request),
+    Response(Response),
+    Event(Event),
+}
+
+/// A message sent from the client to the adapter.
+#[derive(Debug)]
+pub(crate) enum Outgoing {
+    Request {
+        chan: Sender<Result<Value>>,
+        value: Request,
+    },
+    Response(Response),
+}
+
+#[derive(Debug)]
+pub struct Transport {
+    id: usize,
+    client_tx: UnboundedSender<(usize, Payload)>,
+    client_rx: UnboundedReceiver<Outgoing>,
+
+    pending_requests: HashMap<u64, Sender<Result<Value>>>,
+
+    adapter_stdin: BufWriter<ChildStdin>,
+    adapter_stdout: BufReader<ChildStdout>,
+    adapter_stderr: BufReader<ChildStderr>,
+}
+
+impl Transport {
+    pub(crate) fn start(
+        adapter_stdout: BufReader<ChildStdout>,
+        adapter_stdin: BufWriter<ChildStdin>,
+        adapter_stderr: BufReader<ChildStderr>,
+        id: usize,
+    ) -> (
+        UnboundedReceiver<(usize, Payload)>,
+        UnboundedSender<Outgoing>,
+    ) {
+        let (client_tx, rx) = unbounded_channel();
+        let (tx, client_rx) = unbounded_channel();
+
+        let transport = Self {
+            id,
+            adapter_stdout,
+            adapter_stdin,
+            adapter_stderr,
+            client_tx,
+            client_rx,
+            pending_requests: HashMap::default(),
+        };
+
+        tokio::spawn(transport.duplex());
+
+        (rx, tx)
+    }
+
+    async fn recv_adapter_message(
+        reader: &mut (impl AsyncBufRead + Unpin + Send),
+        buffer: &mut String,
+    ) -> Result<Payload> {
+        let mut content_length = None;
+        loop {
+            buffer.truncate(0);
+            if reader.read_line(buffer).await? == 0 {
+                return Err(Error::StreamClosed);
+            }
+            let header = buffer.trim();
+
+            if header.is_empty() {
+                break;
+            }
+
+            if let Some(value) = header.strip_prefix("Content-Length: ") {
+                content_length = Some(value.parse().context("invalid content length")?);
+            }
+        }
+
+        let content_length = content_length.context("missing content length")?;
+
+        let mut content = vec![0; content_length];
+        reader.read_exact(&mut content).await?;
+        let msg = String::from_utf8(content).context("invalid utf8 from debug adapter")?;
+
+        info!("<- DAP {}", msg);
+
+        Ok(serde_json::from_str(&msg)?)
+    }
+
+    async fn recv_adapter_error(
+        err: &mut (impl AsyncBufRead + Unpin + Send),
+        buffer: &mut String,
+    ) -> Result<()> {
+        buffer.truncate(0);
+        if err.read_line(buffer).await? == 0 {
+            // stderr was closed, but the adapter can still talk on stdout
+            std::future::pending::<()>().await;
+        }
+        error!("DAP err <- {}", buffer);
+
+        Ok(())
+    }
+
+    async fn send_to_adapter(&mut self, outgoing: Outgoing) -> Result<()> {
+        let payload = match outgoing {
+            Outgoing::Request { chan, value } => {
+                self.pending_requests.insert(value.seq, chan);
+                Payload::Request(value)
+            }
+            Outgoing::Response(response) => Payload::Response(response),
+        };
+        let json = serde_json::to_string(&payload)?;
+
+        info!("-> DAP {}", json);
+
+        self.adapter_stdin
+            .write_all(format!("Content-Length: {}\r\n\r\n", json.len()).as_bytes())
+            .await?;
+        self.adapter_stdin.write_all(json.as_bytes()).await?;
+        self.adapter_stdin.flush().await?;
+
+        Ok(())
+    }
+
+    async fn process_adapter_message(&mut self, payload: Payload) -> Result<()> {
+        match payload {
+            Payload::Response(response) => {
+                let tx = match self.pending_requests.remove(&response.request_seq) {
+                    Some(tx) => tx,
+                    None => {
+                        error!("response to unknown request {}", response.request_seq);
+                        return Ok(());
+                    }
+                };
+                let result = if response.success {
+                    Ok(response.body.unwrap_or(Value::Null))
+                } else {
+                    Err(Error::Protocol(
+                        response.message.unwrap_or(response.command),
+                    ))
+                };
+
+                if tx.send(result).await.is_err() {
+                    error!(
+                        "Tried sending response into a closed channel (seq={}), original request likely timed out",
+                        response.request_seq
+                    );
+                }
+            }
+            payload => {
+                self.client_tx
+                    .send((self.id, payload))
+                    .context("failed to send a message to the client")?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn duplex(mut self) {
+        let mut recv_buffer = String::new();
+        let mut err_buffer = String::new();
+        loop {
+            tokio::select! {
+                // client -> adapter
+                msg = self.client_rx.recv() => {
+                    match msg {
+                        Some(msg) => {
+                            if let Err(err) = self.send_to_adapter(msg).await {
+                                error!("DAP err: -> {}", err);
+                                break;
+                            }
+                        }
+                        None => break
+                    }
+                }
+                // adapter -> client
+                msg = Self::recv_adapter_message(&mut self.adapter_stdout, &mut recv_buffer) => {
+                    let result = match msg {
+                        Ok(msg) => self.process_adapter_message(msg).await,
+                        Err(err) => Err(err),
+                    };
+                    if let Err(err) = result {
+                        error!("DAP err: <- {}", err);
+                        break;
+                    }
+                }
+                _msg = Self::recv_adapter_error(&mut self.adapter_stderr, &mut err_buffer) => {}
+            }
+        }
+
+        // let the client know the adapter is gone
+        let _ = self.client_tx.send((
+            self.id,
+            Payload::Event(Event {
+                seq: 0,
+                event: "terminated".to_owned(),
+                body: None,
+            }),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_payload() {
+        let payload: Payload = serde_json::from_str(
+            r#"{"seq": 3, "type": "response", "request_seq": 1, "success": true,
+                "command": "initialize", "body": {}}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            payload,
+            Payload::Response(Response { request_seq: 1, .. })
+        ));
+
+        let payload: Payload =
+            serde_json::from_str(r#"{"seq": 4, "type": "event", "event": "initialized"}"#).unwrap();
+        assert!(
+            matches!(payload, Payload::Event(Event { ref event, .. }) if event == "initialized")
+        );
+
+        let request = Payload::Request(Request {
+            seq: 1,
+            command: "threads".to_owned(),
+            arguments: None,
+        });
+        assert_eq!(
+            serde_json::to_string(&request).unwrap(),
+            r#"{"type":"request","seq":1,"command":"threads"}"#
+        );
+    }
+}
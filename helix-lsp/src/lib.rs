@@ -13,6 +13,7 @@ use helix_core::syntax::LanguageConfiguration;
 
 use std::{
     collections::{hash_map::Entry, HashMap},
+    path::Path,
     sync::{
         atomic::{AtomicUsize, Ordering},
         Arc,
@@ -203,6 +204,7 @@ pub mod util {
 #[derive(Debug, PartialEq, Clone)]
 pub enum MethodCall {
     WorkDoneProgressCreate(lsp::WorkDoneProgressCreateParams),
+    ApplyWorkspaceEdit(lsp::ApplyWorkspaceEditParams),
 }
 
 impl MethodCall {
@@ -215,6 +217,12 @@ impl MethodCall {
                     .expect("Failed to parse WorkDoneCreate params");
                 Self::WorkDoneProgressCreate(params)
             }
+            lsp::request::ApplyWorkspaceEdit::METHOD => {
+                let params: lsp::ApplyWorkspaceEditParams = params
+                    .parse()
+                    .expect("Failed to parse ApplyWorkspaceEdit params");
+                Self::ApplyWorkspaceEdit(params)
+            }
             _ => {
                 log::warn!("unhandled lsp request: {}", method);
                 return None;
@@ -301,7 +309,13 @@ impl Registry {
             .map(|(_, client)| client.as_ref())
     }
 
-    pub fn get(&mut self, language_config: &LanguageConfiguration) -> Result<Arc<Client>> {
+    /// The client of the language server of `language_config`, started for the project at `root`
+    /// if it isn't running yet.
+    pub fn get(
+        &mut self,
+        language_config: &LanguageConfiguration,
+        root: Option<&Path>,
+    ) -> Result<Arc<Client>> {
         if let Some(config) = &language_config.language_server {
             // avoid borrow issues
             let inner = &mut self.inner;
@@ -319,7 +333,7 @@ impl Registry {
                         id,
                     )?;
                     // TODO: run this async without blocking
-                    futures_executor::block_on(client.initialize())?;
+                    futures_executor::block_on(client.initialize(root))?;
                     s_incoming.push(UnboundedReceiverStream::new(incoming));
                     let client = Arc::new(client);
 
@@ -332,6 +346,14 @@ impl Registry {
         }
     }
 
+    /// The client of the language server of `language_config` if it's running, without starting
+    /// it.
+    pub fn get_running(&self, language_config: &LanguageConfiguration) -> Option<Arc<Client>> {
+        self.inner
+            .get(&language_config.scope)
+            .map(|(_, client)| client.clone())
+    }
+
     pub fn iter_clients(&self) -> impl Iterator<Item = &Arc<Client>> {
         self.inner.values().map(|(_, client)| client)
     }
@@ -1,8 +1,22 @@
-use helix_core::syntax;
-use helix_lsp::{lsp, util::lsp_pos_to_pos, LspProgressMap};
-use helix_view::{theme, Editor};
-
-use crate::{args::Args, compositor::Compositor, config::Config, job::Jobs, ui};
+use helix_core::{
+    line_ending::{last_line_index, line_end_char_index},
+    search, syntax, Position, RopeSlice, Selection,
+};
+use helix_lsp::{
+    lsp,
+    util::{lsp_pos_to_pos, lsp_range_to_range, pos_to_lsp_pos},
+    LspProgressMap,
+};
+use helix_view::{history, recovery, theme, view::SymbolHighlights, Document, DocumentId, Editor};
+
+use crate::{
+    args::Args,
+    commands::{self, align_view, Align},
+    compositor::Compositor,
+    config::Config,
+    job::{self, Jobs},
+    plugin, ui,
+};
 
 use log::error;
 
@@ -12,13 +26,51 @@ use std::{
     time::{Duration, Instant},
 };
 
-use anyhow::Error;
+use anyhow::{Context, Error};
 
 use crossterm::{
-    event::{Event, EventStream},
+    event::{self, Event, EventStream, KeyCode, KeyEvent, KeyModifiers},
     execute, terminal,
 };
 
+/// How long input has to stop before the editor is considered idle.
+const IDLE_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// How often the contents of modified documents are backed up for crash recovery.
+const BACKUP_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How often followed documents, like the log, are checked for changes on disk.
+const FOLLOW_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The occurrences of a symbol other than the one at `cursor`, sorted for rendering.
+fn symbol_highlights(
+    doc: DocumentId,
+    version: i32,
+    cursor: usize,
+    mut ranges: Vec<std::ops::Range<usize>>,
+) -> SymbolHighlights {
+    ranges.retain(|range| !range.contains(&cursor));
+    ranges.sort_by_key(|range| range.start);
+    SymbolHighlights {
+        doc,
+        version,
+        cursor,
+        ranges,
+    }
+}
+
+/// The char index at `position`, or the closest one in the text, staying on the last line and
+/// before the line ending.
+fn clamped_pos_at_coords(text: RopeSlice, position: Position) -> usize {
+    let row = position.row.min(last_line_index(&text));
+    let line_end = line_end_char_index(&text, row);
+    // a last line without line ending ends on its last character
+    let last_char = text.len_chars().saturating_sub(1);
+    helix_core::pos_at_coords(text, Position::new(row, position.col))
+        .min(line_end)
+        .min(last_char)
+}
+
 pub struct Application {
     compositor: Compositor,
     editor: Editor,
@@ -40,6 +92,26 @@ pub struct Application {
     lsp_progress: LspProgressMap,
 }
 
+/// The keys typing `text` pasted in the terminal presses, used where it isn't inserted as is.
+fn pasted_keys(text: &str) -> Vec<KeyEvent> {
+    text.chars()
+        .map(|ch| {
+            let code = match ch {
+                '\n' => KeyCode::Enter,
+                '\t' => KeyCode::Tab,
+                ch => KeyCode::Char(ch),
+            };
+            KeyEvent::new(code, KeyModifiers::NONE)
+        })
+        .collect()
+}
+
+/// Terminals send the line breaks of pasted text as carriage returns, which are turned back into
+/// line feeds.
+fn normalize_pasted(text: &str) -> String {
+    text.replace("\r\n", "\n").replace('\r', "\n")
+}
+
 impl Application {
     pub fn new(args: Args, mut config: Config) -> Result<Self, Error> {
         use helix_view::editor::Action;
@@ -72,35 +144,100 @@ impl Application {
         let syn_loader_conf = toml::from_slice(lang_conf).expect("Could not parse languages.toml");
         let syn_loader = std::sync::Arc::new(syntax::Loader::new(syn_loader_conf));
 
-        let mut editor = Editor::new(size, theme_loader.clone(), syn_loader.clone());
+        // applications may be created more than once, like in tests, but hooks are global
+        static REGISTER_HOOKS: std::sync::Once = std::sync::Once::new();
+        REGISTER_HOOKS.call_once(|| helix_view::events::register(plugin::hook));
+
+        let mut editor = Editor::new(
+            size,
+            theme_loader.clone(),
+            syn_loader.clone(),
+            config.editor.clone(),
+        );
 
         let editor_view = Box::new(ui::EditorView::new(std::mem::take(&mut config.keys)));
         compositor.push(editor_view);
 
+        if args.load_tutor {
+            let doc = Document::from_stream(&mut std::fs::File::open(helix_core::tutor_file())?)
+                .context("unable to load the tutor")?;
+            editor.new_file_from_document(Action::VerticalSplit, doc);
+        }
+
+        if args.read_stdin {
+            // crossterm reads keys from `/dev/tty` when stdin isn't a terminal, so the editor is
+            // still usable once the pipe is drained
+            let doc =
+                Document::from_stream(&mut std::io::stdin()).context("unable to read stdin")?;
+            editor.new_file_from_document(Action::VerticalSplit, doc);
+        }
+
         if !args.files.is_empty() {
-            let first = &args.files[0]; // we know it's not empty
+            let first = &args.files[0].0; // we know it's not empty
             if first.is_dir() {
                 editor.new_file(Action::VerticalSplit);
-                compositor.push(Box::new(ui::file_picker(first.clone())));
+                compositor.push(Box::new(ui::file_picker(
+                    first.clone(),
+                    &editor.config.file_picker,
+                )));
             } else {
                 let nr_of_files = args.files.len();
                 editor.open(first.to_path_buf(), Action::VerticalSplit)?;
-                for file in args.files {
+                for (file, position) in args.files {
                     if file.is_dir() {
                         return Err(anyhow::anyhow!(
                             "expected a path to file, found a directory. (to open a directory pass it as first argument)"
                         ));
                     } else {
-                        editor.open(file.to_path_buf(), Action::Load)?;
+                        let id = editor.open(file.to_path_buf(), Action::Load)?;
+                        let view_id = editor.tree.focus;
+                        let doc = editor.document_mut(id).unwrap();
+                        let pos = clamped_pos_at_coords(doc.text().slice(..), position);
+                        doc.set_selection(view_id, Selection::point(pos));
                     }
                 }
+                // the first file is the one shown, with its position in the middle
+                let (view, doc) = current!(editor);
+                align_view(doc, view, Align::Center);
                 editor.set_status(format!("Loaded {} files.", nr_of_files));
             }
-        } else {
+        } else if !args.read_stdin && !args.load_tutor {
             editor.new_file(Action::VerticalSplit);
         }
 
         editor.set_theme(theme);
+        history::load(&mut editor.registers);
+
+        // offer to restore the changes a crashed session didn't save
+        let mut backups: Vec<_> = editor
+            .documents()
+            .filter_map(|doc| recovery::find_backup(doc).map(|backup| (doc.id(), backup)))
+            .collect();
+        if !backups.is_empty() {
+            let prompt = ui::Prompt::new(
+                format!(
+                    "restore unsaved changes to {} file(s) from a previous session? (y/n):",
+                    backups.len()
+                ),
+                None,
+                |_input: &str| Vec::new(),
+                move |cx, input, event| {
+                    if event != ui::PromptEvent::Validate || !input.starts_with('y') {
+                        return;
+                    }
+                    for (id, backup) in std::mem::take(&mut backups) {
+                        let doc = match cx.editor.document_mut(id) {
+                            Some(doc) => doc,
+                            None => continue,
+                        };
+                        if let Some(&view_id) = doc.selections().keys().next() {
+                            recovery::restore(doc, view_id, &backup);
+                        }
+                    }
+                },
+            );
+            compositor.push(Box::new(prompt));
+        }
 
         let app = Self {
             compositor,
@@ -136,6 +273,16 @@ impl Application {
         let mut reader = EventStream::new();
         let mut last_render = Instant::now();
         let deadline = Duration::from_secs(1) / 60;
+        let mut backup_interval = tokio::time::interval_at(
+            tokio::time::Instant::now() + BACKUP_INTERVAL,
+            BACKUP_INTERVAL,
+        );
+        let mut follow_interval = tokio::time::interval(FOLLOW_INTERVAL);
+
+        // fires once input stopped for a while, for work that doesn't need to happen on every key
+        let idle_timer = tokio::time::sleep(IDLE_TIMEOUT);
+        tokio::pin!(idle_timer);
+        let mut idle_pending = false;
 
         self.render();
 
@@ -151,7 +298,9 @@ impl Application {
                 biased;
 
                 event = reader.next() => {
-                    self.handle_terminal_events(event)
+                    self.handle_terminal_events(event);
+                    idle_timer.as_mut().reset(tokio::time::Instant::now() + IDLE_TIMEOUT);
+                    idle_pending = true;
                 }
                 Some((id, call)) = self.editor.language_servers.incoming.next() => {
                     self.handle_language_server_message(call, id).await;
@@ -162,6 +311,10 @@ impl Application {
                         last_render = Instant::now();
                     }
                 }
+                Some((id, payload)) = self.editor.debugger_events.next() => {
+                    self.handle_debugger_message(id, payload).await;
+                    self.render();
+                }
                 Some(callback) = self.jobs.futures.next() => {
                     self.jobs.handle_callback(&mut self.editor, &mut self.compositor, callback);
                     self.render();
@@ -170,8 +323,141 @@ impl Application {
                     self.jobs.handle_callback(&mut self.editor, &mut self.compositor, callback);
                     self.render();
                 }
+                _ = &mut idle_timer, if idle_pending => {
+                    idle_pending = false;
+                    self.handle_idle();
+                }
+                _ = backup_interval.tick() => {
+                    if let Err(err) = recovery::write_backups(&self.editor) {
+                        log::error!("failed to write backups: {}", err);
+                    }
+                }
+                _ = follow_interval.tick() => {
+                    if self.reload_followed_documents() {
+                        self.render();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Updates what can wait until the user stops typing.
+    pub fn handle_idle(&mut self) {
+        let mut changed = false;
+        for doc in self.editor.documents.values_mut() {
+            changed |= doc.update_diff();
+            if let Some(dictionary) = &self.editor.dictionary {
+                changed |= doc.update_spelling(dictionary);
+            }
+        }
+        if self.editor.config.symbol_highlights {
+            changed |= self.update_symbol_highlights();
+        }
+        if changed {
+            self.render();
+        }
+    }
+
+    /// Finds the other occurrences of the symbol under the primary cursor of the focused view,
+    /// with the language server if it supports it, or else by matching the word in the lines on
+    /// screen. Returns whether they were found right away.
+    fn update_symbol_highlights(&mut self) -> bool {
+        let (view, doc) = current!(self.editor);
+        let cursor = doc.selection(view.id).cursor();
+        let (doc_id, version) = (doc.id(), doc.version());
+        if view.symbol_highlights(doc).is_some() {
+            return false;
+        }
+
+        let language_server = doc.language_server().filter(|server| {
+            matches!(
+                server.capabilities().document_highlight_provider,
+                Some(lsp::OneOf::Left(true)) | Some(lsp::OneOf::Right(_))
+            )
+        });
+        let language_server = match language_server {
+            Some(language_server) => language_server,
+            None => {
+                let lines = view.first_line..view.last_line(doc) + 1;
+                let ranges = search::word_occurrences(doc.text().slice(..), cursor, lines);
+                view.symbol_highlights = Some(symbol_highlights(doc_id, version, cursor, ranges));
+                return true;
+            }
+        };
+
+        let offset_encoding = language_server.offset_encoding();
+        let pos = pos_to_lsp_pos(doc.text(), cursor, offset_encoding);
+        let future = language_server.text_document_document_highlight(doc.identifier(), pos, None);
+        let view_id = view.id;
+        self.jobs.callback(async move {
+            // servers fail on positions without a symbol, which isn't worth an error
+            let highlights: Vec<lsp::DocumentHighlight> = match future.await {
+                Ok(json) => serde_json::from_value::<Option<_>>(json)?.unwrap_or_default(),
+                Err(err) => {
+                    log::debug!("document highlight failed: {}", err);
+                    Vec::new()
+                }
+            };
+            let call: job::Callback = Box::new(move |editor: &mut Editor, _| {
+                let doc = match editor.documents.get(doc_id) {
+                    Some(doc) if doc.version() == version => doc,
+                    _ => return,
+                };
+                let ranges = highlights
+                    .into_iter()
+                    .filter_map(|highlight| {
+                        lsp_range_to_range(doc.text(), highlight.range, offset_encoding)
+                    })
+                    .map(|range| range.from()..range.to())
+                    .collect();
+                if let Some((view, _)) =
+                    editor.tree.views_mut().find(|(view, _)| view.id == view_id)
+                {
+                    view.symbol_highlights =
+                        Some(symbol_highlights(doc_id, version, cursor, ranges));
+                }
+            });
+            Ok(call)
+        });
+        false
+    }
+
+    /// Reloads the followed documents whose file changed. Views with the cursor at the end of
+    /// the document keep it there. Returns whether any document changed.
+    fn reload_followed_documents(&mut self) -> bool {
+        let editor = &mut self.editor;
+        let mut changed = false;
+        for doc in editor.documents.values_mut() {
+            if !doc.follow || doc.is_modified() || !doc.is_modified_externally() {
+                continue;
+            }
+            // the start of the last line, past which the cursor can't be
+            let end = commands::last_line_start(doc.text().slice(..));
+            let views: Vec<_> = editor
+                .tree
+                .views()
+                .filter(|(view, _)| view.doc == doc.id())
+                .map(|(view, _)| (view.id, doc.selection(view.id).cursor() >= end))
+                .collect();
+            let (view_id, _) = match views.first() {
+                Some(view) => *view,
+                None => continue,
+            };
+            if let Err(err) = doc.reload(view_id) {
+                log::error!("failed to reload {:?}: {}", doc.path(), err);
+                continue;
+            }
+
+            let end = commands::last_line_start(doc.text().slice(..));
+            for (view_id, at_end) in views {
+                if at_end {
+                    doc.set_selection(view_id, Selection::point(end));
+                }
+                editor.tree.get_mut(view_id).ensure_cursor_in_view(doc);
             }
+            changed = true;
         }
+        changed
     }
 
     pub fn handle_terminal_events(&mut self, event: Option<Result<Event, crossterm::ErrorKind>>) {
@@ -188,16 +474,133 @@ impl Application {
                 self.compositor
                     .handle_event(Event::Resize(width, height), &mut cx)
             }
+            Some(Ok(Event::Paste(text))) => {
+                let text = normalize_pasted(&text);
+                let mut redraw = self.compositor.handle_paste(&text, &mut cx);
+                if !redraw {
+                    // the keys typing the text run commands
+                    for key in pasted_keys(&text) {
+                        redraw |= self.compositor.handle_event(Event::Key(key), &mut cx);
+                    }
+                }
+                redraw
+            }
             Some(Ok(event)) => self.compositor.handle_event(event, &mut cx),
             Some(Err(x)) => panic!("{}", x),
             None => panic!(),
         };
+        self.after_terminal_event(should_redraw);
+    }
 
-        if should_redraw && !self.editor.should_close() {
+    fn after_terminal_event(&mut self, should_redraw: bool) {
+        // documents refuse edits when read-only, the commands attempting them don't know
+        let refused_edit = !self.editor.should_close() && doc_mut!(self.editor).take_refused_edit();
+        if refused_edit {
+            self.editor
+                .set_error("The document is read-only".to_owned());
+        }
+
+        if (should_redraw || refused_edit) && !self.editor.should_close() {
             self.render();
         }
     }
 
+    /// Handles a message of the debug adapter of the session `id`, unless that session ended.
+    pub async fn handle_debugger_message(&mut self, id: usize, payload: helix_dap::Payload) {
+        use helix_dap::{events, Event, Payload};
+
+        let debugger = match self.editor.debugger.as_mut() {
+            Some(debugger) if debugger.id() == id => debugger,
+            _ => return,
+        };
+
+        let (event, body) = match payload {
+            Payload::Event(Event { event, body, .. }) => (event, body.unwrap_or_default()),
+            Payload::Request(request) => {
+                // reverse requests like `runInTerminal` aren't supported
+                let message = format!("{} is not supported", request.command);
+                if let Err(err) = debugger.reply(&request, Err(message)) {
+                    error!("failed to reply to the debug adapter: {}", err);
+                }
+                return;
+            }
+            Payload::Response(_) => return,
+        };
+
+        match event.as_str() {
+            "initialized" => {
+                for (path, breakpoints) in &self.editor.breakpoints {
+                    let request = debugger.set_breakpoints(path.clone(), breakpoints.clone());
+                    if let Err(err) = request.await {
+                        error!("failed to set breakpoints in {:?}: {}", path, err);
+                    }
+                }
+                if let Err(err) = debugger.configuration_done().await {
+                    self.editor
+                        .set_error(format!("Failed to start the debug session: {}", err));
+                }
+            }
+            "stopped" => {
+                let stopped: events::Stopped = match serde_json::from_value(body) {
+                    Ok(stopped) => stopped,
+                    Err(err) => {
+                        error!("invalid stopped event: {}", err);
+                        return;
+                    }
+                };
+                if stopped.thread_id.is_some() {
+                    debugger.thread_id = stopped.thread_id;
+                }
+                if let Some(thread_id) = debugger.thread_id {
+                    match debugger.stack_trace(thread_id).await {
+                        Ok(frames) => debugger.active_frame = frames.into_iter().next(),
+                        Err(err) => error!("failed to get the stack trace: {}", err),
+                    }
+                }
+
+                if let Some(frame) = debugger.active_frame.clone() {
+                    commands::dap::jump_to_stack_frame(&mut self.editor, &frame);
+                }
+                let reason = stopped.description.unwrap_or(stopped.reason);
+                self.editor.set_status(match stopped.text {
+                    Some(text) => format!("Stopped: {}: {}", reason, text),
+                    None => format!("Stopped: {}", reason),
+                });
+            }
+            "thread" => {
+                // remember a thread to pause before any has stopped
+                if debugger.thread_id.is_none() {
+                    debugger.thread_id = body
+                        .get("threadId")
+                        .and_then(|id| id.as_u64())
+                        .map(|id| id as usize);
+                }
+            }
+            "continued" => debugger.active_frame = None,
+            "output" => {
+                if let Ok(output) = serde_json::from_value::<events::Output>(body) {
+                    log::info!("debug output: {}", output.output.trim_end());
+                    if !matches!(output.category.as_deref(), Some("telemetry")) {
+                        self.editor.set_status(output.output.trim_end().to_owned());
+                    }
+                }
+            }
+            "exited" => {
+                if let Ok(exited) = serde_json::from_value::<events::Exited>(body) {
+                    self.editor.set_status(format!(
+                        "The debugged program exited with code {}",
+                        exited.exit_code
+                    ));
+                }
+            }
+            "terminated" => {
+                self.editor.debugger = None;
+                self.editor.set_status("Debug session ended".to_owned());
+            }
+            event => log::debug!("unhandled debug adapter event {}", event),
+        }
+    }
+
     pub async fn handle_language_server_message(
         &mut self,
         call: helix_lsp::Call,
@@ -428,6 +831,37 @@ impl Application {
                             }
                         }
                     }
+                    MethodCall::ApplyWorkspaceEdit(params) => {
+                        let offset_encoding = match self
+                            .editor
+                            .language_servers
+                            .get_by_id(server_id)
+                        {
+                            Some(server) => server.offset_encoding(),
+                            None => {
+                                log::warn!("can't find language server with id `{}`", server_id);
+                                return;
+                            }
+                        };
+                        let result = self
+                            .editor
+                            .apply_workspace_edit(offset_encoding, &params.edit);
+                        if let Err(err) = &result {
+                            self.editor
+                                .set_error(format!("Couldn't apply the edit: {}", err));
+                        }
+                        let response = lsp::ApplyWorkspaceEditResponse {
+                            applied: result.is_ok(),
+                            failure_reason: result.err().map(|err| err.to_string()),
+                            failed_change: None,
+                        };
+                        if let Some(server) = self.editor.language_servers.get_by_id(server_id) {
+                            tokio::spawn(
+                                server.reply(id, Ok(serde_json::to_value(response).unwrap())),
+                            );
+                        }
+                        self.render();
+                    }
                 }
                 // self.language_server.reply(
                 //     call.id,
@@ -448,7 +882,14 @@ impl Application {
 
         let mut stdout = stdout();
 
-        execute!(stdout, terminal::EnterAlternateScreen)?;
+        execute!(
+            stdout,
+            terminal::EnterAlternateScreen,
+            event::EnableBracketedPaste
+        )?;
+        if self.config.editor.mouse {
+            execute!(stdout, event::EnableMouseCapture)?;
+        }
 
         // Exit the alternate screen and disable raw mode before panicking
         let hook = std::panic::take_hook();
@@ -456,6 +897,8 @@ impl Application {
             // We can't handle errors properly inside this closure.  And it's
             // probably not a good idea to `unwrap()` inside a panic handler.
             // So we just ignore the `Result`s.
+            let _ = execute!(std::io::stdout(), event::DisableMouseCapture);
+            let _ = execute!(std::io::stdout(), event::DisableBracketedPaste);
             let _ = execute!(std::io::stdout(), terminal::LeaveAlternateScreen);
             let _ = terminal::disable_raw_mode();
             hook(info);
@@ -463,15 +906,79 @@ impl Application {
 
         self.event_loop().await;
 
+        recovery::remove_backups(&self.editor);
+        if let Err(err) = history::save(&self.editor.registers) {
+            log::error!("failed to save the prompt history: {}", err);
+        }
+
         self.editor.close_language_servers(None).await?;
 
         // reset cursor shape
         write!(stdout, "\x1B[2 q")?;
 
-        execute!(stdout, terminal::LeaveAlternateScreen)?;
+        if self.config.editor.mouse {
+            execute!(stdout, event::DisableMouseCapture)?;
+        }
+
+        execute!(
+            stdout,
+            event::DisableBracketedPaste,
+            terminal::LeaveAlternateScreen
+        )?;
 
         terminal::disable_raw_mode()?;
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use helix_core::Rope;
+
+    #[test]
+    fn clamp_coords_past_the_end() {
+        let text = Rope::from("one\ntwo\n");
+        let text = text.slice(..);
+        assert_eq!(clamped_pos_at_coords(text, Position::new(1, 1)), 5);
+        // rows past the end go to the last line, not the empty one after the final line ending
+        assert_eq!(clamped_pos_at_coords(text, Position::new(99999, 0)), 4);
+        assert_eq!(clamped_pos_at_coords(text, Position::new(99999, 99999)), 7);
+        // columns stay before the line ending
+        assert_eq!(clamped_pos_at_coords(text, Position::new(0, 99999)), 3);
+
+        let text = Rope::from("one");
+        assert_eq!(
+            clamped_pos_at_coords(text.slice(..), Position::new(3, 99)),
+            2
+        );
+
+        let text = Rope::from("");
+        assert_eq!(
+            clamped_pos_at_coords(text.slice(..), Position::new(5, 5)),
+            0
+        );
+    }
+
+    #[test]
+    fn normalize_pasted_line_endings() {
+        assert_eq!(normalize_pasted("one\rtwo\r\nthree\n"), "one\ntwo\nthree\n");
+        assert_eq!(normalize_pasted("\r\r\n"), "\n\n");
+        assert_eq!(normalize_pasted(""), "");
+    }
+
+    #[test]
+    fn pasted_text_keys() {
+        assert_eq!(
+            pasted_keys("a\tB\n"),
+            vec![
+                KeyEvent::new(KeyCode::Char('a'), KeyModifiers::NONE),
+                KeyEvent::new(KeyCode::Tab, KeyModifiers::NONE),
+                KeyEvent::new(KeyCode::Char('B'), KeyModifiers::NONE),
+                KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE),
+            ]
+        );
+        assert!(pasted_keys("").is_empty());
+    }
+}
@@ -1,12 +1,17 @@
 use anyhow::{Error, Result};
-use std::path::PathBuf;
+use helix_core::Position;
+use std::path::{Path, PathBuf};
 
 #[derive(Default)]
 pub struct Args {
     pub display_help: bool,
     pub display_version: bool,
+    pub load_tutor: bool,
     pub verbosity: u64,
-    pub files: Vec<PathBuf>,
+    /// Whether `-` was given, to edit what's piped to the editor.
+    pub read_stdin: bool,
+    /// The files to open, with the 0-based position to place the cursor at.
+    pub files: Vec<(PathBuf, Position)>,
 }
 
 impl Args {
@@ -17,11 +22,18 @@ impl Args {
 
         iter.next(); // skip the program, we don't care about that
 
+        // `+N` places the cursor of the next file on line N
+        let mut line = None;
         for arg in &mut iter {
             match arg.as_str() {
                 "--" => break, // stop parsing at this point treat the remaining as files
+                "-" => args.read_stdin = true,
                 "--version" => args.display_version = true,
                 "--help" => args.display_help = true,
+                "--tutor" => args.load_tutor = true,
+                arg if arg.starts_with('+') && arg[1..].parse::<usize>().is_ok() => {
+                    line = arg[1..].parse::<usize>().ok();
+                }
                 arg if arg.starts_with("--") => {
                     return Err(Error::msg(format!(
                         "unexpected double dash argument: {}",
@@ -39,15 +51,71 @@ impl Args {
                         }
                     }
                 }
-                arg => args.files.push(PathBuf::from(arg)),
+                arg => args.files.push(parse_file(arg, line.take())),
             }
         }
 
         // push the remaining args, if any to the files
         for filename in iter {
-            args.files.push(PathBuf::from(filename));
+            args.files.push(parse_file(filename, line.take()));
         }
 
         Ok(args)
     }
 }
+
+/// Splits a `file:line:column` or `file:line` argument into the file and the position, unless a
+/// file with that name exists. Lines and columns start at 1, like in compiler messages, and a
+/// `line` given with `+N` takes precedence.
+fn parse_file(arg: &str, line: Option<usize>) -> (PathBuf, Position) {
+    let to_position = |line: usize, column: usize| {
+        Position::new(line.saturating_sub(1), column.saturating_sub(1))
+    };
+    let path = Path::new(arg);
+    if !path.exists() {
+        let mut parts = arg.rsplitn(3, ':');
+        let numbers: Vec<_> = parts
+            .by_ref()
+            .take(2)
+            .map_while(|part| part.parse::<usize>().ok())
+            .collect();
+        let (file, position) = match (numbers.as_slice(), parts.next()) {
+            ([column, row], Some(file)) => (file, to_position(*row, *column)),
+            ([row], _) => (arg.rsplit_once(':').unwrap().0, to_position(*row, 1)),
+            _ => (arg, Position::default()),
+        };
+        if !file.is_empty() {
+            let position = match line {
+                Some(line) => to_position(line, 1),
+                None => position,
+            };
+            return (PathBuf::from(file), position);
+        }
+    }
+    (path.to_path_buf(), to_position(line.unwrap_or(1), 1))
+}
+
+#[test]
+fn parsing_file_positions() {
+    let parse = |arg| parse_file(arg, None);
+    assert_eq!(
+        parse("src/main.rs:120:8"),
+        (PathBuf::from("src/main.rs"), Position::new(119, 7))
+    );
+    assert_eq!(
+        parse("src/main.rs:120"),
+        (PathBuf::from("src/main.rs"), Position::new(119, 0))
+    );
+    assert_eq!(
+        parse("src/main.rs"),
+        (PathBuf::from("src/main.rs"), Position::new(0, 0))
+    );
+    assert_eq!(
+        parse("notes:todo"),
+        (PathBuf::from("notes:todo"), Position::new(0, 0))
+    );
+    assert_eq!(
+        parse_file("src/main.rs", Some(12)),
+        (PathBuf::from("src/main.rs"), Position::new(11, 0))
+    );
+}
@@ -1,28 +1,33 @@
 use helix_core::{
-    comment, coords_at_pos, find_first_non_whitespace_char, find_root, graphemes, indent,
+    comment, coords_at_pos, find_first_non_whitespace_char,
+    fold::{self, Fold},
+    graphemes, indent,
     line_ending::{
-        get_line_ending_of_str, line_end_char_index, rope_end_without_line_ending,
-        str_is_line_ending,
+        get_line_ending_of_str, last_line_index, line_end_char_index, line_end_grapheme_index,
+        rope_end_without_line_ending, str_is_line_ending,
     },
     match_brackets,
     movement::{self, Direction},
-    object, pos_at_coords,
+    object, pos_at_coords, reflow,
     regex::{self, Regex},
     register::Register,
-    search, selection, surround, textobject, LineEnding, Position, Range, Rope, RopeGraphemes,
-    RopeSlice, Selection, SmallVec, Tendril, Transaction,
+    search, selection, surround, tags, textobject, visual_coords_at_pos, Annotation, LineEnding,
+    Position, Range, Rope, RopeGraphemes, RopeSlice, Selection, SmallVec, Tendril, Transaction,
 };
 
 use helix_view::{
     document::{IndentStyle, Mode},
     editor::Action,
+    events::{self, Event},
     input::KeyEvent,
     keyboard::KeyCode,
+    tree,
     view::{View, PADDING},
     Document, DocumentId, Editor, ViewId,
 };
 
 use anyhow::{anyhow, bail, Context as _};
+use dap::*;
 use helix_lsp::{
     lsp,
     util::{lsp_pos_to_pos, lsp_range_to_range, pos_to_lsp_pos, range_to_lsp_range},
@@ -33,11 +38,11 @@ use movement::Movement;
 
 use crate::{
     compositor::{self, Component, Compositor},
-    ui::{self, Picker, Popup, Prompt, PromptEvent},
+    ui::{self, FilePicker, Picker, Popup, Prompt, PromptEvent},
 };
 
 use crate::job::{self, Job, Jobs};
-use futures_util::{FutureExt, TryFutureExt};
+use futures_util::TryFutureExt;
 use std::num::NonZeroUsize;
 use std::{fmt, future::Future};
 
@@ -49,13 +54,17 @@ use std::{
 use once_cell::sync::Lazy;
 use serde::de::{self, Deserialize, Deserializer};
 
+pub mod dap;
+
+pub type OnKeyCallback = Box<dyn FnOnce(&mut Context, KeyEvent)>;
+
 pub struct Context<'a> {
     pub selected_register: helix_view::RegisterSelection,
     pub count: Option<NonZeroUsize>,
     pub editor: &'a mut Editor,
 
     pub callback: Option<crate::compositor::Callback>,
-    pub on_next_key_callback: Option<Box<dyn FnOnce(&mut Context, KeyEvent)>>,
+    pub on_next_key_callback: Option<OnKeyCallback>,
     pub jobs: &'a mut Jobs,
 }
 
@@ -103,20 +112,20 @@ impl<'a> Context<'a> {
     }
 }
 
-enum Align {
+pub enum Align {
     Top,
     Center,
     Bottom,
 }
 
-fn align_view(doc: &Document, view: &mut View, align: Align) {
+pub fn align_view(doc: &Document, view: &mut View, align: Align) {
     let pos = doc.selection(view.id).cursor();
     let line = doc.text().char_to_line(pos);
 
     let relative = match align {
         Align::Center => view.area.height as usize / 2,
         Align::Top => 0,
-        Align::Bottom => view.area.height as usize,
+        Align::Bottom => view.area.height.saturating_sub(2) as usize, // - 1 for statusline
     };
 
     view.first_line = line.saturating_sub(relative);
@@ -124,18 +133,28 @@ fn align_view(doc: &Document, view: &mut View, align: Align) {
 
 /// A command is composed of a static name, and a function that takes the current state plus a count,
 /// and does a side-effect on the state (usually by creating and applying a transaction).
-#[derive(Copy, Clone)]
-pub struct Command {
-    name: &'static str,
-    fun: fn(cx: &mut Context),
-    doc: &'static str,
+///
+/// Keys can also be bound to a typable command or a plugin, with its arguments, written like
+/// `":write --force"` in the keymap.
+#[derive(Clone)]
+pub enum Command {
+    Static {
+        name: &'static str,
+        fun: fn(cx: &mut Context),
+        doc: &'static str,
+    },
+    Typable {
+        name: String,
+        args: Vec<String>,
+        doc: String,
+    },
 }
 
 macro_rules! commands {
     ( $($name:ident, $doc:literal),* ) => {
         $(
             #[allow(non_upper_case_globals)]
-            pub const $name: Self = Self {
+            pub const $name: Self = Self::Static {
                 name: stringify!($name),
                 fun: $name,
                 doc: $doc
@@ -150,15 +169,54 @@ macro_rules! commands {
 
 impl Command {
     pub fn execute(&self, cx: &mut Context) {
-        (self.fun)(cx);
+        match self {
+            Command::Static { fun, .. } => (fun)(cx),
+            Command::Typable { name, args, .. } => {
+                let mut cx = compositor::Context {
+                    editor: cx.editor,
+                    jobs: cx.jobs,
+                    scroll: None,
+                };
+                let result = match cmd::COMMANDS.get(name.as_str()) {
+                    Some(command) => {
+                        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+                        (command.fun)(&mut cx, &args, PromptEvent::Validate)
+                    }
+                    None => crate::plugin::run_command(cx.editor, name, args),
+                };
+                if let Err(err) = result {
+                    cx.editor.set_error(format!("{}", err));
+                }
+            }
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Command::Static { name, .. } => name,
+            Command::Typable { name, .. } => name,
+        }
     }
 
-    pub fn name(&self) -> &'static str {
-        self.name
+    pub fn doc(&self) -> &str {
+        match self {
+            Command::Static { doc, .. } => doc,
+            Command::Typable { doc, .. } => doc,
+        }
     }
 
-    pub fn doc(&self) -> &'static str {
-        self.doc
+    /// Fails on a typable command that's neither built in nor one of `plugins`, which can only be
+    /// told once the config is loaded.
+    pub fn check(&self, plugins: &[helix_view::editor::PluginConfig]) -> anyhow::Result<()> {
+        match self {
+            Command::Typable { name, .. }
+                if !cmd::COMMANDS.contains_key(name.as_str())
+                    && crate::plugin::find(plugins, name).is_none() =>
+            {
+                bail!("No typable command named '{}'", name)
+            }
+            _ => Ok(()),
+        }
     }
 
     #[rustfmt::skip]
@@ -171,6 +229,10 @@ impl Command {
         extend_char_right, "Extend right",
         extend_line_up, "Extend up",
         extend_line_down, "Extend down",
+        move_visual_line_up, "Move up a screen row",
+        move_visual_line_down, "Move down a screen row",
+        extend_visual_line_up, "Extend up a screen row",
+        extend_visual_line_down, "Extend down a screen row",
         move_next_word_start, "Move to beginning of next word",
         move_prev_word_start, "Move to beginning of previous word",
         move_next_word_end, "Move to end of next word",
@@ -180,6 +242,9 @@ impl Command {
         extend_next_word_start, "Extend to beginning of next word",
         extend_prev_word_start, "Extend to beginning of previous word",
         extend_next_word_end, "Extend to end of next word",
+        extend_next_long_word_start, "Extend to beginning of next long word",
+        extend_prev_long_word_start, "Extend to beginning of previous long word",
+        extend_next_long_word_end, "Extend to end of next long word",
         find_till_char, "Move till next occurance of char",
         find_next_char, "Move to next occurance of char",
         extend_till_char, "Extend till next occurance of char",
@@ -199,10 +264,18 @@ impl Command {
         select_all, "Select whole document",
         select_regex, "Select all regex matches inside selections",
         split_selection, "Split selection into subselections on regex matches",
-        split_selection_on_newline, "Split selection on newlines",
+        replace_regex, "Replace regex matches within selections",
+        split_selection_on_newline, "Split selection into one range per line",
+        merge_selections, "Merge selections",
+        merge_consecutive_selections, "Merge consecutive selections",
+        trim_selections, "Trim whitespace from selections",
+        character_info, "Show the code points and names of the character under the cursor",
         search, "Search for regex pattern",
         search_next, "Select next search match",
         extend_search_next, "Add next search match to selection",
+        search_prev, "Select previous search match",
+        extend_search_prev, "Add previous search match to selection",
+        search_clear_highlight, "Clear the highlighted search matches",
         search_selection, "Use current selection as search pattern",
         extend_line, "Select current line, if already selected, extend to next line",
         extend_to_line_bounds, "Extend selection to line bounds (line-wise selection)",
@@ -218,6 +291,7 @@ impl Command {
         buffer_picker, "Open buffer picker",
         symbol_picker, "Open symbol picker",
         last_picker, "Open last picker",
+        command_palette, "Open command palette",
         prepend_to_line, "Insert at start of line",
         append_to_line, "Insert at end of line",
         open_below, "Open new line below selection",
@@ -226,6 +300,8 @@ impl Command {
         select_mode, "Enter selection extend mode",
         exit_select_mode, "Exit selection mode",
         goto_definition, "Goto definition",
+        goto_tag, "Goto the definition in the tags file",
+        tag_picker, "Open tag picker",
         goto_type_definition, "Goto type definition",
         goto_implementation, "Goto implementation",
         goto_file_start, "Goto file start",
@@ -235,10 +311,19 @@ impl Command {
         goto_window_middle, "Goto window middle",
         goto_window_bottom, "Goto window bottom",
         goto_last_accessed_file, "Goto last accessed file",
+        goto_next_buffer, "Goto next buffer",
+        goto_previous_buffer, "Goto previous buffer",
+        goto_last_modification, "Goto last modification",
         goto_first_diag, "Goto first diagnostic",
         goto_last_diag, "Goto last diagnostic",
         goto_next_diag, "Goto next diagnostic",
         goto_prev_diag, "Goto previous diagnostic",
+        goto_next_function, "Goto next function",
+        goto_prev_function, "Goto previous function",
+        goto_next_class, "Goto next class",
+        goto_prev_class, "Goto previous class",
+        goto_next_change, "Goto next change",
+        goto_prev_change, "Goto previous change",
         goto_line_start, "Goto line start",
         goto_line_end, "Goto line end",
         // TODO: different description ?
@@ -249,16 +334,24 @@ impl Command {
         insert_newline, "Insert newline char",
         delete_char_backward, "Delete previous char",
         delete_char_forward, "Delete next char",
+        insert_register, "Insert register",
+        insert_unicode, "Insert a character by its code point or name",
         delete_word_backward, "Delete previous word",
+        delete_word_forward, "Delete next word",
+        kill_to_line_start, "Delete content till the start of the line",
+        kill_to_line_end, "Delete content till the end of the line",
         undo, "Undo change",
         redo, "Redo change",
         yank, "Yank selection",
+        yank_joined, "Join and yank selections",
         yank_joined_to_clipboard, "Join and yank selections to clipboard",
         yank_main_selection_to_clipboard, "Yank main selection to clipboard",
         replace_with_yanked, "Replace with yanked text",
         replace_selections_with_clipboard, "Replace selections by clipboard content",
         paste_after, "Paste after selection",
         paste_before, "Paste before selection",
+        paste_all_after, "Paste all yanked values after selection",
+        paste_all_before, "Paste all yanked values before selection",
         paste_clipboard_after, "Paste clipboard after selections",
         paste_clipboard_before, "Paste clipboard before selections",
         indent, "Indent selection",
@@ -271,39 +364,89 @@ impl Command {
         hover, "Show docs for item under cursor",
         toggle_comments, "Comment/uncomment selections",
         expand_selection, "Expand selection to parent syntax node",
+        shrink_selection, "Shrink selection to previously expanded syntax node",
+        select_next_sibling, "Select next sibling in syntax tree",
+        select_prev_sibling, "Select previous sibling in syntax tree",
         jump_forward, "Jump forward on jumplist",
         jump_backward, "Jump backward on jumplist",
+        save_selection, "Save the selections to the jumplist",
+        selection_undo, "Restore the selections before the last selection change",
+        selection_redo, "Restore the selections undone by selection_undo",
         rotate_view, "Goto next window",
         hsplit, "Horizontal bottom split",
         vsplit, "Vertical right split",
         wclose, "Close window",
+        wonly, "Close all other windows",
+        jump_view_left, "Jump to the split on the left",
+        jump_view_down, "Jump to the split below",
+        jump_view_up, "Jump to the split above",
+        jump_view_right, "Jump to the split on the right",
+        swap_view_left, "Swap with the split on the left",
+        swap_view_down, "Swap with the split below",
+        swap_view_up, "Swap with the split above",
+        swap_view_right, "Swap with the split on the right",
+        rotate_splits, "Rotate the splits of the current container",
+        grow_split_width, "Make the split wider",
+        shrink_split_width, "Make the split narrower",
+        grow_split_height, "Make the split taller",
+        shrink_split_height, "Make the split shorter",
+        equalize_splits, "Give all splits the same size",
         select_register, "Select register",
+        register_picker, "Pick a register to use for the next command",
         align_view_middle, "Align view middle",
         align_view_top, "Align view top",
         align_view_center, "Align view center",
         align_view_bottom, "Align view bottom",
         scroll_up, "Scroll view up",
         scroll_down, "Scroll view down",
+        fold_toggle, "Fold or unfold the lines around the cursor",
+        fold_all, "Fold everything",
+        unfold_all, "Unfold everything",
         match_brackets, "Goto matching bracket",
         surround_add, "Surround add",
         surround_replace, "Surround replace",
         surround_delete, "Surround delete",
         select_textobject_around, "Select around object",
-        select_textobject_inner, "Select inside object"
+        select_textobject_inner, "Select inside object",
+        shell_pipe, "Pipe selections through shell command",
+        shell_insert_output, "Insert output of shell command before each selection",
+        shell_append_output, "Append output of shell command after each selection",
+        shell_keep_pipe, "Filter selections with shell predicate",
+        sort_selections, "Sort selections",
+        next_snippet_placeholder, "Select next snippet placeholder",
+        indent_selection, "Re-indent selection",
+        reflow, "Hard-wrap selection to text width",
+        spelling_suggestions, "Suggest corrections for the misspelled word under the cursor",
+        dap_launch, "Launch a debug session",
+        dap_toggle_breakpoint, "Toggle breakpoint",
+        dap_continue, "Continue program execution",
+        dap_pause, "Pause program execution",
+        dap_next, "Step to the next line",
+        dap_step_in, "Step into the call",
+        dap_step_out, "Step out of the function",
+        dap_stack_trace, "List the stack frames of the stopped thread",
+        dap_variables, "List the variables of the current stack frame",
+        dap_terminate, "End the debug session"
     );
 }
 
 impl fmt::Debug for Command {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let Command { name, .. } = self;
-        f.debug_tuple("Command").field(name).finish()
+        match self {
+            Command::Static { name, .. } => f.debug_tuple("Command").field(name).finish(),
+            Command::Typable { name, args, .. } => {
+                f.debug_tuple("Command").field(name).field(args).finish()
+            }
+        }
     }
 }
 
 impl fmt::Display for Command {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let Command { name, .. } = self;
-        f.write_str(name)
+        match self {
+            Command::Static { name, .. } => f.write_str(name),
+            Command::Typable { name, args, .. } => write!(f, ":{} {}", name, args.join(" ")),
+        }
     }
 }
 
@@ -311,10 +454,28 @@ impl std::str::FromStr for Command {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(typable) = s.strip_prefix(':') {
+            let mut parts = typable.split_whitespace();
+            let name = parts
+                .next()
+                .ok_or_else(|| anyhow!("Expected a typable command after ':'"))?;
+            // plugins are only known, and checked with `check`, once the config is loaded. They're
+            // documented by their name
+            let doc = match cmd::COMMANDS.get(name) {
+                Some(command) => command.doc.to_owned(),
+                None => s.to_owned(),
+            };
+            return Ok(Command::Typable {
+                name: name.to_owned(),
+                args: parts.map(ToOwned::to_owned).collect(),
+                doc,
+            });
+        }
+
         Command::COMMAND_LIST
             .iter()
-            .copied()
-            .find(|cmd| cmd.name == s)
+            .find(|cmd| cmd.name() == s)
+            .cloned()
             .ok_or_else(|| anyhow!("No command named '{}'", s))
     }
 }
@@ -331,7 +492,24 @@ impl<'de> Deserialize<'de> for Command {
 
 impl PartialEq for Command {
     fn eq(&self, other: &Self) -> bool {
-        self.name() == other.name()
+        match (self, other) {
+            (
+                Command::Typable {
+                    name: first_name,
+                    args: first_args,
+                    ..
+                },
+                Command::Typable {
+                    name: second_name,
+                    args: second_args,
+                    ..
+                },
+            ) => first_name == second_name && first_args == second_args,
+            (Command::Static { name: first, .. }, Command::Static { name: second, .. }) => {
+                first == second
+            }
+            _ => false,
+        }
     }
 }
 
@@ -356,25 +534,97 @@ fn move_char_right(cx: &mut Context) {
 }
 
 fn move_line_up(cx: &mut Context) {
+    move_line(cx, Direction::Backward, Movement::Move)
+}
+
+fn move_line_down(cx: &mut Context) {
+    move_line(cx, Direction::Forward, Movement::Move)
+}
+
+/// Moves `range` `count` times with `step`, which moves it once in the direction `dir`. A folded
+/// range of lines counts as its first line.
+fn move_over_folds(
+    text: RopeSlice,
+    folds: &[Fold],
+    range: Range,
+    dir: Direction,
+    count: usize,
+    behaviour: Movement,
+    step: impl Fn(Range, usize) -> Range,
+) -> Range {
+    if folds.is_empty() {
+        return step(range, count);
+    }
+    let last_line = text.len_lines().saturating_sub(1);
+    let mut range = range;
+    for _ in 0..count {
+        let next = step(range, 1);
+        let line = text.char_to_line(next.head);
+        range = match fold::visible_line(folds, line, dir, last_line) {
+            Some(visible) if visible == line => next,
+            Some(visible) => {
+                movement::move_vertically(text, next, dir, line.abs_diff(visible), behaviour)
+            }
+            // nothing but folded lines ahead
+            None => break,
+        };
+    }
+    range
+}
+
+fn move_line(cx: &mut Context, dir: Direction, behaviour: Movement) {
     let count = cx.count();
     let (view, doc) = current!(cx.editor);
     let text = doc.text().slice(..);
+    let folds = doc.folds(view.id);
     let selection = doc.selection(view.id).transform(|range| {
-        movement::move_vertically(text, range, Direction::Backward, count, Movement::Move)
+        move_over_folds(text, folds, range, dir, count, behaviour, |range, count| {
+            movement::move_vertically(text, range, dir, count, behaviour)
+        })
     });
     doc.set_selection(view.id, selection);
 }
 
-fn move_line_down(cx: &mut Context) {
+fn move_visual_line(cx: &mut Context, dir: Direction, behaviour: Movement) {
     let count = cx.count();
     let (view, doc) = current!(cx.editor);
     let text = doc.text().slice(..);
+    let width = view.inner_width();
+    let tab_width = doc.tab_width();
+    let folds = doc.folds(view.id);
     let selection = doc.selection(view.id).transform(|range| {
-        movement::move_vertically(text, range, Direction::Forward, count, Movement::Move)
+        move_over_folds(text, folds, range, dir, count, behaviour, |range, count| {
+            movement::move_vertically_visual(
+                text,
+                range,
+                dir,
+                count,
+                behaviour,
+                width,
+                tab_width,
+                view.soft_wrap,
+            )
+        })
     });
     doc.set_selection(view.id, selection);
 }
 
+fn move_visual_line_up(cx: &mut Context) {
+    move_visual_line(cx, Direction::Backward, Movement::Move)
+}
+
+fn move_visual_line_down(cx: &mut Context) {
+    move_visual_line(cx, Direction::Forward, Movement::Move)
+}
+
+fn extend_visual_line_up(cx: &mut Context) {
+    move_visual_line(cx, Direction::Backward, Movement::Extend)
+}
+
+fn extend_visual_line_down(cx: &mut Context) {
+    move_visual_line(cx, Direction::Forward, Movement::Extend)
+}
+
 fn goto_line_end(cx: &mut Context) {
     let (view, doc) = current!(cx.editor);
 
@@ -382,9 +632,8 @@ fn goto_line_end(cx: &mut Context) {
         let text = doc.text();
         let line = text.char_to_line(range.head);
 
-        let pos = line_end_char_index(&text.slice(..), line);
-        let pos = graphemes::nth_prev_grapheme_boundary(text.slice(..), pos, 1);
-        let pos = range.head.max(pos).max(text.line_to_char(line));
+        let pos = line_end_grapheme_index(&text.slice(..), line);
+        let pos = range.head.max(pos);
 
         Range::new(
             match doc.mode {
@@ -474,7 +723,17 @@ fn goto_window(cx: &mut Context, align: Align) {
 
     let pos = doc.text().line_to_char(line);
 
-    doc.set_selection(view.id, Selection::point(pos));
+    let selection = goto_selection(doc, view.id, pos);
+    doc.set_selection(view.id, selection);
+}
+
+/// Selection after jumping to `pos`: a point, or in select mode the primary selection extended
+/// to `pos`.
+fn goto_selection(doc: &Document, view_id: ViewId, pos: usize) -> Selection {
+    match doc.mode {
+        Mode::Select => Selection::single(doc.selection(view_id).primary().anchor, pos),
+        Mode::Normal | Mode::Insert => Selection::point(pos),
+    }
 }
 
 fn goto_window_top(cx: &mut Context) {
@@ -565,18 +824,36 @@ fn move_next_long_word_end(cx: &mut Context) {
     doc.set_selection(view.id, selection);
 }
 
+/// Goes to the start of the file, or to the line given by the count.
 fn goto_file_start(cx: &mut Context) {
+    let count = cx.count;
     push_jump(cx.editor);
     let (view, doc) = current!(cx.editor);
-    doc.set_selection(view.id, Selection::point(0));
+    let text = doc.text();
+    let pos = match count {
+        Some(line) => {
+            let line = (line.get() - 1).min(text.len_lines().saturating_sub(1));
+            text.line_to_char(line)
+        }
+        None => 0,
+    };
+    let selection = goto_selection(doc, view.id, pos);
+    doc.set_selection(view.id, selection);
+}
+
+/// The start of the last line of `text`, not counting the empty line after a final line ending:
+/// the end of a followed document.
+pub(crate) fn last_line_start(text: RopeSlice) -> usize {
+    text.line_to_char(last_line_index(&text))
 }
 
 fn goto_file_end(cx: &mut Context) {
     push_jump(cx.editor);
     let (view, doc) = current!(cx.editor);
     let text = doc.text();
-    let last_line = text.line_to_char(text.len_lines().saturating_sub(2));
-    doc.set_selection(view.id, Selection::point(last_line));
+    let last_line = text.line_to_char(last_line_index(&text.slice(..)));
+    let selection = goto_selection(doc, view.id, last_line);
+    doc.set_selection(view.id, selection);
 }
 
 fn extend_next_word_start(cx: &mut Context) {
@@ -620,6 +897,47 @@ fn extend_next_word_end(cx: &mut Context) {
     doc.set_selection(view.id, selection);
 }
 
+fn extend_next_long_word_start(cx: &mut Context) {
+    let count = cx.count();
+    let (view, doc) = current!(cx.editor);
+    let text = doc.text().slice(..);
+
+    let selection = doc.selection(view.id).transform(|range| {
+        let word = movement::move_next_long_word_start(text, range, count);
+        let pos = word.head;
+        Range::new(range.anchor, pos)
+    });
+
+    doc.set_selection(view.id, selection);
+}
+
+fn extend_prev_long_word_start(cx: &mut Context) {
+    let count = cx.count();
+    let (view, doc) = current!(cx.editor);
+    let text = doc.text().slice(..);
+
+    let selection = doc.selection(view.id).transform(|range| {
+        let word = movement::move_prev_long_word_start(text, range, count);
+        let pos = word.head;
+        Range::new(range.anchor, pos)
+    });
+    doc.set_selection(view.id, selection);
+}
+
+fn extend_next_long_word_end(cx: &mut Context) {
+    let count = cx.count();
+    let (view, doc) = current!(cx.editor);
+    let text = doc.text().slice(..);
+
+    let selection = doc.selection(view.id).transform(|range| {
+        let word = movement::move_next_long_word_end(text, range, count);
+        let pos = word.head;
+        Range::new(range.anchor, pos)
+    });
+
+    doc.set_selection(view.id, selection);
+}
+
 #[inline]
 fn find_char_impl<F>(cx: &mut Context, search_fn: F, inclusive: bool, extend: bool)
 where
@@ -844,7 +1162,7 @@ fn switch_to_lowercase(cx: &mut Context) {
     doc.append_changes_to_history(view.id);
 }
 
-fn scroll(cx: &mut Context, offset: usize, direction: Direction) {
+pub fn scroll(cx: &mut Context, offset: usize, direction: Direction) {
     use Direction::*;
     let (view, doc) = current!(cx.editor);
     let cursor = coords_at_pos(doc.text().slice(..), doc.selection(view.id).cursor());
@@ -875,6 +1193,11 @@ fn scroll(cx: &mut Context, offset: usize, direction: Direction) {
         .max(view.first_line + scrolloff)
         .min(last_line.saturating_sub(scrolloff));
 
+    // the cursor is still in view, leave the selections alone
+    if line == cursor.row {
+        return;
+    }
+
     let text = doc.text().slice(..);
     let pos = pos_at_coords(text, Position::new(line, cursor.col)); // this func will properly truncate to line end
 
@@ -927,23 +1250,11 @@ fn extend_char_right(cx: &mut Context) {
 }
 
 fn extend_line_up(cx: &mut Context) {
-    let count = cx.count();
-    let (view, doc) = current!(cx.editor);
-    let text = doc.text().slice(..);
-    let selection = doc.selection(view.id).transform(|range| {
-        movement::move_vertically(text, range, Direction::Backward, count, Movement::Extend)
-    });
-    doc.set_selection(view.id, selection);
+    move_line(cx, Direction::Backward, Movement::Extend)
 }
 
 fn extend_line_down(cx: &mut Context) {
-    let count = cx.count();
-    let (view, doc) = current!(cx.editor);
-    let text = doc.text().slice(..);
-    let selection = doc.selection(view.id).transform(|range| {
-        movement::move_vertically(text, range, Direction::Forward, count, Movement::Extend)
-    });
-    doc.set_selection(view.id, selection);
+    move_line(cx, Direction::Forward, Movement::Extend)
 }
 
 fn select_all(cx: &mut Context) {
@@ -954,111 +1265,303 @@ fn select_all(cx: &mut Context) {
 }
 
 fn select_regex(cx: &mut Context) {
-    let prompt = ui::regex_prompt(cx, "select:".to_string(), move |view, doc, _, regex| {
-        let text = doc.text().slice(..);
-        if let Some(selection) = selection::select_on_matches(text, doc.selection(view.id), &regex)
-        {
-            doc.set_selection(view.id, selection);
-        }
-    });
+    let prompt = ui::regex_prompt(
+        cx,
+        "select:".to_string(),
+        None,
+        move |view, doc, _, regex, _| {
+            let text = doc.text().slice(..);
+            if let Some(selection) =
+                selection::select_on_matches(text, doc.selection(view.id), &regex)
+            {
+                doc.set_selection(view.id, selection);
+            }
+        },
+    );
 
     cx.push_layer(Box::new(prompt));
 }
 
 fn split_selection(cx: &mut Context) {
-    let prompt = ui::regex_prompt(cx, "split:".to_string(), move |view, doc, _, regex| {
-        let text = doc.text().slice(..);
-        let selection = selection::split_on_matches(text, doc.selection(view.id), &regex);
-        doc.set_selection(view.id, selection);
-    });
+    let prompt = ui::regex_prompt(
+        cx,
+        "split:".to_string(),
+        None,
+        move |view, doc, _, regex, _| {
+            let text = doc.text().slice(..);
+            let selection = selection::split_on_matches(text, doc.selection(view.id), &regex);
+            doc.set_selection(view.id, selection);
+        },
+    );
 
     cx.push_layer(Box::new(prompt));
 }
 
+/// Matches of `regex` within `ranges`, as char ranges along with their replacement, in which `$1`
+/// or `${name}` refer to capture groups.
+fn find_replacements(
+    text: RopeSlice,
+    ranges: &[Range],
+    regex: &Regex,
+    replacement: &str,
+) -> Vec<(usize, usize, String)> {
+    let mut replacements = Vec::new();
+
+    for range in ranges {
+        let start = range.from();
+        let end = (range.to() + 1).min(text.len_chars());
+        let fragment = text.slice(start..end).to_string();
+
+        for captures in regex.captures_iter(&fragment) {
+            let mat = captures.get(0).unwrap();
+            let mut replaced = String::new();
+            captures.expand(replacement, &mut replaced);
+
+            let from = start + fragment[..mat.start()].chars().count();
+            let to = from + mat.as_str().chars().count();
+            replacements.push((from, to, replaced));
+        }
+    }
+
+    replacements
+}
+
+/// Highlights the matches of `regex` within `ranges` in the current view, or clears the
+/// highlights if `regex` is `None`.
+fn preview_matches(editor: &mut Editor, ranges: &[Range], regex: Option<&Regex>) {
+    let (view, doc) = current!(editor);
+    view.match_highlights = match regex {
+        Some(regex) => find_replacements(doc.text().slice(..), ranges, regex, "")
+            .into_iter()
+            .map(|(from, to, _)| from..to)
+            .collect(),
+        None => Vec::new(),
+    };
+}
+
+/// Prompts for a regex, then for its replacement, and replaces the matches within `ranges` as a
+/// single undoable change. Matches are highlighted while typing.
+fn replace_prompt(ranges: Vec<Range>) -> Prompt {
+    Prompt::new(
+        "replace:".to_string(),
+        None,
+        |_input: &str| Vec::new(),
+        move |cx: &mut compositor::Context, input: &str, event: PromptEvent| {
+            let regex = match Regex::new(input) {
+                Ok(regex) if !input.is_empty() => regex,
+                Ok(_) => {
+                    preview_matches(cx.editor, &ranges, None);
+                    return;
+                }
+                Err(err) => {
+                    preview_matches(cx.editor, &ranges, None);
+                    if event == PromptEvent::Validate {
+                        cx.editor.set_error(format!("Invalid regex: {}", err));
+                    }
+                    return;
+                }
+            };
+
+            match event {
+                PromptEvent::Abort => preview_matches(cx.editor, &ranges, None),
+                PromptEvent::Update => preview_matches(cx.editor, &ranges, Some(&regex)),
+                PromptEvent::Validate => {
+                    let ranges = ranges.clone();
+                    let callback = async move {
+                        let call: job::Callback =
+                            Box::new(move |_editor: &mut Editor, compositor: &mut Compositor| {
+                                compositor.push(Box::new(replacement_prompt(ranges, regex)));
+                            });
+                        Ok(call)
+                    };
+                    cx.jobs.callback(callback);
+                }
+            }
+        },
+    )
+}
+
+fn replacement_prompt(ranges: Vec<Range>, regex: Regex) -> Prompt {
+    Prompt::new(
+        "with:".to_string(),
+        None,
+        |_input: &str| Vec::new(),
+        move |cx: &mut compositor::Context, input: &str, event: PromptEvent| match event {
+            PromptEvent::Abort => preview_matches(cx.editor, &ranges, None),
+            PromptEvent::Update => preview_matches(cx.editor, &ranges, Some(&regex)),
+            PromptEvent::Validate => {
+                preview_matches(cx.editor, &ranges, None);
+
+                let (view, doc) = current!(cx.editor);
+                let changes = find_replacements(doc.text().slice(..), &ranges, &regex, input);
+                let count = changes.len();
+                let transaction = Transaction::change(
+                    doc.text(),
+                    changes
+                        .into_iter()
+                        .map(|(from, to, replaced)| (from, to, Some(replaced.into()))),
+                );
+                doc.apply(&transaction, view.id);
+                doc.append_changes_to_history(view.id);
+                cx.editor.set_status(format!("{} replacements", count));
+            }
+        },
+    )
+}
+
+/// Replaces the matches of a regex within the selections.
+fn replace_regex(cx: &mut Context) {
+    let (view, doc) = current!(cx.editor);
+    let ranges = doc.selection(view.id).ranges().to_vec();
+    cx.push_layer(Box::new(replace_prompt(ranges)));
+}
+
 fn split_selection_on_newline(cx: &mut Context) {
     let (view, doc) = current!(cx.editor);
     let text = doc.text().slice(..);
-    // only compile the regex once
-    #[allow(clippy::trivial_regex)]
-    static REGEX: Lazy<Regex> =
-        Lazy::new(|| Regex::new(r"\r\n|[\n\r\u{000B}\u{000C}\u{0085}\u{2028}\u{2029}]").unwrap());
-    let selection = selection::split_on_matches(text, doc.selection(view.id), &REGEX);
+    let selection = selection::split_on_newline(text, doc.selection(view.id));
     doc.set_selection(view.id, selection);
 }
 
-fn search_impl(doc: &mut Document, view: &mut View, contents: &str, regex: &Regex, extend: bool) {
-    let text = doc.text();
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SearchResult {
+    Found,
+    /// Found after wrapping around the end (or start) of the document.
+    Wrapped,
+    NotFound,
+}
+
+fn search_impl(
+    doc: &mut Document,
+    view: &mut View,
+    regex: &Regex,
+    extend: bool,
+    direction: Direction,
+    wrap_around: bool,
+) -> SearchResult {
+    let text = doc.text().slice(..);
     let selection = doc.selection(view.id);
-    let start = text.char_to_byte(selection.cursor());
-
-    // use find_at to find the next match after the cursor, loop around the end
-    // Careful, `Regex` uses `bytes` as offsets, not character indices!
-    let mat = regex
-        .find_at(contents, start)
-        .or_else(|| regex.find(contents));
-    // TODO: message on wraparound
-    if let Some(mat) = mat {
-        let start = text.byte_to_char(mat.start());
-        let end = text.byte_to_char(mat.end());
-
-        if end == 0 {
-            // skip empty matches that don't make sense
-            return;
+    let cursor = selection.cursor();
+
+    // Careful, matches are byte ranges, not character indices!
+    let (mat, wrapped) = match direction {
+        Direction::Forward => {
+            // find the next match after the cursor, loop around the end
+            let start = text.char_to_byte((cursor + 1).min(text.len_chars()));
+            match search::find_at(regex, text, start) {
+                Some(mat) => (Some(mat), false),
+                None if wrap_around => (search::find_at(regex, text, 0), true),
+                None => (None, false),
+            }
         }
+        Direction::Backward => {
+            // find the last match before the selection, loop around the start
+            let end = text.char_to_byte(selection.primary().from());
+            let matches = || search::find_iter(regex, text, 0..text.len_bytes());
+            match matches().take_while(|mat| mat.start < end).last() {
+                Some(mat) => (Some(mat), false),
+                None if wrap_around => (matches().last(), true),
+                None => (None, false),
+            }
+        }
+    };
+
+    let mat = match mat {
+        Some(mat) => mat,
+        None => return SearchResult::NotFound,
+    };
 
-        let head = end - 1;
+    let start = text.byte_to_char(mat.start);
+    let end = text.byte_to_char(mat.end);
 
-        let selection = if extend {
-            selection.clone().push(Range::new(start, head))
-        } else {
-            Selection::single(start, head)
-        };
+    if end == 0 {
+        // skip empty matches that don't make sense
+        return SearchResult::NotFound;
+    }
 
-        doc.set_selection(view.id, selection);
-        align_view(doc, view, Align::Center);
+    let head = end - 1;
+
+    let selection = if extend {
+        selection.clone().push(Range::new(start, head))
+    } else {
+        Selection::single(start, head)
     };
+
+    doc.set_selection(view.id, selection);
+    align_view(doc, view, Align::Center);
+
+    if wrapped {
+        SearchResult::Wrapped
+    } else {
+        SearchResult::Found
+    }
 }
 
 // TODO: use one function for search vs extend
 fn search(cx: &mut Context) {
-    let (_, doc) = current!(cx.editor);
-
     // TODO: could probably share with select_on_matches?
 
-    // HAXX: sadly we can't avoid allocating a single string for the whole buffer since we can't
-    // feed chunks into the regex yet
-    let contents = doc.text().slice(..).to_string();
+    let search_config = cx.editor.config.search.clone();
+    cx.editor.search_highlight = true;
 
     let prompt = ui::regex_prompt(
         cx,
         "search:".to_string(),
-        move |view, doc, registers, regex| {
-            search_impl(doc, view, &contents, &regex, false);
-            // TODO: only store on enter (accept), not update
-            registers.write('\\', vec![regex.as_str().to_string()]);
+        Some('\\'),
+        move |view, doc, _, regex, _| {
+            let regex = search_config.build_regex(regex.as_str()).unwrap_or(regex);
+            // preview the first match while typing, extending the selection in select mode
+            let extend = doc.mode() == Mode::Select;
+            search_impl(
+                doc,
+                view,
+                &regex,
+                extend,
+                Direction::Forward,
+                search_config.wrap_around,
+            );
         },
     );
 
     cx.push_layer(Box::new(prompt));
 }
 
-fn search_next_impl(cx: &mut Context, extend: bool) {
+fn search_next_impl(cx: &mut Context, extend: bool, direction: Direction) {
+    cx.editor.search_highlight = true;
+    let search_config = &cx.editor.config.search;
     let (view, doc) = current!(cx.editor);
     let registers = &mut cx.editor.registers;
-    if let Some(query) = registers.read('\\') {
-        let query = query.first().unwrap();
-        let contents = doc.text().slice(..).to_string();
-        let regex = Regex::new(query).unwrap();
-        search_impl(doc, view, &contents, &regex, extend);
+    if let Some(query) = registers.read('\\').and_then(|history| history.last()) {
+        let regex = match search_config.build_regex(query) {
+            Ok(regex) => regex,
+            Err(err) => {
+                cx.editor.set_error(format!("Invalid regex: {}", err));
+                return;
+            }
+        };
+        let wrap_around = search_config.wrap_around;
+        match search_impl(doc, view, &regex, extend, direction, wrap_around) {
+            SearchResult::Found => (),
+            SearchResult::Wrapped => cx.editor.set_status("Wrapped around document".to_owned()),
+            SearchResult::NotFound => cx.editor.set_error("No more matches".to_owned()),
+        }
     }
 }
 
 fn search_next(cx: &mut Context) {
-    search_next_impl(cx, false);
+    search_next_impl(cx, false, Direction::Forward);
+}
+
+fn search_prev(cx: &mut Context) {
+    search_next_impl(cx, false, Direction::Backward);
 }
 
 fn extend_search_next(cx: &mut Context) {
-    search_next_impl(cx, true);
+    search_next_impl(cx, true, Direction::Forward);
+}
+
+fn extend_search_prev(cx: &mut Context) {
+    search_next_impl(cx, true, Direction::Backward);
 }
 
 fn search_selection(cx: &mut Context) {
@@ -1066,10 +1569,14 @@ fn search_selection(cx: &mut Context) {
     let contents = doc.text().slice(..);
     let query = doc.selection(view.id).primary().fragment(contents);
     let regex = regex::escape(&query);
-    cx.editor.registers.write('\\', vec![regex]);
+    cx.editor.registers.get_mut('\\').push(regex);
     search_next(cx);
 }
 
+fn search_clear_highlight(cx: &mut Context) {
+    cx.editor.search_highlight = false;
+}
+
 fn extend_line(cx: &mut Context) {
     let count = cx.count();
     let (view, doc) = current!(cx.editor);
@@ -1082,7 +1589,7 @@ fn extend_line(cx: &mut Context) {
     let line_end = text.char_to_line(pos.head);
     let mut end = line_end_char_index(&text.slice(..), line_end + count.saturating_sub(1));
 
-    if pos.anchor == start && pos.head == end && line_end < (text.len_lines() - 2) {
+    if pos.anchor == start && pos.head == end && line_end < last_line_index(&text.slice(..)) {
         end = line_end_char_index(&text.slice(..), line_end + 1);
     }
 
@@ -1161,6 +1668,66 @@ fn collapse_selection(cx: &mut Context) {
     doc.set_selection(view.id, selection);
 }
 
+fn merge_selections(cx: &mut Context) {
+    let (view, doc) = current!(cx.editor);
+    let selection = doc.selection(view.id).merge_ranges();
+    doc.set_selection(view.id, selection);
+}
+
+fn merge_consecutive_selections(cx: &mut Context) {
+    let (view, doc) = current!(cx.editor);
+    let selection = doc.selection(view.id).merge_consecutive_ranges();
+    doc.set_selection(view.id, selection);
+}
+
+fn trim_selections(cx: &mut Context) {
+    let (view, doc) = current!(cx.editor);
+    let selection = doc.selection(view.id);
+    // when every range is whitespace, keep a cursor at the primary one
+    let selection = selection::trim(doc.text().slice(..), selection)
+        .unwrap_or_else(|| Selection::point(selection.primary().head));
+    doc.set_selection(view.id, selection);
+}
+
+/// Describes `grapheme` by its code points, their names and its UTF-8 bytes.
+fn describe_grapheme(grapheme: &str) -> String {
+    let shown: String = grapheme.chars().flat_map(char::escape_debug).collect();
+    let code_points: Vec<_> = grapheme
+        .chars()
+        .map(|ch| match unicode_names2::name(ch) {
+            Some(name) => format!("U+{:04X} {}", ch as u32, name),
+            // control chars have no name
+            None => format!("U+{:04X}", ch as u32),
+        })
+        .collect();
+    let bytes: Vec<_> = grapheme.bytes().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "\"{}\" {}, UTF-8 {}",
+        shown,
+        code_points.join(", "),
+        bytes.join(" ")
+    )
+}
+
+/// Reports the code points, their names and the UTF-8 bytes of the grapheme under the primary
+/// cursor.
+fn character_info_impl(editor: &mut Editor) {
+    let (view, doc) = current!(editor);
+    let text = doc.text().slice(..);
+    let pos = doc.selection(view.id).primary().head;
+    if pos >= text.len_chars() {
+        editor.set_status("No character under the cursor".to_owned());
+        return;
+    }
+
+    let grapheme = String::from(text.slice(pos..graphemes::next_grapheme_boundary(text, pos)));
+    editor.set_status(describe_grapheme(&grapheme));
+}
+
+fn character_info(cx: &mut Context) {
+    character_info_impl(cx.editor);
+}
+
 fn flip_selections(cx: &mut Context) {
     let (view, doc) = current!(cx.editor);
     let selection = doc
@@ -1171,7 +1738,7 @@ fn flip_selections(cx: &mut Context) {
 }
 
 fn enter_insert_mode(doc: &mut Document) {
-    doc.mode = Mode::Insert;
+    doc.set_mode(Mode::Insert);
 }
 
 // inserts at the start of each selection
@@ -1222,7 +1789,7 @@ mod cmd {
     #[derive(Clone)]
     pub struct TypableCommand {
         pub name: &'static str,
-        pub alias: Option<&'static str>,
+        pub aliases: &'static [&'static str],
         pub doc: &'static str,
         // params, flags, helper, completer
         pub fun: fn(&mut compositor::Context, &[&str], PromptEvent) -> anyhow::Result<()>,
@@ -1266,31 +1833,49 @@ mod cmd {
         Ok(())
     }
 
+    /// Opens a file read-only.
+    fn view(
+        cx: &mut compositor::Context,
+        args: &[&str],
+        _event: PromptEvent,
+    ) -> anyhow::Result<()> {
+        let path = args.first().context("wrong argument count")?;
+        let id = cx.editor.open(path.into(), Action::Replace)?;
+        cx.editor.document_mut(id).unwrap().readonly = true;
+        Ok(())
+    }
+
     fn write_impl<P: AsRef<Path>>(
         cx: &mut compositor::Context,
         path: Option<P>,
+        force: bool,
     ) -> Result<tokio::task::JoinHandle<Result<(), anyhow::Error>>, anyhow::Error> {
-        let jobs = &mut cx.jobs;
-        let (_, doc) = current!(cx.editor);
+        let (view, doc) = current!(cx.editor);
 
         if let Some(path) = path {
             doc.set_path(path.as_ref()).context("invalid filepath")?;
+            // the new extension may belong to a different language
+            doc.detect_language(Some(&cx.editor.theme), &cx.editor.syn_loader);
         }
         if doc.path().is_none() {
             bail!("cannot write a buffer without a filename");
         }
-        let fmt = doc.auto_format().map(|fmt| {
-            let shared = fmt.shared();
-            let callback = make_format_callback(
-                doc.id(),
-                doc.version(),
-                Modified::SetUnmodified,
-                shared.clone(),
-            );
-            jobs.callback(callback);
-            shared
-        });
-        Ok(tokio::spawn(doc.format_and_save(fmt)))
+        if !force && doc.is_modified_externally() {
+            bail!("file was modified on disk since it was read, use :w! to overwrite it");
+        }
+        // formats the document too
+        events::dispatch(Event::DocumentWillSave {
+            doc,
+            view_id: view.id,
+            config: &cx.editor.config,
+        })?;
+        let format_error = doc.take_format_error();
+        let handle = tokio::spawn(doc.save());
+        if let Some(err) = format_error {
+            cx.editor
+                .set_error(format!("Saved without formatting: {}", err));
+        }
+        Ok(handle)
     }
 
     fn write(
@@ -1298,20 +1883,32 @@ mod cmd {
         args: &[&str],
         _event: PromptEvent,
     ) -> anyhow::Result<()> {
-        let handle = write_impl(cx, args.first())?;
+        let handle = write_impl(cx, args.first(), false)?;
         cx.jobs
             .add(Job::new(handle.unwrap_or_else(|e| Err(e.into()))).wait_before_exiting());
 
         Ok(())
     }
 
-    fn new_file(
+    fn force_write(
         cx: &mut compositor::Context,
-        _args: &[&str],
+        args: &[&str],
         _event: PromptEvent,
     ) -> anyhow::Result<()> {
-        cx.editor.new_file(Action::Replace);
-
+        let handle = write_impl(cx, args.first(), true)?;
+        cx.jobs
+            .add(Job::new(handle.unwrap_or_else(|e| Err(e.into()))).wait_before_exiting());
+
+        Ok(())
+    }
+
+    fn new_file(
+        cx: &mut compositor::Context,
+        _args: &[&str],
+        _event: PromptEvent,
+    ) -> anyhow::Result<()> {
+        cx.editor.new_file(Action::Replace);
+
         Ok(())
     }
 
@@ -1323,8 +1920,7 @@ mod cmd {
         let (_, doc) = current!(cx.editor);
 
         if let Some(format) = doc.format() {
-            let callback =
-                make_format_callback(doc.id(), doc.version(), Modified::LeaveModified, format);
+            let callback = make_format_callback(doc.id(), doc.version(), format);
             cx.jobs.callback(callback);
         }
 
@@ -1368,7 +1964,99 @@ mod cmd {
         Ok(())
     }
 
-    /// Sets or reports the current document's line ending setting.
+    fn set_tab_width(
+        cx: &mut compositor::Context,
+        args: &[&str],
+        _event: PromptEvent,
+    ) -> anyhow::Result<()> {
+        // If no argument, report current tab width.
+        let arg = match args.first() {
+            Some(arg) => arg,
+            None => {
+                let tab_width = doc_mut!(cx.editor).tab_width();
+                cx.editor.set_status(format!("{} columns", tab_width));
+                return Ok(());
+            }
+        };
+
+        let tab_width = arg
+            .parse::<usize>()
+            .ok()
+            .filter(|n| (1..=16).contains(n))
+            .context("invalid tab width")?;
+        doc_mut!(cx.editor).tab_width_override = Some(tab_width);
+
+        Ok(())
+    }
+
+    fn reflow_selections(
+        cx: &mut compositor::Context,
+        args: &[&str],
+        _event: PromptEvent,
+    ) -> anyhow::Result<()> {
+        let width = args
+            .first()
+            .map(|arg| arg.parse::<usize>().context("invalid text width"))
+            .transpose()?;
+        let default_width = cx.editor.config.text_width;
+        let (view, doc) = current!(cx.editor);
+        let width = width.unwrap_or_else(|| text_width(doc, default_width));
+        reflow_impl(doc, view.id, width);
+
+        Ok(())
+    }
+
+    fn no_highlight(
+        cx: &mut compositor::Context,
+        _args: &[&str],
+        _event: PromptEvent,
+    ) -> anyhow::Result<()> {
+        cx.editor.search_highlight = false;
+        Ok(())
+    }
+
+    /// Replaces the matches of a regex, prompted for, within the given lines: `%` (the default)
+    /// for the whole document, `N` or `N,M`.
+    fn replace(
+        cx: &mut compositor::Context,
+        args: &[&str],
+        _event: PromptEvent,
+    ) -> anyhow::Result<()> {
+        let (_, doc) = current!(cx.editor);
+        let text = doc.text();
+        let last_line = text.len_lines();
+
+        let (start, end) = match args.first() {
+            None | Some(&"%") => (1, last_line),
+            Some(lines) => {
+                let mut lines = lines.splitn(2, ',').map(|line| line.parse::<usize>());
+                let start = lines.next().unwrap().context("invalid line range")?;
+                let end = lines.next().transpose().context("invalid line range")?;
+                (start, end.unwrap_or(start))
+            }
+        };
+        if start == 0 || start > end || end > last_line {
+            bail!("line range out of bounds");
+        }
+
+        let from = text.line_to_char(start - 1);
+        let to = text.line_to_char(end).saturating_sub(1).max(from);
+        let ranges = vec![Range::new(from, to)];
+
+        let callback = async move {
+            let call: job::Callback =
+                Box::new(move |_editor: &mut Editor, compositor: &mut Compositor| {
+                    compositor.push(Box::new(replace_prompt(ranges)));
+                });
+            Ok(call)
+        };
+        cx.jobs.callback(callback);
+
+        Ok(())
+    }
+
+    /// Reports the current document's line ending setting, or converts the document's line
+    /// endings to the given one.
     fn set_line_ending(
         cx: &mut compositor::Context,
         args: &[&str],
@@ -1405,7 +2093,8 @@ mod cmd {
         };
 
         let line_ending = line_ending.context("invalid line ending")?;
-        doc_mut!(cx.editor).line_ending = line_ending;
+        let (view, doc) = current!(cx.editor);
+        doc.set_line_ending(line_ending, view.id);
         Ok(())
     }
 
@@ -1445,7 +2134,7 @@ mod cmd {
         args: &[&str],
         event: PromptEvent,
     ) -> anyhow::Result<()> {
-        let handle = write_impl(cx, args.first())?;
+        let handle = write_impl(cx, args.first(), false)?;
         let _ = helix_lsp::block_on(handle)?;
         quit(cx, &[], event)
     }
@@ -1455,14 +2144,14 @@ mod cmd {
         args: &[&str],
         event: PromptEvent,
     ) -> anyhow::Result<()> {
-        let handle = write_impl(cx, args.first())?;
+        let handle = write_impl(cx, args.first(), true)?;
         let _ = helix_lsp::block_on(handle)?;
         force_quit(cx, &[], event)
     }
 
     /// Results an error if there are modified buffers remaining and sets editor error,
     /// otherwise returns `Ok(())`
-    fn buffers_remaining_impl(editor: &mut Editor) -> anyhow::Result<()> {
+    pub(super) fn buffers_remaining_impl(editor: &mut Editor) -> anyhow::Result<()> {
         let modified: Vec<_> = editor
             .documents()
             .filter(|doc| doc.is_modified())
@@ -1491,15 +2180,43 @@ mod cmd {
     ) -> anyhow::Result<()> {
         let mut errors = String::new();
 
-        // save all documents
+        // save all modified documents
         for (_, doc) in &mut editor.documents {
+            if !doc.is_modified() {
+                continue;
+            }
             if doc.path().is_none() {
                 errors.push_str("cannot write a buffer without a filename\n");
                 continue;
             }
+            if !force && doc.is_modified_externally() {
+                errors.push_str("file was modified on disk since it was read\n");
+                continue;
+            }
+
+            // any view of the document will do, the selections are mapped through the changes
+            if let Some(&view_id) = doc.selections().keys().next() {
+                let event = Event::DocumentWillSave {
+                    doc,
+                    view_id,
+                    config: &editor.config,
+                };
+                if let Err(err) = events::dispatch(event) {
+                    errors.push_str(&format!("{}\n", err));
+                    continue;
+                }
+            }
+            if let Some(err) = doc.take_format_error() {
+                errors.push_str(&format!("saved without formatting: {}\n", err));
+            }
 
-            // TODO: handle error.
-            let _ = helix_lsp::block_on(tokio::spawn(doc.save()));
+            let result = match helix_lsp::block_on(tokio::spawn(doc.save())) {
+                Ok(result) => result,
+                Err(err) => Err(err.into()),
+            };
+            if let Err(err) = result {
+                errors.push_str(&format!("failed to save: {}\n", err));
+            }
         }
 
         if quit {
@@ -1514,7 +2231,10 @@ mod cmd {
             }
         }
 
-        bail!(errors)
+        if !errors.is_empty() {
+            bail!(errors)
+        }
+        Ok(())
     }
 
     fn write_all(
@@ -1593,6 +2313,21 @@ mod cmd {
         yank_main_selection_to_clipboard_impl(&mut cx.editor)
     }
 
+    fn yank_joined(
+        cx: &mut compositor::Context,
+        args: &[&str],
+        _event: PromptEvent,
+    ) -> anyhow::Result<()> {
+        let (_, doc) = current!(cx.editor);
+        let separator = args
+            .first()
+            .copied()
+            .unwrap_or_else(|| doc.line_ending.as_str());
+        let register = cx.editor.selected_register.take().name();
+        yank_joined_impl(cx.editor, separator, register);
+        Ok(())
+    }
+
     fn yank_joined_to_clipboard(
         cx: &mut compositor::Context,
         args: &[&str],
@@ -1686,6 +2421,38 @@ mod cmd {
         Ok(())
     }
 
+    fn set_option(
+        cx: &mut compositor::Context,
+        args: &[&str],
+        _event: PromptEvent,
+    ) -> anyhow::Result<()> {
+        let (key, value) = match args {
+            [key, value @ ..] if !value.is_empty() => (key, value.join(" ")),
+            _ => bail!("Bad arguments. Usage: `:set key value`"),
+        };
+        cx.editor.config.set(key, &value)
+    }
+
+    fn toggle_option(
+        cx: &mut compositor::Context,
+        args: &[&str],
+        _event: PromptEvent,
+    ) -> anyhow::Result<()> {
+        let key = args.first().context("option name not provided")?;
+        let value = cx.editor.config.toggle(key)?;
+        cx.editor.set_status(format!("{} = {}", key, value));
+        Ok(())
+    }
+
+    fn character_info(
+        cx: &mut compositor::Context,
+        _args: &[&str],
+        _event: PromptEvent,
+    ) -> anyhow::Result<()> {
+        character_info_impl(cx.editor);
+        Ok(())
+    }
+
     /// Sets the [`Document`]'s encoding..
     fn set_encoding(
         cx: &mut compositor::Context,
@@ -1702,6 +2469,26 @@ mod cmd {
         }
     }
 
+    fn sort(
+        cx: &mut compositor::Context,
+        _args: &[&str],
+        _event: PromptEvent,
+    ) -> anyhow::Result<()> {
+        let (view, doc) = current!(cx.editor);
+        sort_impl(doc, view.id, false);
+        Ok(())
+    }
+
+    fn sort_reverse(
+        cx: &mut compositor::Context,
+        _args: &[&str],
+        _event: PromptEvent,
+    ) -> anyhow::Result<()> {
+        let (view, doc) = current!(cx.editor);
+        sort_impl(doc, view.id, true);
+        Ok(())
+    }
+
     /// Reload the [`Document`] from its source file.
     fn reload(
         cx: &mut compositor::Context,
@@ -1712,202 +2499,476 @@ mod cmd {
         doc.reload(view.id)
     }
 
+    /// Opens the log file, following what's written to it.
+    fn log_open(
+        cx: &mut compositor::Context,
+        _args: &[&str],
+        _event: PromptEvent,
+    ) -> anyhow::Result<()> {
+        cx.editor.open(helix_core::log_file(), Action::Replace)?;
+        let (view, doc) = current!(cx.editor);
+        doc.follow = true;
+        doc.readonly = true;
+        let end = last_line_start(doc.text().slice(..));
+        doc.set_selection(view.id, Selection::point(end));
+        align_view(doc, view, Align::Bottom);
+        Ok(())
+    }
+
+    /// Lists the values of the registers in a scratch buffer.
+    fn registers(
+        cx: &mut compositor::Context,
+        _args: &[&str],
+        _event: PromptEvent,
+    ) -> anyhow::Result<()> {
+        let mut list = String::new();
+        for register in cx.editor.registers.iter() {
+            list.push_str(&format!(
+                "{}  {}\n",
+                register.name(),
+                register_preview(register.read())
+            ));
+        }
+        if list.is_empty() {
+            cx.editor.set_status("All registers are empty".to_owned());
+            return Ok(());
+        }
+
+        let mut doc = Document::from(Rope::from(list), None);
+        doc.readonly = true;
+        cx.editor.new_file_from_document(Action::VerticalSplit, doc);
+        Ok(())
+    }
+
+    /// Opens the tutorial as a scratch buffer, so the original stays untouched.
+    fn tutor(
+        cx: &mut compositor::Context,
+        _args: &[&str],
+        _event: PromptEvent,
+    ) -> anyhow::Result<()> {
+        let path = helix_core::tutor_file();
+        let mut file =
+            std::fs::File::open(&path).with_context(|| format!("unable to open {:?}", path))?;
+        cx.editor
+            .new_file_from_document(Action::Replace, Document::from_stream(&mut file)?);
+        Ok(())
+    }
+
+    fn health(
+        cx: &mut compositor::Context,
+        _args: &[&str],
+        _event: PromptEvent,
+    ) -> anyhow::Result<()> {
+        let report = crate::health::report(cx.editor);
+        let mut doc = Document::from(Rope::from(report), None);
+        doc.readonly = true;
+        cx.editor.new_file_from_document(Action::VerticalSplit, doc);
+        Ok(())
+    }
+
+    fn reset_hunk(
+        cx: &mut compositor::Context,
+        _args: &[&str],
+        _event: PromptEvent,
+    ) -> anyhow::Result<()> {
+        let (view, doc) = current!(cx.editor);
+        doc.update_diff();
+
+        let diff_base = doc.diff_base().context("file is not tracked by git")?;
+        let text = doc.text();
+        let line = text.char_to_line(doc.selection(view.id).cursor());
+
+        let hunk = doc
+            .hunks()
+            .iter()
+            .find(|hunk| hunk.shown_lines(text).contains(&line))
+            .context("no change under the cursor")?;
+
+        let start = text.line_to_char(hunk.after.start);
+        let end = text.line_to_char(hunk.after.end);
+        let before = diff_base.slice(
+            diff_base.line_to_char(hunk.before.start)..diff_base.line_to_char(hunk.before.end),
+        );
+        let transaction = Transaction::change(
+            text,
+            std::iter::once((start, end, Some(Tendril::from(before.to_string())))),
+        );
+
+        doc.apply(&transaction, view.id);
+        doc.append_changes_to_history(view.id);
+        Ok(())
+    }
+
+    fn diff(
+        cx: &mut compositor::Context,
+        _args: &[&str],
+        _event: PromptEvent,
+    ) -> anyhow::Result<()> {
+        let (_, doc) = current!(cx.editor);
+        let path = doc
+            .path()
+            .context("scratch buffer has no file to compare to")?;
+        let name = doc.relative_path().unwrap_or_else(|| path.clone());
+        let name = name.to_string_lossy();
+
+        let on_disk =
+            std::fs::read_to_string(path).with_context(|| format!("unable to read {:?}", path))?;
+        let diff = helix_core::diff::unified_diff(
+            &Rope::from(on_disk),
+            doc.text(),
+            &format!("{} (on disk)", name),
+            &format!("{} (buffer)", name),
+        );
+        if diff.is_empty() {
+            cx.editor.set_status("No unsaved changes".to_owned());
+            return Ok(());
+        }
+
+        let mut doc = Document::from(Rope::from(diff), None);
+        doc.readonly = true;
+        cx.editor.new_file_from_document(Action::VerticalSplit, doc);
+        Ok(())
+    }
+
+    fn debug_start(
+        cx: &mut compositor::Context,
+        args: &[&str],
+        _event: PromptEvent,
+    ) -> anyhow::Result<()> {
+        let template = args.first().copied();
+        let params = args.get(1..).unwrap_or(&[]);
+        dap_start_impl(cx.editor, cx.jobs, template, params)
+    }
+
     pub const TYPABLE_COMMAND_LIST: &[TypableCommand] = &[
         TypableCommand {
             name: "quit",
-            alias: Some("q"),
+            aliases: &["q"],
             doc: "Close the current view.",
             fun: quit,
             completer: None,
         },
         TypableCommand {
             name: "quit!",
-            alias: Some("q!"),
+            aliases: &["q!"],
             doc: "Close the current view.",
             fun: force_quit,
             completer: None,
         },
         TypableCommand {
             name: "open",
-            alias: Some("o"),
+            aliases: &["o", "edit", "e"],
             doc: "Open a file from disk into the current view.",
             fun: open,
             completer: Some(completers::filename),
         },
+        TypableCommand {
+            name: "view",
+            aliases: &[],
+            doc: "Open a file from disk into the current view, refusing edits.",
+            fun: view,
+            completer: Some(completers::filename),
+        },
         TypableCommand {
             name: "write",
-            alias: Some("w"),
+            aliases: &["w"],
             doc: "Write changes to disk. Accepts an optional path (:write some/path.txt)",
             fun: write,
             completer: Some(completers::filename),
         },
+        TypableCommand {
+            name: "write!",
+            aliases: &["w!"],
+            doc: "Write changes to disk even if the file was modified by another program. Accepts an optional path (:write! some/path.txt)",
+            fun: force_write,
+            completer: Some(completers::filename),
+        },
         TypableCommand {
             name: "new",
-            alias: Some("n"),
+            aliases: &["n"],
             doc: "Create a new scratch buffer.",
             fun: new_file,
             completer: Some(completers::filename),
         },
         TypableCommand {
             name: "format",
-            alias: Some("fmt"),
+            aliases: &["fmt"],
             doc: "Format the file using a formatter.",
             fun: format,
             completer: None,
         },
         TypableCommand {
             name: "indent-style",
-            alias: None,
+            aliases: &[],
             doc: "Set the indentation style for editing. ('t' for tabs or 1-8 for number of spaces.)",
             fun: set_indent_style,
+            completer: Some(completers::indent_style),
+        },
+        TypableCommand {
+            name: "tab-width",
+            aliases: &[],
+            doc: "Set the display width of tabs in columns (1-16), independently of the indentation style.",
+            fun: set_tab_width,
             completer: None,
         },
         TypableCommand {
             name: "line-ending",
-            alias: None,
-            doc: "Set the document's default line ending. Options: crlf, lf, cr, ff, nel.",
+            aliases: &[],
+            doc: "Convert the document's line endings and set its default. Options: crlf, lf, cr, ff, nel.",
             fun: set_line_ending,
+            completer: Some(completers::line_ending),
+        },
+        TypableCommand {
+            name: "nohl",
+            aliases: &["noh"],
+            doc: "Clear the highlighted search matches.",
+            fun: no_highlight,
+            completer: None,
+        },
+        TypableCommand {
+            name: "replace",
+            aliases: &["s"],
+            doc: "Replace the matches of a regex within the given lines (`%`, `N` or `N,M`; the whole document by default), previewing them while typing.",
+            fun: replace,
+            completer: None,
+        },
+        TypableCommand {
+            name: "reflow",
+            aliases: &[],
+            doc: "Hard-wrap the selected lines to the given width, or the configured text width.",
+            fun: reflow_selections,
             completer: None,
         },
         TypableCommand {
             name: "earlier",
-            alias: Some("ear"),
+            aliases: &["ear"],
             doc: "Jump back to an earlier point in edit history. Accepts a number of steps or a time span.",
             fun: earlier,
             completer: None,
         },
         TypableCommand {
             name: "later",
-            alias: Some("lat"),
+            aliases: &["lat"],
             doc: "Jump to a later point in edit history. Accepts a number of steps or a time span.",
             fun: later,
             completer: None,
         },
         TypableCommand {
             name: "write-quit",
-            alias: Some("wq"),
+            aliases: &["wq"],
             doc: "Writes changes to disk and closes the current view. Accepts an optional path (:wq some/path.txt)",
             fun: write_quit,
             completer: Some(completers::filename),
         },
         TypableCommand {
             name: "write-quit!",
-            alias: Some("wq!"),
+            aliases: &["wq!"],
             doc: "Writes changes to disk and closes the current view forcefully. Accepts an optional path (:wq! some/path.txt)",
             fun: force_write_quit,
             completer: Some(completers::filename),
         },
         TypableCommand {
             name: "write-all",
-            alias: Some("wa"),
-            doc: "Writes changes from all views to disk.",
+            aliases: &["wa"],
+            doc: "Writes all modified buffers to disk.",
             fun: write_all,
             completer: None,
         },
         TypableCommand {
             name: "write-quit-all",
-            alias: Some("wqa"),
+            aliases: &["wqa"],
             doc: "Writes changes from all views to disk and close all views.",
             fun: write_all_quit,
             completer: None,
         },
         TypableCommand {
             name: "write-quit-all!",
-            alias: Some("wqa!"),
+            aliases: &["wqa!"],
             doc: "Writes changes from all views to disk and close all views forcefully (ignoring unsaved changes).",
             fun: force_write_all_quit,
             completer: None,
         },
         TypableCommand {
             name: "quit-all",
-            alias: Some("qa"),
+            aliases: &["qa"],
             doc: "Close all views.",
             fun: quit_all,
             completer: None,
         },
         TypableCommand {
             name: "quit-all!",
-            alias: Some("qa!"),
+            aliases: &["qa!"],
             doc: "Close all views forcefully (ignoring unsaved changes).",
             fun: force_quit_all,
             completer: None,
         },
         TypableCommand {
             name: "theme",
-            alias: None,
+            aliases: &[],
             doc: "Change the theme of current view. Requires theme name as argument (:theme <name>)",
             fun: theme,
             completer: Some(completers::theme),
         },
+        TypableCommand {
+            name: "yank-join",
+            aliases: &[],
+            doc: "Yank joined selections. A separator can be provided as first argument. Default value is newline.",
+            fun: yank_joined,
+            completer: None,
+        },
         TypableCommand {
             name: "clipboard-yank",
-            alias: None,
+            aliases: &[],
             doc: "Yank main selection into system clipboard.",
             fun: yank_main_selection_to_clipboard,
             completer: None,
         },
         TypableCommand {
             name: "clipboard-yank-join",
-            alias: None,
+            aliases: &[],
             doc: "Yank joined selections into system clipboard. A separator can be provided as first argument. Default value is newline.", // FIXME: current UI can't display long doc.
             fun: yank_joined_to_clipboard,
             completer: None,
         },
         TypableCommand {
             name: "clipboard-paste-after",
-            alias: None,
+            aliases: &[],
             doc: "Paste system clipboard after selections.",
             fun: paste_clipboard_after,
             completer: None,
         },
         TypableCommand {
             name: "clipboard-paste-before",
-            alias: None,
+            aliases: &[],
             doc: "Paste system clipboard before selections.",
             fun: paste_clipboard_before,
             completer: None,
         },
         TypableCommand {
             name: "clipboard-paste-replace",
-            alias: None,
+            aliases: &[],
             doc: "Replace selections with content of system clipboard.",
             fun: replace_selections_with_clipboard,
             completer: None,
         },
         TypableCommand {
             name: "show-clipboard-provider",
-            alias: None,
+            aliases: &[],
             doc: "Show clipboard provider name in status bar.",
             fun: show_clipboard_provider,
             completer: None,
         },
         TypableCommand {
             name: "change-current-directory",
-            alias: Some("cd"),
+            aliases: &["cd"],
             doc: "Change the current working directory (:cd <dir>).",
             fun: change_current_directory,
             completer: Some(completers::directory),
         },
         TypableCommand {
             name: "show-directory",
-            alias: Some("pwd"),
+            aliases: &["pwd"],
             doc: "Show the current working directory.",
             fun: show_current_directory,
             completer: None,
         },
+        TypableCommand {
+            name: "set-option",
+            aliases: &["set"],
+            doc: "Set a config option at runtime, like `:set search.smart-case false`.",
+            fun: set_option,
+            completer: Some(completers::option),
+        },
+        TypableCommand {
+            name: "toggle-option",
+            aliases: &["toggle"],
+            doc: "Flip a boolean config option at runtime, like `:toggle auto-info`.",
+            fun: toggle_option,
+            completer: Some(completers::option),
+        },
+        TypableCommand {
+            name: "character-info",
+            aliases: &["char-info", "char"],
+            doc: "Show the code points, their names and the UTF-8 bytes of the character under the cursor.",
+            fun: character_info,
+            completer: None,
+        },
         TypableCommand {
             name: "encoding",
-            alias: None,
+            aliases: &[],
             doc: "Set encoding based on `https://encoding.spec.whatwg.org`",
             fun: set_encoding,
             completer: None,
         },
         TypableCommand {
             name: "reload",
-            alias: None,
+            aliases: &[],
             doc: "Discard changes and reload from the source file.",
             fun: reload,
             completer: None,
+        },
+        TypableCommand {
+            name: "log-open",
+            aliases: &[],
+            doc: "Open the log file, following what's written to it.",
+            fun: log_open,
+            completer: None,
+        },
+        TypableCommand {
+            name: "registers",
+            aliases: &["reg"],
+            doc: "List the values of the registers.",
+            fun: registers,
+            completer: None,
+        },
+        TypableCommand {
+            name: "tutor",
+            aliases: &[],
+            doc: "Open the tutorial.",
+            fun: tutor,
+            completer: None,
+        },
+        TypableCommand {
+            name: "health",
+            aliases: &[],
+            doc: "Report the grammars and language servers found, the clipboard provider and terminal colors.",
+            fun: health,
+            completer: None,
+        },
+        TypableCommand {
+            name: "reset-hunk",
+            aliases: &[],
+            doc: "Revert the lines changed since the git HEAD under the cursor.",
+            fun: reset_hunk,
+            completer: None,
+        },
+        TypableCommand {
+            name: "diff",
+            aliases: &[],
+            doc: "Show the unsaved changes as a diff in a new buffer.",
+            fun: diff,
+            completer: None,
+        },
+        TypableCommand {
+            name: "debug-start",
+            aliases: &["dbg"],
+            doc: "Start a debug session from a template of the language, with the template's arguments. The first template is used if none is given.",
+            fun: debug_start,
+            completer: None,
+        },
+        TypableCommand {
+            name: "sort",
+            aliases: &[],
+            doc: "Sort the selections, or the lines of a single selection.",
+            fun: sort,
+            completer: None,
+        },
+        TypableCommand {
+            name: "rsort",
+            aliases: &[],
+            doc: "Sort the selections, or the lines of a single selection, in reverse order.",
+            fun: sort_reverse,
+            completer: None,
         }
     ];
 
@@ -1916,8 +2977,8 @@ mod cmd {
 
         for cmd in TYPABLE_COMMAND_LIST {
             map.insert(cmd.name, cmd);
-            if let Some(alias) = cmd.alias {
-                map.insert(alias, cmd);
+            for alias in cmd.aliases {
+                map.insert(*alias, cmd);
             }
         }
 
@@ -1925,23 +2986,140 @@ mod cmd {
     });
 }
 
-fn command_mode(cx: &mut Context) {
-    let mut prompt = Prompt::new(
-        ":".to_owned(),
-        Some(':'),
-        |input: &str| {
-            // we use .this over split_whitespace() because we care about empty segments
-            let parts = input.split(' ').collect::<Vec<&str>>();
+/// An entry of the command palette.
+#[derive(Clone)]
+enum PaletteCommand {
+    Static(Command),
+    Typable(&'static cmd::TypableCommand),
+    Plugin(helix_view::editor::PluginConfig),
+}
+
+fn command_palette(cx: &mut Context) {
+    let (_, doc) = current!(cx.editor);
+    let mode = doc.mode();
+    let plugins = cx.editor.config.plugins.clone();
+
+    cx.callback = Some(Box::new(move |compositor: &mut Compositor| {
+        let editor_view = compositor
+            .find(std::any::type_name::<ui::EditorView>())
+            .and_then(|component| component.as_any_mut().downcast_mut::<ui::EditorView>());
+        let keymap = match editor_view {
+            Some(editor_view) => editor_view.keymaps()[&mode].reverse_map(),
+            None => std::collections::HashMap::new(),
+        };
+
+        let commands = Command::COMMAND_LIST
+            .iter()
+            .cloned()
+            .map(PaletteCommand::Static)
+            .chain(
+                cmd::TYPABLE_COMMAND_LIST
+                    .iter()
+                    .map(PaletteCommand::Typable),
+            )
+            .chain(plugins.into_iter().map(PaletteCommand::Plugin))
+            .collect();
+
+        let picker = Picker::with_callback(
+            commands,
+            move |command: &PaletteCommand| match command {
+                PaletteCommand::Static(command) => match keymap.get(command.name()) {
+                    Some(bindings) => {
+                        let bindings: Vec<String> = bindings
+                            .iter()
+                            .map(|keys| keys.iter().map(ToString::to_string).collect())
+                            .collect();
+                        format!(
+                            "{} ({}) {}",
+                            command.name(),
+                            bindings.join(", "),
+                            command.doc()
+                        )
+                        .into()
+                    }
+                    None => format!("{} {}", command.name(), command.doc()).into(),
+                },
+                PaletteCommand::Typable(command) => {
+                    format!(":{} {}", command.name, command.doc).into()
+                }
+                PaletteCommand::Plugin(plugin) => format!(":{} {}", plugin.name, plugin.doc).into(),
+            },
+            move |cx: &mut compositor::Context, command: &PaletteCommand, _action| match command {
+                PaletteCommand::Static(command) => {
+                    let mut cx = Context {
+                        selected_register: helix_view::RegisterSelection::default(),
+                        count: None,
+                        editor: cx.editor,
+                        callback: None,
+                        on_next_key_callback: None,
+                        jobs: cx.jobs,
+                    };
+                    command.execute(&mut cx);
+
+                    let callback = cx.callback;
+                    let on_next_key = cx.on_next_key_callback;
+                    Some(Box::new(move |compositor: &mut Compositor| {
+                        if let Some(on_next_key) = on_next_key {
+                            let editor_view = compositor
+                                .find(std::any::type_name::<ui::EditorView>())
+                                .and_then(|component| {
+                                    component.as_any_mut().downcast_mut::<ui::EditorView>()
+                                });
+                            if let Some(editor_view) = editor_view {
+                                editor_view.set_on_next_key(on_next_key);
+                            }
+                        }
+                        if let Some(callback) = callback {
+                            callback(compositor);
+                        }
+                    }))
+                }
+                PaletteCommand::Typable(command) => {
+                    if let Err(err) = (command.fun)(cx, &[], PromptEvent::Validate) {
+                        cx.editor.set_error(format!("{}", err));
+                    }
+                    None
+                }
+                PaletteCommand::Plugin(plugin) => {
+                    if let Err(err) = crate::plugin::run_command(cx.editor, &plugin.name, &[]) {
+                        cx.editor.set_error(format!("{}", err));
+                    }
+                    None
+                }
+            },
+        );
+        compositor.push(Box::new(picker));
+    }));
+}
+
+fn command_mode(cx: &mut Context) {
+    use fuzzy_matcher::skim::SkimMatcherV2 as Matcher;
+    use fuzzy_matcher::FuzzyMatcher;
+    use std::cmp::Reverse;
+
+    let plugins = cx.editor.config.plugins.clone();
+    let plugin_names: Vec<String> = plugins.iter().map(|plugin| plugin.name.clone()).collect();
+    let mut prompt = Prompt::new(
+        ":".to_owned(),
+        Some(':'),
+        move |input: &str| {
+            // we use .this over split_whitespace() because we care about empty segments
+            let parts = input.split(' ').collect::<Vec<&str>>();
 
             // simple heuristic: if there's no just one part, complete command name.
             // if there's a space, per command completion kicks in.
             if parts.len() <= 1 {
-                let end = 0..;
-                cmd::TYPABLE_COMMAND_LIST
+                let matcher = Matcher::default();
+
+                let mut matches: Vec<_> = cmd::TYPABLE_COMMAND_LIST
                     .iter()
-                    .filter(|command| command.name.contains(input))
-                    .map(|command| (end.clone(), Cow::Borrowed(command.name)))
-                    .collect()
+                    .map(|command| Cow::Borrowed(command.name))
+                    .chain(plugin_names.iter().cloned().map(Cow::Owned))
+                    .filter_map(|name| matcher.fuzzy_match(&name, input).map(|score| (name, score)))
+                    .collect();
+
+                matches.sort_by_key(|(_name, score)| Reverse(*score));
+                matches.into_iter().map(|(name, _)| ((0..), name)).collect()
             } else {
                 let part = parts.last().unwrap();
 
@@ -1974,21 +3152,26 @@ fn command_mode(cx: &mut Context) {
                 return;
             }
 
-            if let Some(cmd) = cmd::COMMANDS.get(parts[0]) {
-                if let Err(e) = (cmd.fun)(cx, &parts[1..], event) {
-                    cx.editor.set_error(format!("{}", e));
+            let result = match cmd::COMMANDS.get(parts[0]) {
+                Some(cmd) => (cmd.fun)(cx, &parts[1..], event),
+                None => {
+                    let args: Vec<String> = parts[1..].iter().map(|&arg| arg.to_owned()).collect();
+                    crate::plugin::run_command(cx.editor, parts[0], &args)
                 }
-            } else {
-                cx.editor
-                    .set_error(format!("no such command: '{}'", parts[0]));
             };
+            if let Err(e) = result {
+                cx.editor.set_error(format!("{}", e));
+            }
         },
     );
-    prompt.doc_fn = Box::new(|input: &str| {
+    prompt.doc_fn = Box::new(move |input: &str| {
         let part = input.split(' ').next().unwrap_or_default();
 
         if let Some(cmd::TypableCommand { doc, .. }) = cmd::COMMANDS.get(part) {
-            return Some(doc);
+            return Some(Cow::Borrowed(doc));
+        }
+        if let Some(plugin) = crate::plugin::find(&plugins, part) {
+            return Some(Cow::Owned(plugin.doc.clone()));
         }
 
         None
@@ -1998,8 +3181,8 @@ fn command_mode(cx: &mut Context) {
 }
 
 fn file_picker(cx: &mut Context) {
-    let root = find_root(None).unwrap_or_else(|| PathBuf::from("./"));
-    let picker = ui::file_picker(root);
+    let root = cx.editor.workspace_root();
+    let picker = ui::file_picker(root, &cx.editor.config.file_picker);
     cx.push_layer(Box::new(picker));
 }
 
@@ -2081,7 +3264,7 @@ fn symbol_picker(cx: &mut Context) {
                     }
                 };
 
-                let picker = Picker::new(
+                let picker = FilePicker::new(
                     symbols,
                     |symbol| (&symbol.name).into(),
                     move |editor: &mut Editor, symbol, _action| {
@@ -2095,6 +3278,7 @@ fn symbol_picker(cx: &mut Context) {
                             align_view(doc, view, Align::Center);
                         }
                     },
+                    |_editor, symbol| location_to_file_location(&symbol.location),
                 );
                 compositor.push(Box::new(picker))
             }
@@ -2152,59 +3336,93 @@ pub fn code_action(cx: &mut Context) {
     )
 }
 
+fn spelling_suggestions(cx: &mut Context) {
+    let (view, doc) = current!(cx.editor);
+    let cursor = doc.selection(view.id).cursor();
+    let range = match doc
+        .misspellings()
+        .iter()
+        .find(|range| range.contains(&cursor))
+    {
+        Some(range) => range.clone(),
+        None => {
+            cx.editor
+                .set_status("No misspelled word under the cursor".to_owned());
+            return;
+        }
+    };
+    let word = String::from(doc.text().slice(range.clone()));
+
+    // `None` adds the word to the dictionary
+    let mut options: Vec<Option<String>> = match &cx.editor.dictionary {
+        Some(dictionary) => dictionary
+            .suggestions(&word, 10)
+            .into_iter()
+            .map(Some)
+            .collect(),
+        None => return,
+    };
+    options.push(None);
+
+    let add_label = format!("Add \"{}\" to the dictionary", word);
+    let picker = Picker::new(
+        options,
+        move |option| match option {
+            Some(suggestion) => suggestion.as_str().into(),
+            None => add_label.clone().into(),
+        },
+        move |editor: &mut Editor, option, _action| match option {
+            Some(suggestion) => {
+                let (view, doc) = current!(editor);
+                let transaction = Transaction::change(
+                    doc.text(),
+                    std::iter::once((range.start, range.end, Some(suggestion.as_str().into()))),
+                );
+                doc.apply(&transaction, view.id);
+                doc.append_changes_to_history(view.id);
+            }
+            None => {
+                if let Err(err) = editor.add_to_dictionary(&word) {
+                    editor.set_error(format!("Failed to add to the dictionary: {}", err));
+                }
+            }
+        },
+    );
+    cx.push_layer(Box::new(picker));
+}
+
+/// Applies `workspace_edit` and reports the documents it changed, which are left unsaved.
 fn apply_workspace_edit(
     editor: &mut Editor,
     offset_encoding: OffsetEncoding,
     workspace_edit: &lsp::WorkspaceEdit,
 ) {
-    if let Some(ref changes) = workspace_edit.changes {
-        log::debug!("workspace changes: {:?}", changes);
-        editor.set_error(String::from("Handling workspace changesis not implemented yet, see https://github.com/helix-editor/helix/issues/183"));
-        return;
-        // Not sure if it works properly, it'll be safer to just panic here to avoid breaking some parts of code on which code actions will be used
-        // TODO: find some example that uses workspace changes, and test it
-        // for (url, edits) in changes.iter() {
-        //     let file_path = url.origin().ascii_serialization();
-        //     let file_path = std::path::PathBuf::from(file_path);
-        //     let file = std::fs::File::open(file_path).unwrap();
-        //     let mut text = Rope::from_reader(file).unwrap();
-        //     let transaction = edits_to_changes(&text, edits);
-        //     transaction.apply(&mut text);
-        // }
-    }
-
-    if let Some(ref document_changes) = workspace_edit.document_changes {
-        match document_changes {
-            lsp::DocumentChanges::Edits(document_edits) => {
-                for document_edit in document_edits {
-                    let (view, doc) = current!(editor);
-                    assert_eq!(doc.url().unwrap(), document_edit.text_document.uri);
-                    let edits = document_edit
-                        .edits
-                        .iter()
-                        .map(|edit| match edit {
-                            lsp::OneOf::Left(text_edit) => text_edit,
-                            lsp::OneOf::Right(annotated_text_edit) => {
-                                &annotated_text_edit.text_edit
-                            }
-                        })
-                        .cloned()
-                        .collect();
-
-                    let transaction = helix_lsp::util::generate_transaction_from_edits(
-                        doc.text(),
-                        edits,
-                        offset_encoding,
-                    );
-                    doc.apply(&transaction, view.id);
-                    doc.append_changes_to_history(view.id);
-                }
-            }
-            lsp::DocumentChanges::Operations(operations) => {
-                log::debug!("document changes - operations: {:?}", operations);
-                editor.set_error(String::from("Handling document operations is not implemented yet, see https://github.com/helix-editor/helix/issues/183"));
-            }
+    let paths = match editor.apply_workspace_edit(offset_encoding, workspace_edit) {
+        Ok(paths) => paths,
+        Err(err) => {
+            editor.set_error(format!("Couldn't apply the edit: {}", err));
+            return;
         }
+    };
+
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let names: Vec<_> = paths
+        .iter()
+        .map(|path| {
+            path.strip_prefix(&cwd)
+                .unwrap_or(path)
+                .display()
+                .to_string()
+        })
+        .collect();
+    match names.as_slice() {
+        [] => editor.set_status("The edit changes nothing".to_owned()),
+        [name] => editor.set_status(format!("Edited {}", name)),
+        names => editor.set_status(format!(
+            "Edited {} files, undo in each or write them with :write-all: {}",
+            names.len(),
+            names.join(", ")
+        )),
     }
 }
 
@@ -2240,13 +3458,6 @@ fn append_to_line(cx: &mut Context) {
     doc.set_selection(view.id, selection);
 }
 
-/// Sometimes when applying formatting changes we want to mark the buffer as unmodified, for
-/// example because we just applied the same changes while saving.
-enum Modified {
-    SetUnmodified,
-    LeaveModified,
-}
-
 // Creates an LspCallback that waits for formatting changes to be computed. When they're done,
 // it applies them, but only if the doc hasn't changed.
 //
@@ -2255,19 +3466,22 @@ enum Modified {
 async fn make_format_callback(
     doc_id: DocumentId,
     doc_version: i32,
-    modified: Modified,
-    format: impl Future<Output = helix_lsp::util::LspFormatting> + Send + 'static,
+    format: impl Future<Output = anyhow::Result<helix_view::document::Formatting>> + Send + 'static,
 ) -> anyhow::Result<job::Callback> {
     let format = format.await;
     let call: job::Callback = Box::new(move |editor: &mut Editor, _compositor: &mut Compositor| {
+        let format = match format {
+            Ok(format) => format,
+            Err(err) => {
+                editor.set_error(format!("Formatting failed: {}", err));
+                return;
+            }
+        };
         let view_id = view!(editor).id;
         if let Some(doc) = editor.document_mut(doc_id) {
             if doc.version() == doc_version {
                 doc.apply(&Transaction::from(format), view_id);
                 doc.append_changes_to_history(view_id);
-                if let Modified::SetUnmodified = modified {
-                    doc.reset_modified();
-                }
             } else {
                 log::info!("discarded formatting changes because the document changed");
             }
@@ -2314,28 +3528,36 @@ fn open(cx: &mut Context, open: Open) {
             )
         };
 
-        // TODO: share logic with insert_newline for indentation
-        let indent_level = indent::suggested_indent_for_pos(
-            doc.language_config(),
-            doc.syntax(),
-            text,
-            line_end_index,
-            true,
-        );
-        let indent = doc.indent_unit().repeat(indent_level);
-        let indent_len = indent.len();
-        let mut text = String::with_capacity(1 + indent_len);
+        // continue line comments, keeping the comment's indentation
+        let current_line = text.line(text.char_to_line(range.head));
+        let indent = match continued_comment(doc, current_line) {
+            Some(prefix) => prefix,
+            None => {
+                // TODO: share logic with insert_newline for indentation
+                let indent_level = indent::suggested_indent_for_pos(
+                    doc.language_config(),
+                    doc.syntax(),
+                    text,
+                    line_end_index,
+                    true,
+                );
+                doc.indent_unit().repeat(indent_level)
+            }
+        };
+        let mut text = String::with_capacity(1 + indent.len());
         text.push_str(doc.line_ending.as_str());
         text.push_str(&indent);
+        let line_len = text.chars().count();
         let text = text.repeat(count);
 
         // calculate new selection ranges
         let pos = offs + line_end_index + line_end_offset_width;
         for i in 0..count {
-            // pos                    -> beginning of reference line,
-            // + (i * (1+indent_len)) -> beginning of i'th line from pos
-            // + indent_len ->        -> indent for i'th line
-            ranges.push(Range::point(pos + (i * (1 + indent_len)) + indent_len));
+            // pos                 -> beginning of reference line,
+            // + (i * line_len)    -> beginning of i'th line from pos
+            // + indent            -> indent for i'th line
+            let indent_len = line_len - doc.line_ending.len_chars();
+            ranges.push(Range::point(pos + (i * line_len) + indent_len));
         }
 
         offs += text.chars().count();
@@ -2348,6 +3570,14 @@ fn open(cx: &mut Context, open: Open) {
     doc.apply(&transaction, view.id);
 }
 
+/// If `line` is a line comment, returns its indentation followed by the comment token, to start
+/// a new line with.
+fn continued_comment(doc: &Document, line: RopeSlice) -> Option<String> {
+    let token = doc.language_config()?.comment_token.as_deref()?;
+    let start = comment::line_comment_start(line, token)?;
+    Some(format!("{}{} ", line.slice(..start), token))
+}
+
 // o inserts a new line after each line with a selection
 fn open_below(cx: &mut Context) {
     open(cx, Open::Below)
@@ -2361,7 +3591,7 @@ fn open_above(cx: &mut Context) {
 fn normal_mode(cx: &mut Context) {
     let (view, doc) = current!(cx.editor);
 
-    doc.mode = Mode::Normal;
+    doc.set_mode(Mode::Normal);
 
     doc.append_changes_to_history(view.id);
 
@@ -2387,6 +3617,22 @@ fn push_jump(editor: &mut Editor) {
     view.jumps.push(jump);
 }
 
+fn goto_last_modification(cx: &mut Context) {
+    let doc = doc_mut!(cx.editor);
+    let pos = match doc.last_edit_pos() {
+        Some(pos) => pos.min(doc.text().len_chars().saturating_sub(1)),
+        None => {
+            cx.editor
+                .set_error("no modification in this buffer".to_owned());
+            return;
+        }
+    };
+    push_jump(cx.editor);
+    let (view, doc) = current!(cx.editor);
+    let selection = goto_selection(doc, view.id, pos);
+    doc.set_selection(view.id, selection);
+}
+
 fn goto_last_accessed_file(cx: &mut Context) {
     let alternate_file = view!(cx.editor).last_accessed_doc;
     if let Some(alt) = alternate_file {
@@ -2396,12 +3642,43 @@ fn goto_last_accessed_file(cx: &mut Context) {
     }
 }
 
+fn goto_next_buffer(cx: &mut Context) {
+    goto_buffer(cx.editor, Direction::Forward);
+}
+
+fn goto_previous_buffer(cx: &mut Context) {
+    goto_buffer(cx.editor, Direction::Backward);
+}
+
+/// Opens the document after or before the current one in the order they were opened, wrapping
+/// around.
+fn goto_buffer(editor: &mut Editor, direction: Direction) {
+    let current = view!(editor).doc;
+    let ids = editor.document_order();
+    let index = ids.iter().position(|id| *id == current).unwrap_or(0);
+    let index = match direction {
+        Direction::Forward => (index + 1) % ids.len(),
+        Direction::Backward => (index + ids.len() - 1) % ids.len(),
+    };
+    editor.switch(ids[index], Action::Replace);
+}
+
 fn select_mode(cx: &mut Context) {
-    doc_mut!(cx.editor).mode = Mode::Select;
+    doc_mut!(cx.editor).set_mode(Mode::Select);
 }
 
 fn exit_select_mode(cx: &mut Context) {
-    doc_mut!(cx.editor).mode = Mode::Normal;
+    doc_mut!(cx.editor).set_mode(Mode::Normal);
+}
+
+/// The file and lines of `location`, for previews.
+fn location_to_file_location(location: &lsp::Location) -> Option<ui::FileLocation> {
+    let path = location.uri.to_file_path().ok()?;
+    let lines = (
+        location.range.start.line as usize,
+        location.range.end.line as usize,
+    );
+    Some((path, Some(lines)))
 }
 
 fn goto_impl(
@@ -2444,7 +3721,7 @@ fn goto_impl(
             editor.set_error("No definition found.".to_string());
         }
         _locations => {
-            let picker = ui::Picker::new(
+            let picker = ui::FilePicker::new(
                 locations,
                 |location| {
                     let file = location.uri.as_str();
@@ -2454,17 +3731,141 @@ fn goto_impl(
                 move |editor: &mut Editor, location, action| {
                     jump_to(editor, location, offset_encoding, action)
                 },
+                |_editor, location| location_to_file_location(location),
             );
             compositor.push(Box::new(picker));
         }
     }
 }
 
+/// The tags of the tags file closest to the current document, or to the working directory for
+/// scratch buffers.
+fn load_tags(editor: &Editor) -> anyhow::Result<Vec<tags::Tag>> {
+    let doc = &editor.documents[view!(editor).doc];
+    let dir = match doc.path().and_then(|path| path.parent()) {
+        Some(dir) => dir.to_path_buf(),
+        None => std::env::current_dir()?,
+    };
+    let path = tags::find_file(&dir).context("no tags file found")?;
+    tags::load(&path).with_context(|| format!("unable to read {:?}", path))
+}
+
+/// Opens the file of `tag` and selects its name at its definition.
+fn jump_to_tag(editor: &mut Editor, tag: &tags::Tag, action: Action) {
+    if let Err(err) = editor.open(tag.file.clone(), action) {
+        editor.set_error(format!("unable to open {:?}: {}", tag.file, err));
+        return;
+    }
+    let (view, doc) = current!(editor);
+    let text = doc.text().slice(..);
+    let line = match tag.line(text) {
+        Some(line) => line,
+        None => {
+            editor.set_error(format!("{} is no longer in {:?}", tag.name, tag.file));
+            return;
+        }
+    };
+    let start = text.line_to_char(line);
+    let range = match String::from(text.line(line)).find(&tag.name) {
+        Some(offset) => {
+            let from = start + text.line(line).byte_to_char(offset);
+            // ranges include their end
+            Range::new(from, from + tag.name.chars().count().saturating_sub(1))
+        }
+        None => Range::point(start),
+    };
+    doc.set_selection(view.id, Selection::single(range.anchor, range.head));
+    align_view(doc, view, Align::Center);
+}
+
+fn tag_file_location(tag: &tags::Tag) -> Option<ui::FileLocation> {
+    let lines = match tag.address {
+        tags::Address::Line(line) => Some((line, line)),
+        tags::Address::Pattern(_) => None,
+    };
+    Some((tag.file.clone(), lines))
+}
+
+fn format_tag(tag: &tags::Tag) -> Cow<'_, str> {
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let file = tag.file.strip_prefix(&cwd).unwrap_or(&tag.file);
+    match &tag.kind {
+        Some(kind) => format!("{} ({}) {}", tag.name, kind, file.display()).into(),
+        None => format!("{} {}", tag.name, file.display()).into(),
+    }
+}
+
+/// Jumps to the definition of the symbol under the cursor, or the selected one, in the tags file.
+/// Picks among the definitions when there are several.
+fn goto_tag(cx: &mut Context) {
+    let (view, doc) = current!(cx.editor);
+    let text = doc.text().slice(..);
+    let primary = doc.selection(view.id).primary();
+    // a selection of more than the char under the cursor is the name to look up
+    let range = if primary.to() > primary.from() {
+        primary
+    } else {
+        textobject::textobject_word(text, primary, textobject::TextObject::Inside, 1)
+    };
+    let name = String::from(range.fragment(text));
+    if name.trim().is_empty() {
+        cx.editor
+            .set_status("No symbol under the cursor".to_owned());
+        return;
+    }
+
+    let tags = match load_tags(cx.editor) {
+        Ok(tags) => tags,
+        Err(err) => {
+            cx.editor.set_error(err.to_string());
+            return;
+        }
+    };
+    let tags: Vec<_> = tags.into_iter().filter(|tag| tag.name == name).collect();
+
+    push_jump(cx.editor);
+    match tags.as_slice() {
+        [] => cx.editor.set_error(format!("No tag found for {}", name)),
+        [tag] => jump_to_tag(cx.editor, tag, Action::Replace),
+        _ => {
+            let picker = ui::FilePicker::new(
+                tags,
+                format_tag,
+                |editor: &mut Editor, tag, action| jump_to_tag(editor, tag, action),
+                |_editor, tag| tag_file_location(tag),
+            );
+            cx.push_layer(Box::new(picker));
+        }
+    }
+}
+
+/// Picks among all the tags of the tags file.
+fn tag_picker(cx: &mut Context) {
+    let tags = match load_tags(cx.editor) {
+        Ok(tags) => tags,
+        Err(err) => {
+            cx.editor.set_error(err.to_string());
+            return;
+        }
+    };
+    let picker = ui::FilePicker::new(
+        tags,
+        format_tag,
+        |editor: &mut Editor, tag, action| {
+            push_jump(editor);
+            jump_to_tag(editor, tag, action)
+        },
+        |_editor, tag| tag_file_location(tag),
+    );
+    cx.push_layer(Box::new(picker));
+}
+
 fn goto_definition(cx: &mut Context) {
     let (view, doc) = current!(cx.editor);
     let language_server = match doc.language_server() {
         Some(language_server) => language_server,
-        None => return,
+        // tags are all there is without a language server
+        None => return goto_tag(cx),
     };
 
     let offset_encoding = language_server.offset_encoding();
@@ -2605,7 +4006,8 @@ fn goto_pos(editor: &mut Editor, pos: usize) {
 
     let (view, doc) = current!(editor);
 
-    doc.set_selection(view.id, Selection::point(pos));
+    let selection = goto_selection(doc, view.id, pos);
+    doc.set_selection(view.id, selection);
     align_view(doc, view, Align::Center);
 }
 
@@ -2678,6 +4080,115 @@ fn goto_prev_diag(cx: &mut Context) {
     goto_pos(editor, diag);
 }
 
+/// Selects the next or previous `object` (`function`, `class`) from the language's textobjects
+/// query, or extends the selection to it in select mode.
+fn goto_ts_object_impl(cx: &mut Context, object: &str, direction: Direction) {
+    let count = cx.count();
+    let (view, doc) = current!(cx.editor);
+
+    let (lang_config, syntax) = match (doc.language_config(), doc.syntax()) {
+        (Some(config), Some(syntax)) => (config, syntax),
+        _ => {
+            cx.editor
+                .set_status("Syntax tree is not available in current buffer".to_owned());
+            return;
+        }
+    };
+
+    let text = doc.text().slice(..);
+    let root = syntax.tree().root_node();
+    let selection = doc.selection(view.id).transform(|range| {
+        let new_range = textobject::goto_treesitter_object(
+            text,
+            range,
+            object,
+            direction,
+            root,
+            lang_config,
+            count,
+        );
+        match doc.mode {
+            Mode::Select => Range::new(range.anchor, new_range.head),
+            Mode::Normal | Mode::Insert => new_range,
+        }
+    });
+
+    doc.set_selection(view.id, selection);
+}
+
+fn goto_next_function(cx: &mut Context) {
+    goto_ts_object_impl(cx, "function", Direction::Forward)
+}
+
+fn goto_prev_function(cx: &mut Context) {
+    goto_ts_object_impl(cx, "function", Direction::Backward)
+}
+
+fn goto_next_class(cx: &mut Context) {
+    goto_ts_object_impl(cx, "class", Direction::Forward)
+}
+
+fn goto_prev_class(cx: &mut Context) {
+    goto_ts_object_impl(cx, "class", Direction::Backward)
+}
+
+/// Selects the lines of the next or previous hunk changed since the git `HEAD`, or extends the
+/// selection to it in select mode.
+fn goto_change_impl(cx: &mut Context, direction: Direction) {
+    let count = cx.count();
+    let (view, doc) = current!(cx.editor);
+
+    if doc.diff_base().is_none() {
+        cx.editor
+            .set_status("File is not tracked by git".to_owned());
+        return;
+    }
+    doc.update_diff();
+
+    let text = doc.text().slice(..);
+    let hunks = doc.hunks();
+    let selection = doc.selection(view.id).transform(|range| {
+        let line = text.char_to_line(range.head);
+        let hunk = match direction {
+            Direction::Forward => hunks
+                .iter()
+                .filter(|hunk| hunk.after.start > line)
+                .nth(count - 1),
+            Direction::Backward => hunks
+                .iter()
+                .rev()
+                .filter(|hunk| hunk.after.start < line)
+                .nth(count - 1),
+        };
+        let hunk = match hunk {
+            Some(hunk) => hunk,
+            None => return range,
+        };
+
+        let start = text.line_to_char(hunk.after.start.min(text.len_lines() - 1));
+        let new_range = if hunk.after.is_empty() {
+            Range::point(start)
+        } else {
+            let end = line_end_char_index(&text, hunk.after.end - 1);
+            Range::new(start, end.saturating_sub(1).max(start))
+        };
+        match doc.mode {
+            Mode::Select => Range::new(range.anchor, new_range.head),
+            Mode::Normal | Mode::Insert => new_range,
+        }
+    });
+
+    doc.set_selection(view.id, selection);
+}
+
+fn goto_next_change(cx: &mut Context) {
+    goto_change_impl(cx, Direction::Forward)
+}
+
+fn goto_prev_change(cx: &mut Context) {
+    goto_change_impl(cx, Direction::Backward)
+}
+
 fn signature_help(cx: &mut Context) {
     let (view, doc) = current!(cx.editor);
 
@@ -2808,14 +4319,20 @@ pub mod insert {
     const POST_HOOKS: &[PostHook] = &[completion, signature_help];
 
     pub fn insert_char(cx: &mut Context, c: char) {
+        let hooks: &[Hook] = if cx.editor.config.auto_pairs {
+            HOOKS
+        } else {
+            &[insert]
+        };
         let (view, doc) = current!(cx.editor);
 
         let text = doc.text();
         let selection = doc.selection(view.id);
 
         // run through insert hooks, stopping on the first one that returns Some(t)
-        for hook in HOOKS {
+        for hook in hooks {
             if let Some(transaction) = hook(text, selection, c) {
+                let transaction = transaction.with_annotation(Annotation::InsertChar);
                 doc.apply(&transaction, view.id);
                 break;
             }
@@ -2829,13 +4346,107 @@ pub mod insert {
         }
     }
 
+    /// Reads a register name and inserts that register's contents at each cursor. Each cursor
+    /// gets its own value, the last one is repeated for the remaining cursors.
+    pub fn insert_register(cx: &mut Context) {
+        cx.on_next_key(move |cx, event| {
+            let name = match event {
+                KeyEvent {
+                    code: KeyCode::Char(ch),
+                    ..
+                } => ch,
+                _ => return,
+            };
+
+            let (view, doc) = current!(cx.editor);
+            let values = match cx.editor.registers.read(name) {
+                Some(values) if !values.is_empty() => values,
+                _ => return,
+            };
+
+            let last = values.last().unwrap();
+            let mut values = values.iter().chain(std::iter::repeat(last));
+            let transaction =
+                Transaction::change_by_selection(doc.text(), doc.selection(view.id), |range| {
+                    let value = values.next().unwrap();
+                    (range.head, range.head, Some(value.as_str().into()))
+                })
+                .with_annotation(Annotation::Paste);
+            doc.apply(&transaction, view.id);
+        })
+    }
+
+    /// Prompts for a character by its hexadecimal code point, like `e9`, `U+00E9` or `0xe9`, or by
+    /// its Unicode name, like `latin small letter e with acute`, and inserts it at each cursor.
+    pub fn insert_unicode(cx: &mut Context) {
+        let prompt = Prompt::new(
+            "unicode:".to_owned(),
+            None,
+            |_input: &str| Vec::new(),
+            move |cx: &mut compositor::Context, input: &str, event: PromptEvent| {
+                if event != PromptEvent::Validate || input.is_empty() {
+                    return;
+                }
+
+                let ch = match parse_character(input) {
+                    Some(ch) => ch,
+                    None => {
+                        cx.editor.set_error(format!(
+                            "Invalid code point or character name: {}",
+                            input.trim()
+                        ));
+                        return;
+                    }
+                };
+                let (view, doc) = current!(cx.editor);
+                let transaction = Transaction::insert(
+                    doc.text(),
+                    doc.selection(view.id),
+                    Tendril::from(ch.encode_utf8(&mut [0; 4]) as &str),
+                )
+                .with_annotation(Annotation::InsertChar);
+                doc.apply(&transaction, view.id);
+            },
+        );
+        cx.push_layer(Box::new(prompt));
+    }
+
+    /// Parses a code point, falling back to a character name when the input isn't one.
+    pub(super) fn parse_character(input: &str) -> Option<char> {
+        parse_code_point(input).or_else(|| unicode_names2::character(input.trim()))
+    }
+
+    pub(super) fn parse_code_point(input: &str) -> Option<char> {
+        let input = input.trim();
+        let hex = ["U+", "u+", "0x", "0X", "\\u"]
+            .iter()
+            .find_map(|prefix| input.strip_prefix(prefix))
+            .unwrap_or(input);
+        u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+    }
+
+    /// Inserts `text` pasted in the terminal at each cursor as is, without auto-pairs or
+    /// indentation, and as a single undo step.
+    pub fn insert_pasted(editor: &mut Editor, text: &str) {
+        let (view, doc) = current!(editor);
+        let text = match doc.line_ending {
+            LineEnding::LF => Tendril::from(text),
+            line_ending => Tendril::from(text.replace('\n', line_ending.as_str()).as_str()),
+        };
+        let transaction = Transaction::insert(doc.text(), doc.selection(view.id), text)
+            .with_annotation(Annotation::Paste);
+        doc.apply(&transaction, view.id);
+        doc.append_changes_to_history(view.id);
+    }
+
     pub fn insert_tab(cx: &mut Context) {
         let (view, doc) = current!(cx.editor);
         // TODO: round out to nearest indentation level (for example a line with 3 spaces should
         // indent by one to reach 4 spaces).
 
         let indent = Tendril::from(doc.indent_unit());
-        let transaction = Transaction::insert(doc.text(), doc.selection(view.id), indent);
+        let transaction = Transaction::insert(doc.text(), doc.selection(view.id), indent)
+            .with_annotation(Annotation::InsertChar);
         doc.apply(&transaction, view.id);
     }
 
@@ -2860,6 +4471,12 @@ pub mod insert {
             };
             let curr = contents.get_char(pos).unwrap_or(' ');
 
+            // continue line comments when breaking a line after the comment token
+            let line = text.char_to_line(pos);
+            let comment = continued_comment(doc, text.line(line)).filter(|prefix| {
+                pos - text.line_to_char(line) >= prefix.trim_end().chars().count()
+            });
+
             // TODO: offset range.head by 1? when calculating?
             let indent_level = indent::suggested_indent_for_pos(
                 doc.language_config(),
@@ -2868,7 +4485,9 @@ pub mod insert {
                 pos.saturating_sub(1),
                 true,
             );
-            let indent = doc.indent_unit().repeat(indent_level);
+            let indent = comment
+                .clone()
+                .unwrap_or_else(|| doc.indent_unit().repeat(indent_level));
             let mut text = String::with_capacity(1 + indent.len());
             text.push_str(doc.line_ending.as_str());
             text.push_str(&indent);
@@ -2888,7 +4507,7 @@ pub mod insert {
             ));
 
             // if between a bracket pair
-            if helix_core::auto_pairs::PAIRS.contains(&(prev, curr)) {
+            if comment.is_none() && helix_core::auto_pairs::PAIRS.contains(&(prev, curr)) {
                 // another newline, indent the end bracket one level less
                 let indent = doc.indent_unit().repeat(indent_level.saturating_sub(1));
                 text.push_str(doc.line_ending.as_str());
@@ -2900,7 +4519,9 @@ pub mod insert {
             (pos, pos, Some(text.into()))
         });
 
-        transaction = transaction.with_selection(Selection::new(ranges, selection.primary_index()));
+        transaction = transaction
+            .with_selection(Selection::new(ranges, selection.primary_index()))
+            .with_annotation(Annotation::InsertChar);
         //
 
         doc.apply(&transaction, view.id);
@@ -2918,7 +4539,8 @@ pub mod insert {
                     range.head,
                     None,
                 )
-            });
+            })
+            .with_annotation(Annotation::DeleteChar);
         doc.apply(&transaction, view.id);
     }
 
@@ -2933,19 +4555,122 @@ pub mod insert {
                     graphemes::nth_next_grapheme_boundary(text, range.head, count),
                     None,
                 )
-            });
+            })
+            .with_annotation(Annotation::DeleteChar);
         doc.apply(&transaction, view.id);
     }
 
+    /// Start of the word before `pos`, skipping whitespace. A line ending right before `pos` is
+    /// a word of its own, so deleting it joins the lines.
+    pub(super) fn prev_word_start(text: RopeSlice, pos: usize) -> usize {
+        use helix_core::chars::{categorize_char, CharCategory};
+
+        let mut chars = text.chars_at(pos);
+        let mut pos = pos;
+        match chars.prev().map(categorize_char) {
+            None => return pos,
+            Some(CharCategory::Eol) => return graphemes::prev_grapheme_boundary(text, pos),
+            Some(_) => chars.next(),
+        };
+
+        let mut category = None;
+        while let Some(ch) = chars.prev() {
+            let ch_category = categorize_char(ch);
+            match (category, ch_category) {
+                (_, CharCategory::Eol) => break,
+                (None, CharCategory::Whitespace) => (),
+                (None, _) => category = Some(ch_category),
+                (Some(category), _) if category != ch_category => break,
+                _ => (),
+            }
+            pos -= 1;
+        }
+        pos
+    }
+
+    /// End of the word after `pos`, including the whitespace that follows it. A line ending
+    /// right after `pos` is a word of its own.
+    pub(super) fn next_word_end(text: RopeSlice, pos: usize) -> usize {
+        use helix_core::chars::{categorize_char, CharCategory};
+
+        let category = match text.get_char(pos).map(categorize_char) {
+            None => return pos,
+            Some(CharCategory::Eol) => return graphemes::next_grapheme_boundary(text, pos),
+            Some(category) => category,
+        };
+
+        let mut pos = pos;
+        let mut in_word = category != CharCategory::Whitespace;
+        for ch in text.chars_at(pos) {
+            match categorize_char(ch) {
+                CharCategory::Eol => break,
+                CharCategory::Whitespace => in_word = false,
+                ch_category if !in_word || ch_category != category => break,
+                _ => (),
+            }
+            pos += 1;
+        }
+        pos
+    }
+
     pub fn delete_word_backward(cx: &mut Context) {
         let count = cx.count();
         let (view, doc) = current!(cx.editor);
         let text = doc.text().slice(..);
-        let selection = doc
-            .selection(view.id)
-            .transform(|range| movement::move_prev_word_start(text, range, count));
-        doc.set_selection(view.id, selection);
-        delete_selection(cx)
+        let transaction =
+            Transaction::change_by_selection(doc.text(), doc.selection(view.id), |range| {
+                let start = (0..count).fold(range.head, |pos, _| prev_word_start(text, pos));
+                (start, range.head, None)
+            })
+            .with_annotation(Annotation::DeleteChar);
+        doc.apply(&transaction, view.id);
+    }
+
+    pub fn delete_word_forward(cx: &mut Context) {
+        let count = cx.count();
+        let (view, doc) = current!(cx.editor);
+        let text = doc.text().slice(..);
+        let transaction =
+            Transaction::change_by_selection(doc.text(), doc.selection(view.id), |range| {
+                let end = (0..count).fold(range.head, |pos, _| next_word_end(text, pos));
+                (range.head, end, None)
+            })
+            .with_annotation(Annotation::DeleteChar);
+        doc.apply(&transaction, view.id);
+    }
+
+    /// Deletes up to the first non-whitespace character of the line, or to the start of the
+    /// line if the cursor is within the indentation.
+    pub fn kill_to_line_start(cx: &mut Context) {
+        let (view, doc) = current!(cx.editor);
+        let text = doc.text().slice(..);
+        let transaction =
+            Transaction::change_by_selection(doc.text(), doc.selection(view.id), |range| {
+                let line = text.char_to_line(range.head);
+                let line_start = text.line_to_char(line);
+                let start = match find_first_non_whitespace_char(text.line(line)) {
+                    Some(first) if line_start + first < range.head => line_start + first,
+                    _ => line_start,
+                };
+                (start, range.head, None)
+            });
+        doc.apply(&transaction, view.id);
+    }
+
+    /// Deletes up to the end of the line, or the line ending if the cursor is already there.
+    pub fn kill_to_line_end(cx: &mut Context) {
+        let (view, doc) = current!(cx.editor);
+        let text = doc.text().slice(..);
+        let transaction =
+            Transaction::change_by_selection(doc.text(), doc.selection(view.id), |range| {
+                let line = text.char_to_line(range.head);
+                let mut end = line_end_char_index(&text, line);
+                if end == range.head {
+                    end = text.line_to_char((line + 1).min(text.len_lines()));
+                }
+                (range.head, end, None)
+            });
+        doc.apply(&transaction, view.id);
     }
 }
 
@@ -2955,15 +4680,32 @@ pub mod insert {
 // storing it?
 
 fn undo(cx: &mut Context) {
+    let count = cx.count();
     let (view, doc) = current!(cx.editor);
     let view_id = view.id;
-    doc.undo(view_id);
+    let undone = (0..count).take_while(|_| doc.undo(view_id)).count();
+    report_history_steps(cx.editor, undone, "undone", "Already at oldest change");
 }
 
 fn redo(cx: &mut Context) {
+    let count = cx.count();
     let (view, doc) = current!(cx.editor);
     let view_id = view.id;
-    doc.redo(view_id);
+    let redone = (0..count).take_while(|_| doc.redo(view_id)).count();
+    report_history_steps(cx.editor, redone, "redone", "Already at newest change");
+}
+
+/// Reports how many changes `undo` or `redo` stepped through, or `at_end` if there were none.
+fn report_history_steps(editor: &mut Editor, steps: usize, verb: &str, at_end: &str) {
+    // read-only documents report the refused edit instead
+    if doc_mut!(editor).readonly {
+        return;
+    }
+    match steps {
+        0 => editor.set_status(at_end.to_string()),
+        1 => editor.set_status(format!("1 change {}", verb)),
+        steps => editor.set_status(format!("{} changes {}", steps, verb)),
+    }
 }
 
 // Yank / Paste
@@ -2989,6 +4731,32 @@ fn yank(cx: &mut Context) {
     cx.editor.set_status(msg)
 }
 
+/// Yanks the selections joined with `separator` to the register `register`, as a single value.
+fn yank_joined_impl(editor: &mut Editor, separator: &str, register: char) {
+    let (view, doc) = current!(editor);
+
+    let values: Vec<String> = doc
+        .selection(view.id)
+        .fragments(doc.text().slice(..))
+        .map(Cow::into_owned)
+        .collect();
+
+    let msg = format!(
+        "joined and yanked {} selection(s) to register {}",
+        values.len(),
+        register,
+    );
+
+    let joined = values.join(separator);
+    editor.registers.write(register, vec![joined]);
+    editor.set_status(msg);
+}
+
+fn yank_joined(cx: &mut Context) {
+    let line_ending = current!(cx.editor).1.line_ending;
+    yank_joined_impl(cx.editor, line_ending.as_str(), cx.selected_register.name());
+}
+
 fn yank_joined_to_clipboard_impl(editor: &mut Editor, separator: &str) -> anyhow::Result<()> {
     let (view, doc) = current!(editor);
 
@@ -3017,7 +4785,9 @@ fn yank_joined_to_clipboard_impl(editor: &mut Editor, separator: &str) -> anyhow
 
 fn yank_joined_to_clipboard(cx: &mut Context) {
     let line_ending = current!(cx.editor).1.line_ending;
-    let _ = yank_joined_to_clipboard_impl(&mut cx.editor, line_ending.as_str());
+    if let Err(err) = yank_joined_to_clipboard_impl(&mut cx.editor, line_ending.as_str()) {
+        cx.editor.set_error(err.to_string());
+    }
 }
 
 fn yank_main_selection_to_clipboard_impl(editor: &mut Editor) -> anyhow::Result<()> {
@@ -3037,7 +4807,9 @@ fn yank_main_selection_to_clipboard_impl(editor: &mut Editor) -> anyhow::Result<
 }
 
 fn yank_main_selection_to_clipboard(cx: &mut Context) {
-    let _ = yank_main_selection_to_clipboard_impl(&mut cx.editor);
+    if let Err(err) = yank_main_selection_to_clipboard_impl(&mut cx.editor) {
+        cx.editor.set_error(err.to_string());
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -3046,43 +4818,99 @@ enum Paste {
     After,
 }
 
+/// Position at which `range` is pasted into: around the selection, or around its lines if the
+/// pasted values are `linewise`.
+fn paste_pos(text: &Rope, range: &Range, action: Paste, linewise: bool) -> usize {
+    match (action, linewise) {
+        // paste linewise before
+        (Paste::Before, true) => text.line_to_char(text.char_to_line(range.from())),
+        // paste linewise after
+        (Paste::After, true) => text.line_to_char(text.char_to_line(range.to()) + 1),
+        // paste insert
+        (Paste::Before, false) => range.from(),
+        // paste append
+        (Paste::After, false) => range.to() + 1,
+    }
+}
+
+/// If any of values ends with a line ending, it's a linewise paste.
+fn is_linewise(values: &[String]) -> bool {
+    values
+        .iter()
+        .any(|value| get_line_ending_of_str(value).is_some())
+}
+
+/// Pastes one value at each selection, repeating the last value for the remaining selections.
+/// Each value is inserted `count` times.
 fn paste_impl(
     values: &[String],
     doc: &mut Document,
     view: &View,
     action: Paste,
+    count: usize,
 ) -> Option<Transaction> {
     let repeat = std::iter::repeat(
         values
             .last()
-            .map(|value| Tendril::from_slice(value))
+            .map(|value| Tendril::from(value.repeat(count).as_str()))
             .unwrap(),
     );
 
-    // if any of values ends with a line ending, it's linewise paste
-    let linewise = values
-        .iter()
-        .any(|value| get_line_ending_of_str(value).is_some());
+    let linewise = is_linewise(values);
 
-    let mut values = values.iter().cloned().map(Tendril::from).chain(repeat);
+    let mut values = values
+        .iter()
+        .map(|value| Tendril::from(value.repeat(count).as_str()))
+        .chain(repeat);
 
     let text = doc.text();
 
     let transaction = Transaction::change_by_selection(text, doc.selection(view.id), |range| {
-        let pos = match (action, linewise) {
-            // paste linewise before
-            (Paste::Before, true) => text.line_to_char(text.char_to_line(range.from())),
-            // paste linewise after
-            (Paste::After, true) => text.line_to_char(text.char_to_line(range.to()) + 1),
-            // paste insert
-            (Paste::Before, false) => range.from(),
-            // paste append
-            (Paste::After, false) => range.to() + 1,
-        };
+        let pos = paste_pos(text, range, action, linewise);
         (pos, pos, Some(values.next().unwrap()))
     });
 
-    Some(transaction)
+    Some(transaction.with_annotation(Annotation::Paste))
+}
+
+/// Pastes every value at each selection and selects each pasted value.
+fn paste_all_impl(
+    values: &[String],
+    doc: &mut Document,
+    view: &View,
+    action: Paste,
+) -> Option<Transaction> {
+    let linewise = is_linewise(values);
+    let fragment = Tendril::from(values.concat().as_str());
+    let fragment_len = fragment.chars().count();
+    if fragment_len == 0 {
+        return None;
+    }
+
+    let text = doc.text();
+    let mut ranges = SmallVec::new();
+    // chars inserted at the previous selections
+    let mut offs = 0;
+
+    let transaction = Transaction::change_by_selection(text, doc.selection(view.id), |range| {
+        let pos = paste_pos(text, range, action, linewise);
+        let mut start = pos + offs;
+        for value in values {
+            let len = value.chars().count();
+            if len > 0 {
+                ranges.push(Range::new(start, start + len - 1));
+            }
+            start += len;
+        }
+        offs += fragment_len;
+        (pos, pos, Some(fragment.clone()))
+    });
+
+    Some(
+        transaction
+            .with_selection(Selection::new(ranges, 0))
+            .with_annotation(Annotation::Paste),
+    )
 }
 
 fn paste_clipboard_impl(editor: &mut Editor, action: Paste) -> anyhow::Result<()> {
@@ -3091,7 +4919,7 @@ fn paste_clipboard_impl(editor: &mut Editor, action: Paste) -> anyhow::Result<()
     match editor
         .clipboard_provider
         .get_contents()
-        .map(|contents| paste_impl(&[contents], doc, view, action))
+        .map(|contents| paste_impl(&[contents], doc, view, action, 1))
     {
         Ok(Some(transaction)) => {
             doc.apply(&transaction, view.id);
@@ -3104,11 +4932,15 @@ fn paste_clipboard_impl(editor: &mut Editor, action: Paste) -> anyhow::Result<()
 }
 
 fn paste_clipboard_after(cx: &mut Context) {
-    let _ = paste_clipboard_impl(&mut cx.editor, Paste::After);
+    if let Err(err) = paste_clipboard_impl(&mut cx.editor, Paste::After) {
+        cx.editor.set_error(err.to_string());
+    }
 }
 
 fn paste_clipboard_before(cx: &mut Context) {
-    let _ = paste_clipboard_impl(&mut cx.editor, Paste::Before);
+    if let Err(err) = paste_clipboard_impl(&mut cx.editor, Paste::Before) {
+        cx.editor.set_error(err.to_string());
+    }
 }
 
 fn replace_with_yanked(cx: &mut Context) {
@@ -3117,12 +4949,18 @@ fn replace_with_yanked(cx: &mut Context) {
     let registers = &mut cx.editor.registers;
 
     if let Some(values) = registers.read(reg_name) {
-        if let Some(yank) = values.first() {
+        if let Some(last) = values.last() {
+            // one value per selection, repeating the last one
+            let mut values = values.iter().chain(std::iter::repeat(last));
             let transaction =
                 Transaction::change_by_selection(doc.text(), doc.selection(view.id), |range| {
                     let max_to = rope_end_without_line_ending(&doc.text().slice(..));
                     let to = std::cmp::min(max_to, range.to() + 1);
-                    (range.from(), to, Some(yank.as_str().into()))
+                    (
+                        range.from(),
+                        to,
+                        Some(values.next().unwrap().as_str().into()),
+                    )
                 });
 
             doc.apply(&transaction, view.id);
@@ -3152,17 +4990,20 @@ fn replace_selections_with_clipboard_impl(editor: &mut Editor) -> anyhow::Result
 }
 
 fn replace_selections_with_clipboard(cx: &mut Context) {
-    let _ = replace_selections_with_clipboard_impl(&mut cx.editor);
+    if let Err(err) = replace_selections_with_clipboard_impl(&mut cx.editor) {
+        cx.editor.set_error(err.to_string());
+    }
 }
 
 fn paste_after(cx: &mut Context) {
+    let count = cx.count();
     let reg_name = cx.selected_register.name();
     let (view, doc) = current!(cx.editor);
     let registers = &mut cx.editor.registers;
 
     if let Some(transaction) = registers
         .read(reg_name)
-        .and_then(|values| paste_impl(values, doc, view, Paste::After))
+        .and_then(|values| paste_impl(values, doc, view, Paste::After, count))
     {
         doc.apply(&transaction, view.id);
         doc.append_changes_to_history(view.id);
@@ -3170,13 +5011,42 @@ fn paste_after(cx: &mut Context) {
 }
 
 fn paste_before(cx: &mut Context) {
+    let count = cx.count();
+    let reg_name = cx.selected_register.name();
+    let (view, doc) = current!(cx.editor);
+    let registers = &mut cx.editor.registers;
+
+    if let Some(transaction) = registers
+        .read(reg_name)
+        .and_then(|values| paste_impl(values, doc, view, Paste::Before, count))
+    {
+        doc.apply(&transaction, view.id);
+        doc.append_changes_to_history(view.id);
+    }
+}
+
+fn paste_all_after(cx: &mut Context) {
+    let reg_name = cx.selected_register.name();
+    let (view, doc) = current!(cx.editor);
+    let registers = &mut cx.editor.registers;
+
+    if let Some(transaction) = registers
+        .read(reg_name)
+        .and_then(|values| paste_all_impl(values, doc, view, Paste::After))
+    {
+        doc.apply(&transaction, view.id);
+        doc.append_changes_to_history(view.id);
+    }
+}
+
+fn paste_all_before(cx: &mut Context) {
     let reg_name = cx.selected_register.name();
     let (view, doc) = current!(cx.editor);
     let registers = &mut cx.editor.registers;
 
     if let Some(transaction) = registers
         .read(reg_name)
-        .and_then(|values| paste_impl(values, doc, view, Paste::Before))
+        .and_then(|values| paste_all_impl(values, doc, view, Paste::Before))
     {
         doc.apply(&transaction, view.id);
         doc.append_changes_to_history(view.id);
@@ -3225,7 +5095,7 @@ fn unindent(cx: &mut Context) {
     let lines = get_lines(doc, view.id);
     let mut changes = Vec::with_capacity(lines.len());
     let tab_width = doc.tab_width();
-    let indent_width = count * tab_width;
+    let indent_width = count * doc.indent_width();
 
     for line_idx in lines {
         let line = doc.text().line(line_idx);
@@ -3235,7 +5105,7 @@ fn unindent(cx: &mut Context) {
         for ch in line.chars() {
             match ch {
                 ' ' => width += 1,
-                '\t' => width = (width / tab_width + 1) * tab_width,
+                '\t' => width += graphemes::grapheme_width_at("\t", width, tab_width),
                 _ => break,
             }
 
@@ -3259,6 +5129,94 @@ fn unindent(cx: &mut Context) {
     doc.append_changes_to_history(view.id);
 }
 
+/// Replaces the leading whitespace of every selected line with the indentation suggested by the
+/// language's indent query.
+fn indent_selection(cx: &mut Context) {
+    let (view, doc) = current!(cx.editor);
+
+    let (language_config, syntax) = match (doc.language_config(), doc.syntax()) {
+        (Some(config), Some(syntax)) if config.indent_query().is_some() => (config, syntax),
+        _ => {
+            cx.editor
+                .set_error("No indentation rules for this language".to_owned());
+            return;
+        }
+    };
+
+    let text = doc.text().slice(..);
+    let tab_width = doc.tab_width();
+    let mut changes = Vec::new();
+
+    for line in get_lines(doc, view.id) {
+        let first_non_whitespace = match find_first_non_whitespace_char(text.line(line)) {
+            Some(pos) => pos,
+            // leave blank lines alone
+            None => continue,
+        };
+
+        let level =
+            indent::suggested_indent_for_line(language_config, Some(syntax), text, line, tab_width);
+        let indent = doc.indent_unit().repeat(level);
+
+        let start = text.line_to_char(line);
+        let end = start + first_non_whitespace;
+        if text.slice(start..end) != indent.as_str() {
+            changes.push((start, end, Some(Tendril::from(indent.as_str()))));
+        }
+    }
+
+    let transaction = Transaction::change(doc.text(), changes.into_iter());
+    doc.apply(&transaction, view.id);
+    doc.append_changes_to_history(view.id);
+}
+
+fn text_width(doc: &Document, editor_default: usize) -> usize {
+    doc.language_config()
+        .and_then(|config| config.text_width)
+        .unwrap_or(editor_default)
+}
+
+/// Rewraps the lines covered by each selection to `width` columns, repeating the indentation and
+/// comment token of each paragraph on its continuation lines.
+fn reflow_impl(doc: &mut Document, view_id: ViewId, width: usize) {
+    let text = doc.text().slice(..);
+    let comment_token = doc
+        .language_config()
+        .and_then(|config| config.comment_token.as_deref());
+    let line_ending = doc.line_ending.as_str();
+
+    let mut changes = Vec::new();
+    let mut last_line = None;
+    for range in doc.selection(view_id) {
+        let start_line = text.char_to_line(range.from());
+        let end_line = text.char_to_line(range.to());
+        // selections sharing lines are reflowed once
+        if matches!(last_line, Some(last) if start_line <= last) {
+            continue;
+        }
+        last_line = Some(end_line);
+
+        let start = text.line_to_char(start_line);
+        let end = line_end_char_index(&text, end_line);
+        let fragment = text.slice(start..end).to_string();
+        let reflowed = reflow::reflow(&fragment, width, comment_token, line_ending);
+        if reflowed != fragment {
+            changes.push((start, end, Some(Tendril::from(reflowed.as_str()))));
+        }
+    }
+
+    let transaction = Transaction::change(doc.text(), changes.into_iter());
+    doc.apply(&transaction, view_id);
+    doc.append_changes_to_history(view_id);
+}
+
+fn reflow(cx: &mut Context) {
+    let default_width = cx.editor.config.text_width;
+    let (view, doc) = current!(cx.editor);
+    let width = text_width(doc, default_width);
+    reflow_impl(doc, view.id, width);
+}
+
 fn format_selections(cx: &mut Context) {
     let (view, doc) = current!(cx.editor);
 
@@ -3351,15 +5309,105 @@ fn join_selections(cx: &mut Context) {
     doc.append_changes_to_history(view.id);
 }
 
+fn sort_selections(cx: &mut Context) {
+    let (view, doc) = current!(cx.editor);
+    sort_impl(doc, view.id, false);
+}
+
+/// Sorts the contents of the selections. A single selection has its lines sorted instead. When
+/// every item is a number they are compared numerically, otherwise lexicographically.
+fn sort_impl(doc: &mut Document, view_id: ViewId, reverse: bool) {
+    let text = doc.text();
+    let slice = text.slice(..);
+    let selection = doc.selection(view_id);
+
+    let changes: Vec<_> = if selection.len() == 1 {
+        let range = selection.primary();
+        let start = text.char_to_line(range.from());
+        let end = text.char_to_line(range.to());
+        if start == end {
+            return;
+        }
+
+        let mut lines: Vec<String> = (start..=end)
+            .map(|line| {
+                let from = text.line_to_char(line);
+                let to = line_end_char_index(&slice, line);
+                slice.slice(from..to).to_string()
+            })
+            .collect();
+        sort_strings(&mut lines, reverse);
+
+        let content = lines.join(doc.line_ending.as_str());
+        vec![(
+            text.line_to_char(start),
+            line_end_char_index(&slice, end),
+            Some(content.into()),
+        )]
+    } else {
+        let mut fragments: Vec<String> = selection
+            .iter()
+            .map(|range| range.fragment(slice).into_owned())
+            .collect();
+        sort_strings(&mut fragments, reverse);
+
+        selection
+            .iter()
+            .zip(fragments)
+            .map(|(range, fragment)| {
+                let to = std::cmp::min(range.to() + 1, text.len_chars());
+                (range.from(), to, Some(fragment.into()))
+            })
+            .collect()
+    };
+
+    let transaction = Transaction::change(doc.text(), changes.into_iter());
+    doc.apply(&transaction, view_id);
+    doc.append_changes_to_history(view_id);
+}
+
+fn sort_strings(items: &mut [String], reverse: bool) {
+    // "nan" and "inf" parse too, but are sorted as text
+    let numbers: Option<Vec<f64>> = items
+        .iter()
+        .map(|item| {
+            item.trim()
+                .parse()
+                .ok()
+                .filter(|number: &f64| number.is_finite())
+        })
+        .collect();
+
+    match numbers {
+        Some(numbers) => {
+            let mut pairs: Vec<_> = numbers.into_iter().zip(items.iter().cloned()).collect();
+            pairs.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+            for (item, (_, sorted)) in items.iter_mut().zip(pairs) {
+                *item = sorted;
+            }
+        }
+        None => items.sort(),
+    }
+
+    if reverse {
+        items.reverse();
+    }
+}
+
 fn keep_selections(cx: &mut Context) {
     // keep selections matching regex
-    let prompt = ui::regex_prompt(cx, "keep:".to_string(), move |view, doc, _, regex| {
-        let text = doc.text().slice(..);
+    let prompt = ui::regex_prompt(
+        cx,
+        "keep:".to_string(),
+        None,
+        move |view, doc, _, regex, _| {
+            let text = doc.text().slice(..);
 
-        if let Some(selection) = selection::keep_matches(text, doc.selection(view.id), &regex) {
-            doc.set_selection(view.id, selection);
-        }
-    });
+            if let Some(selection) = selection::keep_matches(text, doc.selection(view.id), &regex) {
+                doc.set_selection(view.id, selection);
+            }
+        },
+    );
 
     cx.push_layer(Box::new(prompt));
 }
@@ -3413,7 +5461,16 @@ fn completion(cx: &mut Context) {
 
     let language_server = match doc.language_server() {
         Some(language_server) => language_server,
-        None => return,
+        None => {
+            let trigger_offset = doc.selection(view.id).cursor();
+            let items = buffer_word_completions(cx.editor, OffsetEncoding::Utf8);
+            if !items.is_empty() {
+                cx.callback = Some(Box::new(move |compositor: &mut Compositor| {
+                    show_completion(compositor, items, OffsetEncoding::Utf8, trigger_offset);
+                }));
+            }
+            return;
+        }
     };
 
     let offset_encoding = language_server.offset_encoding();
@@ -3436,7 +5493,7 @@ fn completion(cx: &mut Context) {
                 return;
             }
 
-            let items = match response {
+            let mut items = match response {
                 Some(lsp::CompletionResponse::Array(items)) => items,
                 // TODO: do something with is_incomplete
                 Some(lsp::CompletionResponse::List(lsp::CompletionList {
@@ -3446,21 +5503,123 @@ fn completion(cx: &mut Context) {
                 None => Vec::new(),
             };
 
+            // fall back to words from open buffers
+            if items.is_empty() {
+                items = buffer_word_completions(editor, offset_encoding);
+            }
+
             // TODO: if no completion, show some message or something
             if items.is_empty() {
                 return;
             }
-            let size = compositor.size();
-            let ui = compositor
-                .find(std::any::type_name::<ui::EditorView>())
-                .unwrap();
-            if let Some(ui) = ui.as_any_mut().downcast_mut::<ui::EditorView>() {
-                ui.set_completion(items, offset_encoding, trigger_offset, size);
-            };
+            show_completion(compositor, items, offset_encoding, trigger_offset);
         },
     );
 }
 
+fn show_completion(
+    compositor: &mut Compositor,
+    items: Vec<lsp::CompletionItem>,
+    offset_encoding: OffsetEncoding,
+    trigger_offset: usize,
+) {
+    let size = compositor.size();
+    let ui = compositor
+        .find(std::any::type_name::<ui::EditorView>())
+        .unwrap();
+    if let Some(ui) = ui.as_any_mut().downcast_mut::<ui::EditorView>() {
+        ui.set_completion(items, offset_encoding, trigger_offset, size);
+    };
+}
+
+/// Lines scanned for words on each side of the cursor of a document, by buffer word completion.
+const WORD_COMPLETION_LINES: usize = 1000;
+/// Buffer word completion stops scanning once it found as many words.
+const MAX_WORD_COMPLETIONS: usize = 100;
+
+/// Completes the word before the cursor with the words of the open documents that start with it,
+/// so completion works without a language server. Only the lines around the cursor of each
+/// document are scanned, starting with the current one.
+fn buffer_word_completions(
+    editor: &Editor,
+    offset_encoding: OffsetEncoding,
+) -> Vec<lsp::CompletionItem> {
+    use helix_core::chars::char_is_word;
+    use std::collections::BTreeSet;
+
+    let view = view!(editor);
+    let doc = &editor.documents[view.doc];
+    let text = doc.text().slice(..);
+    let cursor = doc.selection(view.id).cursor();
+
+    let mut start = cursor;
+    while start > 0 && char_is_word(text.char(start - 1)) {
+        start -= 1;
+    }
+    if start == cursor {
+        return Vec::new();
+    }
+    let prefix = text.slice(start..cursor).to_string();
+
+    let others = editor
+        .documents()
+        .filter(|other| other.id() != doc.id())
+        .filter_map(|other| {
+            let (_, selection) = other.selections().iter().next()?;
+            Some((other, selection.cursor()))
+        });
+
+    // sorted and deduplicated
+    let mut words = BTreeSet::new();
+    'documents: for (doc, cursor) in std::iter::once((doc, cursor)).chain(others) {
+        let text = doc.text();
+        let line = text.char_to_line(cursor);
+        let first_line = line.saturating_sub(WORD_COMPLETION_LINES);
+        let end_line = (line + WORD_COMPLETION_LINES + 1).min(text.len_lines());
+        let lines = text.slice(text.line_to_char(first_line)..text.line_to_char(end_line));
+
+        let mut word = String::new();
+        for ch in lines.chars().chain(std::iter::once(' ')) {
+            if char_is_word(ch) {
+                word.push(ch);
+            } else if !word.is_empty() {
+                // the word being completed is skipped
+                if word.len() > prefix.len() && word.starts_with(&prefix) {
+                    words.insert(std::mem::take(&mut word));
+                    if words.len() >= MAX_WORD_COMPLETIONS {
+                        break 'documents;
+                    }
+                } else {
+                    word.clear();
+                }
+            }
+        }
+    }
+
+    let range = lsp::Range::new(
+        pos_to_lsp_pos(doc.text(), start, offset_encoding),
+        pos_to_lsp_pos(doc.text(), cursor, offset_encoding),
+    );
+    words
+        .into_iter()
+        .map(|word| lsp::CompletionItem {
+            label: word.clone(),
+            kind: Some(lsp::CompletionItemKind::Text),
+            text_edit: Some(lsp::CompletionTextEdit::Edit(lsp::TextEdit::new(
+                range, word,
+            ))),
+            ..Default::default()
+        })
+        .collect()
+}
+
+fn next_snippet_placeholder(cx: &mut Context) {
+    let (view, doc) = current!(cx.editor);
+    if let Some(tabstop) = doc.next_snippet_tabstop() {
+        doc.set_selection(view.id, tabstop);
+    }
+}
+
 fn hover(cx: &mut Context) {
     let (view, doc) = current!(cx.editor);
 
@@ -3530,23 +5689,73 @@ fn expand_selection(cx: &mut Context) {
 
     if let Some(syntax) = doc.syntax() {
         let text = doc.text().slice(..);
-        let selection = object::expand_selection(syntax, text, doc.selection(view.id));
-        doc.set_selection(view.id, selection);
-    }
+        let current_selection = doc.selection(view.id);
+        let selection = object::expand_selection(syntax, text, current_selection);
+
+        if *current_selection != selection {
+            view.object_selections.push(current_selection.clone());
+            doc.set_selection(view.id, selection);
+        }
+    }
 }
 
-fn match_brackets(cx: &mut Context) {
+/// Whether every range of `inner` lies within a range of `outer`.
+fn selection_contains(outer: &Selection, inner: &Selection) -> bool {
+    inner.iter().all(|inner| {
+        outer
+            .iter()
+            .any(|outer| outer.from() <= inner.from() && inner.to() <= outer.to())
+    })
+}
+
+fn shrink_selection(cx: &mut Context) {
     let (view, doc) = current!(cx.editor);
+    let current_selection = doc.selection(view.id);
+
+    // undo the last expansion if the selection wasn't changed since
+    if let Some(prev_selection) = view.object_selections.pop() {
+        if selection_contains(current_selection, &prev_selection) {
+            doc.set_selection(view.id, prev_selection);
+            return;
+        }
+        view.object_selections.clear();
+    }
 
     if let Some(syntax) = doc.syntax() {
-        let pos = doc.selection(view.id).cursor();
-        if let Some(pos) = match_brackets::find(syntax, doc.text(), pos) {
-            let selection = Selection::point(pos);
-            doc.set_selection(view.id, selection);
-        };
+        let text = doc.text().slice(..);
+        let selection = object::shrink_selection(syntax, text, current_selection);
+        doc.set_selection(view.id, selection);
     }
 }
 
+fn select_sibling_impl(cx: &mut Context, forward: bool) {
+    let (view, doc) = current!(cx.editor);
+
+    if let Some(syntax) = doc.syntax() {
+        let text = doc.text().slice(..);
+        let selection = object::select_sibling(syntax, text, doc.selection(view.id), forward);
+        doc.set_selection(view.id, selection);
+    }
+}
+
+fn select_next_sibling(cx: &mut Context) {
+    select_sibling_impl(cx, true)
+}
+
+fn select_prev_sibling(cx: &mut Context) {
+    select_sibling_impl(cx, false)
+}
+
+fn match_brackets(cx: &mut Context) {
+    let (view, doc) = current!(cx.editor);
+
+    let pos = doc.selection(view.id).cursor();
+    if let Some(pos) = match_brackets::find_matching(doc.syntax(), doc.text(), pos) {
+        let selection = Selection::point(pos);
+        doc.set_selection(view.id, selection);
+    };
+}
+
 //
 
 fn jump_forward(cx: &mut Context) {
@@ -3581,6 +5790,32 @@ fn jump_backward(cx: &mut Context) {
     };
 }
 
+fn selection_undo(cx: &mut Context) {
+    let (view, doc) = current!(cx.editor);
+    let current = (doc.id(), doc.version(), doc.selection(view.id).clone());
+    match view.selection_history.undo(current) {
+        Some(selection) => doc.set_selection(view.id, selection),
+        None => cx.editor.set_status("No older selection".to_owned()),
+    }
+}
+
+fn selection_redo(cx: &mut Context) {
+    let (view, doc) = current!(cx.editor);
+    let current = (doc.id(), doc.version(), doc.selection(view.id).clone());
+    match view.selection_history.redo(current) {
+        Some(selection) => doc.set_selection(view.id, selection),
+        None => cx.editor.set_status("No newer selection".to_owned()),
+    }
+}
+
+/// Adds the selections to the jumplist, to come back to with `jump_backward`.
+fn save_selection(cx: &mut Context) {
+    let (view, doc) = current!(cx.editor);
+    view.jumps.push((doc.id(), doc.selection(view.id).clone()));
+    cx.editor
+        .set_status("Selection saved to jumplist".to_owned());
+}
+
 fn rotate_view(cx: &mut Context) {
     cx.editor.focus_next()
 }
@@ -3609,11 +5844,147 @@ fn vsplit(cx: &mut Context) {
 }
 
 fn wclose(cx: &mut Context) {
+    // closing the last split quits, don't lose unsaved changes
+    if cx.editor.tree.views().count() == 1 {
+        if let Err(err) = cmd::buffers_remaining_impl(cx.editor) {
+            cx.editor.set_error(err.to_string());
+            return;
+        }
+    }
+
     let view_id = view!(cx.editor).id;
     // close current split
     cx.editor.close(view_id, /* close_buffer */ false);
 }
 
+fn wonly(cx: &mut Context) {
+    let focus = view!(cx.editor).id;
+    let others: Vec<_> = cx
+        .editor
+        .tree
+        .views()
+        .map(|(view, _)| view.id)
+        .filter(|id| *id != focus)
+        .collect();
+    for view_id in others {
+        cx.editor.close(view_id, /* close_buffer */ false);
+    }
+}
+
+fn jump_view_left(cx: &mut Context) {
+    cx.editor.focus_direction(tree::Direction::Left)
+}
+
+fn jump_view_down(cx: &mut Context) {
+    cx.editor.focus_direction(tree::Direction::Down)
+}
+
+fn jump_view_up(cx: &mut Context) {
+    cx.editor.focus_direction(tree::Direction::Up)
+}
+
+fn jump_view_right(cx: &mut Context) {
+    cx.editor.focus_direction(tree::Direction::Right)
+}
+
+fn swap_view_left(cx: &mut Context) {
+    cx.editor.swap_split_in_direction(tree::Direction::Left)
+}
+
+fn swap_view_down(cx: &mut Context) {
+    cx.editor.swap_split_in_direction(tree::Direction::Down)
+}
+
+fn swap_view_up(cx: &mut Context) {
+    cx.editor.swap_split_in_direction(tree::Direction::Up)
+}
+
+fn swap_view_right(cx: &mut Context) {
+    cx.editor.swap_split_in_direction(tree::Direction::Right)
+}
+
+fn rotate_splits(cx: &mut Context) {
+    cx.editor.tree.rotate_splits()
+}
+
+fn grow_split_width(cx: &mut Context) {
+    let count = cx.count() as i32;
+    cx.editor.grow_split(false, count)
+}
+
+fn shrink_split_width(cx: &mut Context) {
+    let count = cx.count() as i32;
+    cx.editor.grow_split(false, -count)
+}
+
+fn grow_split_height(cx: &mut Context) {
+    let count = cx.count() as i32;
+    cx.editor.grow_split(true, count)
+}
+
+fn shrink_split_height(cx: &mut Context) {
+    let count = cx.count() as i32;
+    cx.editor.grow_split(true, -count)
+}
+
+fn equalize_splits(cx: &mut Context) {
+    cx.editor.tree.equalize_splits()
+}
+
+/// The values of a register on one line, with their count when there are several, shortened to
+/// fit a picker or status line.
+fn register_preview(values: &[String]) -> String {
+    const MAX_WIDTH: usize = 60;
+    let mut preview = String::new();
+    if values.len() > 1 {
+        preview.push_str(&format!("[{}] ", values.len()));
+    }
+    for (i, value) in values.iter().enumerate() {
+        if i > 0 {
+            preview.push_str(", ");
+        }
+        for ch in value.chars() {
+            match ch {
+                '\n' => preview.push('⏎'),
+                '\t' => preview.push('→'),
+                '\r' => {}
+                ch => preview.push(ch),
+            }
+        }
+        if preview.chars().count() > MAX_WIDTH {
+            break;
+        }
+    }
+    if preview.chars().count() > MAX_WIDTH {
+        preview = preview.chars().take(MAX_WIDTH - 1).collect();
+        preview.push('…');
+    }
+    preview
+}
+
+/// Picks a register among the ones holding values, to use for the next command.
+fn register_picker(cx: &mut Context) {
+    let registers: Vec<(char, String)> = cx
+        .editor
+        .registers
+        .iter()
+        .map(|register| (register.name(), register_preview(register.read())))
+        .collect();
+    if registers.is_empty() {
+        cx.editor.set_status("All registers are empty".to_owned());
+        return;
+    }
+
+    let picker = Picker::new(
+        registers,
+        |(name, preview): &(char, String)| format!("{}  {}", name, preview).into(),
+        |editor: &mut Editor, (name, _): &(char, String), _action| {
+            editor.selected_register.select(*name);
+        },
+    );
+    cx.push_layer(Box::new(picker));
+}
+
 fn select_register(cx: &mut Context) {
     cx.on_next_key(move |cx, event| {
         if let KeyEvent {
@@ -3644,12 +6015,9 @@ fn align_view_bottom(cx: &mut Context) {
 fn align_view_middle(cx: &mut Context) {
     let (view, doc) = current!(cx.editor);
     let pos = doc.selection(view.id).cursor();
-    let pos = coords_at_pos(doc.text().slice(..), pos);
+    let pos = visual_coords_at_pos(doc.text().slice(..), pos, doc.tab_width());
 
-    const OFFSET: usize = 7; // gutters
-    view.first_col = pos
-        .col
-        .saturating_sub(((view.area.width as usize).saturating_sub(OFFSET)) / 2);
+    view.first_col = pos.col.saturating_sub(view.inner_width() / 2);
 }
 
 fn scroll_up(cx: &mut Context) {
@@ -3660,6 +6028,68 @@ fn scroll_down(cx: &mut Context) {
     scroll(cx, cx.count(), Direction::Forward);
 }
 
+/// Moves the cursors hidden by the folds of the current view to the first line of their fold.
+fn move_cursors_out_of_folds(editor: &mut Editor) {
+    let (view, doc) = current!(editor);
+    let text = doc.text().slice(..);
+    let folds = doc.folds(view.id);
+    if !doc
+        .selection(view.id)
+        .iter()
+        .any(|range| fold::is_hidden(folds, text.char_to_line(range.head)))
+    {
+        return;
+    }
+    let selection = doc.selection(view.id).transform(|range| {
+        let line = text.char_to_line(range.head);
+        match fold::visible_line(folds, line, Direction::Backward, line) {
+            Some(visible) if visible != line => movement::move_vertically(
+                text,
+                range,
+                Direction::Backward,
+                line - visible,
+                Movement::Move,
+            ),
+            _ => range,
+        }
+    });
+    doc.set_selection(view.id, selection);
+}
+
+fn fold_toggle(cx: &mut Context) {
+    let (view, doc) = current!(cx.editor);
+    let text = doc.text().slice(..);
+    let line = text.char_to_line(doc.selection(view.id).cursor());
+    let mut folds = doc.folds(view.id).to_vec();
+
+    if let Some(fold) = fold::innermost(&folds, line).cloned() {
+        folds.retain(|other| *other != fold);
+    } else {
+        let ranges = fold::fold_ranges(doc.syntax(), text, doc.tab_width());
+        match fold::innermost(&ranges, line) {
+            Some(fold) => folds.push(fold.clone()),
+            None => {
+                cx.editor.set_error("Nothing to fold".to_string());
+                return;
+            }
+        }
+    }
+    doc.set_folds(view.id, folds);
+    move_cursors_out_of_folds(cx.editor);
+}
+
+fn fold_all(cx: &mut Context) {
+    let (view, doc) = current!(cx.editor);
+    let ranges = fold::fold_ranges(doc.syntax(), doc.text().slice(..), doc.tab_width());
+    doc.set_folds(view.id, ranges);
+    move_cursors_out_of_folds(cx.editor);
+}
+
+fn unfold_all(cx: &mut Context) {
+    let (view, doc) = current!(cx.editor);
+    doc.set_folds(view.id, Vec::new());
+}
+
 fn select_textobject_around(cx: &mut Context) {
     select_textobject(cx, textobject::TextObject::Around);
 }
@@ -3788,3 +6218,550 @@ fn surround_delete(cx: &mut Context) {
         }
     })
 }
+
+/// Runs `cmd` with the system shell, feeding `input` to its stdin. Returns the command's stdout
+/// and whether it exited successfully.
+fn shell_impl(cmd: &str, input: Option<&[u8]>) -> anyhow::Result<(Tendril, bool)> {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    #[cfg(windows)]
+    let (shell, flag) = ("cmd", "/C");
+    #[cfg(not(windows))]
+    let (shell, flag) = ("sh", "-c");
+
+    let mut process = Command::new(shell)
+        .arg(flag)
+        .arg(cmd)
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("failed to spawn `{}`", shell))?;
+
+    // write from a separate thread so a command producing a lot of output can't deadlock us
+    let writer = input.map(|input| {
+        let mut stdin = process.stdin.take().unwrap();
+        let input = input.to_vec();
+        std::thread::spawn(move || stdin.write_all(&input))
+    });
+
+    let output = process.wait_with_output()?;
+    if let Some(writer) = writer {
+        // the command may exit without reading all of its input, that's not an error
+        let _ = writer.join();
+    }
+
+    if !output.stderr.is_empty() {
+        log::error!("shell error: {}", String::from_utf8_lossy(&output.stderr));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok((Tendril::from(stdout.as_ref()), output.status.success()))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ShellBehavior {
+    /// Replace each selection with the output of the command, fed the selection on stdin.
+    Replace,
+    /// Insert the output before each selection.
+    Insert,
+    /// Insert the output after each selection.
+    Append,
+}
+
+fn shell_pipe(cx: &mut Context) {
+    shell(cx, "pipe:", ShellBehavior::Replace);
+}
+
+fn shell_insert_output(cx: &mut Context) {
+    shell(cx, "insert-output:", ShellBehavior::Insert);
+}
+
+fn shell_append_output(cx: &mut Context) {
+    shell(cx, "append-output:", ShellBehavior::Append);
+}
+
+fn shell_keep_pipe(cx: &mut Context) {
+    let prompt = Prompt::new(
+        "keep-pipe:".to_owned(),
+        Some('|'),
+        |_input: &str| Vec::new(),
+        move |cx: &mut compositor::Context, input: &str, event: PromptEvent| {
+            if event != PromptEvent::Validate || input.is_empty() {
+                return;
+            }
+
+            let (view, doc) = current!(cx.editor);
+            let text = doc.text().slice(..);
+            let selection = doc.selection(view.id);
+
+            let mut ranges = SmallVec::with_capacity(selection.len());
+            let old_index = selection.primary_index();
+            let mut index: Option<usize> = None;
+
+            for (i, range) in selection.ranges().iter().enumerate() {
+                let fragment = range.fragment(text);
+                match shell_impl(input, Some(fragment.as_bytes())) {
+                    Ok((_, true)) => {
+                        ranges.push(*range);
+                        // keep the primary selection if it survives, otherwise the next one
+                        if i >= old_index && index.is_none() {
+                            index = Some(ranges.len() - 1);
+                        }
+                    }
+                    Ok((_, false)) => (),
+                    Err(err) => {
+                        cx.editor.set_error(err.to_string());
+                        return;
+                    }
+                }
+            }
+
+            if ranges.is_empty() {
+                cx.editor.set_error("No selections remaining".to_owned());
+                return;
+            }
+
+            let index = index.unwrap_or(ranges.len() - 1);
+            doc.set_selection(view.id, Selection::new(ranges, index));
+        },
+    );
+
+    cx.push_layer(Box::new(prompt));
+}
+
+fn shell(cx: &mut Context, prompt: &str, behavior: ShellBehavior) {
+    let pipe = behavior == ShellBehavior::Replace;
+    let prompt = Prompt::new(
+        prompt.to_owned(),
+        Some('|'),
+        |_input: &str| Vec::new(),
+        move |cx: &mut compositor::Context, input: &str, event: PromptEvent| {
+            if event != PromptEvent::Validate || input.is_empty() {
+                return;
+            }
+
+            let (view, doc) = current!(cx.editor);
+            let text = doc.text().slice(..);
+            let selection = doc.selection(view.id);
+
+            let mut changes = Vec::with_capacity(selection.len());
+            for range in selection {
+                let fragment = range.fragment(text);
+                let stdin = if pipe {
+                    Some(fragment.as_bytes())
+                } else {
+                    None
+                };
+                let output = match shell_impl(input, stdin) {
+                    Ok((output, true)) => output,
+                    Ok((_, false)) => {
+                        cx.editor.set_error(format!("`{}` failed", input));
+                        return;
+                    }
+                    Err(err) => {
+                        cx.editor.set_error(err.to_string());
+                        return;
+                    }
+                };
+
+                let end = std::cmp::min(range.to() + 1, text.len_chars());
+                let (from, to) = match behavior {
+                    ShellBehavior::Replace => (range.from(), end),
+                    ShellBehavior::Insert => (range.from(), range.from()),
+                    ShellBehavior::Append => (end, end),
+                };
+                changes.push((from, to, Some(output)));
+            }
+
+            let transaction = Transaction::change(doc.text(), changes.into_iter());
+            doc.apply(&transaction, view.id);
+            doc.append_changes_to_history(view.id);
+        },
+    );
+
+    cx.push_layer(Box::new(prompt));
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use std::sync::Arc;
+
+    /// An editor with the default configuration and no language, showing a scratch buffer.
+    pub(crate) fn editor() -> Editor {
+        let runtime = helix_core::runtime_dir();
+        let themes = Arc::new(helix_view::theme::Loader::new(&runtime, &runtime));
+        let languages = Arc::new(helix_core::syntax::Loader::new(
+            helix_core::syntax::Configuration {
+                language: Vec::new(),
+            },
+        ));
+        let mut editor = Editor::new(
+            helix_view::graphics::Rect::new(0, 0, 80, 24),
+            themes,
+            languages,
+            helix_view::editor::Config::default(),
+        );
+        editor.new_file(Action::VerticalSplit);
+        editor
+    }
+
+    /// Runs `command` on `editor`.
+    pub(crate) fn run(editor: &mut Editor, command: fn(&mut Context)) {
+        let mut jobs = Jobs::new();
+        let mut cx = Context {
+            selected_register: helix_view::RegisterSelection::default(),
+            count: None,
+            editor,
+            callback: None,
+            on_next_key_callback: None,
+            jobs: &mut jobs,
+        };
+        command(&mut cx);
+    }
+
+    /// A directory of the temporary directory of a test, removed with its files once dropped.
+    pub(crate) struct TempDir(PathBuf);
+
+    impl TempDir {
+        pub(crate) fn new() -> Self {
+            use std::sync::atomic::{AtomicUsize, Ordering};
+            static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+            let dir = std::env::temp_dir().join(format!(
+                "helix-term-test-{}-{}",
+                std::process::id(),
+                NEXT.fetch_add(1, Ordering::Relaxed)
+            ));
+            std::fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        /// Writes `text` to the file `name` of the directory.
+        pub(crate) fn file(&self, name: &str, text: &str) -> PathBuf {
+            let path = self.0.join(name);
+            std::fs::write(&path, text).unwrap();
+            path
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn yank_at_end_of_followed_file() {
+        let mut editor = editor();
+        let dir = TempDir::new();
+        let path = dir.file("followed.log", "first\nsecond\n");
+        editor.open(path, Action::Replace).unwrap();
+        // like `:log-open`, without the log file
+        let (view, doc) = current!(editor);
+        let end = last_line_start(doc.text().slice(..));
+        doc.set_selection(view.id, Selection::point(end));
+
+        run(&mut editor, yank);
+        assert_eq!(editor.registers.read('"'), Some(&["s".to_owned()][..]));
+    }
+
+    #[test]
+    fn paste_inserts_text_as_is() {
+        let mut editor = editor();
+        insert::insert_pasted(&mut editor, "fn f(\n    a,\n");
+        let (_, doc) = current!(editor);
+        // no closing bracket is paired and no indentation is added after the line breaks
+        assert_eq!(doc.text().to_string(), "fn f(\n    a,\n\n");
+
+        doc.line_ending = LineEnding::Crlf;
+        insert::insert_pasted(&mut editor, "b\n");
+        let (_, doc) = current!(editor);
+        assert!(doc.text().to_string().contains("b\r\n"));
+    }
+
+    #[test]
+    fn goto_tag_selects_name() {
+        let mut editor = editor();
+        let dir = TempDir::new();
+        dir.file("tags", "helper\ttagged.rs\t/^fn helper() {}$/;\"\tf\n");
+        let path = dir.file("tagged.rs", "fn a() {}\nfn helper() {}\n// a helper\n");
+        editor.open(path, Action::Replace).unwrap();
+
+        // the word under the cursor
+        let (view, doc) = current!(editor);
+        doc.set_selection(view.id, Selection::point(30));
+        run(&mut editor, goto_tag);
+        let (view, doc) = current!(editor);
+        assert_eq!(doc.selection(view.id).primary(), Range::new(13, 18));
+
+        // a selection of two chars isn't expanded to the word
+        doc.set_selection(view.id, Selection::single(13, 14));
+        run(&mut editor, goto_tag);
+        assert_eq!(
+            editor.status_msg.as_ref().map(|(msg, _)| msg.as_str()),
+            Some("No tag found for he")
+        );
+    }
+
+    #[test]
+    fn failed_workspace_edit_closes_opened_documents() {
+        let mut editor = editor();
+        let dir = TempDir::new();
+        let open = dir.file("edit-open.txt", "one\n");
+        let closed = dir.file("edit-closed.txt", "two\n");
+        editor.open(open.clone(), Action::Load).unwrap();
+
+        let edit = |path: &PathBuf, version| lsp::TextDocumentEdit {
+            text_document: lsp::OptionalVersionedTextDocumentIdentifier {
+                uri: lsp::Url::from_file_path(path).unwrap(),
+                version,
+            },
+            edits: vec![lsp::OneOf::Left(lsp::TextEdit::new(
+                lsp::Range::default(),
+                "edited ".to_owned(),
+            ))],
+        };
+        // the open document changed since the edit was computed
+        let workspace_edit = lsp::WorkspaceEdit {
+            document_changes: Some(lsp::DocumentChanges::Edits(vec![
+                edit(&closed, None),
+                edit(&open, Some(42)),
+            ])),
+            ..Default::default()
+        };
+        assert!(editor
+            .apply_workspace_edit(OffsetEncoding::Utf8, &workspace_edit)
+            .is_err());
+
+        let paths: Vec<_> = editor
+            .documents()
+            .filter_map(|doc| doc.path().cloned())
+            .collect();
+        assert_eq!(paths, vec![open.canonicalize().unwrap()]);
+        assert!(editor
+            .documents()
+            .all(|doc| !doc.text().to_string().contains("edited")));
+    }
+
+    #[test]
+    fn describe_graphemes() {
+        assert_eq!(
+            describe_grapheme("a"),
+            "\"a\" U+0061 LATIN SMALL LETTER A, UTF-8 61"
+        );
+        assert_eq!(
+            describe_grapheme("e\u{301}"),
+            // combining marks are escaped, they'd combine with the quote
+            "\"e\\u{301}\" U+0065 LATIN SMALL LETTER E, U+0301 COMBINING ACUTE ACCENT, UTF-8 65 cc 81"
+        );
+        assert_eq!(describe_grapheme("\n"), "\"\\n\" U+000A, UTF-8 0a");
+    }
+
+    #[test]
+    fn character_info_aliases() {
+        for name in ["character-info", "char-info", "char"] {
+            assert_eq!(cmd::COMMANDS[name].name, "character-info");
+        }
+    }
+
+    #[test]
+    fn parse_code_points() {
+        use super::insert::parse_code_point;
+
+        assert_eq!(parse_code_point("e9"), Some('é'));
+        assert_eq!(parse_code_point(" U+00E9 "), Some('é'));
+        assert_eq!(parse_code_point("u+1f600"), Some('😀'));
+        assert_eq!(parse_code_point("0x41"), Some('A'));
+        assert_eq!(parse_code_point("\\u41"), Some('A'));
+        assert_eq!(parse_code_point("U+10FFFF"), Some('\u{10FFFF}'));
+        // invalid hex, surrogates and code points past U+10FFFF aren't characters
+        assert_eq!(parse_code_point(""), None);
+        assert_eq!(parse_code_point("U+"), None);
+        assert_eq!(parse_code_point("xyz"), None);
+        assert_eq!(parse_code_point("U+D800"), None);
+        assert_eq!(parse_code_point("0xDFFF"), None);
+        assert_eq!(parse_code_point("U+110000"), None);
+    }
+
+    #[test]
+    fn parse_characters_by_name() {
+        use super::insert::parse_character;
+
+        assert_eq!(parse_character("U+00E9"), Some('é'));
+        assert_eq!(
+            parse_character("latin small letter e with acute"),
+            Some('é')
+        );
+        assert_eq!(parse_character(" GREEK SMALL LETTER LAMDA "), Some('λ'));
+        assert_eq!(parse_character("not a character name"), None);
+    }
+
+    #[test]
+    fn insert_with_and_without_auto_pairs() {
+        let mut editor = editor();
+        run(&mut editor, |cx| insert::insert_char(cx, '('));
+        assert_eq!(current!(editor).1.text().to_string(), "()\n");
+
+        editor.config.set("auto-pairs", "false").unwrap();
+        run(&mut editor, |cx| insert::insert_char(cx, '['));
+        assert_eq!(current!(editor).1.text().to_string(), "([)\n");
+    }
+
+    #[test]
+    fn goto_buffer_in_open_order() {
+        let mut editor = editor();
+        let first_view = editor.tree.focus;
+        let b = editor.new_file(Action::VerticalSplit);
+        let c = editor.new_file(Action::Replace);
+        // the document closed first frees the slot reused by the next one
+        editor.close(first_view, true);
+        let d = editor.new_file(Action::Replace);
+        assert_eq!(editor.document_order(), [b, c, d]);
+        assert_eq!(editor.documents.keys().next(), Some(d));
+
+        run(&mut editor, goto_next_buffer);
+        assert_eq!(view!(editor).doc, b);
+        run(&mut editor, goto_next_buffer);
+        assert_eq!(view!(editor).doc, c);
+        run(&mut editor, goto_previous_buffer);
+        run(&mut editor, goto_previous_buffer);
+        assert_eq!(view!(editor).doc, d);
+    }
+
+    #[test]
+    fn insert_mode_word_boundaries() {
+        use super::insert::{next_word_end, prev_word_start};
+
+        let prev = |text: &str, pos| prev_word_start(Rope::from(text).slice(..), pos);
+        let next = |text: &str, pos| next_word_end(Rope::from(text).slice(..), pos);
+
+        // words stop at punctuation, and punctuation runs are words of their own
+        assert_eq!(prev("foo.bar", 7), 4);
+        assert_eq!(prev("foo..", 5), 3);
+        assert_eq!(next("foo.bar", 0), 3);
+        assert_eq!(next("foo..bar", 3), 5);
+        // whitespace runs go with the word before them
+        assert_eq!(prev("foo bar   ", 10), 4);
+        assert_eq!(prev("foo   ", 6), 0);
+        assert_eq!(next("foo   bar", 0), 6);
+        assert_eq!(next("   bar", 0), 3);
+        // line endings are words of their own and stop the other words
+        assert_eq!(prev("a\nb", 2), 1);
+        assert_eq!(prev("a\r\nb", 3), 1);
+        assert_eq!(prev("a\n  b", 4), 2);
+        assert_eq!(next("a\nb", 1), 2);
+        assert_eq!(next("a\r\nb", 1), 3);
+        assert_eq!(next("a  \nb", 0), 3);
+        // nothing before the start or after the end of the document
+        assert_eq!(prev("foo", 0), 0);
+        assert_eq!(prev("  foo", 2), 0);
+        assert_eq!(next("foo", 3), 3);
+        assert_eq!(next("foo  ", 0), 5);
+    }
+
+    #[test]
+    fn sort_strings_by_case_and_number() {
+        let sorted = |items: &[&str], reverse| {
+            let mut items: Vec<String> = items.iter().map(|item| item.to_string()).collect();
+            sort_strings(&mut items, reverse);
+            items
+        };
+
+        // uppercase letters come before lowercase ones
+        assert_eq!(sorted(&["b", "B", "a", "A"], false), ["A", "B", "a", "b"]);
+        assert_eq!(sorted(&["b", "B", "a", "A"], true), ["b", "a", "B", "A"]);
+        // numbers are compared by value, even with surrounding whitespace
+        assert_eq!(
+            sorted(&["10", " 9", "-1.5", "2"], false),
+            ["-1.5", "2", " 9", "10"]
+        );
+        assert_eq!(sorted(&["10", "9", "2"], true), ["10", "9", "2"]);
+        // a single item that isn't a number compares all of them as text
+        assert_eq!(sorted(&["10", "9", "x"], false), ["10", "9", "x"]);
+        assert_eq!(sorted(&["b", "", "a"], false), ["", "a", "b"]);
+        // non-finite numbers aren't numbers
+        assert_eq!(sorted(&["1", "nan", "0"], false), ["0", "1", "nan"]);
+        assert_eq!(sorted(&["1", "inf", "-1"], false), ["-1", "1", "inf"]);
+        assert_eq!(
+            sorted(&["2", "infinity", "10"], false),
+            ["10", "2", "infinity"]
+        );
+        assert_eq!(sorted(&["1e999", "9"], false), ["1e999", "9"]);
+        assert!(sorted(&[], false).is_empty());
+    }
+
+    #[test]
+    fn sort_selections_and_lines() {
+        let view = ViewId::default();
+        let sorted = |text: &str, selection: Selection, reverse| {
+            let mut doc = Document::from(Rope::from(text), None);
+            doc.set_selection(view, selection);
+            sort_impl(&mut doc, view, reverse);
+            doc.text().to_string()
+        };
+
+        // each cursor of a multiple selection is an item
+        let cursors = Selection::new(
+            vec![Range::point(0), Range::point(2), Range::point(4)].into(),
+            0,
+        );
+        assert_eq!(sorted("c b a\n", cursors.clone(), false), "a b c\n");
+        assert_eq!(sorted("c b a\n", cursors, true), "c b a\n");
+        let words = Selection::new(
+            vec![Range::new(0, 1), Range::point(4), Range::new(7, 9)].into(),
+            0,
+        );
+        assert_eq!(sorted("10, 9, 100\n", words, false), "9, 10, 100\n");
+        // a single selection sorts the lines it spans, unless it's within one
+        assert_eq!(
+            sorted("b\nB\na\nc\n", Selection::single(0, 4), false),
+            "B\na\nb\nc\n"
+        );
+        assert_eq!(sorted("c b a\n", Selection::single(0, 4), false), "c b a\n");
+        assert_eq!(sorted("", Selection::point(0), false), "");
+    }
+
+    #[test]
+    fn buffer_word_completions_near_the_cursor() {
+        let mut editor = editor();
+        let set_text = |editor: &mut Editor, text: &str| {
+            let (view, doc) = current!(editor);
+            let transaction = Transaction::change(
+                doc.text(),
+                std::iter::once((0, doc.text().len_chars(), Some(text.into()))),
+            );
+            doc.apply(&transaction, view.id);
+            let end = doc.text().len_chars();
+            doc.set_selection(view.id, Selection::point(end));
+        };
+        let labels = |editor: &Editor| -> Vec<String> {
+            buffer_word_completions(editor, OffsetEncoding::Utf8)
+                .into_iter()
+                .map(|item| item.label)
+                .collect()
+        };
+
+        set_text(&mut editor, "word_in_other\n");
+        editor.new_file(Action::VerticalSplit);
+        let far = "\n".repeat(WORD_COMPLETION_LINES + 1);
+        set_text(
+            &mut editor,
+            &format!("word_far{}word_b word_a wo\nwor", far),
+        );
+        assert_eq!(labels(&editor), ["word_a", "word_b", "word_in_other"]);
+
+        // the current document is scanned first
+        let words: Vec<String> = (0..MAX_WORD_COMPLETIONS * 2)
+            .map(|i| format!("word{:03}", i))
+            .collect();
+        set_text(&mut editor, &format!("{}\nwor", words.join(" ")));
+        assert_eq!(labels(&editor), &words[..MAX_WORD_COMPLETIONS]);
+    }
+}
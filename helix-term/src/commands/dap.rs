@@ -0,0 +1,450 @@
+use super::{align_view, Align, Context, Editor};
+use crate::{
+    compositor::Compositor,
+    job::Callback,
+    ui::{FilePicker, Picker},
+};
+use helix_core::{syntax::DebugTemplate, Selection};
+use helix_dap::{Client, SourceBreakpoint, StackFrame, Variable};
+use helix_view::editor::Action;
+
+use anyhow::{anyhow, bail, Context as _};
+use serde_json::Value;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio_stream::wrappers::UnboundedReceiverStream;
+
+/// Opens the source of `frame` and moves the cursor to its position.
+pub fn jump_to_stack_frame(editor: &mut Editor, frame: &StackFrame) {
+    let path = match frame.source.as_ref().and_then(|source| source.path.clone()) {
+        Some(path) => path,
+        None => return,
+    };
+    if let Err(err) = editor.open(path, Action::Replace) {
+        editor.set_error(format!("Unable to open the source of the frame: {}", err));
+        return;
+    }
+
+    let (view, doc) = current!(editor);
+    let text = doc.text();
+    let line = frame.line.saturating_sub(1).min(text.len_lines() - 1);
+    let line_start = text.line_to_char(line);
+    let line_len = text.line(line).len_chars();
+    let pos = line_start
+        + frame
+            .column
+            .saturating_sub(1)
+            .min(line_len.saturating_sub(1));
+    doc.set_selection(view.id, Selection::point(pos));
+    align_view(doc, view, Align::Center);
+}
+
+/// Replaces the `{0}`, `{1}`, ... placeholders in the strings of `value` by `params`.
+fn fill_placeholders(value: Value, params: &[&str]) -> anyhow::Result<Value> {
+    Ok(match value {
+        Value::String(string) => {
+            let mut filled = String::new();
+            let mut rest = string.as_str();
+            while let Some(start) = rest.find('{') {
+                let placeholder = rest[start + 1..].find('}').and_then(|end| {
+                    Some((end, rest[start + 1..start + 1 + end].parse::<usize>().ok()?))
+                });
+                filled.push_str(&rest[..start]);
+                match placeholder {
+                    Some((end, index)) => {
+                        let param: &str = params.get(index).ok_or_else(|| {
+                            anyhow!("the debug template needs argument {}", index)
+                        })?;
+                        filled.push_str(param);
+                        rest = &rest[start + end + 2..];
+                    }
+                    None => {
+                        filled.push('{');
+                        rest = &rest[start + 1..];
+                    }
+                }
+            }
+            filled.push_str(rest);
+            Value::String(filled)
+        }
+        Value::Array(values) => Value::Array(
+            values
+                .into_iter()
+                .map(|value| fill_placeholders(value, params))
+                .collect::<anyhow::Result<_>>()?,
+        ),
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(key, value)| Ok((key, fill_placeholders(value, params)?)))
+                .collect::<anyhow::Result<_>>()?,
+        ),
+        value => value,
+    })
+}
+
+/// The id of the next debug session, which tags the messages of its adapter so that those of an
+/// ended session are told apart.
+static NEXT_SESSION_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Starts a debug session for the language of the current document with the template named
+/// `template`, or the first one.
+pub fn dap_start_impl(
+    editor: &mut Editor,
+    jobs: &mut crate::job::Jobs,
+    template: Option<&str>,
+    params: &[&str],
+) -> anyhow::Result<()> {
+    if editor.debugger.is_some() {
+        bail!("A debug session is already running");
+    }
+
+    let (_, doc) = current!(editor);
+    let config = doc
+        .language_config()
+        .and_then(|config| config.debugger.as_ref())
+        .context("No debug adapter configured for this language")?;
+
+    let template: &DebugTemplate = match template {
+        Some(name) => config
+            .templates
+            .iter()
+            .find(|template| template.name == name)
+            .with_context(|| format!("No debug template named {}", name))?,
+        None => config
+            .templates
+            .first()
+            .context("No debug templates configured for this language")?,
+    };
+    if template.request != "launch" {
+        bail!("Unsupported debug request: {}", template.request);
+    }
+    let args = fill_placeholders(serde_json::to_value(&template.args)?, params)?;
+
+    let (mut client, events) = Client::start(
+        &config.command,
+        &config.args,
+        NEXT_SESSION_ID.fetch_add(1, Ordering::Relaxed),
+    )?;
+    let adapter_id = config.name.clone();
+
+    jobs.callback(async move {
+        let result = client.initialize(adapter_id).await;
+        let call: Callback = Box::new(move |editor: &mut Editor, _: &mut Compositor| {
+            if let Err(err) = result {
+                editor.set_error(format!("Failed to start the debug session: {}", err));
+                return;
+            }
+            // another session may have started while this one initialized
+            if editor.debugger.is_some() {
+                editor.set_error("A debug session is already running".to_owned());
+                return;
+            }
+
+            // the adapter sends an `initialized` event once it's ready for breakpoints, the
+            // response to `launch` may only arrive after they are set
+            let launch = client.launch(args);
+            tokio::spawn(async move {
+                if let Err(err) = launch.await {
+                    log::error!("failed to launch the debugged program: {}", err);
+                }
+            });
+
+            editor
+                .debugger_events
+                .push(UnboundedReceiverStream::new(events));
+            editor.debugger = Some(client);
+            editor.set_status("Debug session started".to_owned());
+        });
+        Ok(call)
+    });
+    editor.set_status("Starting the debug session".to_owned());
+    Ok(())
+}
+
+pub fn dap_launch(cx: &mut Context) {
+    let (_, doc) = current!(cx.editor);
+    let templates: Vec<String> = match doc
+        .language_config()
+        .and_then(|config| config.debugger.as_ref())
+    {
+        Some(config) => config
+            .templates
+            .iter()
+            .map(|template| template.name.clone())
+            .collect(),
+        None => {
+            cx.editor
+                .set_error("No debug adapter configured for this language".to_owned());
+            return;
+        }
+    };
+
+    let picker = Picker::with_callback(
+        templates,
+        |template| template.as_str().into(),
+        |cx, template, _action| {
+            if let Err(err) = dap_start_impl(cx.editor, cx.jobs, Some(template), &[]) {
+                cx.editor.set_error(err.to_string());
+            }
+            None
+        },
+    );
+    cx.push_layer(Box::new(picker));
+}
+
+pub fn dap_toggle_breakpoint(cx: &mut Context) {
+    let (view, doc) = current!(cx.editor);
+    let path = match doc.path() {
+        Some(path) => path.clone(),
+        None => {
+            cx.editor
+                .set_error("Can't set a breakpoint in a scratch buffer".to_owned());
+            return;
+        }
+    };
+    // breakpoint lines start at 1
+    let line = doc.text().char_to_line(doc.selection(view.id).cursor()) + 1;
+
+    let breakpoints = cx.editor.breakpoints.entry(path.clone()).or_default();
+    match breakpoints
+        .iter()
+        .position(|breakpoint| breakpoint.line == line)
+    {
+        Some(index) => {
+            breakpoints.remove(index);
+        }
+        None => breakpoints.push(SourceBreakpoint { line }),
+    }
+    let breakpoints = breakpoints.clone();
+
+    if let Some(debugger) = &cx.editor.debugger {
+        let request = debugger.set_breakpoints(path, breakpoints);
+        cx.jobs.callback(async move {
+            let breakpoints = request.await?;
+            let call: Callback = Box::new(move |editor: &mut Editor, _: &mut Compositor| {
+                if let Some(breakpoint) = breakpoints.iter().find(|breakpoint| !breakpoint.verified)
+                {
+                    editor.set_error(format!(
+                        "Breakpoint not verified: {}",
+                        breakpoint.message.as_deref().unwrap_or("unknown reason")
+                    ));
+                }
+            });
+            Ok(call)
+        });
+    }
+}
+
+/// Sends a request for the stopped thread, the program runs again until the next `stopped`
+/// event.
+fn resume<F, R>(cx: &mut Context, request: F)
+where
+    F: FnOnce(&Client, usize) -> R,
+    R: Future<Output = helix_dap::Result<Value>> + Send + 'static,
+{
+    let debugger = match &mut cx.editor.debugger {
+        Some(debugger) => debugger,
+        None => {
+            cx.editor
+                .set_error("No debug session is running".to_owned());
+            return;
+        }
+    };
+    let thread_id = match (&debugger.active_frame, debugger.thread_id) {
+        (Some(_), Some(thread_id)) => thread_id,
+        _ => {
+            cx.editor
+                .set_error("The debugged program isn't stopped".to_owned());
+            return;
+        }
+    };
+
+    let request = request(debugger, thread_id);
+    debugger.active_frame = None;
+    cx.jobs.spawn(async move {
+        request.await?;
+        Ok(())
+    });
+}
+
+pub fn dap_continue(cx: &mut Context) {
+    resume(cx, Client::continue_thread)
+}
+
+pub fn dap_next(cx: &mut Context) {
+    resume(cx, Client::next)
+}
+
+pub fn dap_step_in(cx: &mut Context) {
+    resume(cx, Client::step_in)
+}
+
+pub fn dap_step_out(cx: &mut Context) {
+    resume(cx, Client::step_out)
+}
+
+pub fn dap_pause(cx: &mut Context) {
+    let debugger = match &cx.editor.debugger {
+        Some(debugger) => debugger,
+        None => {
+            cx.editor
+                .set_error("No debug session is running".to_owned());
+            return;
+        }
+    };
+    let thread_id = match debugger.thread_id {
+        Some(thread_id) => thread_id,
+        None => {
+            cx.editor
+                .set_error("The debugged program has no threads yet".to_owned());
+            return;
+        }
+    };
+
+    let request = debugger.pause(thread_id);
+    cx.jobs.spawn(async move {
+        request.await?;
+        Ok(())
+    });
+}
+
+pub fn dap_terminate(cx: &mut Context) {
+    let debugger = match &cx.editor.debugger {
+        Some(debugger) => debugger,
+        None => {
+            cx.editor
+                .set_error("No debug session is running".to_owned());
+            return;
+        }
+    };
+
+    let request = debugger.disconnect();
+    cx.jobs.callback(async move {
+        // the adapter is dropped, and killed, either way
+        let result = request.await;
+        let call: Callback = Box::new(move |editor: &mut Editor, _: &mut Compositor| {
+            editor.debugger = None;
+            match result {
+                Ok(_) => editor.set_status("Debug session ended".to_owned()),
+                Err(err) => editor.set_error(format!("Debug session ended: {}", err)),
+            }
+        });
+        Ok(call)
+    });
+}
+
+pub fn dap_stack_trace(cx: &mut Context) {
+    let debugger = match &cx.editor.debugger {
+        Some(debugger) => debugger,
+        None => {
+            cx.editor
+                .set_error("No debug session is running".to_owned());
+            return;
+        }
+    };
+    let thread_id = match (&debugger.active_frame, debugger.thread_id) {
+        (Some(_), Some(thread_id)) => thread_id,
+        _ => {
+            cx.editor
+                .set_error("The debugged program isn't stopped".to_owned());
+            return;
+        }
+    };
+
+    let request = debugger.stack_trace(thread_id);
+    cx.jobs.callback(async move {
+        let result = request.await;
+        let call: Callback =
+            Box::new(
+                move |editor: &mut Editor, compositor: &mut Compositor| match result {
+                    Ok(frames) => compositor.push(Box::new(stack_frames_picker(frames))),
+                    Err(err) => editor.set_error(err.to_string()),
+                },
+            );
+        Ok(call)
+    });
+}
+
+/// A picker of `frames`, the selected one is the frame variables are inspected in.
+fn stack_frames_picker(frames: Vec<StackFrame>) -> FilePicker<StackFrame> {
+    FilePicker::new(
+        frames,
+        |frame| {
+            let file = frame
+                .source
+                .as_ref()
+                .and_then(|source| source.name.as_deref())
+                .unwrap_or("<unknown>");
+            format!("{} {}:{}", frame.name, file, frame.line).into()
+        },
+        |editor: &mut Editor, frame, _action| {
+            if let Some(debugger) = &mut editor.debugger {
+                debugger.active_frame = Some(frame.clone());
+            }
+            jump_to_stack_frame(editor, frame);
+        },
+        |_editor, frame| {
+            let path = frame.source.as_ref()?.path.clone()?;
+            let line = frame.line.checked_sub(1)?;
+            Some((path, Some((line, line))))
+        },
+    )
+}
+
+/// Opens a picker of the variables `request` resolves into, or reports its error.
+fn push_variables_picker<F>(jobs: &mut crate::job::Jobs, request: F)
+where
+    F: Future<Output = helix_dap::Result<Vec<Variable>>> + Send + 'static,
+{
+    jobs.callback(async move {
+        let result = request.await;
+        let call: Callback =
+            Box::new(
+                move |editor: &mut Editor, compositor: &mut Compositor| match result {
+                    Ok(variables) => compositor.push(Box::new(variables_picker(variables))),
+                    Err(err) => editor.set_error(err.to_string()),
+                },
+            );
+        Ok(call)
+    });
+}
+
+/// A picker of `variables`. Structured variables open a picker of their fields.
+fn variables_picker(variables: Vec<Variable>) -> Picker<Variable> {
+    Picker::with_callback(
+        variables,
+        |variable| match &variable.ty {
+            Some(ty) => format!("{}: {} = {}", variable.name, ty, variable.value).into(),
+            None => format!("{} = {}", variable.name, variable.value).into(),
+        },
+        |cx, variable, _action| {
+            if variable.variables_reference == 0 {
+                return None;
+            }
+            let debugger = cx.editor.debugger.as_ref()?;
+            push_variables_picker(cx.jobs, debugger.variables(variable.variables_reference));
+            None
+        },
+    )
+}
+
+pub fn dap_variables(cx: &mut Context) {
+    let debugger = match &cx.editor.debugger {
+        Some(debugger) => debugger,
+        None => {
+            cx.editor
+                .set_error("No debug session is running".to_owned());
+            return;
+        }
+    };
+    let frame_id = match &debugger.active_frame {
+        Some(frame) => frame.id,
+        None => {
+            cx.editor
+                .set_error("The debugged program isn't stopped".to_owned());
+            return;
+        }
+    };
+
+    push_variables_picker(cx.jobs, debugger.frame_variables(frame_id));
+}
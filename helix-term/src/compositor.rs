@@ -40,6 +40,12 @@ pub trait Component: Any + AnyComponent {
     }
     // , args: ()
 
+    /// Handles `text` pasted in the terminal all at once, returning whether it did. Otherwise the
+    /// text is handled as typed keys.
+    fn handle_paste(&mut self, _text: &str, _ctx: &mut Context) -> bool {
+        false
+    }
+
     /// Should redraw? Useful for saving redraw cycles if we know component didn't change.
     fn should_update(&self) -> bool {
         true
@@ -67,9 +73,10 @@ pub trait Component: Any + AnyComponent {
 }
 
 use anyhow::Error;
-use std::io::stdout;
+use std::io::{stdout, BufWriter, Stdout};
 use tui::backend::CrosstermBackend;
-type Terminal = tui::terminal::Terminal<CrosstermBackend<std::io::Stdout>>;
+// frames are written out in one go when the terminal is flushed
+type Terminal = tui::terminal::Terminal<CrosstermBackend<BufWriter<Stdout>>>;
 
 pub struct Compositor {
     layers: Vec<Box<dyn Component>>,
@@ -80,7 +87,7 @@ pub struct Compositor {
 
 impl Compositor {
     pub fn new() -> Result<Self, Error> {
-        let backend = CrosstermBackend::new(stdout());
+        let backend = CrosstermBackend::new(BufWriter::new(stdout()));
         let terminal = Terminal::new(backend)?;
         Ok(Self {
             layers: Vec::new(),
@@ -111,10 +118,23 @@ impl Compositor {
     }
 
     pub fn handle_event(&mut self, event: Event, cx: &mut Context) -> bool {
+        // every layer lays itself out again for the new size, not just the topmost one
+        if let Event::Resize(..) = event {
+            for layer in self.layers.iter_mut() {
+                if let EventResult::Consumed(Some(callback)) = layer.handle_event(event.clone(), cx)
+                {
+                    callback(self);
+                    // the callback may have changed the layers
+                    break;
+                }
+            }
+            return true;
+        }
+
         // propagate events through the layers until we either find a layer that consumes it or we
         // run out of layers (event bubbling)
         for layer in self.layers.iter_mut().rev() {
-            match layer.handle_event(event, cx) {
+            match layer.handle_event(event.clone(), cx) {
                 EventResult::Consumed(Some(callback)) => {
                     callback(self);
                     return true;
@@ -126,6 +146,14 @@ impl Compositor {
         false
     }
 
+    /// Gives `text` pasted in the terminal to the topmost layer, returning whether it handled it.
+    pub fn handle_paste(&mut self, text: &str, cx: &mut Context) -> bool {
+        match self.layers.last_mut() {
+            Some(layer) => layer.handle_paste(text, cx),
+            None => false,
+        }
+    }
+
     pub fn render(&mut self, cx: &mut Context) {
         self.terminal
             .autoresize()
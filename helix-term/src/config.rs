@@ -9,6 +9,8 @@ pub struct Config {
     pub lsp: LspConfig,
     #[serde(default)]
     pub keys: Keymaps,
+    #[serde(default)]
+    pub editor: helix_view::editor::Config,
 }
 
 #[derive(Debug, Default, Clone, PartialEq, Deserialize)]
@@ -0,0 +1,78 @@
+//! The report of `:health`, about what the editor found in its environment.
+
+use helix_view::Editor;
+use std::fmt::Write;
+
+/// Whether the terminal advertises 24-bit colors.
+fn supports_true_color() -> bool {
+    matches!(
+        std::env::var("COLORTERM").as_deref(),
+        Ok("truecolor") | Ok("24bit")
+    )
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
+/// The report as text: the clipboard provider and terminal colors, then a line per configured
+/// language with whether its grammar loads and its language server is on the `PATH`.
+pub fn report(editor: &Editor) -> String {
+    let mut report = String::new();
+    // writing to a string can't fail
+    let _ = writeln!(
+        report,
+        "Runtime directory: {}",
+        helix_core::runtime_dir().display()
+    );
+    let _ = writeln!(report, "Log file: {}", helix_core::log_file().display());
+    let _ = writeln!(
+        report,
+        "Clipboard provider: {}",
+        editor.clipboard_provider.name()
+    );
+    let _ = writeln!(report, "True color: {}", yes_no(supports_true_color()));
+    let _ = writeln!(report);
+
+    let mut languages: Vec<_> = editor.syn_loader.language_configs_iter().collect();
+    languages.sort_by(|a, b| a.language_id().cmp(b.language_id()));
+    let width = languages
+        .iter()
+        .map(|language| language.language_id().len())
+        .chain(std::iter::once("Language".len()))
+        .max()
+        .unwrap_or_default();
+
+    let _ = writeln!(
+        report,
+        "{:width$}  Grammar  Language server",
+        "Language",
+        width = width
+    );
+    for language in languages {
+        let language_server = match &language.language_server {
+            Some(config) => {
+                let found = which::which(&config.command).is_ok();
+                format!(
+                    "{} ({})",
+                    config.command,
+                    if found { "found" } else { "not found" }
+                )
+            }
+            None => "none".to_owned(),
+        };
+        let _ = writeln!(
+            report,
+            "{:width$}  {:7}  {}",
+            language.language_id(),
+            yes_no(language.has_grammar()),
+            language_server,
+            width = width
+        );
+    }
+    report
+}
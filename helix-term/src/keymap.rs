@@ -1,9 +1,10 @@
 pub use crate::commands::Command;
 use crate::config::Config;
 use helix_core::hashmap;
-use helix_view::{document::Mode, info::Info, input::KeyEvent};
+use helix_view::{document::Mode, editor::PluginConfig, info::Info, input::KeyEvent};
 use serde::Deserialize;
 use std::{
+    borrow::Cow,
     collections::HashMap,
     ops::{Deref, DerefMut},
 };
@@ -47,13 +48,13 @@ macro_rules! keymap {
     };
 
     (@trie
-        { $label:literal $($($key:literal)|+ => $value:tt,)+ }
+        { $label:literal $(sticky=$sticky:literal)? $($($key:literal)|+ => $value:tt,)+ }
     ) => {
-        keymap!({ $label $($($key)|+ => $value,)+ })
+        keymap!({ $label $(sticky=$sticky)? $($($key)|+ => $value,)+ })
     };
 
     (
-        { $label:literal $($($key:literal)|+ => $value:tt,)+ }
+        { $label:literal $(sticky=$sticky:literal)? $($($key:literal)|+ => $value:tt,)+ }
     ) => {
         // modified from the hashmap! macro
         {
@@ -70,7 +71,9 @@ macro_rules! keymap {
                     _order.push(_key);
                 )+
             )*
-            $crate::keymap::KeyTrie::Node($crate::keymap::KeyTrieNode::new($label, _map, _order))
+            let mut _node = $crate::keymap::KeyTrieNode::new($label, _map, _order);
+            $( _node.is_sticky = $sticky; )?
+            $crate::keymap::KeyTrie::Node(_node)
         }
     };
 }
@@ -84,6 +87,9 @@ pub struct KeyTrieNode {
     map: HashMap<KeyEvent, KeyTrie>,
     #[serde(skip)]
     order: Vec<KeyEvent>,
+    /// Sticky nodes stay active after a command was executed, until cancelled with escape.
+    #[serde(skip)]
+    pub is_sticky: bool,
 }
 
 impl KeyTrieNode {
@@ -92,6 +98,7 @@ impl KeyTrieNode {
             name: name.to_string(),
             map,
             order,
+            is_sticky: false,
         }
     }
 
@@ -236,6 +243,9 @@ pub struct Keymap {
     root: KeyTrie,
     #[serde(skip)]
     state: Vec<KeyEvent>,
+    /// The sticky node currently in effect, if any.
+    #[serde(skip)]
+    sticky: Option<KeyTrieNode>,
 }
 
 impl Keymap {
@@ -243,6 +253,7 @@ impl Keymap {
         Keymap {
             root,
             state: Vec::new(),
+            sticky: None,
         }
     }
 
@@ -250,20 +261,44 @@ impl Keymap {
         &self.root
     }
 
+    /// Returns list of keys waiting to be disambiguated.
+    pub fn pending(&self) -> &[KeyEvent] {
+        &self.state
+    }
+
+    /// Returns the sticky node currently in effect, if any.
+    pub fn sticky(&self) -> Option<&KeyTrieNode> {
+        self.sticky.as_ref()
+    }
+
     /// Lookup `key` in the keymap to try and find a command to execute
     pub fn get(&mut self, key: KeyEvent) -> KeymapResult {
+        if key == key!(Esc) && self.state.is_empty() {
+            self.sticky = None;
+        }
+
         let &first = self.state.get(0).unwrap_or(&key);
-        let trie = match self.root.search(&[first]) {
-            Some(&KeyTrie::Leaf(cmd)) => return KeymapResult::Matched(cmd),
+        let root = match self.sticky {
+            Some(ref node) => Cow::Owned(KeyTrie::Node(node.clone())),
+            None => Cow::Borrowed(&self.root),
+        };
+        let trie = match root.search(&[first]) {
+            Some(KeyTrie::Leaf(cmd)) => return KeymapResult::Matched(cmd.clone()),
             None => return KeymapResult::NotFound,
             Some(t) => t,
         };
         self.state.push(key);
         match trie.search(&self.state[1..]) {
-            Some(&KeyTrie::Node(ref map)) => KeymapResult::Pending(map.clone()),
-            Some(&KeyTrie::Leaf(command)) => {
+            Some(&KeyTrie::Node(ref map)) => {
+                if map.is_sticky {
+                    self.state.clear();
+                    self.sticky = Some(map.clone());
+                }
+                KeymapResult::Pending(map.clone())
+            }
+            Some(KeyTrie::Leaf(command)) => {
                 self.state.clear();
-                KeymapResult::Matched(command)
+                KeymapResult::Matched(command.clone())
             }
             None => KeymapResult::Cancelled(self.state.drain(..).collect()),
         }
@@ -272,6 +307,40 @@ impl Keymap {
     pub fn merge(&mut self, other: Self) {
         self.root.merge_nodes(other.root);
     }
+
+    /// Returns the key sequences bound to each command, by command name. Shorter sequences come
+    /// first.
+    pub fn reverse_map(&self) -> HashMap<String, Vec<Vec<KeyEvent>>> {
+        fn map_node(
+            commands: &mut HashMap<String, Vec<Vec<KeyEvent>>>,
+            trie: &KeyTrie,
+            keys: &mut Vec<KeyEvent>,
+        ) {
+            match trie {
+                KeyTrie::Leaf(command) => commands
+                    .entry(command.name().to_owned())
+                    .or_default()
+                    .push(keys.clone()),
+                KeyTrie::Node(node) => {
+                    for (&key, trie) in &node.map {
+                        keys.push(key);
+                        map_node(commands, trie, keys);
+                        keys.pop();
+                    }
+                }
+            }
+        }
+
+        let mut commands = HashMap::new();
+        map_node(&mut commands, &self.root, &mut Vec::new());
+        for keys in commands.values_mut() {
+            keys.sort_by_cached_key(|keys| {
+                let name: String = keys.iter().map(ToString::to_string).collect();
+                (keys.len(), name)
+            });
+        }
+        commands
+    }
 }
 
 impl Deref for Keymap {
@@ -308,10 +377,10 @@ impl DerefMut for Keymaps {
 
 impl Default for Keymaps {
     fn default() -> Keymaps {
-        let normal = keymap!({ "Normal mode"
+        let mut normal = keymap!({ "Normal mode"
             "h" | "left" => move_char_left,
-            "j" | "down" => move_line_down,
-            "k" | "up" => move_line_up,
+            "j" | "down" => move_visual_line_down,
+            "k" | "up" => move_visual_line_up,
             "l" | "right" => move_char_right,
 
             "t" => find_till_char,
@@ -344,13 +413,18 @@ impl Default for Keymaps {
                 "l" => goto_line_end,
                 "s" => goto_first_nonwhitespace,
                 "d" => goto_definition,
+                "T" => goto_tag,
                 "y" => goto_type_definition,
                 "r" => goto_reference,
                 "i" => goto_implementation,
                 "t" => goto_window_top,
-                "m" => goto_window_middle,
+                "m" | "c" => goto_window_middle,
                 "b" => goto_window_bottom,
                 "a" => goto_last_accessed_file,
+                "n" => goto_next_buffer,
+                "p" => goto_previous_buffer,
+                ";" => goto_last_modification,
+                "q" => reflow,
             },
             ":" => command_mode,
 
@@ -369,9 +443,16 @@ impl Default for Keymaps {
 
             "s" => select_regex,
             "A-s" => split_selection_on_newline,
+            "A-minus" => merge_selections,
+            "A-_" => merge_consecutive_selections,
+            "_" => trim_selections,
             "S" => split_selection,
             ";" => collapse_selection,
             "A-;" => flip_selections,
+            "A-up" => expand_selection,
+            "A-down" => shrink_selection,
+            "A-right" => select_next_sibling,
+            "A-left" => select_prev_sibling,
             "%" => select_all,
             "x" => extend_line,
             "X" => extend_to_line_bounds,
@@ -388,31 +469,43 @@ impl Default for Keymaps {
             "[" => { "Left bracket"
                 "d" => goto_prev_diag,
                 "D" => goto_first_diag,
+                "f" => goto_prev_function,
+                "c" => goto_prev_class,
+                "g" => goto_prev_change,
             },
             "]" => { "Right bracket"
                 "d" => goto_next_diag,
                 "D" => goto_last_diag,
+                "f" => goto_next_function,
+                "c" => goto_next_class,
+                "g" => goto_next_change,
             },
 
             "/" => search,
             // ? for search_reverse
             "n" => search_next,
             "N" => extend_search_next,
+            "A-n" => search_prev,
+            "A-N" => extend_search_prev,
             // N for search_prev
             "*" => search_selection,
 
             "u" => undo,
             "U" => redo,
+            "A-u" => selection_undo,
+            "A-U" => selection_redo,
 
             "y" => yank,
             // yank_all
             "p" => paste_after,
-            // paste_all
             "P" => paste_before,
+            "A-p" => paste_all_after,
+            "A-P" => paste_all_before,
 
             ">" => indent,
             "<" => unindent,
-            "=" => format_selections,
+            "=" => indent_selection,
+            "A-=" => format_selections,
             "J" => join_selections,
             // TODO: conflicts hover/doc
             "K" => keep_selections,
@@ -434,12 +527,29 @@ impl Default for Keymaps {
             "C-f" | "pagedown" => page_down,
             "C-u" => half_page_up,
             "C-d" => half_page_down,
+            "C-y" => scroll_up,
+            "C-e" => scroll_down,
 
             "C-w" => { "Window"
                 "C-w" | "w" => rotate_view,
-                "C-h" | "h" => hsplit,
+                "C-s" | "s" => hsplit,
                 "C-v" | "v" => vsplit,
                 "C-q" | "q" => wclose,
+                "C-o" | "o" => wonly,
+                "C-h" | "h" | "left" => jump_view_left,
+                "C-j" | "j" | "down" => jump_view_down,
+                "C-k" | "k" | "up" => jump_view_up,
+                "C-l" | "l" | "right" => jump_view_right,
+                "H" => swap_view_left,
+                "J" => swap_view_down,
+                "K" => swap_view_up,
+                "L" => swap_view_right,
+                "C-r" | "r" => rotate_splits,
+                ">" => grow_split_width,
+                "<" => shrink_split_width,
+                "+" => grow_split_height,
+                "minus" => shrink_split_height,
+                "=" => equalize_splits,
             },
 
             // move under <space>c
@@ -451,19 +561,50 @@ impl Default for Keymaps {
             // supposedly "C-i" but did not work
             "tab" => jump_forward,
             "C-o" => jump_backward,
-            // "C-s" => save_selection,
+            "C-s" => save_selection,
 
             "space" => { "Space"
                 "f" => file_picker,
                 "b" => buffer_picker,
                 "s" => symbol_picker,
+                "t" => tag_picker,
                 "a" => code_action,
+                "z" => spelling_suggestions,
                 "'" => last_picker,
+                "\"" => register_picker,
+                "?" => command_palette,
+                "d" => { "Debug"
+                    "l" => dap_launch,
+                    "b" => dap_toggle_breakpoint,
+                    "c" => dap_continue,
+                    "h" => dap_pause,
+                    "n" => dap_next,
+                    "i" => dap_step_in,
+                    "o" => dap_step_out,
+                    "s" => dap_stack_trace,
+                    "v" => dap_variables,
+                    "t" => dap_terminate,
+                },
                 "w" => { "Window"
                     "C-w" | "w" => rotate_view,
-                    "C-h" | "h" => hsplit,
+                    "C-s" | "s" => hsplit,
                     "C-v" | "v" => vsplit,
                     "C-q" | "q" => wclose,
+                    "C-o" | "o" => wonly,
+                    "C-h" | "h" | "left" => jump_view_left,
+                    "C-j" | "j" | "down" => jump_view_down,
+                    "C-k" | "k" | "up" => jump_view_up,
+                    "C-l" | "l" | "right" => jump_view_right,
+                    "H" => swap_view_left,
+                    "J" => swap_view_down,
+                    "K" => swap_view_up,
+                    "L" => swap_view_right,
+                    "C-r" | "r" => rotate_splits,
+                    ">" => grow_split_width,
+                    "<" => shrink_split_width,
+                    "+" => grow_split_height,
+                    "minus" => shrink_split_height,
+                    "=" => equalize_splits,
                 },
                 "y" => yank_joined_to_clipboard,
                 "Y" => yank_main_selection_to_clipboard,
@@ -479,24 +620,42 @@ impl Default for Keymaps {
                 "m" => align_view_middle,
                 "k" => scroll_up,
                 "j" => scroll_down,
+                "a" => fold_toggle,
+                "M" => fold_all,
+                "R" => unfold_all,
             },
 
             "\"" => select_register,
+            "|" => shell_pipe,
+            "!" => shell_insert_output,
+            "A-!" => shell_append_output,
+            "$" => shell_keep_pipe,
         });
+        // the sticky view mode has the keys of the view mode
+        let mut view = normal.search(&[key!('z')]).unwrap().clone();
+        view.node_mut().unwrap().is_sticky = true;
+        let root = normal.node_mut().unwrap();
+        root.insert(key!('Z'), view);
+        root.order.push(key!('Z'));
+
         // TODO: decide whether we want normal mode to also be select mode (kakoune-like), or whether
         // we keep this separate select mode. More keys can fit into normal mode then, but it's weird
         // because some selection operations can now be done from normal mode, some from select mode.
         let mut select = normal.clone();
         select.merge_nodes(keymap!({ "Select mode"
             "h" | "left" => extend_char_left,
-            "j" | "down" => extend_line_down,
-            "k" | "up" => extend_line_up,
+            "j" | "down" => extend_visual_line_down,
+            "k" | "up" => extend_visual_line_up,
             "l" | "right" => extend_char_right,
 
             "w" => extend_next_word_start,
             "b" => extend_prev_word_start,
             "e" => extend_next_word_end,
 
+            "W" => extend_next_long_word_start,
+            "B" => extend_prev_long_word_start,
+            "E" => extend_next_long_word_end,
+
             "t" => extend_till_char,
             "f" => extend_next_char,
             "T" => extend_till_prev_char,
@@ -504,7 +663,7 @@ impl Default for Keymaps {
 
             "home" => goto_line_start,
             "end" => goto_line_end,
-            "esc" => exit_select_mode,
+            "v" | "esc" => exit_select_mode,
         }));
         let insert = keymap!({ "Insert mode"
             "esc" => normal_mode,
@@ -514,10 +673,13 @@ impl Default for Keymaps {
             "ret" => insert_newline,
             "tab" => insert_tab,
             "C-w" => delete_word_backward,
+            "A-d" => delete_word_forward,
+            "C-u" => kill_to_line_start,
+            "C-k" => kill_to_line_end,
 
             "left" => move_char_left,
-            "down" => move_line_down,
-            "up" => move_line_up,
+            "down" => move_visual_line_down,
+            "up" => move_visual_line_up,
             "right" => move_char_right,
             "pageup" => page_up,
             "pagedown" => page_down,
@@ -525,6 +687,11 @@ impl Default for Keymaps {
             "end" => goto_line_end_newline,
 
             "C-x" => completion,
+            "C-l" => next_snippet_placeholder,
+            "C-r" => insert_register,
+            "C-v" => { "Insert special"
+                "u" => insert_unicode,
+            },
         });
         Keymaps(hashmap!(
             Mode::Normal => Keymap::new(normal),
@@ -534,6 +701,20 @@ impl Default for Keymaps {
     }
 }
 
+impl Keymaps {
+    /// Fails on the first bound typable command that's neither built in nor one of `plugins`.
+    pub fn check_commands(&self, plugins: &[PluginConfig]) -> anyhow::Result<()> {
+        fn check(trie: &KeyTrie, plugins: &[PluginConfig]) -> anyhow::Result<()> {
+            match trie {
+                KeyTrie::Leaf(command) => command.check(plugins),
+                KeyTrie::Node(node) => node.values().try_for_each(|trie| check(trie, plugins)),
+            }
+        }
+        self.values()
+            .try_for_each(|keymap| check(keymap.root(), plugins))
+    }
+}
+
 /// Merge default config keys with user overwritten keys for custom user config.
 pub fn merge_keys(mut config: Config) -> Config {
     let mut delta = std::mem::take(&mut config.keys);
@@ -603,3 +784,119 @@ fn merge_partial_keys() {
     assert!(merged_config.keys.0.get(&Mode::Normal).unwrap().len() > 1);
     assert!(merged_config.keys.0.get(&Mode::Insert).unwrap().len() > 0);
 }
+
+#[test]
+fn sticky_node_stays_active() {
+    let mut keymap = Keymap::new(keymap!({ "Normal mode"
+        "Z" => { "View" sticky=true
+            "j" => scroll_down,
+        },
+        "j" => move_line_down,
+    }));
+
+    assert!(matches!(keymap.get(key!('Z')), KeymapResult::Pending(_)));
+    assert_eq!(
+        keymap.get(key!('j')),
+        KeymapResult::Matched(Command::scroll_down)
+    );
+    assert_eq!(
+        keymap.get(key!('j')),
+        KeymapResult::Matched(Command::scroll_down),
+        "Sticky node should remain active after a command"
+    );
+    keymap.get(key!(Esc));
+    assert_eq!(
+        keymap.get(key!('j')),
+        KeymapResult::Matched(Command::move_line_down),
+        "Escape should leave the sticky node"
+    );
+}
+
+#[test]
+fn typable_command_bindings() {
+    use std::str::FromStr;
+
+    let keys: Keymaps = toml::from_str(
+        r#"
+            [normal]
+            "C-s" = ":write --force"
+            "C-u" = ":upcase"
+        "#,
+    )
+    .unwrap();
+    let keymap = &keys.0[&Mode::Normal];
+
+    match keymap.root().search(&[KeyEvent::from_str("C-s").unwrap()]) {
+        Some(KeyTrie::Leaf(Command::Typable { name, args, doc })) => {
+            assert_eq!(name, "write");
+            assert_eq!(args, &["--force"]);
+            assert_eq!(
+                doc,
+                "Write changes to disk. Accepts an optional path (:write some/path.txt)"
+            );
+        }
+        trie => panic!("expected a typable command, got {:?}", trie),
+    }
+    // plugins are resolved when the command runs
+    assert!(matches!(
+        keymap.root().search(&[KeyEvent::from_str("C-u").unwrap()]),
+        Some(KeyTrie::Leaf(Command::Typable { name, .. })) if name == "upcase"
+    ));
+
+    // and checked once the config is loaded
+    let err = keys.check_commands(&[]).unwrap_err();
+    assert_eq!(err.to_string(), "No typable command named 'upcase'");
+    let plugins: Vec<PluginConfig> = toml::from_str::<helix_view::editor::Config>(
+        r#"
+            [[plugins]]
+            name = "upcase"
+            command = "tr"
+        "#,
+    )
+    .unwrap()
+    .plugins;
+    keys.check_commands(&plugins).unwrap();
+
+    let typo: Keymaps = toml::from_str(
+        r#"
+            [normal.space]
+            "w" = ":wrte"
+        "#,
+    )
+    .unwrap();
+    let err = typo.check_commands(&plugins).unwrap_err();
+    assert_eq!(err.to_string(), "No typable command named 'wrte'");
+}
+
+#[test]
+fn sticky_view_mode() {
+    let keys = Keymaps::default();
+    for mode in [Mode::Normal, Mode::Select] {
+        let root = keys.0[&mode].root();
+        let view = root.search(&[key!('z')]).and_then(KeyTrie::node).unwrap();
+        let sticky = root.search(&[key!('Z')]).and_then(KeyTrie::node).unwrap();
+        assert!(!view.is_sticky);
+        assert!(sticky.is_sticky);
+        assert_eq!(view, sticky);
+    }
+}
+
+#[test]
+fn default_editing_bindings() {
+    let keys = Keymaps::default();
+    for mode in [Mode::Normal, Mode::Select] {
+        let keymap = &keys.0[&mode];
+        assert_eq!(
+            keymap.root().search(&[key!('g'), key!('q')]),
+            Some(&KeyTrie::Leaf(Command::reflow))
+        );
+        assert_eq!(
+            keymap.root().search(&[key!('=')]),
+            Some(&KeyTrie::Leaf(Command::indent_selection))
+        );
+        assert_eq!(
+            keymap.root().search(&["A-=".parse().unwrap()]),
+            Some(&KeyTrie::Leaf(Command::format_selections))
+        );
+    }
+}
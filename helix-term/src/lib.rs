@@ -6,6 +6,8 @@ pub mod args;
 pub mod commands;
 pub mod compositor;
 pub mod config;
+pub mod health;
 pub mod job;
 pub mod keymap;
+pub mod plugin;
 pub mod ui;
@@ -40,7 +40,7 @@ async fn main() -> Result<()> {
         std::fs::create_dir_all(&cache_dir).ok();
     }
 
-    let logpath = cache_dir.join("helix.log");
+    let logpath = helix_core::log_file();
     let help = format!(
         "\
 {} {}
@@ -51,10 +51,13 @@ USAGE:
     hx [FLAGS] [files]...
 
 ARGS:
-    <files>...    Sets the input file to use
+    <files>...    Sets the input file to use, placing the cursor at
+                  `file:line:column` or `file:line`, or on line N with `+N file`
+                  Pass `-` to edit what's piped to hx
 
 FLAGS:
     -h, --help       Prints help information
+    --tutor          Loads the tutorial
     -v               Increases logging verbosity each use for up to 3 times
                      (default file: {})
     -V, --version    Prints version information
@@ -85,7 +88,11 @@ FLAGS:
     }
 
     let config = match std::fs::read_to_string(conf_dir.join("config.toml")) {
-        Ok(config) => merge_keys(toml::from_str(&config)?),
+        Ok(config) => {
+            let config = merge_keys(toml::from_str(&config)?);
+            config.keys.check_commands(&config.editor.plugins)?;
+            config
+        }
         Err(err) if err.kind() == std::io::ErrorKind::NotFound => Config::default(),
         Err(err) => return Err(Error::new(err)),
     };
@@ -0,0 +1,405 @@
+//! Plugins: external programs configured under `[editor.plugins]`, run as typable commands or on
+//! events. A plugin is given the document as JSON on stdin and may answer with JSON on stdout,
+//! see `book/src/plugins.md` for the format.
+
+use anyhow::{bail, Context as _};
+use helix_core::{Range, Selection, Transaction};
+use helix_view::{
+    editor::{PluginConfig, PluginEvent},
+    events::Event,
+    Document, Editor, ViewId,
+};
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// Version of the JSON plugins are given and answer with, bumped on incompatible changes.
+pub const API_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct SelectionRange {
+    anchor: usize,
+    head: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct Request<'a> {
+    version: u32,
+    /// `command` when run as a typable command, or the name of the event.
+    event: &'a str,
+    args: &'a [String],
+    path: Option<&'a Path>,
+    language: Option<&'a str>,
+    text: String,
+    selections: Vec<SelectionRange>,
+    primary: usize,
+}
+
+/// A replacement of the chars `from..to` by `text`.
+#[derive(Debug, Deserialize)]
+struct Edit {
+    from: usize,
+    to: usize,
+    text: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Response {
+    edits: Vec<Edit>,
+    selections: Option<Vec<SelectionRange>>,
+    primary: usize,
+    status: Option<String>,
+}
+
+/// Runs `plugin` on the document and applies the edits and selections it answers with. Returns
+/// its status message, if any.
+fn run(
+    plugin: &PluginConfig,
+    doc: &mut Document,
+    view_id: ViewId,
+    event: &str,
+    args: &[String],
+) -> anyhow::Result<Option<String>> {
+    let selection = doc.selection(view_id);
+    let request = Request {
+        version: API_VERSION,
+        event,
+        args,
+        path: doc.path().map(|path| path.as_path()),
+        language: doc.language_config().map(|config| config.language_id()),
+        text: doc.text().to_string(),
+        selections: selection
+            .iter()
+            .map(|range| SelectionRange {
+                anchor: range.anchor,
+                head: range.head,
+            })
+            .collect(),
+        primary: selection.primary_index(),
+    };
+    let input = serde_json::to_vec(&request)?;
+
+    let mut process = Command::new(&plugin.command)
+        .args(&plugin.args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("failed to spawn `{}`", plugin.command))?;
+
+    // write and read from separate threads so a plugin answering before reading everything can't
+    // deadlock us
+    let mut stdin = process.stdin.take().unwrap();
+    let writer = std::thread::spawn(move || stdin.write_all(&input));
+    let read_all = |mut pipe: Box<dyn Read + Send>| {
+        std::thread::spawn(move || {
+            let mut output = Vec::new();
+            let _ = pipe.read_to_end(&mut output);
+            output
+        })
+    };
+    let stdout = read_all(Box::new(process.stdout.take().unwrap()));
+    let stderr = read_all(Box::new(process.stderr.take().unwrap()));
+
+    let deadline = Instant::now() + Duration::from_secs(plugin.timeout);
+    let status = loop {
+        if let Some(status) = process.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            // the pipes close with the process, ending the threads
+            let _ = process.kill();
+            let _ = process.wait();
+            bail!(
+                "`{}` timed out after {} seconds",
+                plugin.command,
+                plugin.timeout
+            );
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    };
+    let _ = writer.join();
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+
+    if !stderr.is_empty() {
+        log::error!(
+            "plugin `{}`: {}",
+            plugin.command,
+            String::from_utf8_lossy(&stderr)
+        );
+    }
+    if !status.success() {
+        bail!("`{}` failed: {}", plugin.command, status);
+    }
+
+    let stdout = String::from_utf8_lossy(&stdout);
+    if stdout.trim().is_empty() {
+        return Ok(None);
+    }
+    let response: Response = serde_json::from_str(&stdout)
+        .with_context(|| format!("invalid answer from `{}`", plugin.command))?;
+    apply(doc, view_id, &response)?;
+    Ok(response.status)
+}
+
+fn apply(doc: &mut Document, view_id: ViewId, response: &Response) -> anyhow::Result<()> {
+    let len = doc.text().len_chars();
+    let mut end = 0;
+    for edit in &response.edits {
+        if edit.from < end || edit.to < edit.from || edit.to > len {
+            bail!("plugin edits must be ordered, not overlap and be within the document");
+        }
+        end = edit.to;
+    }
+
+    // check the selections against the text after the edits, before making any of them
+    let selection = match &response.selections {
+        Some(selections) => {
+            let len = response.edits.iter().fold(len, |len, edit| {
+                len - (edit.to - edit.from) + edit.text.chars().count()
+            });
+            // a range may end at the end of the text, but a cursor can't be past the last char
+            let within = |range: &SelectionRange| {
+                let (from, to) = (range.anchor.min(range.head), range.anchor.max(range.head));
+                to <= len && (from < to || from < len || len == 0)
+            };
+            if selections.is_empty()
+                || response.primary >= selections.len()
+                || !selections.iter().all(within)
+            {
+                bail!("plugin selections must be within the document and have a primary one");
+            }
+            let ranges = selections
+                .iter()
+                .map(|range| Range::new(range.anchor, range.head))
+                .collect();
+            Some(Selection::new(ranges, response.primary))
+        }
+        None => None,
+    };
+
+    if !response.edits.is_empty() {
+        let transaction = Transaction::change(
+            doc.text(),
+            response
+                .edits
+                .iter()
+                .map(|edit| (edit.from, edit.to, Some(edit.text.as_str().into()))),
+        );
+        doc.apply(&transaction, view_id);
+        doc.append_changes_to_history(view_id);
+    }
+    if let Some(selection) = selection {
+        doc.set_selection(view_id, selection);
+    }
+    Ok(())
+}
+
+/// The plugin run as the typable command `name`.
+pub fn find<'a>(plugins: &'a [PluginConfig], name: &str) -> Option<&'a PluginConfig> {
+    plugins.iter().find(|plugin| plugin.name == name)
+}
+
+/// Runs the plugin named `name` on the current document, as a typable command.
+pub fn run_command(editor: &mut Editor, name: &str, args: &[String]) -> anyhow::Result<()> {
+    let plugin = match find(&editor.config.plugins, name) {
+        Some(plugin) => plugin.clone(),
+        None => bail!("no such command: '{}'", name),
+    };
+    let (view, doc) = current!(editor);
+    if let Some(status) = run(&plugin, doc, view.id, "command", args)? {
+        editor.set_status(status);
+    }
+    Ok(())
+}
+
+/// Runs the plugins hooked on the events they're configured for, in the order they're configured.
+pub fn hook(event: &mut Event) -> anyhow::Result<()> {
+    if let Event::DocumentWillSave {
+        doc,
+        view_id,
+        config,
+    } = event
+    {
+        for plugin in config
+            .plugins
+            .iter()
+            .filter(|plugin| plugin.events.contains(&PluginEvent::Save))
+        {
+            run(plugin, doc, *view_id, "save", &[])?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use helix_core::Rope;
+
+    fn document(text: &str) -> (Document, ViewId) {
+        let mut doc = Document::from(Rope::from(text), None);
+        let view_id = ViewId::default();
+        doc.set_selection(view_id, Selection::point(0));
+        (doc, view_id)
+    }
+
+    /// A plugin answering with `answer` after reading the request.
+    fn plugin(name: &str, answer: &str) -> PluginConfig {
+        PluginConfig {
+            name: name.to_owned(),
+            command: "sh".to_owned(),
+            args: vec![
+                "-c".to_owned(),
+                format!("cat >/dev/null; printf '%s' '{}'", answer),
+            ],
+            doc: String::new(),
+            events: vec![PluginEvent::Save],
+            timeout: 5,
+        }
+    }
+
+    fn selections(ranges: &[(usize, usize)]) -> Option<Vec<SelectionRange>> {
+        Some(
+            ranges
+                .iter()
+                .map(|&(anchor, head)| SelectionRange { anchor, head })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn apply_edits() {
+        let (mut doc, view_id) = document("hello world\n");
+        let edit = |from, to, text: &str| Edit {
+            from,
+            to,
+            text: text.to_owned(),
+        };
+        let response = Response {
+            edits: vec![edit(0, 5, "HELLO"), edit(6, 11, "there")],
+            ..Default::default()
+        };
+        apply(&mut doc, view_id, &response).unwrap();
+        assert_eq!(doc.text().to_string(), "HELLO there\n");
+
+        for edits in [
+            vec![edit(6, 11, ""), edit(0, 5, "")],
+            vec![edit(0, 6, ""), edit(5, 8, "")],
+            vec![edit(5, 13, "")],
+        ] {
+            let response = Response {
+                edits,
+                ..Default::default()
+            };
+            assert!(apply(&mut doc, view_id, &response).is_err());
+        }
+        assert_eq!(doc.text().to_string(), "HELLO there\n");
+    }
+
+    #[test]
+    fn apply_selections() {
+        let (mut doc, view_id) = document("abc\n");
+        let response = |ranges: &[(usize, usize)], primary| Response {
+            selections: selections(ranges),
+            primary,
+            ..Default::default()
+        };
+
+        // the whole text
+        apply(&mut doc, view_id, &response(&[(4, 0)], 0)).unwrap();
+        assert_eq!(doc.selection(view_id).primary(), Range::new(4, 0));
+        apply(&mut doc, view_id, &response(&[(0, 1), (2, 2)], 1)).unwrap();
+        let selection = doc.selection(view_id);
+        assert_eq!(selection.primary(), Range::new(2, 2));
+        assert_eq!(selection.ranges()[0], Range::new(0, 1));
+
+        // a cursor past the last char, a range past the end, no primary or no selections
+        for (ranges, primary) in [(&[(4, 4)][..], 0), (&[(2, 5)], 0), (&[(0, 1)], 1), (&[], 0)] {
+            assert!(apply(&mut doc, view_id, &response(ranges, primary)).is_err());
+        }
+        assert_eq!(doc.selection(view_id).primary(), Range::new(2, 2));
+
+        // the only position of an empty text
+        let (mut doc, view_id) = document("");
+        apply(&mut doc, view_id, &response(&[(0, 0)], 0)).unwrap();
+    }
+
+    #[test]
+    fn apply_selections_after_edits() {
+        let (mut doc, view_id) = document("abc\n");
+        let response = |text: &str, ranges: &[(usize, usize)]| Response {
+            edits: vec![Edit {
+                from: 0,
+                to: 3,
+                text: text.to_owned(),
+            }],
+            selections: selections(ranges),
+            ..Default::default()
+        };
+
+        // the selections are within the text once it's shorter, so nothing is changed
+        assert!(apply(&mut doc, view_id, &response("a", &[(0, 3)])).is_err());
+        assert_eq!(doc.text().to_string(), "abc\n");
+        assert!(!doc.is_modified());
+
+        // or longer
+        apply(&mut doc, view_id, &response("abcdef", &[(0, 7)])).unwrap();
+        assert_eq!(doc.text().to_string(), "abcdef\n");
+        assert_eq!(doc.selection(view_id).primary(), Range::new(0, 7));
+    }
+
+    #[test]
+    fn run_plugin() {
+        let (mut doc, view_id) = document("hello\n");
+        let plugin = plugin(
+            "upcase",
+            r#"{"edits": [{"from": 0, "to": 5, "text": "HELLO"}], "status": "done"}"#,
+        );
+        let status = run(&plugin, &mut doc, view_id, "command", &[]).unwrap();
+        assert_eq!(status.as_deref(), Some("done"));
+        assert_eq!(doc.text().to_string(), "HELLO\n");
+
+        let mut failing = plugin.clone();
+        failing.args = vec!["-c".to_owned(), "exit 1".to_owned()];
+        assert!(run(&failing, &mut doc, view_id, "command", &[]).is_err());
+    }
+
+    #[test]
+    fn run_plugin_timeout() {
+        let (mut doc, view_id) = document("hello\n");
+        let mut plugin = plugin("slow", "");
+        plugin.args = vec!["-c".to_owned(), "exec sleep 10".to_owned()];
+        plugin.timeout = 1;
+
+        let start = Instant::now();
+        let err = run(&plugin, &mut doc, view_id, "command", &[]).unwrap_err();
+        assert!(err.to_string().contains("timed out"));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn hooks_run_in_order() {
+        let (mut doc, view_id) = document("x\n");
+        let mut config = helix_view::editor::Config::default();
+        // each plugin inserts at the start, so the last one run comes first
+        for name in ["first", "second", "third"] {
+            let answer = format!(
+                r#"{{"edits": [{{"from": 0, "to": 0, "text": "{}"}}]}}"#,
+                &name[..1]
+            );
+            config.plugins.push(plugin(name, &answer));
+        }
+        hook(&mut Event::DocumentWillSave {
+            doc: &mut doc,
+            view_id,
+            config: &config,
+        })
+        .unwrap();
+        assert_eq!(doc.text().to_string(), "tsfx\n");
+    }
+}
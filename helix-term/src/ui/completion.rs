@@ -4,7 +4,7 @@ use tui::buffer::Buffer as Surface;
 
 use std::borrow::Cow;
 
-use helix_core::Transaction;
+use helix_core::{snippet, Transaction};
 use helix_view::{graphics::Rect, Editor};
 
 use crate::commands;
@@ -95,13 +95,24 @@ impl Completion {
                         doc.apply(&remove, view.id);
                     }
 
+                    let is_snippet =
+                        item.insert_text_format == Some(lsp::InsertTextFormat::Snippet);
+                    let mut snippet = None;
+
                     let transaction = if let Some(edit) = &item.text_edit {
-                        let edit = match edit {
+                        let mut edit = match edit {
                             lsp::CompletionTextEdit::Edit(edit) => edit.clone(),
                             lsp::CompletionTextEdit::InsertAndReplace(item) => {
                                 unimplemented!("completion: insert_and_replace {:?}", item)
                             }
                         };
+                        if is_snippet {
+                            let start =
+                                util::lsp_pos_to_pos(doc.text(), edit.range.start, offset_encoding);
+                            let parsed = snippet::parse(&edit.new_text);
+                            edit.new_text = parsed.text.clone();
+                            snippet = start.map(|start| (start, parsed));
+                        }
                         util::generate_transaction_from_edits(
                             doc.text(),
                             vec![edit],
@@ -110,14 +121,31 @@ impl Completion {
                     } else {
                         let text = item.insert_text.as_ref().unwrap_or(&item.label);
                         let cursor = doc.selection(view.id).cursor();
+                        let text = if is_snippet {
+                            let parsed = snippet::parse(text);
+                            let text = parsed.text.clone();
+                            snippet = Some((cursor, parsed));
+                            text
+                        } else {
+                            text.clone()
+                        };
                         Transaction::change(
                             doc.text(),
-                            vec![(cursor, cursor, Some(text.as_str().into()))].into_iter(),
+                            vec![(cursor, cursor, Some(text.into()))].into_iter(),
                         )
                     };
 
                     doc.apply(&transaction, view.id);
 
+                    // select the first tabstop, the others are visited with
+                    // next_snippet_placeholder
+                    if let Some((start, snippet)) = snippet {
+                        let mut tabstops = snippet.selections(start);
+                        let first = tabstops.remove(0);
+                        doc.set_selection(view.id, first);
+                        doc.set_snippet_tabstops(tabstops);
+                    }
+
                     if let Some(additional_edits) = &item.additional_text_edits {
                         // gopls uses this to add extra imports
                         if !additional_edits.is_empty() {
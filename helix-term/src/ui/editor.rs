@@ -1,40 +1,59 @@
 use crate::{
     commands,
     compositor::{Component, Context, EventResult},
-    key,
+    job, key,
     keymap::{KeymapResult, Keymaps},
     ui::{Completion, ProgressSpinners},
 };
 
 use helix_core::{
+    chars::char_is_line_ending,
     coords_at_pos,
+    diagnostic::Severity,
+    fold,
     graphemes::{ensure_grapheme_boundary, next_grapheme_boundary},
+    line_ending::line_end_char_index,
+    movement::Direction,
+    regex::Regex,
+    search,
     syntax::{self, HighlightEvent},
-    LineEnding, Position, Range,
+    unicode::width::{UnicodeWidthChar, UnicodeWidthStr},
+    visual_coords_at_pos,
+    wrap::SoftWrap,
+    LineEnding, Position, Selection,
 };
 use helix_view::{
+    current,
     document::Mode,
-    graphics::{CursorKind, Modifier, Rect, Style},
+    editor::{Action, IndentGuidesConfig},
+    graphics::{Color, CursorKind, Modifier, Rect, Style},
     info::Info,
     input::KeyEvent,
     keyboard::{KeyCode, KeyModifiers},
-    Document, Editor, Theme, View,
+    tree::{self, Layout},
+    view, Document, DocumentId, Editor, Theme, View, ViewId,
 };
 use std::borrow::Cow;
+use std::time::{Duration, Instant};
 
-use crossterm::event::Event;
+use crossterm::event::{Event, MouseButton, MouseEvent, MouseEventKind};
 use tui::buffer::Buffer as Surface;
 
 pub struct EditorView {
     keymaps: Keymaps,
-    on_next_key: Option<Box<dyn FnOnce(&mut commands::Context, KeyEvent)>>,
+    on_next_key: Option<commands::OnKeyCallback>,
     last_insert: (commands::Command, Vec<KeyEvent>),
     completion: Option<Completion>,
     spinners: ProgressSpinners,
     pub autoinfo: Option<Info>,
+    /// When the pending keys were pressed, `autoinfo` is only shown once `auto-info-delay` passed.
+    autoinfo_since: Option<Instant>,
+    /// The view whose border is being dragged with the mouse, and the layout it resizes.
+    resizing: Option<(ViewId, Layout)>,
 }
 
-const OFFSET: u16 = 7; // 1 diagnostic + 5 linenr + 1 gutter
+/// Number of lines scrolled per mouse wheel step.
+const MOUSE_SCROLL_LINES: usize = 3;
 
 impl Default for EditorView {
     fn default() -> Self {
@@ -51,6 +70,8 @@ impl EditorView {
             completion: None,
             spinners: ProgressSpinners::default(),
             autoinfo: None,
+            autoinfo_since: None,
+            resizing: None,
         }
     }
 
@@ -58,6 +79,16 @@ impl EditorView {
         &mut self.spinners
     }
 
+    pub fn keymaps(&self) -> &Keymaps {
+        &self.keymaps
+    }
+
+    /// Sets the callback that consumes the next key press, for commands run from outside the
+    /// editor view.
+    pub fn set_on_next_key(&mut self, callback: commands::OnKeyCallback) {
+        self.on_next_key = Some(callback);
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub fn render_view(
         &self,
@@ -68,15 +99,29 @@ impl EditorView {
         theme: &Theme,
         is_focused: bool,
         loader: &syntax::Loader,
+        config: &helix_view::editor::Config,
+        search: Option<&Regex>,
     ) {
         let area = Rect::new(
-            view.area.x + OFFSET,
+            view.area.x + view.gutter_offset(),
             view.area.y,
-            view.area.width - OFFSET,
+            view.area.width.saturating_sub(view.gutter_offset()),
             view.area.height.saturating_sub(1),
         ); // - 1 for statusline
 
-        self.render_buffer(doc, view, area, surface, theme, is_focused, loader);
+        Self::render_buffer(
+            doc, view, area, surface, theme, is_focused, loader, config, search,
+        );
+        if config.inline_diagnostics.enable {
+            Self::render_inline_diagnostics(
+                doc,
+                view,
+                area,
+                surface,
+                theme,
+                config.inline_diagnostics.min_severity,
+            );
+        }
 
         // if we're not at the edge of the screen, draw a right border
         if viewport.right() != view.area.right() {
@@ -104,7 +149,6 @@ impl EditorView {
 
     #[allow(clippy::too_many_arguments)]
     pub fn render_buffer(
-        &self,
         doc: &Document,
         view: &View,
         viewport: Rect,
@@ -112,6 +156,8 @@ impl EditorView {
         theme: &Theme,
         is_focused: bool,
         loader: &syntax::Loader,
+        config: &helix_view::editor::Config,
+        search: Option<&Regex>,
     ) {
         let text = doc.text().slice(..);
 
@@ -124,36 +170,36 @@ impl EditorView {
 
             start..end
         };
-
-        // TODO: range doesn't actually restrict source, just highlight range
-        let highlights: Vec<_> = match doc.syntax() {
-            Some(syntax) => {
-                let scopes = theme.scopes();
-                syntax
-                    .highlight_iter(text.slice(..), Some(range), None, |language| {
-                        loader
-                                .language_config_for_scope(&format!("source.{}", language))
-                                .and_then(|language_config| {
-                                    let config = language_config.highlight_config(scopes)?;
-                                    let config_ref = config.as_ref();
-                                    // SAFETY: the referenced `HighlightConfiguration` behind
-                                    // the `Arc` is guaranteed to remain valid throughout the
-                                    // duration of the highlight.
-                                    let config_ref = unsafe {
-                                        std::mem::transmute::<
-                                            _,
-                                            &'static syntax::HighlightConfiguration,
-                                        >(config_ref)
-                                    };
-                                    Some(config_ref)
-                                })
-                    })
-                    .collect() // TODO: we collect here to avoid holding the lock, fix later
+        // the parts of the range not hidden by folds, the only ones highlighted and drawn
+        let segments: Vec<_> = fold::shown_lines(doc.folds(view.id), view.first_line, last_line)
+            .into_iter()
+            .map(|lines| text.line_to_byte(lines.start)..text.line_to_byte(lines.end))
+            .collect();
+
+        let unhighlighted = |segment: &std::ops::Range<usize>| {
+            vec![HighlightEvent::Source {
+                start: segment.start,
+                end: segment.end,
+            }]
+        };
+        let highlights: Vec<_> = match (doc.syntax(), doc.highlights(view.id)) {
+            // computed in the background
+            (Some(_), Some(highlights))
+                if highlights.range.start <= range.start && range.end <= highlights.range.end =>
+            {
+                clip_highlights(&highlights.events, &segments, text.len_bytes())
             }
-            None => vec![Ok(HighlightEvent::Source {
-                start: range.start,
-                end: range.end,
-            })],
+            // nothing was highlighted in the background for this part and version yet, like in
+            // previews or right after an edit
+            (Some(syntax), _) => segments
+                .iter()
+                .flat_map(|segment| {
+                    syntax
+                        .highlights(text, segment.clone(), None, loader, theme.scopes())
+                        .unwrap_or_else(|_| unhighlighted(segment))
+                })
+                .collect(),
+            (None, _) => segments.iter().flat_map(unhighlighted).collect(),
         };
         let mut spans = Vec::new();
         let mut visual_x = 0u16;
@@ -161,7 +207,45 @@ impl EditorView {
         let tab_width = doc.tab_width();
         let tab = " ".repeat(tab_width);
 
-        let highlights = highlights.into_iter().map(|event| match event.unwrap() {
+        let whitespace = &config.whitespace;
+        let whitespace_style = theme
+            .try_get("ui.whitespace")
+            .unwrap_or_else(|| Style::default().add_modifier(Modifier::DIM));
+        let visible_tab = format!(
+            "{}{}",
+            whitespace.characters.tab,
+            " ".repeat(tab_width.saturating_sub(1))
+        );
+        // tabs are drawn up to the next tab stop, as a prefix of these
+        let tab_prefix = |tab: &str, width: usize| -> usize {
+            tab.char_indices().nth(width).map_or(tab.len(), |(i, _)| i)
+        };
+        let visible_space = whitespace.characters.space.to_string();
+        let visible_nbsp = whitespace.characters.nbsp.to_string();
+
+        // start of the trailing whitespace of each line on screen
+        let trailing_whitespace: Vec<usize> = if whitespace.render {
+            (view.first_line..=last_line)
+                .map(|line| {
+                    let line_start = text.line_to_char(line);
+                    let mut pos = line_end_char_index(&text, line);
+                    while pos > line_start && text.char(pos - 1).is_whitespace() {
+                        pos -= 1;
+                    }
+                    pos
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let is_trailing_whitespace = |pos| {
+            matches!(
+                trailing_whitespace.get(text.char_to_line(pos) - view.first_line),
+                Some(&start) if pos >= start
+            )
+        };
+
+        let highlights = highlights.into_iter().map(|event| match event {
             // convert byte offsets to char offset
             HighlightEvent::Source { start, end } => {
                 let start = ensure_grapheme_boundary(text, text.byte_to_char(start));
@@ -171,6 +255,24 @@ impl EditorView {
             event => event,
         });
 
+        // char positions at which soft wrapped lines continue on the next row
+        let mut wrap_points = Vec::new();
+        if view.soft_wrap != SoftWrap::None {
+            for line in view.first_line..=last_line {
+                if view.line_height(doc, line) == 0 {
+                    continue;
+                }
+                let line_start = text.line_to_char(line);
+                wrap_points.extend(
+                    view.line_breaks(doc, line)
+                        .into_iter()
+                        .skip(1)
+                        .map(|offset| line_start + offset),
+                );
+            }
+        }
+        let mut wrap_points = wrap_points.into_iter().peekable();
+
         let selections = doc.selection(view.id);
         let primary_idx = selections.primary_index();
 
@@ -204,6 +306,10 @@ impl EditorView {
                 .find_scope_index("ui.cursor.primary")
                 .unwrap_or(cursor_scope);
 
+            // the terminal cursor is shown in place of the primary cursor unless it's a block
+            let draw_primary_cursor =
+                config.cursor_shape.from_mode(doc.mode()) == CursorKind::Block;
+
             for (i, range) in selections.iter().enumerate() {
                 let (cursor_scope, selection_scope) = if i == primary_idx {
                     (primary_cursor_scope, primary_selection_scope)
@@ -211,6 +317,15 @@ impl EditorView {
                     (cursor_scope, selection_scope)
                 };
 
+                let cursor_scope = if i == primary_idx && !draw_primary_cursor {
+                    if range.head == range.anchor {
+                        continue;
+                    }
+                    selection_scope
+                } else {
+                    cursor_scope
+                };
+
                 let cursor_end = next_grapheme_boundary(text, range.head); // Used in every case below.
 
                 if range.head == range.anchor {
@@ -252,6 +367,106 @@ impl EditorView {
                 .collect(),
         ));
 
+        // misspelling injection
+        let spelling_scope = theme
+            .find_scope_index("spelling")
+            .unwrap_or(diagnostic_scope);
+        let highlights = Box::new(syntax::merge(
+            highlights,
+            doc.misspellings()
+                .iter()
+                .map(|range| (spelling_scope, range.clone()))
+                .collect(),
+        ));
+
+        // match injection
+        let match_scope = theme
+            .find_scope_index("ui.search.match")
+            .unwrap_or(selection_scope);
+        let highlights = Box::new(syntax::merge(
+            highlights,
+            view.match_highlights
+                .iter()
+                .map(|range| (match_scope, range.clone()))
+                .collect(),
+        ));
+
+        // symbol occurrences injection
+        let highlights: Box<dyn Iterator<Item = HighlightEvent>> =
+            match theme.find_scope_index("ui.highlight.symbol") {
+                Some(symbol_scope) => Box::new(syntax::merge(
+                    highlights,
+                    view.symbol_highlights(doc)
+                        .iter()
+                        .flat_map(|highlights| &highlights.ranges)
+                        .map(|range| (symbol_scope, range.clone()))
+                        .collect(),
+                )),
+                None => highlights,
+            };
+
+        // search match injection, limited to the lines on screen
+        let search_matches = match search {
+            Some(regex) => {
+                let start = text.line_to_byte(view.first_line);
+                let end = text.line_to_byte((last_line + 1).min(text.len_lines()));
+                search::find_iter(regex, text, start..end)
+                    .filter(|mat| !mat.is_empty())
+                    .map(|mat| {
+                        let range = text.byte_to_char(mat.start)..text.byte_to_char(mat.end);
+                        (match_scope, range)
+                    })
+                    .collect()
+            }
+            None => Vec::new(),
+        };
+        let highlights = Box::new(syntax::merge(highlights, search_matches));
+
+        // render rulers first so that text and selections are drawn on top of them
+        let ruler_style = theme
+            .try_get("ui.ruler")
+            .unwrap_or_else(|| Style::default().bg(Color::DarkGray));
+        let rulers = doc
+            .language_config()
+            .and_then(|config| config.rulers.as_ref())
+            .unwrap_or(&config.rulers);
+
+        for ruler in rulers {
+            let col = (*ruler as usize).saturating_sub(1);
+            if col < view.first_col || col >= view.first_col + viewport.width as usize {
+                continue;
+            }
+            let x = viewport.x + (col - view.first_col) as u16;
+            for y in viewport.top()..viewport.bottom() {
+                surface.get_mut(x, y).set_style(ruler_style);
+            }
+        }
+
+        // the line and column of the primary cursor, under the text like the rulers
+        let cursor = doc.selection(view.id).cursor();
+        if config.cursorline {
+            let line = text.char_to_line(cursor);
+            let start = view.screen_coords_at_pos(doc, text, text.line_to_char(line));
+            if let Some(start) = start {
+                let height = (view.line_height(doc, line) as u16)
+                    .min(viewport.height.saturating_sub(start.row as u16));
+                let area = Rect::new(
+                    viewport.x,
+                    viewport.y + start.row as u16,
+                    viewport.width,
+                    height,
+                );
+                surface.set_style(area, theme.get("ui.cursorline"));
+            }
+        }
+        if config.cursorcolumn {
+            let pos = view.screen_coords_at_pos(doc, text, cursor);
+            if let Some(pos) = pos.filter(|pos| pos.col < viewport.width as usize) {
+                let area = Rect::new(viewport.x + pos.col as u16, viewport.y, 1, viewport.height);
+                surface.set_style(area, theme.get("ui.cursorcolumn"));
+            }
+        }
+
         'outer: for event in highlights {
             match event {
                 HighlightEvent::HighlightStart(span) => {
@@ -263,14 +478,28 @@ impl EditorView {
                 HighlightEvent::Source { start, end } => {
                     let text = text.slice(start..end);
 
-                    use helix_core::graphemes::{grapheme_width, RopeGraphemes};
+                    use helix_core::graphemes::{grapheme_width, grapheme_width_at, RopeGraphemes};
 
                     let style = spans.iter().fold(theme.get("ui.text"), |acc, span| {
                         let style = theme.get(theme.scopes()[span.0].as_str());
                         acc.patch(style)
                     });
 
+                    let mut char_pos = start;
+
                     for grapheme in RopeGraphemes::new(text) {
+                        if wrap_points.peek() == Some(&char_pos) {
+                            wrap_points.next();
+                            visual_x = 0;
+                            line += 1;
+
+                            if line >= viewport.height {
+                                break 'outer;
+                            }
+                        }
+                        let grapheme_pos = char_pos;
+                        char_pos += grapheme.len_chars();
+
                         let out_of_bounds = visual_x < view.first_col as u16
                             || visual_x >= viewport.width + view.first_col as u16;
 
@@ -295,24 +524,59 @@ impl EditorView {
                         } else {
                             let grapheme = Cow::from(grapheme);
 
-                            let (grapheme, width) = if grapheme == "\t" {
-                                // make sure we display tab as appropriate amount of spaces
-                                (tab.as_str(), tab_width)
-                            } else {
-                                // Cow will prevent allocations if span contained in a single slice
-                                // which should really be the majority case
-                                let width = grapheme_width(&grapheme);
-                                (grapheme.as_ref(), width)
+                            let (grapheme, width, visible_whitespace) = match grapheme.as_ref() {
+                                "\t" => {
+                                    let width =
+                                        grapheme_width_at("\t", visual_x as usize, tab_width);
+                                    // make sure we display tab as appropriate amount of spaces
+                                    let tab = if whitespace.render {
+                                        &visible_tab
+                                    } else {
+                                        &tab
+                                    };
+                                    let tab = &tab[..tab_prefix(tab, width)];
+                                    (tab, width, whitespace.render)
+                                }
+                                "\u{a0}" if whitespace.render => (visible_nbsp.as_str(), 1, true),
+                                " " if whitespace.render
+                                    && is_trailing_whitespace(grapheme_pos) =>
+                                {
+                                    (visible_space.as_str(), 1, true)
+                                }
+                                _ => {
+                                    // Cow will prevent allocations if span contained in a single slice
+                                    // which should really be the majority case
+                                    let width = grapheme_width(&grapheme);
+                                    (grapheme.as_ref(), width, false)
+                                }
                             };
 
-                            if !out_of_bounds {
-                                // if we're offscreen just keep going until we hit a new line
+                            let style = if visible_whitespace {
+                                style.patch(whitespace_style)
+                            } else {
+                                style
+                            };
+                            let first_col = view.first_col as u16;
+                            let last_col = first_col + viewport.width;
+                            let end_x = visual_x.saturating_add(width as u16);
+                            if visual_x >= first_col && end_x <= last_col {
                                 surface.set_string(
-                                    viewport.x + visual_x - view.first_col as u16,
+                                    viewport.x + visual_x - first_col,
                                     viewport.y + line,
                                     grapheme,
                                     style,
                                 );
+                            } else {
+                                // wide graphemes cut off by the edges of the view are drawn as
+                                // blanks, offscreen ones are skipped until we hit a new line
+                                for x in visual_x.max(first_col)..end_x.min(last_col) {
+                                    surface.set_string(
+                                        viewport.x + x - first_col,
+                                        viewport.y + line,
+                                        " ",
+                                        style,
+                                    );
+                                }
                             }
 
                             visual_x = visual_x.saturating_add(width as u16);
@@ -322,95 +586,264 @@ impl EditorView {
             }
         }
 
+        if config.indent_guides.render {
+            Self::render_indent_guides(doc, view, viewport, surface, theme, &config.indent_guides);
+        }
+
         // render gutters
 
-        let linenr: Style = theme.get("ui.linenr");
-        let warning: Style = theme.get("warning");
-        let error: Style = theme.get("error");
-        let info: Style = theme.get("info");
-        let hint: Style = theme.get("hint");
+        // lines having a cursor, only shown for the focused view
+        let selected_lines: Vec<usize> = if is_focused {
+            doc.selection(view.id)
+                .iter()
+                .map(|range| text.char_to_line(range.head))
+                .collect()
+        } else {
+            Vec::new()
+        };
 
-        for (i, line) in (view.first_line..last_line).enumerate() {
-            use helix_core::diagnostic::Severity;
-            if let Some(diagnostic) = doc.diagnostics().iter().find(|d| d.line == line) {
-                surface.set_stringn(
-                    viewport.x - OFFSET,
-                    viewport.y + i as u16,
-                    "●",
-                    1,
-                    match diagnostic.severity {
-                        Some(Severity::Error) => error,
-                        Some(Severity::Warning) | None => warning,
-                        Some(Severity::Info) => info,
-                        Some(Severity::Hint) => hint,
-                    },
-                );
+        let mut x = viewport.x - view.gutter_offset();
+        for gutter_type in &view.gutters {
+            let gutter = gutter_type.gutter();
+            let width = gutter.width();
+            let mut row = 0;
+            for line in view.first_line..last_line {
+                if row >= viewport.height {
+                    break;
+                }
+                let height = view.line_height(doc, line) as u16;
+                if height == 0 {
+                    // folded
+                    continue;
+                }
+                let selected = selected_lines.contains(&line);
+                if let Some((text, style)) =
+                    gutter.render_line(doc, view, theme, config, line, selected)
+                {
+                    surface.set_stringn(x, viewport.y + row, text, width as usize, style);
+                }
+                row += height;
             }
+            x += width;
+        }
 
-            // line numbers having selections are rendered differently
-            surface.set_stringn(
-                viewport.x + 1 - OFFSET,
-                viewport.y + i as u16,
-                format!("{:>5}", line + 1),
-                5,
-                linenr,
-            );
+        // render the bracket matching the one at or before the cursor
+        if is_focused {
+            use helix_core::match_brackets;
+            let cursor = doc.selection(view.id).cursor();
+            // TODO: set cursor position for IME
+            let pos = match_brackets::find_highlight(doc.syntax(), doc.text(), cursor)
+                // brackets scrolled out of view on the left aren't drawn
+                .filter(|&pos| {
+                    view.soft_wrap != SoftWrap::None
+                        || visual_coords_at_pos(text, pos, doc.tab_width()).col >= view.first_col
+                })
+                .and_then(|pos| view.screen_coords_at_pos(doc, text, pos))
+                // ensure col is on screen
+                .filter(|pos| pos.col < viewport.width as usize);
+
+            if let Some(pos) = pos {
+                let style = theme.try_get("ui.cursor.match").unwrap_or_else(|| {
+                    Style::default()
+                        .add_modifier(Modifier::REVERSED)
+                        .add_modifier(Modifier::DIM)
+                });
+
+                surface
+                    .get_mut(viewport.x + pos.col as u16, viewport.y + pos.row as u16)
+                    .set_style(style);
+            }
         }
+    }
 
-        // render selections and selected linenr(s)
-        let linenr_select: Style = theme
-            .try_get("ui.linenr.selected")
-            .unwrap_or_else(|| theme.get("ui.linenr"));
+    /// Draws a guide at each indent level in the leading whitespace of the lines on screen, over
+    /// the blank cells only. Blank lines have none.
+    fn render_indent_guides(
+        doc: &Document,
+        view: &View,
+        viewport: Rect,
+        surface: &mut Surface,
+        theme: &Theme,
+        config: &IndentGuidesConfig,
+    ) {
+        let text = doc.text().slice(..);
+        let style = theme
+            .try_get("ui.virtual.indent-guide")
+            .unwrap_or_else(|| Style::default().add_modifier(Modifier::DIM));
+        let symbol = config.character.to_string();
+        let tab_width = doc.tab_width();
+        let indent_width = doc.indent_width().max(1);
+        let first_level = if config.skip_first_level { 1 } else { 0 };
 
-        if is_focused {
-            let screen = {
-                let start = text.line_to_char(view.first_line);
-                let end = text.line_to_char(last_line + 1);
-                Range::new(start, end)
-            };
+        let mut row = 0;
+        for line in view.first_line..text.len_lines() {
+            if row >= viewport.height {
+                break;
+            }
 
-            let selection = doc.selection(view.id);
-
-            for selection in selection.iter().filter(|range| range.overlaps(&screen)) {
-                let head = view.screen_coords_at_pos(doc, text, selection.head);
-                if let Some(head) = head {
-                    surface.set_stringn(
-                        viewport.x + 1 - OFFSET,
-                        viewport.y + head.row as u16,
-                        format!("{:>5}", view.first_line + head.row + 1),
-                        5,
-                        linenr_select,
-                    );
-
-                    // TODO: set cursor position for IME
-                    if let Some(syntax) = doc.syntax() {
-                        use helix_core::match_brackets;
-                        let pos = doc.selection(view.id).cursor();
-                        let pos = match_brackets::find(syntax, doc.text(), pos)
-                            .and_then(|pos| view.screen_coords_at_pos(doc, text, pos));
-
-                        if let Some(pos) = pos {
-                            // ensure col is on screen
-                            if (pos.col as u16) < viewport.width + view.first_col as u16
-                                && pos.col >= view.first_col
-                            {
-                                let style = theme.try_get("ui.cursor.match").unwrap_or_else(|| {
-                                    Style::default()
-                                        .add_modifier(Modifier::REVERSED)
-                                        .add_modifier(Modifier::DIM)
-                                });
-
-                                surface
-                                    .get_mut(
-                                        viewport.x + pos.col as u16,
-                                        viewport.y + pos.row as u16,
-                                    )
-                                    .set_style(style);
-                            }
-                        }
+            let mut indent = 0;
+            let mut blank = true;
+            for ch in text.line(line).chars() {
+                match ch {
+                    ' ' => indent += 1,
+                    '\t' => indent += tab_width - indent % tab_width,
+                    _ => {
+                        blank = char_is_line_ending(ch);
+                        break;
+                    }
+                }
+            }
+
+            let height = view.line_height(doc, line) as u16;
+            if !blank && height > 0 {
+                for col in (first_level * indent_width..indent).step_by(indent_width) {
+                    if col < view.first_col || col >= view.first_col + viewport.width as usize {
+                        continue;
+                    }
+                    let x = viewport.x + (col - view.first_col) as u16;
+                    let cell = surface.get_mut(x, viewport.y + row);
+                    if cell.symbol == " " {
+                        cell.set_symbol(&symbol).set_style(style);
                     }
                 }
             }
+            row += height;
+        }
+    }
+
+    /// Marks breakpoints, and the line the debugged program is stopped at, in the gutter.
+    fn render_debug_gutter(editor: &Editor, doc: &Document, view: &View, surface: &mut Surface) {
+        let path = match doc.path() {
+            Some(path) => path,
+            None => return,
+        };
+        let breakpoints = editor.breakpoints.get(path);
+        let active_line = editor
+            .debugger
+            .as_ref()
+            .and_then(|debugger| debugger.active_frame.as_ref())
+            .filter(|frame| {
+                frame
+                    .source
+                    .as_ref()
+                    .and_then(|source| source.path.as_ref())
+                    == Some(path)
+            })
+            .map(|frame| frame.line.saturating_sub(1));
+        if breakpoints.is_none() && active_line.is_none() {
+            return;
+        }
+
+        let breakpoint_style = editor.theme.get("ui.debug.breakpoint");
+        let active_style = editor.theme.get("ui.debug.active");
+        let height = view.area.height.saturating_sub(1); // - 1 for statusline
+
+        let mut row = 0;
+        for line in view.first_line..view.last_line(doc) {
+            if row >= height {
+                break;
+            }
+
+            let symbol = if active_line == Some(line) {
+                Some(("▶", active_style))
+            } else if breakpoints
+                .into_iter()
+                .flatten()
+                .any(|breakpoint| breakpoint.line == line + 1)
+            {
+                Some(("●", breakpoint_style))
+            } else {
+                None
+            };
+            if let Some((symbol, style)) = symbol {
+                surface.set_stringn(view.area.x, view.area.y + row, symbol, 1, style);
+            }
+
+            row += view.line_height(doc, line) as u16;
+        }
+    }
+
+    /// Draws the first message of the diagnostics of each line, at least as severe as
+    /// `min_severity`, after the end of the line. The text isn't part of the document, so it
+    /// doesn't move anything.
+    pub fn render_inline_diagnostics(
+        doc: &Document,
+        view: &View,
+        viewport: Rect,
+        surface: &mut Surface,
+        theme: &Theme,
+        min_severity: Severity,
+    ) {
+        let text = doc.text().slice(..);
+        let last_line = view.last_line(doc);
+
+        let styles = [
+            (Severity::Error, theme.get("error")),
+            (Severity::Warning, theme.get("warning")),
+            (Severity::Info, theme.get("info")),
+            (Severity::Hint, theme.get("hint")),
+        ];
+
+        for line in view.first_line..=last_line {
+            let (diagnostic, severity) = match doc
+                .diagnostics()
+                .iter()
+                .filter(|diagnostic| diagnostic.line == line)
+                .map(|diagnostic| {
+                    let severity = diagnostic.severity.unwrap_or(Severity::Warning);
+                    (diagnostic, severity)
+                })
+                .find(|(_, severity)| *severity <= min_severity)
+            {
+                Some(diagnostic) => diagnostic,
+                None => continue,
+            };
+
+            let line_end = line_end_char_index(&text, line);
+            let pos = match view.screen_coords_at_pos(doc, text, line_end) {
+                Some(pos) => pos,
+                None => continue,
+            };
+            // leave room for the cursor at the end of the line. Only views that aren't soft
+            // wrapped scroll horizontally, cutting off the start of the message when the end of
+            // the line is left of the view
+            let (col, skip) = if view.soft_wrap == SoftWrap::None {
+                let col = visual_coords_at_pos(text, line_end, doc.tab_width()).col + 2;
+                (
+                    col.saturating_sub(view.first_col),
+                    view.first_col.saturating_sub(col),
+                )
+            } else {
+                (pos.col + 2, 0)
+            };
+            let x = viewport.x as usize + col;
+            if x >= viewport.right() as usize {
+                continue;
+            }
+
+            let style = styles
+                .iter()
+                .find(|(s, _)| *s == severity)
+                .map(|(_, style)| *style)
+                .unwrap_or_default()
+                .add_modifier(Modifier::DIM);
+            let mut skipped = 0;
+            let message = diagnostic
+                .message
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .trim_start_matches(|ch: char| {
+                    skipped += ch.width().unwrap_or(0);
+                    skipped <= skip
+                });
+            surface.set_stringn(
+                x as u16,
+                viewport.y + pos.row as u16,
+                message,
+                viewport.right() as usize - x,
+                style,
+            );
         }
     }
 
@@ -470,6 +903,64 @@ impl EditorView {
         );
     }
 
+    /// The tabs of the bufferline, one per open document in the order they were opened, with the
+    /// column they start at.
+    fn bufferline_tabs(editor: &Editor, x: u16) -> Vec<(DocumentId, u16, String)> {
+        let mut x = x;
+        editor
+            .document_order()
+            .iter()
+            .map(|&id| {
+                let doc = &editor.documents[id];
+                let name = doc
+                    .path()
+                    .and_then(|path| path.file_name())
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| "[scratch]".to_owned());
+                let title = format!(" {}{} ", name, if doc.is_modified() { "[+]" } else { "" });
+                let start = x;
+                x = x.saturating_add(title.width() as u16);
+                (id, start, title)
+            })
+            .collect()
+    }
+
+    /// Lists the open documents on the row `area`, highlighting the one of the focused view.
+    pub fn render_bufferline(editor: &Editor, area: Rect, surface: &mut Surface) {
+        let theme = &editor.theme;
+        let style = theme
+            .try_get("ui.bufferline")
+            .unwrap_or_else(|| theme.get("ui.statusline.inactive"));
+        let active_style = theme
+            .try_get("ui.bufferline.active")
+            .unwrap_or_else(|| theme.get("ui.statusline"));
+        surface.set_style(area, style);
+
+        let current = view!(editor).doc;
+        for (id, x, title) in Self::bufferline_tabs(editor, area.x) {
+            if x >= area.right() {
+                break;
+            }
+            let style = if id == current { active_style } else { style };
+            surface.set_stringn(x, area.y, title, (area.right() - x) as usize, style);
+        }
+    }
+
+    /// The document whose tab of the bufferline is at `column`, if the bufferline is shown on
+    /// `row`.
+    fn bufferline_document_at(editor: &Editor, row: u16, column: u16) -> Option<DocumentId> {
+        let area = editor.tree.area();
+        if !editor.config.bufferline || area.y.checked_sub(1) != Some(row) {
+            return None;
+        }
+        Self::bufferline_tabs(editor, area.x)
+            .into_iter()
+            .take_while(|(_, x, _)| *x <= column)
+            .last()
+            .filter(|(_, x, title)| column < x + title.width() as u16)
+            .map(|(id, _, _)| id)
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub fn render_statusline(
         &self,
@@ -513,7 +1004,12 @@ impl EditorView {
         if let Some(path) = doc.relative_path() {
             let path = path.to_string_lossy();
 
-            let title = format!("{}{}", path, if doc.is_modified() { "[+]" } else { "" });
+            let title = format!(
+                "{}{}{}",
+                path,
+                if doc.is_modified() { "[+]" } else { "" },
+                if doc.readonly { "[readonly]" } else { "" }
+            );
             surface.set_stringn(
                 viewport.x + 8,
                 viewport.y,
@@ -546,11 +1042,18 @@ impl EditorView {
             format!("{}:{}", pos.row + 1, pos.col + 1) // convert to 1-indexing
         };
 
+        // Only show the encoding if it's not the default.
+        let encoding_info = match doc.encoding().name() {
+            "UTF-8" => String::new(),
+            name => format!("{}    ", name),
+        };
+
         // Render them to the status line together.
         let right_side_text = format!(
-            "{}    {} ",
+            "{}    {}{} ",
             &diag_count[..diag_count.len().min(4)],
             // indent_info,
+            encoding_info,
             position_info
         );
         let text_len = right_side_text.len() as u16;
@@ -573,9 +1076,29 @@ impl EditorView {
         event: KeyEvent,
     ) -> Option<KeymapResult> {
         self.autoinfo = None;
-        match self.keymaps.get_mut(&mode).unwrap().get(event) {
-            KeymapResult::Matched(command) => command.execute(cxt),
-            KeymapResult::Pending(node) => self.autoinfo = Some(node.into()),
+        self.autoinfo_since = None;
+        let keymap = self.keymaps.get_mut(&mode).unwrap();
+        let auto_info = cxt.editor.config.auto_info;
+        match keymap.get(event) {
+            KeymapResult::Matched(command) => {
+                // keep showing the available keys while a sticky node is active
+                if let Some(node) = keymap.sticky().filter(|_| auto_info) {
+                    self.autoinfo = Some(node.clone().into());
+                }
+                command.execute(cxt)
+            }
+            KeymapResult::Pending(node) if auto_info => {
+                self.autoinfo = Some(node.into());
+                self.autoinfo_since = Some(Instant::now());
+                // render again once the delay passed, if no key came in the meantime
+                let delay = Duration::from_millis(cxt.editor.config.auto_info_delay);
+                cxt.jobs.callback(async move {
+                    tokio::time::sleep(delay).await;
+                    let call: job::Callback = Box::new(|_, _| {});
+                    Ok(call)
+                });
+            }
+            KeymapResult::Pending(_) => {}
             k @ KeymapResult::NotFound | k @ KeymapResult::Cancelled(_) => return Some(k),
         }
         None
@@ -640,6 +1163,114 @@ impl EditorView {
         }
     }
 
+    /// The view whose right border, the gap before the next split, or bottom border, its
+    /// statusline above the next split, is at `row` and `column`.
+    fn split_border_at(editor: &Editor, row: u16, column: u16) -> Option<(ViewId, Layout)> {
+        editor.tree.views().find_map(|(view, _)| {
+            let area = view.area;
+            let (layout, direction) =
+                if column == area.right() && (area.y..area.bottom()).contains(&row) {
+                    (Layout::Vertical, tree::Direction::Right)
+                } else if row + 1 == area.bottom() && (area.x..area.right()).contains(&column) {
+                    (Layout::Horizontal, tree::Direction::Down)
+                } else {
+                    return None;
+                };
+            editor
+                .tree
+                .find_split_in_direction(view.id, direction)
+                .map(|_| (view.id, layout))
+        })
+    }
+
+    fn handle_mouse_event(&mut self, event: MouseEvent, cx: &mut Context) -> EventResult {
+        let editor = &mut cx.editor;
+
+        let result = editor.tree.views().find_map(|(view, _focused)| {
+            view.pos_at_screen_coords(&editor.documents[view.doc], event.row, event.column)
+                .map(|pos| (pos, view.id))
+        });
+
+        match event.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                self.resizing = None;
+                if let Some(id) = Self::bufferline_document_at(editor, event.row, event.column) {
+                    editor.switch(id, Action::Replace);
+                    return EventResult::Consumed(None);
+                }
+                if let Some((pos, view_id)) = result {
+                    let doc = &mut editor.documents[editor.tree.get(view_id).doc];
+                    doc.set_selection(view_id, Selection::point(pos));
+                    editor.tree.focus = view_id;
+                    return EventResult::Consumed(None);
+                }
+                if let Some(border) = Self::split_border_at(editor, event.row, event.column) {
+                    self.resizing = Some(border);
+                    return EventResult::Consumed(None);
+                }
+                EventResult::Ignored
+            }
+
+            MouseEventKind::Up(MouseButton::Left) if self.resizing.is_some() => {
+                self.resizing = None;
+                EventResult::Consumed(None)
+            }
+
+            MouseEventKind::Drag(MouseButton::Left) => {
+                if let Some((view_id, layout)) = self.resizing {
+                    let area = match editor.tree.views().find(|(view, _)| view.id == view_id) {
+                        Some((view, _)) => view.area,
+                        None => return EventResult::Ignored,
+                    };
+                    let cells = match layout {
+                        Layout::Vertical => event.column as i32 - area.right() as i32,
+                        Layout::Horizontal => event.row as i32 - (area.bottom() as i32 - 1),
+                    };
+                    editor.tree.move_split_border(view_id, layout, cells);
+                    return EventResult::Consumed(None);
+                }
+
+                let (view, doc) = current!(editor);
+                let pos = match view.pos_at_screen_coords(doc, event.row, event.column) {
+                    Some(pos) => pos,
+                    None => return EventResult::Ignored,
+                };
+
+                let primary = doc.selection(view.id).primary();
+                doc.set_selection(view.id, Selection::single(primary.anchor, pos));
+                EventResult::Consumed(None)
+            }
+
+            MouseEventKind::ScrollUp | MouseEventKind::ScrollDown => {
+                let direction = match event.kind {
+                    MouseEventKind::ScrollUp => Direction::Backward,
+                    _ => Direction::Forward,
+                };
+
+                // scroll the view under the mouse without focusing it
+                let current_view = editor.tree.focus;
+                if let Some((_pos, view_id)) = result {
+                    editor.tree.focus = view_id;
+                }
+
+                let mut cxt = commands::Context {
+                    selected_register: helix_view::RegisterSelection::default(),
+                    editor,
+                    count: None,
+                    callback: None,
+                    on_next_key_callback: None,
+                    jobs: cx.jobs,
+                };
+                commands::scroll(&mut cxt, MOUSE_SCROLL_LINES, direction);
+
+                cx.editor.tree.focus = current_view;
+                EventResult::Consumed(None)
+            }
+
+            _ => EventResult::Ignored,
+        }
+    }
+
     pub fn set_completion(
         &mut self,
         items: Vec<helix_lsp::lsp::CompletionItem>,
@@ -659,7 +1290,11 @@ impl Component for EditorView {
         match event {
             Event::Resize(width, height) => {
                 // HAXX: offset the render area height by 1 to account for prompt/commandline
-                cx.editor.resize(Rect::new(0, 0, width, height - 1));
+                cx.editor
+                    .resize(Rect::new(0, 0, width, height.saturating_sub(1)));
+                if let Some(completion) = &mut self.completion {
+                    completion.required_size((width, height));
+                }
                 EventResult::Consumed(None)
             }
             Event::Key(key) => {
@@ -668,8 +1303,15 @@ impl Component for EditorView {
                 // clear status
                 cx.editor.status_msg = None;
 
-                let (_, doc) = current!(cx.editor);
+                let (view, doc) = current!(cx.editor);
                 let mode = doc.mode();
+                // between keys, so the selection changed by a prompt or a language server response
+                // is remembered too
+                view.selection_history.record((
+                    doc.id(),
+                    doc.version(),
+                    doc.selection(view.id).clone(),
+                ));
 
                 let mut cxt = commands::Context {
                     selected_register: helix_view::RegisterSelection::default(),
@@ -714,6 +1356,11 @@ impl Component for EditorView {
                             if !consumed {
                                 self.insert_mode(&mut cxt, key);
 
+                                // commit every key, the history groups consecutive typing into
+                                // a single undo step
+                                let (view, doc) = current!(cxt.editor);
+                                doc.append_changes_to_history(view.id);
+
                                 // lastly we recalculate completion
                                 if let Some(completion) = &mut self.completion {
                                     completion.update(&mut cxt);
@@ -765,17 +1412,88 @@ impl Component for EditorView {
 
                 EventResult::Consumed(callback)
             }
-            Event::Mouse(_) => EventResult::Ignored,
+            Event::Mouse(event) => self.handle_mouse_event(event, cx),
+            // pasted text arrives through `handle_paste`
+            Event::Paste(_) | Event::FocusGained | Event::FocusLost => EventResult::Ignored,
         }
     }
 
+    fn handle_paste(&mut self, text: &str, cx: &mut Context) -> bool {
+        // pasted text is only inserted as is in insert mode, elsewhere the keys run commands
+        if self.on_next_key.is_some() || doc_mut!(cx.editor).mode() != Mode::Insert {
+            return false;
+        }
+        cx.editor.status_msg = None;
+        self.completion = None;
+
+        commands::insert::insert_pasted(cx.editor, text);
+        // repeating the insert types the text
+        self.last_insert.1.extend(text.chars().map(|ch| {
+            let code = match ch {
+                '\n' => KeyCode::Enter,
+                '\t' => KeyCode::Tab,
+                ch => KeyCode::Char(ch),
+            };
+            KeyEvent {
+                code,
+                modifiers: KeyModifiers::NONE,
+            }
+        }));
+
+        let (view, doc) = current!(cx.editor);
+        view.ensure_cursor_in_view(doc);
+        true
+    }
+
     fn render(&self, area: Rect, surface: &mut Surface, cx: &mut Context) {
         // clear with background color
         surface.set_style(area, cx.editor.theme.get("ui.background"));
 
+        // the bufferline takes the top row
+        let bufferline = cx.editor.config.bufferline && area.height > 1;
+        let views_area = if bufferline {
+            Rect::new(area.x, area.y + 1, area.width, area.height - 1)
+        } else {
+            area
+        };
+
         // if the terminal size suddenly changed, we need to trigger a resize
-        cx.editor
-            .resize(Rect::new(area.x, area.y, area.width, area.height - 1)); // - 1 to account for commandline
+        cx.editor.resize(Rect::new(
+            views_area.x,
+            views_area.y,
+            views_area.width,
+            views_area.height.saturating_sub(1),
+        )); // - 1 to account for commandline
+
+        let search = cx.editor.search_highlight_regex();
+
+        // highlight what's on screen in the background, along with a screen above and below
+        for (view, _) in cx.editor.tree.views() {
+            let doc = &mut cx.editor.documents[view.doc];
+            let text = doc.text().slice(..);
+            let height = view.area.height as usize;
+            let first_line = view.first_line.saturating_sub(height);
+            let last_line = (view.last_line(doc) + height).min(text.len_lines() - 1);
+            let range = text.line_to_byte(first_line)..text.line_to_byte(last_line + 1);
+
+            let scopes = cx.editor.theme.scopes().to_vec();
+            if let Some(request) =
+                doc.request_highlights(view.id, range, cx.editor.syn_loader.clone(), scopes)
+            {
+                let (doc_id, view_id) = (doc.id(), view.id);
+                cx.jobs.callback(async move {
+                    let highlights = request.await;
+                    let call: job::Callback = Box::new(move |editor: &mut Editor, _| {
+                        if let (Some(highlights), Some(doc)) =
+                            (highlights, editor.documents.get_mut(doc_id))
+                        {
+                            doc.set_highlights(view_id, highlights);
+                        }
+                    });
+                    Ok(call)
+                });
+            }
+        }
 
         for (view, is_focused) in cx.editor.tree.views() {
             let doc = cx.editor.document(view.doc).unwrap();
@@ -788,26 +1506,59 @@ impl Component for EditorView {
                 &cx.editor.theme,
                 is_focused,
                 loader,
+                &cx.editor.config,
+                search.as_ref(),
             );
+            Self::render_debug_gutter(cx.editor, doc, view, surface);
+        }
+
+        if bufferline {
+            Self::render_bufferline(cx.editor, Rect::new(area.x, area.y, area.width, 1), surface);
         }
 
-        if let Some(ref info) = self.autoinfo {
+        let delay = Duration::from_millis(cx.editor.config.auto_info_delay);
+        let autoinfo_due = match self.autoinfo_since {
+            Some(since) => since.elapsed() >= delay,
+            None => true,
+        };
+        if let Some(info) = self.autoinfo.as_ref().filter(|_| autoinfo_due) {
             info.render(area, surface, cx);
         }
 
+        // render pending count and keys in the bottom right corner
+        let mode = cx.editor.documents[view!(cx.editor).doc].mode();
+        let mut pending = String::new();
+        if let Some(count) = cx.editor.count {
+            pending.push_str(&count.to_string());
+        }
+        for key in self.keymaps[&mode].pending() {
+            pending.push_str(&key.to_string());
+        }
+        if !pending.is_empty() {
+            let width = pending.width() as u16;
+            surface.set_string(
+                area.x + area.width.saturating_sub(width + 1),
+                area.y + area.height.saturating_sub(1),
+                pending,
+                cx.editor.theme.get("ui.text"),
+            );
+        }
+
         // render status msg
         if let Some((status_msg, severity)) = &cx.editor.status_msg {
-            use helix_view::editor::Severity;
-            let style = if *severity == Severity::Error {
-                cx.editor.theme.get("error")
-            } else {
-                cx.editor.theme.get("ui.text")
+            let style = match severity {
+                Severity::Error => cx.editor.theme.get("error"),
+                Severity::Warning => cx.editor.theme.get("warning"),
+                Severity::Info | Severity::Hint => cx.editor.theme.get("ui.text"),
             };
 
-            surface.set_string(
+            // messages with several lines, like a list of errors, are shown on one
+            let status_msg = status_msg.lines().collect::<Vec<_>>().join(" ");
+            surface.set_stringn(
                 area.x,
                 area.y + area.height.saturating_sub(1),
-                status_msg,
+                &status_msg,
+                area.width as usize,
                 style,
             );
         }
@@ -818,10 +1569,6 @@ impl Component for EditorView {
     }
 
     fn cursor(&self, _area: Rect, editor: &Editor) -> (Option<Position>, CursorKind) {
-        // match view.doc.mode() {
-        //     Mode::Insert => write!(stdout, "\x1B[6 q"),
-        //     mode => write!(stdout, "\x1B[2 q"),
-        // };
         editor.cursor()
     }
 }
@@ -835,3 +1582,191 @@ fn canonicalize_key(key: &mut KeyEvent) {
         key.modifiers.remove(KeyModifiers::SHIFT)
     }
 }
+
+/// The highlight `events` within the byte ranges `segments`, sorted and disjoint, with the parts
+/// they don't cover left unhighlighted. The events may be for an older version of the text of
+/// length `len`.
+fn clip_highlights(
+    events: &[HighlightEvent],
+    segments: &[std::ops::Range<usize>],
+    len: usize,
+) -> Vec<HighlightEvent> {
+    let mut segments = segments
+        .iter()
+        .map(|segment| segment.start..segment.end.min(len))
+        .filter(|segment| !segment.is_empty())
+        .peekable();
+    // the sources of the segments between `from` and `to`
+    let mut push_sources = |clipped: &mut Vec<HighlightEvent>, from: usize, to: usize| {
+        while let Some(segment) = segments.peek() {
+            if segment.end <= from {
+                segments.next();
+                continue;
+            }
+            if segment.start >= to {
+                break;
+            }
+            clipped.push(HighlightEvent::Source {
+                start: segment.start.max(from),
+                end: segment.end.min(to),
+            });
+            if segment.end > to {
+                break;
+            }
+            segments.next();
+        }
+    };
+
+    let mut pos = 0;
+    let mut clipped = Vec::with_capacity(events.len() + 1);
+    for event in events {
+        match *event {
+            HighlightEvent::Source {
+                end: source_end, ..
+            } => {
+                // sources continue where the previous one ended, so that gaps are still drawn
+                if source_end > pos {
+                    push_sources(&mut clipped, pos, source_end);
+                    pos = source_end;
+                }
+            }
+            event => clipped.push(event),
+        }
+    }
+    push_sources(&mut clipped, pos, usize::MAX);
+    clipped
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use helix_core::syntax::Highlight;
+    use HighlightEvent::{HighlightEnd, HighlightStart, Source};
+
+    #[test]
+    fn clip_highlights_to_segments() {
+        let events = [
+            Source { start: 0, end: 4 },
+            HighlightStart(Highlight(1)),
+            Source { start: 4, end: 10 },
+            HighlightEnd,
+            Source { start: 10, end: 20 },
+        ];
+        assert_eq!(
+            clip_highlights(&events, std::slice::from_ref(&(2..12)), 20),
+            vec![
+                Source { start: 2, end: 4 },
+                HighlightStart(Highlight(1)),
+                Source { start: 4, end: 10 },
+                HighlightEnd,
+                Source { start: 10, end: 12 },
+            ]
+        );
+
+        // folded text is skipped, even within a highlight
+        assert_eq!(
+            clip_highlights(&events, &[0..2, 6..8, 15..18], 20),
+            vec![
+                Source { start: 0, end: 2 },
+                HighlightStart(Highlight(1)),
+                Source { start: 6, end: 8 },
+                HighlightEnd,
+                Source { start: 15, end: 18 },
+            ]
+        );
+    }
+
+    #[test]
+    fn clip_highlights_of_other_lengths() {
+        // highlights of a longer text stop at the end of the current one
+        let events = [Source { start: 0, end: 20 }];
+        assert_eq!(
+            clip_highlights(&events, std::slice::from_ref(&(5..30)), 10),
+            vec![Source { start: 5, end: 10 }]
+        );
+        // the text past the highlights of a shorter one is left unhighlighted
+        let events = [
+            HighlightStart(Highlight(0)),
+            Source { start: 0, end: 5 },
+            HighlightEnd,
+        ];
+        assert_eq!(
+            clip_highlights(&events, std::slice::from_ref(&(3..8)), 10),
+            vec![
+                HighlightStart(Highlight(0)),
+                Source { start: 3, end: 5 },
+                HighlightEnd,
+                Source { start: 5, end: 8 },
+            ]
+        );
+    }
+
+    #[test]
+    fn clip_highlights_gaps_and_empty_segments() {
+        // a gap between sources is drawn with the highlights around it
+        let events = [
+            Source { start: 2, end: 4 },
+            HighlightStart(Highlight(2)),
+            Source { start: 6, end: 8 },
+            HighlightEnd,
+        ];
+        assert_eq!(
+            clip_highlights(&events, &[0..0, 0..10, 12..12], 10),
+            vec![
+                Source { start: 0, end: 4 },
+                HighlightStart(Highlight(2)),
+                Source { start: 4, end: 8 },
+                HighlightEnd,
+                Source { start: 8, end: 10 },
+            ]
+        );
+        // no segments leaves only the highlight boundaries
+        assert_eq!(
+            clip_highlights(&events, &[], 10),
+            vec![HighlightStart(Highlight(2)), HighlightEnd]
+        );
+    }
+
+    #[test]
+    fn inline_diagnostics_scroll_horizontally() {
+        use helix_core::diagnostic::{Diagnostic, Range};
+
+        let mut doc = Document::from(helix_core::Rope::from("abcdef\nab\n"), None);
+        let diagnostic = |line, message: &str| Diagnostic {
+            range: Range { start: 0, end: 0 },
+            line,
+            message: message.to_owned(),
+            severity: Some(Severity::Error),
+        };
+        doc.set_diagnostics(vec![diagnostic(0, "long"), diagnostic(1, "short")]);
+        let mut view = View::new(DocumentId::default());
+        view.area = Rect::new(0, 0, 12, 3);
+        let viewport = Rect::new(0, 0, 12, 2);
+        let theme = &*helix_view::theme::DEFAULT_THEME;
+        let render = |view: &View| {
+            let mut surface = Surface::empty(viewport);
+            EditorView::render_inline_diagnostics(
+                &doc,
+                view,
+                viewport,
+                &mut surface,
+                theme,
+                Severity::Hint,
+            );
+            (0..viewport.height)
+                .map(|y| {
+                    (0..viewport.width)
+                        .map(|x| surface.get(x, y).symbol.clone())
+                        .collect::<String>()
+                        .trim_end()
+                        .to_owned()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(render(&view), ["        long", "    short"]);
+        // the messages move with the text, cut off where the text is left of the view
+        view.first_col = 5;
+        assert_eq!(render(&view), ["   long", "hort"]);
+    }
+}
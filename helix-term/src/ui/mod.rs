@@ -13,7 +13,7 @@ pub use completion::Completion;
 pub use editor::EditorView;
 pub use markdown::Markdown;
 pub use menu::Menu;
-pub use picker::Picker;
+pub use picker::{FileLocation, FilePicker, Picker};
 pub use popup::Popup;
 pub use prompt::{Prompt, PromptEvent};
 pub use spinner::{ProgressSpinners, Spinner};
@@ -21,14 +21,16 @@ pub use text::Text;
 
 use helix_core::regex::Regex;
 use helix_core::register::Registers;
-use helix_view::{Document, Editor, View};
+use helix_view::{editor::FilePickerConfig, Document, Editor, View};
 
 use std::path::PathBuf;
+use std::time::SystemTime;
 
 pub fn regex_prompt(
     cx: &mut crate::commands::Context,
     prompt: String,
-    fun: impl Fn(&mut View, &mut Document, &mut Registers, Regex) + 'static,
+    history_register: Option<char>,
+    fun: impl Fn(&mut View, &mut Document, &mut Registers, Regex, PromptEvent) + 'static,
 ) -> Prompt {
     let (view, doc) = current!(cx.editor);
     let view_id = view.id;
@@ -36,7 +38,7 @@ pub fn regex_prompt(
 
     Prompt::new(
         prompt,
-        None,
+        history_register,
         |_input: &str| Vec::new(), // this is fine because Vec::new() doesn't allocate
         move |cx: &mut crate::compositor::Context, input: &str, event: PromptEvent| {
             match event {
@@ -44,10 +46,7 @@ pub fn regex_prompt(
                     let (view, doc) = current!(cx.editor);
                     doc.set_selection(view.id, snapshot.clone());
                 }
-                PromptEvent::Validate => {
-                    // TODO: push_jump to store selection just before jump
-                }
-                PromptEvent::Update => {
+                PromptEvent::Update | PromptEvent::Validate => {
                     // skip empty input, TODO: trigger default
                     if input.is_empty() {
                         return;
@@ -61,7 +60,13 @@ pub fn regex_prompt(
                             // revert state to what it was before the last update
                             doc.set_selection(view.id, snapshot.clone());
 
-                            fun(view, doc, registers, regex);
+                            fun(view, doc, registers, regex, event);
+
+                            // jump back to the selection from before the prompt
+                            if event == PromptEvent::Validate && *doc.selection(view.id) != snapshot
+                            {
+                                view.jumps.push((doc.id(), snapshot.clone()));
+                            }
 
                             view.ensure_cursor_in_view(doc);
                         }
@@ -73,45 +78,65 @@ pub fn regex_prompt(
     )
 }
 
-pub fn file_picker(root: PathBuf) -> Picker<PathBuf> {
-    use ignore::Walk;
-    use std::time;
-    let files = Walk::new(root.clone()).filter_map(|entry| match entry {
-        Ok(entry) => {
-            // filter dirs, but we might need special handling for symlinks!
-            if !entry.file_type().map_or(false, |entry| entry.is_dir()) {
-                let time = if let Ok(metadata) = entry.metadata() {
-                    metadata
-                        .accessed()
-                        .or_else(|_| metadata.modified())
-                        .or_else(|_| metadata.created())
-                        .unwrap_or(time::UNIX_EPOCH)
-                } else {
-                    time::UNIX_EPOCH
-                };
-
-                Some((entry.into_path(), time))
-            } else {
-                None
-            }
-        }
-        Err(_err) => None,
-    });
-
-    let mut files: Vec<_> = if root.join(".git").is_dir() {
-        files.collect()
-    } else {
-        const MAX: usize = 8192;
-        files.take(MAX).collect()
+/// Picks a file of `root`, the most recently accessed first.
+pub fn file_picker(root: PathBuf, config: &FilePickerConfig) -> FilePicker<(PathBuf, SystemTime)> {
+    use ignore::WalkBuilder;
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
     };
 
-    files.sort_by_key(|file| std::cmp::Reverse(file.1));
-
-    let files = files.into_iter().map(|(path, _)| path).collect();
+    let walk = WalkBuilder::new(&root)
+        .hidden(config.hidden)
+        .git_ignore(config.git_ignore)
+        .git_global(config.git_ignore)
+        .git_exclude(config.git_ignore)
+        .ignore(config.git_ignore)
+        .build();
+
+    // walk the files on a background thread and stream them into the picker, stopping early if
+    // the picker is closed
+    let files = Arc::new(Mutex::new(Vec::new()));
+    let done = Arc::new(AtomicBool::new(false));
+    {
+        let files = Arc::downgrade(&files);
+        let done = done.clone();
+        let limit = if root.join(".git").is_dir() {
+            usize::MAX
+        } else {
+            8192
+        };
+        std::thread::spawn(move || {
+            let paths = walk
+                .filter_map(|entry| entry.ok())
+                // filter dirs, but we might need special handling for symlinks!
+                .filter(|entry| !entry.file_type().map_or(false, |entry| entry.is_dir()))
+                .take(limit);
+
+            for entry in paths {
+                let time = entry
+                    .metadata()
+                    .ok()
+                    .and_then(|metadata| {
+                        metadata
+                            .accessed()
+                            .or_else(|_| metadata.modified())
+                            .or_else(|_| metadata.created())
+                            .ok()
+                    })
+                    .unwrap_or(SystemTime::UNIX_EPOCH);
+                match files.upgrade() {
+                    Some(files) => files.lock().unwrap().push((entry.into_path(), time)),
+                    None => return,
+                }
+            }
+            done.store(true, Ordering::Release);
+        });
+    }
 
-    Picker::new(
-        files,
-        move |path: &PathBuf| {
+    FilePicker::new(
+        Vec::new(),
+        move |(path, _time): &(PathBuf, SystemTime)| {
             // format_fn
             path.strip_prefix(&root)
                 .unwrap_or(path)
@@ -119,12 +144,15 @@ pub fn file_picker(root: PathBuf) -> Picker<PathBuf> {
                 .unwrap()
                 .into()
         },
-        move |editor: &mut Editor, path: &PathBuf, action| {
+        move |editor: &mut Editor, (path, _time): &(PathBuf, SystemTime), action| {
             editor
                 .open(path.into(), action)
                 .expect("editor.open failed");
         },
+        |_editor, (path, _time)| Some((path.clone(), None)),
     )
+    .with_order(|(_, a), (_, b)| b.cmp(a))
+    .with_stream(files, done)
 }
 
 pub mod completers {
@@ -188,6 +216,28 @@ pub mod completers {
         })
     }
 
+    pub fn indent_style(input: &str) -> Vec<Completion> {
+        options(input, &["tabs", "1", "2", "3", "4", "5", "6", "7", "8"])
+    }
+
+    pub fn option(input: &str) -> Vec<Completion> {
+        options(input, helix_view::editor::Config::OPTIONS)
+    }
+
+    pub fn line_ending(input: &str) -> Vec<Completion> {
+        options(input, &["crlf", "lf", "cr", "ff", "nel"])
+    }
+
+    /// Completes `input` with the `options` it's a prefix of.
+    fn options(input: &str, options: &[&'static str]) -> Vec<Completion> {
+        let input = input.to_lowercase();
+        options
+            .iter()
+            .filter(|option| option.starts_with(&input))
+            .map(|option| ((0..), Cow::Borrowed(*option)))
+            .collect()
+    }
+
     #[derive(Copy, Clone, PartialEq, Eq)]
     enum FileMatch {
         /// Entry should be ignored
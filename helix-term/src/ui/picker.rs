@@ -1,4 +1,4 @@
-use crate::compositor::{Component, Compositor, Context, EventResult};
+use crate::compositor::{Callback, Component, Compositor, Context, EventResult};
 use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
 use tui::{
     buffer::Buffer as Surface,
@@ -9,15 +9,27 @@ use fuzzy_matcher::skim::SkimMatcherV2 as Matcher;
 use fuzzy_matcher::FuzzyMatcher;
 
 use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+use std::time::Duration;
 
-use crate::ui::{Prompt, PromptEvent};
-use helix_core::Position;
+use crate::job;
+use crate::ui::{EditorView, Prompt, PromptEvent};
+use helix_core::{line_ending::line_end_char_index, Position, Selection};
 use helix_view::{
     editor::Action,
     graphics::{Color, CursorKind, Rect, Style},
-    Editor,
+    Document, DocumentId, Editor, View, ViewId,
 };
 
+/// Orders options having the same score.
+type OrderFn<T> = Box<dyn Fn(&T, &T) -> std::cmp::Ordering>;
+
 pub struct Picker<T> {
     options: Vec<T>,
     // filter: String,
@@ -32,7 +44,9 @@ pub struct Picker<T> {
     prompt: Prompt,
 
     format_fn: Box<dyn Fn(&T) -> Cow<str>>,
-    callback_fn: Box<dyn Fn(&mut Editor, &T, Action)>,
+    callback_fn: Box<dyn Fn(&mut Context, &T, Action) -> Option<Callback>>,
+    /// Options with the same score keep the order they were added in unless set.
+    order_fn: Option<OrderFn<T>>,
 }
 
 impl<T> Picker<T> {
@@ -40,6 +54,19 @@ impl<T> Picker<T> {
         options: Vec<T>,
         format_fn: impl Fn(&T) -> Cow<str> + 'static,
         callback_fn: impl Fn(&mut Editor, &T, Action) + 'static,
+    ) -> Self {
+        Self::with_callback(options, format_fn, move |cx, option, action| {
+            callback_fn(cx.editor, option, action);
+            None
+        })
+    }
+
+    /// Like `new`, but the callback is given the whole context and may return a callback to run
+    /// on the compositor once the picker is closed.
+    pub fn with_callback(
+        options: Vec<T>,
+        format_fn: impl Fn(&T) -> Cow<str> + 'static,
+        callback_fn: impl Fn(&mut Context, &T, Action) -> Option<Callback> + 'static,
     ) -> Self {
         let prompt = Prompt::new(
             "".to_string(),
@@ -59,6 +86,7 @@ impl<T> Picker<T> {
             prompt,
             format_fn: Box::new(format_fn),
             callback_fn: Box::new(callback_fn),
+            order_fn: None,
         };
 
         // TODO: scoring on empty input should just use a fastpath
@@ -67,6 +95,27 @@ impl<T> Picker<T> {
         picker
     }
 
+    /// Orders the options having the same score with `order_fn`, like the most recent first.
+    pub fn with_order(mut self, order_fn: impl Fn(&T, &T) -> std::cmp::Ordering + 'static) -> Self {
+        self.order_fn = Some(Box::new(order_fn));
+        self.sort_matches();
+        self
+    }
+
+    /// Sorts the matches by score, then by `order_fn`.
+    fn sort_matches(&mut self) {
+        let options = &self.options;
+        match &self.order_fn {
+            Some(order_fn) => self.matches.sort_by(|(a, a_score), (b, b_score)| {
+                b_score
+                    .cmp(a_score)
+                    .then_with(|| order_fn(&options[*a], &options[*b]))
+            }),
+            // keep the original order of options with the same score
+            None => self.matches.sort_by_key(|(_, score)| -score),
+        }
+    }
+
     pub fn score(&mut self) {
         // need to borrow via pattern match otherwise it complains about simultaneous borrow
         let Self {
@@ -98,12 +147,35 @@ impl<T> Picker<T> {
                         .map(|score| (index, score))
                 }),
         );
-        matches.sort_unstable_by_key(|(_, score)| -score);
+        self.sort_matches();
 
         // reset cursor position
         self.cursor = 0;
     }
 
+    /// Adds `options`, scoring them against the current pattern. The cursor stays in place.
+    pub fn add_options(&mut self, options: impl IntoIterator<Item = T>) {
+        let start = self.options.len();
+        self.options.extend(options);
+
+        let pattern = &self.prompt.line;
+        let matcher = &self.matcher;
+        let format_fn = &self.format_fn;
+        self.matches
+            .extend(
+                self.options
+                    .iter()
+                    .enumerate()
+                    .skip(start)
+                    .filter_map(|(index, option)| {
+                        matcher
+                            .fuzzy_match(&(format_fn)(option), pattern)
+                            .map(|score| (index, score))
+                    }),
+            );
+        self.sort_matches();
+    }
+
     pub fn move_up(&mut self) {
         self.cursor = self.cursor.saturating_sub(1);
     }
@@ -124,6 +196,21 @@ impl<T> Picker<T> {
             .map(|(index, _score)| &self.options[*index])
     }
 
+    /// Runs the callback on the selected option and closes the picker.
+    fn accept(&self, cx: &mut Context, action: Action) -> EventResult {
+        let callback = self
+            .selection()
+            .and_then(|option| (self.callback_fn)(cx, option, action));
+
+        EventResult::Consumed(Some(Box::new(move |compositor: &mut Compositor| {
+            // remove the layer
+            compositor.last_picker = compositor.pop();
+            if let Some(callback) = callback {
+                callback(compositor);
+            }
+        })))
+    }
+
     pub fn save_filter(&mut self) {
         self.filters.clear();
         self.filters
@@ -174,6 +261,7 @@ impl<T: 'static> Component for Picker<T> {
             | KeyEvent {
                 code: KeyCode::Char('p'),
                 modifiers: KeyModifiers::CONTROL,
+                ..
             } => self.move_up(),
             KeyEvent {
                 code: KeyCode::Down,
@@ -185,6 +273,7 @@ impl<T: 'static> Component for Picker<T> {
             | KeyEvent {
                 code: KeyCode::Char('n'),
                 modifiers: KeyModifiers::CONTROL,
+                ..
             } => self.move_down(),
             KeyEvent {
                 code: KeyCode::Esc, ..
@@ -192,6 +281,7 @@ impl<T: 'static> Component for Picker<T> {
             | KeyEvent {
                 code: KeyCode::Char('c'),
                 modifiers: KeyModifiers::CONTROL,
+                ..
             } => {
                 return close_fn;
             }
@@ -199,32 +289,26 @@ impl<T: 'static> Component for Picker<T> {
                 code: KeyCode::Enter,
                 ..
             } => {
-                if let Some(option) = self.selection() {
-                    (self.callback_fn)(&mut cx.editor, option, Action::Replace);
-                }
-                return close_fn;
+                return self.accept(cx, Action::Replace);
             }
             KeyEvent {
                 code: KeyCode::Char('h'),
                 modifiers: KeyModifiers::CONTROL,
+                ..
             } => {
-                if let Some(option) = self.selection() {
-                    (self.callback_fn)(&mut cx.editor, option, Action::HorizontalSplit);
-                }
-                return close_fn;
+                return self.accept(cx, Action::HorizontalSplit);
             }
             KeyEvent {
                 code: KeyCode::Char('v'),
                 modifiers: KeyModifiers::CONTROL,
+                ..
             } => {
-                if let Some(option) = self.selection() {
-                    (self.callback_fn)(&mut cx.editor, option, Action::VerticalSplit);
-                }
-                return close_fn;
+                return self.accept(cx, Action::VerticalSplit);
             }
             KeyEvent {
                 code: KeyCode::Char(' '),
                 modifiers: KeyModifiers::CONTROL,
+                ..
             } => {
                 self.save_filter();
             }
@@ -240,8 +324,17 @@ impl<T: 'static> Component for Picker<T> {
     }
 
     fn render(&self, area: Rect, surface: &mut Surface, cx: &mut Context) {
-        let area = inner_rect(area);
+        self.render_in(inner_rect(area), surface, cx);
+    }
+
+    fn cursor(&self, area: Rect, editor: &Editor) -> (Option<Position>, CursorKind) {
+        self.cursor_in(inner_rect(area), editor)
+    }
+}
 
+impl<T> Picker<T> {
+    /// Renders the picker within the frame `area`.
+    fn render_in(&self, area: Rect, surface: &mut Surface, cx: &mut Context) {
         // -- Render the frame:
 
         // clear area
@@ -304,9 +397,8 @@ impl<T: 'static> Component for Picker<T> {
         }
     }
 
-    fn cursor(&self, area: Rect, editor: &Editor) -> (Option<Position>, CursorKind) {
+    fn cursor_in(&self, area: Rect, editor: &Editor) -> (Option<Position>, CursorKind) {
         // TODO: this is mostly duplicate code
-        let area = inner_rect(area);
         let block = Block::default().borders(Borders::ALL);
         // calculate the inner area inside the box
         let inner = block.inner(area);
@@ -317,3 +409,270 @@ impl<T: 'static> Component for Picker<T> {
         self.prompt.cursor(area, editor)
     }
 }
+
+/// File path and the range of lines to preview, if any.
+pub type FileLocation = (PathBuf, Option<(usize, usize)>);
+
+type FileCallback<T> = Box<dyn Fn(&Editor, &T) -> Option<FileLocation>>;
+
+/// How often options streamed into a picker are added.
+const STREAM_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Files bigger than this aren't previewed.
+const MAX_PREVIEW_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Pickers narrower than this don't show a preview.
+const MIN_WIDTH_FOR_PREVIEW: u16 = 80;
+
+/// A picker that previews the location its selected option points to.
+pub struct FilePicker<T> {
+    picker: Picker<T>,
+    /// Documents loaded for previews, by path. They're loaded when first previewed and kept
+    /// until the picker is closed.
+    preview_cache: RefCell<HashMap<PathBuf, Option<Document>>>,
+    file_fn: FileCallback<T>,
+    stream: Option<Stream<T>>,
+}
+
+/// Options found by a background task, added to the picker as they come in.
+struct Stream<T> {
+    pending: Arc<Mutex<Vec<T>>>,
+    /// Set once the task is done adding options.
+    done: Arc<AtomicBool>,
+    /// Whether an update of the picker is already scheduled.
+    scheduled: Cell<bool>,
+}
+
+impl<T> FilePicker<T> {
+    pub fn new(
+        options: Vec<T>,
+        format_fn: impl Fn(&T) -> Cow<str> + 'static,
+        callback_fn: impl Fn(&mut Editor, &T, Action) + 'static,
+        file_fn: impl Fn(&Editor, &T) -> Option<FileLocation> + 'static,
+    ) -> Self {
+        Self {
+            picker: Picker::new(options, format_fn, callback_fn),
+            preview_cache: RefCell::new(HashMap::new()),
+            file_fn: Box::new(file_fn),
+            stream: None,
+        }
+    }
+
+    /// Orders the options having the same score with `order_fn`.
+    pub fn with_order(mut self, order_fn: impl Fn(&T, &T) -> std::cmp::Ordering + 'static) -> Self {
+        self.picker = self.picker.with_order(order_fn);
+        self
+    }
+
+    /// Adds the options a background task pushes to `pending`, until it sets `done`.
+    pub fn with_stream(mut self, pending: Arc<Mutex<Vec<T>>>, done: Arc<AtomicBool>) -> Self {
+        self.stream = Some(Stream {
+            pending,
+            done,
+            scheduled: Cell::new(false),
+        });
+        self
+    }
+
+    /// Moves the options found so far into the picker.
+    fn update_stream(&mut self) {
+        let stream = match &self.stream {
+            Some(stream) => stream,
+            None => return,
+        };
+        stream.scheduled.set(false);
+
+        // check before taking the options, so that none are pushed after the last update
+        let done = stream.done.load(Ordering::Acquire);
+        let options = std::mem::take(&mut *stream.pending.lock().unwrap());
+        self.picker.add_options(options);
+
+        if done {
+            self.stream = None;
+        }
+    }
+
+    /// Loads the document at `path`, taking its contents from the editor if it's open so that
+    /// unsaved changes are shown.
+    fn load_preview(editor: &Editor, path: &Path) -> Option<Document> {
+        let open = editor
+            .documents()
+            .find(|doc| doc.path().map(|doc_path| doc_path == path).unwrap_or(false));
+
+        let mut doc = match open {
+            Some(doc) => {
+                let mut preview = Document::from(doc.text().clone(), None);
+                preview.set_path(path).ok()?;
+                preview.detect_language(Some(&editor.theme), &editor.syn_loader);
+                preview
+            }
+            None => {
+                if std::fs::metadata(path).ok()?.len() > MAX_PREVIEW_SIZE {
+                    return None;
+                }
+                Document::open(
+                    path.to_path_buf(),
+                    None,
+                    Some(&editor.theme),
+                    Some(&editor.syn_loader),
+                )
+                .ok()?
+            }
+        };
+        doc.set_selection(ViewId::default(), Selection::point(0));
+        Some(doc)
+    }
+
+    fn render_preview(&self, area: Rect, surface: &mut Surface, cx: &mut Context) {
+        use tui::widgets::Widget;
+        let block = Block::default().borders(Borders::ALL);
+        let inner = block.inner(area);
+        block.render(area, surface);
+
+        let (path, lines) = match self
+            .picker
+            .selection()
+            .and_then(|option| (self.file_fn)(cx.editor, option))
+        {
+            Some(location) => location,
+            None => return,
+        };
+
+        let mut cache = self.preview_cache.borrow_mut();
+        let doc = cache
+            .entry(path)
+            .or_insert_with_key(|path| Self::load_preview(cx.editor, path));
+        let doc = match doc {
+            Some(doc) => doc,
+            None => {
+                let style = cx.editor.theme.get("ui.text");
+                surface.set_string(inner.x + 1, inner.y, "<No preview available>", style);
+                return;
+            }
+        };
+
+        let text = doc.text().slice(..);
+        let last_line = text.len_lines().saturating_sub(1);
+        let (start, end) = lines.unwrap_or((0, 0));
+        let (start, end) = (start.min(last_line), end.min(last_line));
+
+        // select the previewed lines, and show them in the middle of the pane
+        let from = text.line_to_char(start);
+        let to = line_end_char_index(&text, end).saturating_sub(1).max(from);
+        doc.set_selection(ViewId::default(), Selection::single(from, to));
+
+        let mut view = View::new(DocumentId::default());
+        view.gutters = cx.editor.config.gutters.clone();
+        // + 1 for the statusline, which isn't rendered
+        view.area = Rect::new(inner.x, inner.y, inner.width, inner.height + 1);
+        view.first_line = start.saturating_sub(inner.height as usize / 2);
+
+        let viewport = Rect::new(
+            inner.x + view.gutter_offset(),
+            inner.y,
+            inner.width.saturating_sub(view.gutter_offset()),
+            inner.height,
+        );
+        EditorView::render_buffer(
+            doc,
+            &view,
+            viewport,
+            surface,
+            &cx.editor.theme,
+            false,
+            &cx.editor.syn_loader,
+            &cx.editor.config,
+            None,
+        );
+    }
+}
+
+impl<T: 'static> Component for FilePicker<T> {
+    fn handle_event(&mut self, event: Event, cx: &mut Context) -> EventResult {
+        self.picker.handle_event(event, cx)
+    }
+
+    fn render(&self, area: Rect, surface: &mut Surface, cx: &mut Context) {
+        // pick up the streamed options in a little while, the update will render again
+        if let Some(stream) = &self.stream {
+            if !stream.scheduled.replace(true) {
+                cx.jobs.callback(async {
+                    tokio::time::sleep(STREAM_INTERVAL).await;
+                    let call: job::Callback = Box::new(|_editor, compositor: &mut Compositor| {
+                        let picker = compositor
+                            .find(std::any::type_name::<Self>())
+                            .and_then(|component| component.as_any_mut().downcast_mut::<Self>());
+                        if let Some(picker) = picker {
+                            picker.update_stream();
+                        }
+                    });
+                    Ok(call)
+                });
+            }
+        }
+
+        let area = inner_rect(area);
+
+        if area.width < MIN_WIDTH_FOR_PREVIEW {
+            return self.picker.render_in(area, surface, cx);
+        }
+
+        // clear area
+        let background = cx.editor.theme.get("ui.background");
+        surface.clear_with(area, background);
+
+        let picker_width = area.width / 2;
+        let picker_area = Rect::new(area.x, area.y, picker_width, area.height);
+        let preview_area = Rect::new(
+            area.x + picker_width,
+            area.y,
+            area.width - picker_width,
+            area.height,
+        );
+
+        self.picker.render_in(picker_area, surface, cx);
+        self.render_preview(preview_area, surface, cx);
+    }
+
+    fn cursor(&self, area: Rect, editor: &Editor) -> (Option<Position>, CursorKind) {
+        let area = inner_rect(area);
+
+        if area.width < MIN_WIDTH_FOR_PREVIEW {
+            return self.picker.cursor_in(area, editor);
+        }
+
+        let picker_area = Rect::new(area.x, area.y, area.width / 2, area.height);
+        self.picker.cursor_in(picker_area, editor)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn names<T>(picker: &mut Picker<(&'static str, T)>) -> Vec<&'static str> {
+        let mut names = Vec::new();
+        picker.cursor = 0;
+        while let Some((name, _)) = picker.selection() {
+            names.push(*name);
+            if picker.cursor + 1 == picker.matches.len() {
+                break;
+            }
+            picker.move_down();
+        }
+        names
+    }
+
+    #[test]
+    fn order_options_with_the_same_score() {
+        let options = vec![("b", 1), ("a", 3), ("c", 2)];
+        let mut picker = Picker::new(options, |(name, _)| (*name).into(), |_, _, _| ());
+        assert_eq!(names(&mut picker), ["b", "a", "c"]);
+
+        // the most recent first, also among the options added later
+        let mut picker = picker.with_order(|(_, a), (_, b)| b.cmp(a));
+        assert_eq!(names(&mut picker), ["a", "c", "b"]);
+        picker.add_options(vec![("d", 0), ("e", 4)]);
+        assert_eq!(names(&mut picker), ["e", "a", "c", "b", "d"]);
+    }
+}
@@ -52,8 +52,9 @@ impl<T: Component> Component for Popup<T> {
     fn handle_event(&mut self, event: Event, cx: &mut Context) -> EventResult {
         let key = match event {
             Event::Key(event) => event,
-            Event::Resize(_, _) => {
-                // TODO: calculate inner area, call component's handle_event with that area
+            Event::Resize(width, height) => {
+                // fit the contents into the new size
+                self.required_size((width, height));
                 return EventResult::Ignored;
             }
             _ => return EventResult::Ignored,
@@ -72,11 +73,13 @@ impl<T: Component> Component for Popup<T> {
             | KeyEvent {
                 code: KeyCode::Char('c'),
                 modifiers: KeyModifiers::CONTROL,
+                ..
             } => close_fn,
 
             KeyEvent {
                 code: KeyCode::Char('d'),
                 modifiers: KeyModifiers::CONTROL,
+                ..
             } => {
                 self.scroll(self.size.1 as usize / 2, true);
                 EventResult::Consumed(None)
@@ -84,6 +87,7 @@ impl<T: Component> Component for Popup<T> {
             KeyEvent {
                 code: KeyCode::Char('u'),
                 modifiers: KeyModifiers::CONTROL,
+                ..
             } => {
                 self.scroll(self.size.1 as usize / 2, false);
                 EventResult::Consumed(None)
@@ -94,10 +98,12 @@ impl<T: Component> Component for Popup<T> {
         // tab/enter/ctrl-k or whatever will confirm the selection/ ctrl-n/ctrl-p for scroll.
     }
 
-    fn required_size(&mut self, _viewport: (u16, u16)) -> Option<(u16, u16)> {
+    fn required_size(&mut self, viewport: (u16, u16)) -> Option<(u16, u16)> {
+        // max width, max height
+        let max_size = (viewport.0.min(120), viewport.1.saturating_sub(1).min(26));
         let (width, height) = self
             .contents
-            .required_size((120, 26)) // max width, max height
+            .required_size(max_size)
             .expect("Component needs required_size implemented in order to be embedded in a popup");
 
         self.size = (width, height);
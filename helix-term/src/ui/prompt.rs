@@ -24,7 +24,7 @@ pub struct Prompt {
     history_pos: Option<usize>,
     completion_fn: Box<dyn FnMut(&str) -> Vec<Completion>>,
     callback_fn: Box<dyn FnMut(&mut Context, &str, PromptEvent)>,
-    pub doc_fn: Box<dyn Fn(&str) -> Option<&'static str>>,
+    pub doc_fn: Box<dyn Fn(&str) -> Option<Cow<'static, str>>>,
 }
 
 #[derive(Clone, Copy, PartialEq)]
@@ -207,6 +207,14 @@ impl Prompt {
         self.completion = (self.completion_fn)(&self.line);
     }
 
+    pub fn delete_char_forwards(&mut self) {
+        let pos = self.eval_movement(Movement::ForwardChar(1));
+        self.line.replace_range(self.cursor..pos, "");
+
+        self.exit_selection();
+        self.completion = (self.completion_fn)(&self.line);
+    }
+
     pub fn delete_word_backwards(&mut self) {
         let pos = self.eval_movement(Movement::BackwardWord(1));
         self.line.replace_range(pos..self.cursor, "");
@@ -216,6 +224,22 @@ impl Prompt {
         self.completion = (self.completion_fn)(&self.line);
     }
 
+    pub fn delete_word_forwards(&mut self) {
+        let pos = self.eval_movement(Movement::ForwardWord(1));
+        self.line.replace_range(self.cursor..pos, "");
+
+        self.exit_selection();
+        self.completion = (self.completion_fn)(&self.line);
+    }
+
+    pub fn kill_to_start_of_line(&mut self) {
+        self.line.replace_range(..self.cursor, "");
+        self.cursor = 0;
+
+        self.exit_selection();
+        self.completion = (self.completion_fn)(&self.line);
+    }
+
     pub fn kill_to_end_of_line(&mut self) {
         let pos = self.eval_movement(Movement::EndOfLine);
         self.line.replace_range(self.cursor..pos, "");
@@ -405,10 +429,12 @@ impl Component for Prompt {
             KeyEvent {
                 code: KeyCode::Char(c),
                 modifiers: KeyModifiers::NONE,
+                ..
             }
             | KeyEvent {
                 code: KeyCode::Char(c),
                 modifiers: KeyModifiers::SHIFT,
+                ..
             } => {
                 self.insert_char(c);
                 (self.callback_fn)(cx, &self.line, PromptEvent::Update);
@@ -416,6 +442,7 @@ impl Component for Prompt {
             KeyEvent {
                 code: KeyCode::Char('c'),
                 modifiers: KeyModifiers::CONTROL,
+                ..
             }
             | KeyEvent {
                 code: KeyCode::Esc, ..
@@ -426,22 +453,27 @@ impl Component for Prompt {
             KeyEvent {
                 code: KeyCode::Left,
                 modifiers: KeyModifiers::ALT,
+                ..
             }
             | KeyEvent {
                 code: KeyCode::Char('b'),
                 modifiers: KeyModifiers::ALT,
+                ..
             } => self.move_cursor(Movement::BackwardWord(1)),
             KeyEvent {
                 code: KeyCode::Right,
                 modifiers: KeyModifiers::ALT,
+                ..
             }
             | KeyEvent {
                 code: KeyCode::Char('f'),
                 modifiers: KeyModifiers::ALT,
+                ..
             } => self.move_cursor(Movement::ForwardWord(1)),
             KeyEvent {
                 code: KeyCode::Char('f'),
                 modifiers: KeyModifiers::CONTROL,
+                ..
             }
             | KeyEvent {
                 code: KeyCode::Right,
@@ -450,6 +482,7 @@ impl Component for Prompt {
             KeyEvent {
                 code: KeyCode::Char('b'),
                 modifiers: KeyModifiers::CONTROL,
+                ..
             }
             | KeyEvent {
                 code: KeyCode::Left,
@@ -458,30 +491,77 @@ impl Component for Prompt {
             KeyEvent {
                 code: KeyCode::End,
                 modifiers: KeyModifiers::NONE,
+                ..
             }
             | KeyEvent {
                 code: KeyCode::Char('e'),
                 modifiers: KeyModifiers::CONTROL,
+                ..
             } => self.move_end(),
             KeyEvent {
                 code: KeyCode::Home,
                 modifiers: KeyModifiers::NONE,
+                ..
             }
             | KeyEvent {
                 code: KeyCode::Char('a'),
                 modifiers: KeyModifiers::CONTROL,
+                ..
             } => self.move_start(),
             KeyEvent {
                 code: KeyCode::Char('w'),
                 modifiers: KeyModifiers::CONTROL,
-            } => self.delete_word_backwards(),
+                ..
+            }
+            | KeyEvent {
+                code: KeyCode::Backspace,
+                modifiers: KeyModifiers::ALT,
+                ..
+            } => {
+                self.delete_word_backwards();
+                (self.callback_fn)(cx, &self.line, PromptEvent::Update);
+            }
+            KeyEvent {
+                code: KeyCode::Char('d'),
+                modifiers: KeyModifiers::ALT,
+                ..
+            } => {
+                self.delete_word_forwards();
+                (self.callback_fn)(cx, &self.line, PromptEvent::Update);
+            }
+            KeyEvent {
+                code: KeyCode::Char('u'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            } => {
+                self.kill_to_start_of_line();
+                (self.callback_fn)(cx, &self.line, PromptEvent::Update);
+            }
             KeyEvent {
                 code: KeyCode::Char('k'),
                 modifiers: KeyModifiers::CONTROL,
-            } => self.kill_to_end_of_line(),
+                ..
+            } => {
+                self.kill_to_end_of_line();
+                (self.callback_fn)(cx, &self.line, PromptEvent::Update);
+            }
+            KeyEvent {
+                code: KeyCode::Char('d'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            }
+            | KeyEvent {
+                code: KeyCode::Delete,
+                modifiers: KeyModifiers::NONE,
+                ..
+            } => {
+                self.delete_char_forwards();
+                (self.callback_fn)(cx, &self.line, PromptEvent::Update);
+            }
             KeyEvent {
                 code: KeyCode::Backspace,
                 modifiers: KeyModifiers::NONE,
+                ..
             } => {
                 self.delete_char_backwards();
                 (self.callback_fn)(cx, &self.line, PromptEvent::Update);
@@ -497,9 +577,11 @@ impl Component for Prompt {
                     (self.callback_fn)(cx, &self.line, PromptEvent::Validate);
 
                     if let Some(register) = self.history_register {
-                        // store in history
+                        // store in history, unless it's a repeat of the last entry
                         let register = cx.editor.registers.get_mut(register);
-                        register.push(self.line.clone());
+                        if register.read().last() != Some(&self.line) {
+                            register.push(self.line.clone());
+                        }
                     }
                     return close_fn;
                 }
@@ -507,6 +589,7 @@ impl Component for Prompt {
             KeyEvent {
                 code: KeyCode::Char('p'),
                 modifiers: KeyModifiers::CONTROL,
+                ..
             }
             | KeyEvent {
                 code: KeyCode::Up, ..
@@ -519,6 +602,7 @@ impl Component for Prompt {
             KeyEvent {
                 code: KeyCode::Char('n'),
                 modifiers: KeyModifiers::CONTROL,
+                ..
             }
             | KeyEvent {
                 code: KeyCode::Down,
@@ -539,6 +623,7 @@ impl Component for Prompt {
             KeyEvent {
                 code: KeyCode::Char('q'),
                 modifiers: KeyModifiers::CONTROL,
+                ..
             } => self.exit_selection(),
             _ => (),
         };
@@ -1,7 +1,7 @@
 use crate::{backend::Backend, buffer::Cell};
 use crossterm::{
     cursor::{CursorShape, Hide, MoveTo, SetCursorShape, Show},
-    execute, queue,
+    queue,
     style::{
         Attribute as CAttribute, Color as CColor, Print, SetAttribute, SetBackgroundColor,
         SetForegroundColor,
@@ -86,7 +86,7 @@ where
     }
 
     fn hide_cursor(&mut self) -> io::Result<()> {
-        map_error(execute!(self.buffer, Hide))
+        map_error(queue!(self.buffer, Hide))
     }
 
     fn show_cursor(&mut self, kind: CursorKind) -> io::Result<()> {
@@ -96,7 +96,7 @@ where
             CursorKind::Underline => CursorShape::UnderScore,
             CursorKind::Hidden => unreachable!(),
         };
-        map_error(execute!(self.buffer, Show, SetCursorShape(shape)))
+        map_error(queue!(self.buffer, Show, SetCursorShape(shape)))
     }
 
     fn get_cursor(&mut self) -> io::Result<(u16, u16)> {
@@ -105,11 +105,11 @@ where
     }
 
     fn set_cursor(&mut self, x: u16, y: u16) -> io::Result<()> {
-        map_error(execute!(self.buffer, MoveTo(x, y)))
+        map_error(queue!(self.buffer, MoveTo(x, y)))
     }
 
     fn clear(&mut self) -> io::Result<()> {
-        map_error(execute!(self.buffer, Clear(ClearType::All)))
+        map_error(queue!(self.buffer, Clear(ClearType::All)))
     }
 
     fn size(&self) -> io::Result<Rect> {
@@ -12,6 +12,7 @@ pub use self::crossterm::CrosstermBackend;
 mod test;
 pub use self::test::TestBackend;
 
+/// A terminal to draw to. Output may be buffered until [`Backend::flush`] is called.
 pub trait Backend {
     fn draw<'a, I>(&mut self, content: I) -> Result<(), io::Error>
     where
@@ -47,6 +47,9 @@ where
     current: usize,
     /// Whether the cursor is currently hidden
     hidden_cursor: bool,
+    /// The cursor position and kind set by the last draw call, they're only sent again when
+    /// they change or cells were drawn
+    last_cursor: Option<(Option<(u16, u16)>, CursorKind)>,
     /// Viewport
     viewport: Viewport,
 }
@@ -58,7 +61,10 @@ where
     fn drop(&mut self) {
         // Attempt to restore the cursor state
         if self.hidden_cursor {
-            if let Err(err) = self.show_cursor(CursorKind::Block) {
+            if let Err(err) = self
+                .show_cursor(CursorKind::Block)
+                .and_then(|_| self.backend.flush())
+            {
                 eprintln!("Failed to show the cursor: {}", err);
             }
         }
@@ -94,6 +100,7 @@ where
             ],
             current: 0,
             hidden_cursor: false,
+            last_cursor: None,
             viewport: options.viewport,
         })
     }
@@ -121,10 +128,19 @@ where
     /// Obtains a difference between the previous and the current buffer and passes it to the
     /// current backend for drawing.
     pub fn flush(&mut self) -> io::Result<()> {
+        self.flush_changes().map(|_| ())
+    }
+
+    /// Like [`Terminal::flush`], returns whether any cell changed.
+    fn flush_changes(&mut self) -> io::Result<bool> {
         let previous_buffer = &self.buffers[1 - self.current];
         let current_buffer = &self.buffers[self.current];
         let updates = previous_buffer.diff(current_buffer);
-        self.backend.draw(updates.into_iter())
+        if updates.is_empty() {
+            return Ok(false);
+        }
+        self.backend.draw(updates.into_iter())?;
+        Ok(true)
     }
 
     /// Updates the Terminal so that internal buffers match the requested size. Requested size will
@@ -165,15 +181,20 @@ where
         // let cursor_position = frame.cursor_position;
 
         // Draw to stdout
-        self.flush()?;
+        let changed = self.flush_changes()?;
 
-        if let Some((x, y)) = cursor_position {
-            self.set_cursor(x, y)?;
-        }
+        // drawing moves the cursor
+        let cursor = (cursor_position, cursor_kind);
+        if changed || self.last_cursor != Some(cursor) {
+            if let Some((x, y)) = cursor_position {
+                self.set_cursor(x, y)?;
+            }
 
-        match cursor_kind {
-            CursorKind::Hidden => self.hide_cursor()?,
-            kind => self.show_cursor(kind)?,
+            match cursor_kind {
+                CursorKind::Hidden => self.hide_cursor()?,
+                kind => self.show_cursor(kind)?,
+            }
+            self.last_cursor = Some(cursor);
         }
 
         // Swap buffers
@@ -210,6 +231,7 @@ where
         self.backend.clear()?;
         // Reset the back buffer to make sure the next update will redraw everything.
         self.buffers[1 - self.current].reset();
+        self.last_cursor = None;
         Ok(())
     }
 
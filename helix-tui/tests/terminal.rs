@@ -1,7 +1,9 @@
 use helix_tui::{
     backend::{Backend, TestBackend},
+    buffer::{Buffer, Cell},
     Terminal,
 };
+use helix_view::graphics::{CursorKind, Style};
 
 #[test]
 fn terminal_buffer_size_should_be_limited() {
@@ -31,3 +33,32 @@ fn terminal_buffer_size_should_be_limited() {
 //     assert_eq!(frame.area, Rect::new(0, 0, 8, 8));
 //     Ok(())
 // }
+
+#[test]
+fn terminal_draw_only_writes_changed_cells() {
+    let backend = TestBackend::new(10, 2);
+    let mut terminal = Terminal::new(backend).unwrap();
+
+    terminal
+        .current_buffer_mut()
+        .set_string(0, 0, "hello", Style::default());
+    terminal.draw(Some((5, 0)), CursorKind::Block).unwrap();
+    terminal
+        .backend()
+        .assert_buffer(&Buffer::with_lines(vec!["hello     ", "          "]));
+
+    // cells that didn't change since the last frame aren't written again
+    let mut cell = Cell::default();
+    cell.set_symbol("x");
+    terminal
+        .backend_mut()
+        .draw(std::iter::once((9, 1, &cell)))
+        .unwrap();
+    terminal
+        .current_buffer_mut()
+        .set_string(0, 0, "help", Style::default());
+    terminal.draw(Some((4, 0)), CursorKind::Block).unwrap();
+    terminal
+        .backend()
+        .assert_buffer(&Buffer::with_lines(vec!["help      ", "         x"]));
+}
@@ -1,5 +1,6 @@
 // Implementation reference: https://github.com/neovim/neovim/blob/f2906a4669a2eef6d7bf86a29648793d63c98949/runtime/autoload/provider/clipboard.vim#L68-L152
 
+use crate::editor::Config;
 use anyhow::Result;
 use std::borrow::Cow;
 
@@ -24,7 +25,7 @@ macro_rules! command_provider {
     }};
 }
 
-pub fn get_clipboard_provider() -> Box<dyn ClipboardProvider> {
+pub fn get_clipboard_provider(config: &Config) -> Box<dyn ClipboardProvider> {
     // TODO: support for user-defined provider, probably when we have plugin support by setting a
     // variable?
 
@@ -80,6 +81,11 @@ pub fn get_clipboard_provider() -> Box<dyn ClipboardProvider> {
         #[cfg(target_os = "windows")]
         return Box::new(provider::WindowsProvider);
 
+        #[cfg(not(target_os = "windows"))]
+        if config.osc52_clipboard {
+            return Box::new(provider::TerminalProvider::new(config.osc52_max_size));
+        }
+
         #[cfg(not(target_os = "windows"))]
         return Box::new(provider::NopProvider);
     }
@@ -106,6 +112,7 @@ mod provider {
     use super::ClipboardProvider;
     use anyhow::{bail, Context as _, Result};
     use std::borrow::Cow;
+    use std::sync::Mutex;
 
     #[derive(Debug)]
     pub struct NopProvider;
@@ -124,6 +131,72 @@ mod provider {
         }
     }
 
+    /// Copies to the clipboard of the terminal with an OSC 52 escape sequence, which works
+    /// through SSH and tmux. Terminals rarely let programs read their clipboard, so pasting gives
+    /// back what was last copied from the editor.
+    #[derive(Debug)]
+    pub struct TerminalProvider {
+        contents: Mutex<String>,
+        max_size: usize,
+    }
+
+    impl TerminalProvider {
+        pub fn new(max_size: usize) -> Self {
+            Self {
+                contents: Mutex::new(String::new()),
+                max_size,
+            }
+        }
+    }
+
+    impl ClipboardProvider for TerminalProvider {
+        fn name(&self) -> Cow<'_, str> {
+            Cow::Borrowed("termcode")
+        }
+
+        fn get_contents(&self) -> Result<String> {
+            Ok(self.contents.lock().unwrap().clone())
+        }
+
+        fn set_contents(&self, contents: String) -> Result<()> {
+            use std::io::Write;
+
+            if contents.len() > self.max_size {
+                bail!(
+                    "{} bytes are too many for the terminal clipboard, the maximum is {}",
+                    contents.len(),
+                    self.max_size
+                );
+            }
+            let mut stdout = std::io::stdout();
+            write!(stdout, "\x1b]52;c;{}\x07", base64(contents.as_bytes()))?;
+            stdout.flush()?;
+            *self.contents.lock().unwrap() = contents;
+            Ok(())
+        }
+    }
+
+    /// Encodes `bytes` in padded base64, the encoding of OSC 52.
+    pub(super) fn base64(bytes: &[u8]) -> String {
+        const ALPHABET: &[u8; 64] =
+            b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+        let mut encoded = String::with_capacity(bytes.len() * 4 / 3 + 4);
+        for chunk in bytes.chunks(3) {
+            let n = (chunk[0] as u32) << 16
+                | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+                | *chunk.get(2).unwrap_or(&0) as u32;
+            for i in 0..4 {
+                if i <= chunk.len() {
+                    encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+                } else {
+                    encoded.push('=');
+                }
+            }
+        }
+        encoded
+    }
+
     #[cfg(target_os = "windows")]
     #[derive(Debug)]
     pub struct WindowsProvider;
@@ -216,3 +289,18 @@ mod provider {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::provider::base64;
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+        assert_eq!(base64("héllo\n".as_bytes()), "aMOpbGxvCg==");
+    }
+}
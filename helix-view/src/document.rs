@@ -4,21 +4,32 @@ use std::cell::Cell;
 use std::collections::HashMap;
 use std::fmt::Display;
 use std::future::Future;
+use std::ops::Range;
 use std::path::{Component, Path, PathBuf};
 use std::str::FromStr;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
 
+use futures_util::future::{BoxFuture, FutureExt};
 use helix_core::{
     chars::{char_is_line_ending, char_is_whitespace},
+    diff::{self, Hunk, LineDiff},
+    editorconfig::EditorConfig,
+    fold::Fold,
     history::History,
-    line_ending::auto_detect_line_ending,
-    syntax::{self, LanguageConfiguration},
-    ChangeSet, Diagnostic, LineEnding, Rope, RopeBuilder, Selection, State, Syntax, Transaction,
-    DEFAULT_LINE_ENDING,
+    line_ending::{auto_detect_line_ending, get_line_ending, line_end_char_index},
+    spelling::{self, Dictionary},
+    syntax::{self, HighlightEvent, LanguageConfiguration},
+    Annotation, Assoc, ChangeSet, Diagnostic, LineEnding, Operation, Rope, RopeBuilder, Selection,
+    State, Syntax, Tendril, Transaction, DEFAULT_LINE_ENDING,
 };
 use helix_lsp::util::LspFormatting;
 
-use crate::{DocumentId, Theme, ViewId};
+use crate::{
+    events::{self, Event},
+    DocumentId, Theme, ViewId,
+};
 
 const BUF_SIZE: usize = 8192;
 
@@ -76,13 +87,27 @@ pub struct Document {
 
     path: Option<PathBuf>,
     encoding: &'static encoding_rs::Encoding,
+    /// Modification time of the file when it was last read or written. Shared with the save
+    /// future, which updates it once the file has been written.
+    mtime: Arc<Mutex<Option<SystemTime>>>,
 
     /// Current editing mode.
     pub mode: Mode,
     pub restore_cursor: bool,
+    /// Whether the document is reloaded as its file grows on disk, like `tail -f`.
+    pub follow: bool,
+    /// Whether edits are refused, for files opened with `:view` or that can't be written, and
+    /// buffers generated by the editor.
+    pub readonly: bool,
+    /// Whether an edit was refused since the last [`Document::take_refused_edit`].
+    refused_edit: bool,
 
     /// Current indent style.
     pub indent_style: IndentStyle,
+    /// Display width of tabs set for this document, overriding the language's.
+    pub tab_width_override: Option<usize>,
+    /// The settings of the `.editorconfig` files above the document.
+    pub editor_config: EditorConfig,
 
     /// The document's default line ending.
     pub line_ending: LineEnding,
@@ -93,6 +118,9 @@ pub struct Document {
 
     /// Pending changes since last history commit.
     changes: ChangeSet,
+    /// Annotation of the pending changes, deciding whether they're grouped with the previous
+    /// revision. Unannotated changes, like applying a completion, don't change it.
+    changes_annotation: Option<Annotation>,
     /// State at last commit. Used for calculating reverts.
     old_state: Option<State>,
     /// Undo tree.
@@ -105,6 +133,111 @@ pub struct Document {
 
     diagnostics: Vec<Diagnostic>,
     language_server: Option<Arc<helix_lsp::Client>>,
+
+    /// Tabstops of the last expanded snippet that weren't visited yet, mapped through changes.
+    snippet_tabstops: Vec<Selection>,
+
+    /// Contents the document is compared to for the diff gutter, usually the file at the git
+    /// `HEAD`.
+    diff_base: Option<Rope>,
+    /// Hunks of lines that differ from `diff_base`.
+    hunks: Vec<Hunk>,
+    /// How the lines differ from `diff_base`, ordered by line.
+    line_diffs: Vec<(usize, LineDiff)>,
+    /// Whether the document changed since `line_diffs` were computed.
+    diff_outdated: bool,
+    /// Char ranges of the misspelled words.
+    misspellings: Vec<std::ops::Range<usize>>,
+    /// The version `misspellings` were computed for, `None` until they are.
+    spelling_version: Option<i32>,
+    /// Char ranges changed since, whose lines are checked again.
+    spelling_changes: Vec<std::ops::Range<usize>>,
+    /// The folded lines of each view, kept over the same lines through changes.
+    folds: HashMap<ViewId, Vec<Fold>>,
+
+    /// The highlights of the current version computed in the background for each view.
+    highlights: HashMap<ViewId, Highlights>,
+    /// The version and byte range being highlighted in the background for each view, and the
+    /// flag that cancels it.
+    pending_highlights: HashMap<ViewId, (i32, Range<usize>, Arc<AtomicUsize>)>,
+
+    /// Why formatting before the last save failed, the document being saved as is.
+    format_error: Option<String>,
+}
+
+/// Highlight events computed in the background for a byte range of one version of the text.
+#[derive(Debug, Clone)]
+pub struct Highlights {
+    pub version: i32,
+    pub range: Range<usize>,
+    pub events: Arc<Vec<HighlightEvent>>,
+}
+
+/// Changes formatting a document, computed in the background.
+#[derive(Debug, Clone)]
+pub enum Formatting {
+    Lsp(LspFormatting),
+    /// The output of an external formatter, applied as the difference with the text it was given.
+    External {
+        doc: Rope,
+        formatted: Rope,
+    },
+}
+
+impl From<Formatting> for Transaction {
+    fn from(formatting: Formatting) -> Transaction {
+        let transaction: Transaction = match formatting {
+            Formatting::Lsp(formatting) => formatting.into(),
+            Formatting::External { doc, formatted } => diff::compare_ropes(&doc, &formatted),
+        };
+        transaction.with_annotation(Annotation::Format)
+    }
+}
+
+/// Pipes `text` through the formatter `command`, run from the directory `cwd`, returning its
+/// output.
+async fn run_formatter(
+    command: &str,
+    args: &[String],
+    cwd: Option<&Path>,
+    text: &Rope,
+) -> anyhow::Result<Rope> {
+    use std::process::Stdio;
+    use tokio::io::AsyncWriteExt;
+
+    let mut process = tokio::process::Command::new(command);
+    process
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if let Some(cwd) = cwd {
+        process.current_dir(cwd);
+    }
+    let mut process = process
+        .spawn()
+        .with_context(|| format!("failed to run `{}`", command))?;
+
+    let mut stdin = process.stdin.take().unwrap();
+    let input = text.to_string();
+    // write concurrently so a formatter writing before it read everything can't deadlock us
+    let (written, output) = tokio::join!(
+        async move { stdin.write_all(input.as_bytes()).await },
+        process.wait_with_output()
+    );
+    let output = output?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "`{}` {}: {}",
+            command,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    written?;
+    let formatted = String::from_utf8(output.stdout)
+        .with_context(|| format!("`{}` printed invalid UTF-8", command))?;
+    Ok(Rope::from(formatted))
 }
 
 use std::fmt;
@@ -158,11 +291,14 @@ pub fn from_reader<R: std::io::Read + ?Sized>(
     let (encoding, mut decoder, mut slice, mut is_empty) = {
         let read = reader.read(&mut buf)?;
         let is_empty = read == 0;
-        let encoding = encoding.unwrap_or_else(|| {
-            let mut encoding_detector = chardetng::EncodingDetector::new();
-            encoding_detector.feed(&buf, is_empty);
-            encoding_detector.guess(None, true)
-        });
+        // A byte order mark takes precedence over the detector, which doesn't know about UTF-16.
+        let encoding = encoding
+            .or_else(|| encoding_rs::Encoding::for_bom(&buf[..read]).map(|(encoding, _)| encoding))
+            .unwrap_or_else(|| {
+                let mut encoding_detector = chardetng::EncodingDetector::new();
+                encoding_detector.feed(&buf, is_empty);
+                encoding_detector.guess(None, true)
+            });
         let decoder = encoding.new_decoder();
 
         // If the amount of bytes read from the reader is less than
@@ -260,6 +396,28 @@ pub async fn to_writer<'a, W: tokio::io::AsyncWriteExt + Unpin + ?Sized>(
         .chunks()
         .filter(|c| !c.is_empty())
         .chain(std::iter::once(""));
+
+    // encoding_rs can decode UTF-16 but only encodes it as UTF-8, so it's handled here. The byte
+    // order mark that was stripped when decoding is written back.
+    if encoding == encoding_rs::UTF_16LE || encoding == encoding_rs::UTF_16BE {
+        let little_endian = encoding == encoding_rs::UTF_16LE;
+        let to_bytes = |unit: u16| {
+            if little_endian {
+                unit.to_le_bytes()
+            } else {
+                unit.to_be_bytes()
+            }
+        };
+
+        writer.write_all(&to_bytes(0xFEFF)).await?;
+        for chunk in iter {
+            let bytes: Vec<u8> = chunk.encode_utf16().flat_map(to_bytes).collect();
+            writer.write_all(&bytes).await?;
+        }
+        writer.flush().await?;
+        return Ok(());
+    }
+
     let mut buf = [0u8; BUF_SIZE];
     let mut encoder = encoding.new_encoder();
     let mut total_written = 0usize;
@@ -306,6 +464,12 @@ pub async fn to_writer<'a, W: tokio::io::AsyncWriteExt + Unpin + ?Sized>(
     Ok(())
 }
 
+fn file_mtime(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
 /// Inserts the final line ending into `rope` if it's missing. [Why?](https://stackoverflow.com/questions/729692/why-should-text-files-end-with-a-newline)
 pub fn with_line_ending(rope: &mut Rope) -> LineEnding {
     // search for line endings
@@ -402,11 +566,13 @@ pub fn normalize_path(path: &Path) -> PathBuf {
     ret
 }
 
-/// Returns the canonical, absolute form of a path with all intermediate components normalized.
+/// Returns the canonical, absolute form of a path with all intermediate components normalized
+/// and a leading `~` expanded to the home directory.
 ///
 /// This function is used instead of `std::fs::canonicalize` because we don't want to verify
 /// here if the path exists, just normalize it's components.
 pub fn canonicalize_path(path: &Path) -> std::io::Result<PathBuf> {
+    let path = expand_tilde(path);
     let path = if path.is_relative() {
         std::env::current_dir().map(|current_dir| current_dir.join(path))?
     } else {
@@ -429,24 +595,54 @@ impl Document {
             id: DocumentId::default(),
             path: None,
             encoding,
+            mtime: Arc::new(Mutex::new(None)),
             text,
             selections: HashMap::default(),
             indent_style: IndentStyle::Spaces(4),
+            tab_width_override: None,
+            editor_config: EditorConfig::default(),
             mode: Mode::Normal,
             restore_cursor: false,
+            follow: false,
+            readonly: false,
+            refused_edit: false,
             syntax: None,
             language: None,
             changes,
+            changes_annotation: None,
             old_state,
             diagnostics: Vec::new(),
+            diff_base: None,
+            hunks: Vec::new(),
+            line_diffs: Vec::new(),
+            diff_outdated: false,
+            misspellings: Vec::new(),
+            spelling_version: None,
+            spelling_changes: Vec::new(),
+            folds: HashMap::new(),
+            highlights: HashMap::new(),
+            pending_highlights: HashMap::new(),
+            format_error: None,
             version: 0,
             history: Cell::new(History::default()),
             last_saved_revision: 0,
             language_server: None,
             line_ending: DEFAULT_LINE_ENDING,
+            snippet_tabstops: Vec::new(),
         }
     }
 
+    /// Create a scratch document from what's read from `reader`, like a pipe. Encoding is
+    /// auto-detected.
+    pub fn from_stream<R: std::io::Read + ?Sized>(reader: &mut R) -> Result<Self, Error> {
+        let (mut rope, encoding) = from_reader(reader, None)?;
+        let line_ending = with_line_ending(&mut rope);
+        let mut doc = Self::from(rope, Some(encoding));
+        doc.detect_indent_style();
+        doc.line_ending = line_ending;
+        Ok(doc)
+    }
+
     // TODO: async fn?
     /// Create a new document from `path`. Encoding is auto-detected, but it can be manually
     /// overwritten with the `encoding` parameter.
@@ -465,7 +661,12 @@ impl Document {
             (Rope::from(DEFAULT_LINE_ENDING.as_str()), encoding)
         };
 
-        let line_ending = with_line_ending(&mut rope);
+        let editor_config = EditorConfig::find(&path);
+        let line_ending = if editor_config.insert_final_newline == Some(false) {
+            auto_detect_line_ending(&rope).unwrap_or(DEFAULT_LINE_ENDING)
+        } else {
+            with_line_ending(&mut rope)
+        };
         let mut doc = Self::from(rope, Some(encoding));
 
         // set the path and try detecting the language
@@ -477,13 +678,50 @@ impl Document {
         // Detect indentation style and set line ending.
         doc.detect_indent_style();
         doc.line_ending = line_ending;
+        doc.apply_editor_config(editor_config);
+        doc.set_mtime(file_mtime(&path));
+        doc.readonly =
+            matches!(std::fs::metadata(&path), Ok(metadata) if metadata.permissions().readonly());
 
         Ok(doc)
     }
 
+    /// Applies the indentation and line ending of `config`, over the detected ones, and keeps it
+    /// for the settings applied on save.
+    pub fn apply_editor_config(&mut self, config: EditorConfig) {
+        use helix_core::editorconfig::IndentStyle as Style;
+
+        let indent_size = config
+            .indent_size
+            .or(config.tab_width)
+            .map(|size| size.clamp(1, 8) as u8);
+        match (config.indent_style, self.indent_style) {
+            (Some(Style::Tab), _) => self.indent_style = IndentStyle::Tabs,
+            (Some(Style::Space), IndentStyle::Spaces(detected)) => {
+                self.indent_style = IndentStyle::Spaces(indent_size.unwrap_or(detected))
+            }
+            (Some(Style::Space), IndentStyle::Tabs) => {
+                self.indent_style = IndentStyle::Spaces(indent_size.unwrap_or(4))
+            }
+            (None, IndentStyle::Spaces(_)) => {
+                if let Some(size) = indent_size {
+                    self.indent_style = IndentStyle::Spaces(size);
+                }
+            }
+            (None, IndentStyle::Tabs) => {}
+        }
+        if let Some(tab_width) = config.tab_width.or(config.indent_size) {
+            self.tab_width_override = Some(tab_width.clamp(1, 16));
+        }
+        if let Some(line_ending) = config.line_ending {
+            self.line_ending = line_ending;
+        }
+        self.editor_config = config;
+    }
+
     /// The same as [`format`], but only returns formatting changes if auto-formatting
     /// is configured.
-    pub fn auto_format(&self) -> Option<impl Future<Output = LspFormatting> + 'static> {
+    pub fn auto_format(&self) -> Option<BoxFuture<'static, anyhow::Result<Formatting>>> {
         if self.language_config().map(|c| c.auto_format) == Some(true) {
             self.format()
         } else {
@@ -492,38 +730,70 @@ impl Document {
     }
 
     /// If supported, returns the changes that should be applied to this document in order
-    /// to format it nicely.
-    pub fn format(&self) -> Option<impl Future<Output = LspFormatting> + 'static> {
-        if let Some(language_server) = self.language_server.clone() {
+    /// to format it nicely. The formatter of the language takes precedence over the language
+    /// server. It runs from the directory of the document, with `{path}` in its arguments
+    /// replaced by the path of the document.
+    pub fn format(&self) -> Option<BoxFuture<'static, anyhow::Result<Formatting>>> {
+        if let Some(formatter) = self
+            .language_config()
+            .and_then(|config| config.formatter.as_ref())
+        {
+            let text = self.text.clone();
+            let command = formatter.command.clone();
+            let path = self
+                .path
+                .as_ref()
+                .map(|path| path.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let args: Vec<_> = formatter
+                .args
+                .iter()
+                .map(|arg| arg.replace("{path}", &path))
+                .collect();
+            let cwd = self
+                .path
+                .as_ref()
+                .and_then(|path| path.parent())
+                .map(Path::to_path_buf);
+            let fut = async move {
+                let formatted = run_formatter(&command, &args, cwd.as_deref(), &text).await?;
+                Ok(Formatting::External {
+                    doc: text,
+                    formatted,
+                })
+            };
+            Some(fut.boxed())
+        } else if let Some(language_server) = self.language_server.clone() {
             let text = self.text.clone();
             let id = self.identifier();
             let fut = async move {
                 let edits = language_server
                     .text_document_formatting(id, lsp::FormattingOptions::default(), None)
-                    .await
-                    .unwrap_or_else(|e| {
-                        log::warn!("LSP formatting failed: {}", e);
-                        Default::default()
-                    });
-                LspFormatting {
+                    .await?;
+                Ok(Formatting::Lsp(LspFormatting {
                     doc: text,
                     edits,
                     offset_encoding: language_server.offset_encoding(),
-                }
+                }))
             };
-            Some(fut)
+            Some(fut.boxed())
         } else {
             None
         }
     }
 
+    /// Why formatting the document failed when it was last about to be saved, if it did.
+    pub fn take_format_error(&mut self) -> Option<String> {
+        self.format_error.take()
+    }
+
     pub fn save(&mut self) -> impl Future<Output = Result<(), anyhow::Error>> {
         self.save_impl::<futures_util::future::Ready<_>>(None)
     }
 
     pub fn format_and_save(
         &mut self,
-        formatting: Option<impl Future<Output = LspFormatting>>,
+        formatting: Option<impl Future<Output = Formatting>>,
     ) -> impl Future<Output = anyhow::Result<()>> {
         self.save_impl(formatting)
     }
@@ -534,7 +804,7 @@ impl Document {
     /// at its `path()`.
     ///
     /// If `formatting` is present, it supplies some changes that we apply to the text before saving.
-    fn save_impl<F: Future<Output = LspFormatting>>(
+    fn save_impl<F: Future<Output = Formatting>>(
         &mut self,
         formatting: Option<F>,
     ) -> impl Future<Output = Result<(), anyhow::Error>> {
@@ -552,6 +822,7 @@ impl Document {
         self.reset_modified();
 
         let encoding = self.encoding;
+        let mtime = self.mtime.clone();
 
         // We encode the file according to the `Document`'s encoding.
         async move {
@@ -574,8 +845,10 @@ impl Document {
                 }
             }
 
-            let mut file = File::create(path).await?;
+            let mut file = File::create(&path).await?;
             to_writer(&mut file, encoding, &text).await?;
+            *mtime.lock().unwrap() = file_mtime(&path);
+            log::info!("wrote {}", path.display());
 
             if let Some(language_server) = language_server {
                 language_server
@@ -604,22 +877,39 @@ impl Document {
             return Err(anyhow!("can't find file to reload from"));
         }
 
-        let mut file = std::fs::File::open(path.unwrap())?;
+        let path = path.unwrap();
+        let mut file = std::fs::File::open(path)?;
         let (mut rope, ..) = from_reader(&mut file, Some(encoding))?;
         let line_ending = with_line_ending(&mut rope);
+        let mtime = file_mtime(path);
 
         let transaction = helix_core::diff::compare_ropes(self.text(), &rope);
-        self.apply(&transaction, view_id);
+        // the file changing isn't an edit, read-only documents follow it too
+        self.apply_unchecked(&transaction, view_id);
         self.append_changes_to_history(view_id);
         self.reset_modified();
 
         // Detect indentation style and set line ending.
         self.detect_indent_style();
         self.line_ending = line_ending;
+        self.set_mtime(mtime);
 
         Ok(())
     }
 
+    fn set_mtime(&mut self, mtime: Option<SystemTime>) {
+        *self.mtime.lock().unwrap() = mtime;
+    }
+
+    /// Whether the file on disk was modified since the document last read or wrote it.
+    pub fn is_modified_externally(&self) -> bool {
+        let mtime = *self.mtime.lock().unwrap();
+        match (mtime, self.path.as_deref().and_then(file_mtime)) {
+            (Some(known), Some(current)) => current != known,
+            _ => false,
+        }
+    }
+
     /// Sets the [`Document`]'s encoding with the encoding correspondent to `label`.
     pub fn set_encoding(&mut self, label: &str) -> Result<(), Error> {
         match encoding_rs::Encoding::for_label(label.as_bytes()) {
@@ -635,6 +925,15 @@ impl Document {
     }
 
     fn detect_indent_style(&mut self) {
+        let indent_config = self
+            .language
+            .as_ref()
+            .and_then(|config| config.indent.as_ref());
+        if matches!(indent_config, Some(config) if config.enforce) {
+            self.indent_style = self.language_indent_style();
+            return;
+        }
+
         // Build a histogram of the indentation *increases* between
         // subsequent lines, ignoring lines that are all whitespace.
         //
@@ -746,17 +1045,22 @@ impl Document {
             };
         } else {
             // Fall back to language-based setting.
-            let indent = self
-                .language
-                .as_ref()
-                .and_then(|config| config.indent.as_ref())
-                .map_or("  ", |config| config.unit.as_str()); // fallback to 2 spaces
+            self.indent_style = self.language_indent_style();
+        }
+    }
 
-            self.indent_style = if indent.starts_with(' ') {
-                IndentStyle::Spaces(indent.len() as u8)
-            } else {
-                IndentStyle::Tabs
-            };
+    /// The indent style configured for the document's language.
+    fn language_indent_style(&self) -> IndentStyle {
+        let indent = self
+            .language
+            .as_ref()
+            .and_then(|config| config.indent.as_ref())
+            .map_or("  ", |config| config.unit.as_str()); // fallback to 2 spaces
+
+        if indent.starts_with(' ') {
+            IndentStyle::Spaces(indent.len() as u8)
+        } else {
+            IndentStyle::Tabs
         }
     }
 
@@ -766,6 +1070,8 @@ impl Document {
         // if parent doesn't exist we still want to open the document
         // and error out when document is saved
         self.path = Some(path);
+        // whatever is at the new path wasn't read by us
+        self.set_mtime(None);
 
         Ok(())
     }
@@ -780,6 +1086,7 @@ impl Document {
             if let Some(highlight_config) = language_config.highlight_config(scopes) {
                 let syntax = Syntax::new(&self.text, highlight_config);
                 self.syntax = Some(syntax);
+                self.reset_highlights();
                 // TODO: config.configure(scopes) is now delayed, is that ok?
             }
 
@@ -806,8 +1113,22 @@ impl Document {
     }
 
     pub fn set_selection(&mut self, view_id: ViewId, selection: Selection) {
+        // the folds hiding a cursor, moved there by a search or a jump, are opened
+        if let Some(folds) = self.folds.get_mut(&view_id) {
+            let text = self.text.slice(..);
+            let lines: Vec<_> = selection
+                .iter()
+                .map(|range| text.char_to_line(range.head))
+                .collect();
+            folds.retain(|fold| {
+                !lines
+                    .iter()
+                    .any(|line| *fold.start() < *line && line <= fold.end())
+            });
+        }
         // TODO: use a transaction?
         self.selections.insert(view_id, selection);
+        events::notify(Event::SelectionChanged { doc: self, view_id });
     }
 
     fn apply_impl(&mut self, transaction: &Transaction, view_id: ViewId) -> bool {
@@ -823,11 +1144,43 @@ impl Document {
                 .cloned()
                 .unwrap_or_else(|| self.selection(view_id).clone().map(transaction.changes()));
             self.set_selection(view_id, selection);
+
+            // the other views showing the document keep their selection over the same text
+            for (id, selection) in &mut self.selections {
+                if *id != view_id {
+                    *selection = selection.clone().map(transaction.changes());
+                }
+            }
         }
 
         if !transaction.changes().is_empty() {
             self.version += 1;
 
+            // keep the misspellings in place until they're checked again
+            for range in &mut self.misspellings {
+                let start = transaction.changes().map_pos(range.start, Assoc::After);
+                let end = transaction.changes().map_pos(range.end, Assoc::Before);
+                *range = start..end.max(start);
+            }
+
+            for tabstop in &mut self.snippet_tabstops {
+                *tabstop = tabstop.clone().map(transaction.changes());
+            }
+
+            // folds left with no line to hide are dropped
+            let changes = transaction.changes();
+            for folds in self.folds.values_mut() {
+                let text = &self.text;
+                folds.retain_mut(|fold| {
+                    let [start, end] = [*fold.start(), *fold.end()].map(|line| {
+                        let pos = changes.map_pos(old_doc.line_to_char(line), Assoc::After);
+                        text.char_to_line(pos)
+                    });
+                    *fold = start..=end;
+                    end > start
+                });
+            }
+
             // update tree-sitter syntax tree
             if let Some(syntax) = &mut self.syntax {
                 // TODO: no unwrap
@@ -846,24 +1199,24 @@ impl Document {
             //     diagnostic.line = self.text.char_to_line(diagnostic.range.start);
             // }
 
-            // emit lsp notification
-            if let Some(language_server) = &self.language_server {
-                let notify = language_server.text_document_did_change(
-                    self.versioned_identifier(),
-                    &old_doc,
-                    self.text(),
-                    transaction.changes(),
-                );
-
-                if let Some(notify) = notify {
-                    tokio::spawn(notify);
-                } //.expect("failed to emit textDocument/didChange");
-            }
+            events::notify(Event::DocumentDidChange {
+                doc: self,
+                old_text: &old_doc,
+                changes: transaction.changes(),
+            });
         }
         success
     }
 
+    /// Applies `transaction`, unless it changes the text of a read-only document.
     pub fn apply(&mut self, transaction: &Transaction, view_id: ViewId) -> bool {
+        if self.refuse_edit(transaction) {
+            return false;
+        }
+        self.apply_unchecked(transaction, view_id)
+    }
+
+    fn apply_unchecked(&mut self, transaction: &Transaction, view_id: ViewId) -> bool {
         // store the state just before any changes are made. This allows us to undo to the
         // state just before a transaction was applied.
         if self.changes.is_empty() && !transaction.changes().is_empty() {
@@ -871,6 +1224,13 @@ impl Document {
                 doc: self.text.clone(),
                 selection: self.selection(view_id).clone(),
             });
+            self.changes_annotation = transaction.annotation();
+        } else if !transaction.changes().is_empty() {
+            self.changes_annotation = match (self.changes_annotation, transaction.annotation()) {
+                (previous, None) => previous,
+                (Some(previous), Some(annotation)) if !annotation.groups_with(previous) => None,
+                (_, annotation) => annotation,
+            };
         }
 
         let success = self.apply_impl(transaction, view_id);
@@ -884,7 +1244,24 @@ impl Document {
         success
     }
 
-    pub fn undo(&mut self, view_id: ViewId) {
+    /// Whether `transaction` changes the text of a read-only document, noting the refusal.
+    fn refuse_edit(&mut self, transaction: &Transaction) -> bool {
+        let refused = self.readonly && !transaction.changes().is_empty();
+        self.refused_edit |= refused;
+        refused
+    }
+
+    /// Whether an edit was refused since the last call, because the document is read-only.
+    pub fn take_refused_edit(&mut self) -> bool {
+        std::mem::take(&mut self.refused_edit)
+    }
+
+    /// Reverts the current revision, returning whether there was one.
+    pub fn undo(&mut self, view_id: ViewId) -> bool {
+        if self.readonly {
+            self.refused_edit = true;
+            return false;
+        }
         let mut history = self.history.take();
         let success = if let Some(transaction) = history.undo() {
             self.apply_impl(transaction, view_id)
@@ -897,9 +1274,15 @@ impl Document {
             // reset changeset to fix len
             self.changes = ChangeSet::new(self.text());
         }
+        success
     }
 
-    pub fn redo(&mut self, view_id: ViewId) {
+    /// Reapplies the last undone revision, returning whether there was one.
+    pub fn redo(&mut self, view_id: ViewId) -> bool {
+        if self.readonly {
+            self.refused_edit = true;
+            return false;
+        }
         let mut history = self.history.take();
         let success = if let Some(transaction) = history.redo() {
             self.apply_impl(transaction, view_id)
@@ -912,20 +1295,46 @@ impl Document {
             // reset changeset to fix len
             self.changes = ChangeSet::new(self.text());
         }
+        success
     }
 
     pub fn earlier(&mut self, view_id: ViewId, uk: helix_core::history::UndoKind) {
+        if self.readonly {
+            self.refused_edit = true;
+            return;
+        }
         let txns = self.history.get_mut().earlier(uk);
+        let success = !txns.is_empty();
         for txn in txns {
             self.apply_impl(&txn, view_id);
         }
+
+        if success {
+            // reset changeset to fix len
+            self.changes = ChangeSet::new(self.text());
+        }
     }
 
     pub fn later(&mut self, view_id: ViewId, uk: helix_core::history::UndoKind) {
+        if self.readonly {
+            self.refused_edit = true;
+            return;
+        }
         let txns = self.history.get_mut().later(uk);
+        let success = !txns.is_empty();
         for txn in txns {
             self.apply_impl(&txn, view_id);
         }
+
+        if success {
+            // reset changeset to fix len
+            self.changes = ChangeSet::new(self.text());
+        }
+    }
+
+    /// Position of the last change committed to history, if any.
+    pub fn last_edit_pos(&mut self) -> Option<usize> {
+        self.history.get_mut().last_edit_pos()
     }
 
     /// Commit pending changes to history
@@ -936,10 +1345,13 @@ impl Document {
 
         let new_changeset = ChangeSet::new(self.text());
         let changes = std::mem::replace(&mut self.changes, new_changeset);
-        // Instead of doing this messy merge we could always commit, and based on transaction
-        // annotations either add a new layer or compose into the previous one.
-        let transaction =
+        // the history composes the changes into the previous revision if their annotation groups
+        // with it
+        let mut transaction =
             Transaction::from(changes).with_selection(self.selection(view_id).clone());
+        if let Some(annotation) = self.changes_annotation.take() {
+            transaction = transaction.with_annotation(annotation);
+        }
 
         // HAXX: we need to reconstruct the state as it was before the changes..
         let old_state = self.old_state.take().expect("no old_state available");
@@ -949,6 +1361,92 @@ impl Document {
         self.history.set(history);
     }
 
+    /// Converts every line ending in the document to `line_ending`, which is also used for lines
+    /// inserted from now on.
+    pub fn set_line_ending(&mut self, line_ending: LineEnding, view_id: ViewId) {
+        self.line_ending = line_ending;
+
+        let text = self.text.slice(..);
+        let changes: Vec<_> = (0..text.len_lines())
+            .filter_map(|line| {
+                let current = get_line_ending(&text.line(line))?;
+                if current == line_ending {
+                    return None;
+                }
+                let end = line_end_char_index(&text, line);
+                Some((
+                    end,
+                    end + current.len_chars(),
+                    Some(Tendril::from(line_ending.as_str())),
+                ))
+            })
+            .collect();
+
+        if changes.is_empty() {
+            return;
+        }
+
+        let transaction = Transaction::change(self.text(), changes.into_iter());
+        self.apply(&transaction, view_id);
+        self.append_changes_to_history(view_id);
+    }
+
+    /// Removes trailing whitespace from every line if `trim_trailing`, and makes sure the
+    /// document ends with a line ending if `final_line_ending`. The cleanup is committed to
+    /// history like any other edit.
+    pub fn clean_up_whitespace(
+        &mut self,
+        view_id: ViewId,
+        trim_trailing: bool,
+        final_line_ending: bool,
+    ) {
+        let text = self.text.slice(..);
+        let len = text.len_chars();
+        let mut changes = Vec::new();
+
+        for line in (0..text.len_lines()).filter(|_| trim_trailing) {
+            let start = text.line_to_char(line);
+            let end = line_end_char_index(&text, line);
+            let mut from = end;
+            while from > start && char_is_whitespace(text.char(from - 1)) {
+                from -= 1;
+            }
+            if from < end {
+                changes.push((from, end, None));
+            }
+        }
+
+        if final_line_ending && len > 0 && !char_is_line_ending(text.char(len - 1)) {
+            let line_ending = Some(Tendril::from(self.line_ending.as_str()));
+            match changes.last_mut() {
+                Some((_, to, content)) if *to == len => *content = line_ending,
+                _ => changes.push((len, len, line_ending)),
+            }
+        }
+
+        if changes.is_empty() {
+            return;
+        }
+
+        let transaction = Transaction::change(self.text(), changes.into_iter());
+        self.apply(&transaction, view_id);
+        self.append_changes_to_history(view_id);
+    }
+
+    /// Sets the tabstops of a newly expanded snippet, replacing any previous ones.
+    pub fn set_snippet_tabstops(&mut self, tabstops: Vec<Selection>) {
+        self.snippet_tabstops = tabstops;
+    }
+
+    /// Removes and returns the next tabstop of the last expanded snippet.
+    pub fn next_snippet_tabstop(&mut self) -> Option<Selection> {
+        if self.snippet_tabstops.is_empty() {
+            None
+        } else {
+            Some(self.snippet_tabstops.remove(0))
+        }
+    }
+
     pub fn id(&self) -> DocumentId {
         self.id
     }
@@ -971,6 +1469,24 @@ impl Document {
         self.mode
     }
 
+    /// Switches to `mode`, except to insert mode in read-only documents.
+    pub fn set_mode(&mut self, mode: Mode) {
+        if self.readonly && mode == Mode::Insert {
+            self.refused_edit = true;
+            return;
+        }
+        let old_mode = std::mem::replace(&mut self.mode, mode);
+        if old_mode != mode {
+            // edits of different modes are never the same undo step
+            self.history.get_mut().seal();
+            events::notify(Event::ModeChanged {
+                doc: self,
+                old_mode,
+                new_mode: mode,
+            });
+        }
+    }
+
     /// Corresponding language scope name. Usually `source.<lang>`.
     pub fn language(&self) -> Option<&str> {
         self.language
@@ -998,12 +1514,24 @@ impl Document {
         self.syntax.as_ref()
     }
 
-    /// Tab size in columns.
+    /// Tab size in columns, the distance between tab stops.
     pub fn tab_width(&self) -> usize {
-        self.language
-            .as_ref()
-            .and_then(|config| config.indent.as_ref())
-            .map_or(4, |config| config.tab_width) // fallback to 4 columns
+        self.tab_width_override
+            .or_else(|| {
+                self.language
+                    .as_ref()
+                    .and_then(|config| config.indent.as_ref())
+                    .map(|config| config.tab_width)
+            })
+            .unwrap_or(4) // fallback to 4 columns
+    }
+
+    /// Width of a single level of indentation in columns.
+    pub fn indent_width(&self) -> usize {
+        match self.indent_style {
+            IndentStyle::Tabs => self.tab_width(),
+            IndentStyle::Spaces(width) => width as usize,
+        }
     }
 
     /// Returns a string containing a single level of indentation.
@@ -1048,6 +1576,10 @@ impl Document {
         &self.selections[&view_id]
     }
 
+    pub fn selections(&self) -> &HashMap<ViewId, Selection> {
+        &self.selections
+    }
+
     pub fn relative_path(&self) -> Option<PathBuf> {
         let cwdir = std::env::current_dir().expect("couldn't determine current directory");
 
@@ -1085,6 +1617,331 @@ impl Document {
     pub fn set_diagnostics(&mut self, diagnostics: Vec<Diagnostic>) {
         self.diagnostics = diagnostics;
     }
+
+    /// Sets the contents the document is compared to for the diff gutter.
+    pub fn set_diff_base(&mut self, diff_base: Option<Rope>) {
+        self.diff_base = diff_base;
+        self.hunks.clear();
+        self.line_diffs.clear();
+        self.diff_outdated = self.diff_base.is_some();
+        self.update_diff();
+    }
+
+    /// Recomputes the line diffs if the document changed since they were last computed. Returns
+    /// whether they were.
+    pub fn update_diff(&mut self) -> bool {
+        if !self.diff_outdated {
+            return false;
+        }
+        self.diff_outdated = false;
+
+        if let Some(diff_base) = &self.diff_base {
+            self.hunks = diff::compare_lines(diff_base, &self.text);
+            self.line_diffs = diff::line_diffs(&self.text, &self.hunks);
+        }
+        true
+    }
+
+    pub fn diff_base(&self) -> Option<&Rope> {
+        self.diff_base.as_ref()
+    }
+
+    /// Hunks of lines that differ from the diff base, ordered by line. They're only up to date
+    /// after `update_diff`.
+    pub fn hunks(&self) -> &[Hunk] {
+        &self.hunks
+    }
+
+    /// The highlights computed in the background for `view_id`, if they're for the current
+    /// version of the text.
+    pub fn highlights(&self, view_id: ViewId) -> Option<&Highlights> {
+        self.highlights
+            .get(&view_id)
+            .filter(|highlights| highlights.version == self.version)
+    }
+
+    /// Keeps the `highlights` requested for `view_id`, unless they were superseded or the text
+    /// changed since.
+    pub fn set_highlights(&mut self, view_id: ViewId, highlights: Highlights) {
+        if !matches!(self.pending_highlights.get(&view_id), Some((version, range, _)) if *version == highlights.version && *range == highlights.range)
+        {
+            return;
+        }
+        self.pending_highlights.remove(&view_id);
+        if highlights.version == self.version {
+            self.highlights.insert(view_id, highlights);
+        }
+    }
+
+    /// Drops the highlights, which refer to the scopes of the theme they were computed with.
+    pub fn reset_highlights(&mut self) {
+        self.highlights.clear();
+        for (_, (_, _, cancellation_flag)) in self.pending_highlights.drain() {
+            cancellation_flag.store(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Starts highlighting the byte `range` of the current version for `view_id` unless it
+    /// already is, or was. The view's highlighting of an older version or another range is
+    /// cancelled. The returned future computes the highlights on a worker thread.
+    pub fn request_highlights(
+        &mut self,
+        view_id: ViewId,
+        range: Range<usize>,
+        loader: Arc<syntax::Loader>,
+        scopes: Vec<String>,
+    ) -> Option<impl Future<Output = Option<Highlights>>> {
+        let covers = |version: i32, cached: &Range<usize>| {
+            version == self.version && cached.start <= range.start && range.end <= cached.end
+        };
+        if matches!(self.highlights.get(&view_id), Some(highlights) if covers(highlights.version, &highlights.range))
+            || matches!(self.pending_highlights.get(&view_id), Some((version, pending, _)) if covers(*version, pending))
+        {
+            return None;
+        }
+        let syntax = self.syntax.clone()?;
+
+        if let Some((_, _, cancellation_flag)) = self.pending_highlights.remove(&view_id) {
+            cancellation_flag.store(1, Ordering::Relaxed);
+        }
+        let cancellation_flag = Arc::new(AtomicUsize::new(0));
+        self.pending_highlights.insert(
+            view_id,
+            (self.version, range.clone(), cancellation_flag.clone()),
+        );
+
+        let text = self.text.clone();
+        let version = self.version;
+        Some(async move {
+            tokio::task::spawn_blocking(move || {
+                let events = syntax
+                    .highlights(
+                        text.slice(..),
+                        range.clone(),
+                        Some(&cancellation_flag),
+                        &loader,
+                        &scopes,
+                    )
+                    .ok()?;
+                Some(Highlights {
+                    version,
+                    range,
+                    events: Arc::new(events),
+                })
+            })
+            .await
+            .ok()
+            .flatten()
+        })
+    }
+
+    /// Checks the spelling of the lines changed since it was last checked, or of the whole
+    /// document if it never was. Returns whether it was checked.
+    pub fn update_spelling(&mut self, dictionary: &Dictionary) -> bool {
+        match self.spelling_version {
+            Some(version) if version == self.version => return false,
+            Some(_) => {}
+            None => {
+                self.check_spelling(dictionary);
+                return true;
+            }
+        }
+
+        // the whole lines of the changes, merged when they touch
+        let text = self.text.slice(..);
+        let mut lines: Vec<std::ops::Range<usize>> = Vec::new();
+        let mut changes = std::mem::take(&mut self.spelling_changes);
+        changes.sort_by_key(|range| range.start);
+        for range in changes {
+            let start = text.line_to_char(text.char_to_line(range.start));
+            let end = text.line_to_char((text.char_to_line(range.end) + 1).min(text.len_lines()));
+            match lines.last_mut() {
+                Some(last) if start <= last.end => last.end = last.end.max(end),
+                _ => lines.push(start..end),
+            }
+        }
+
+        self.misspellings
+            .retain(|word| !lines.iter().any(|line| line.contains(&word.start)));
+        for line in lines {
+            let bytes = text.char_to_byte(line.start)..text.char_to_byte(line.end);
+            let ranges = spelling::checked_ranges(self.syntax.as_ref(), text, bytes);
+            self.misspellings
+                .extend(spelling::misspelled(dictionary, text, &ranges));
+        }
+        self.misspellings.sort_by_key(|word| word.start);
+        self.spelling_version = Some(self.version);
+        true
+    }
+
+    /// Checks the spelling of the comments and strings of the document, or all of its text if it
+    /// has no syntax tree.
+    pub fn check_spelling(&mut self, dictionary: &Dictionary) {
+        self.spelling_version = Some(self.version);
+        self.spelling_changes.clear();
+        let text = self.text.slice(..);
+        let ranges = spelling::checked_ranges(self.syntax.as_ref(), text, 0..text.len_bytes());
+        self.misspellings = spelling::misspelled(dictionary, text, &ranges);
+    }
+
+    /// Char ranges of the misspelled words. They're only up to date after `update_spelling`.
+    pub fn misspellings(&self) -> &[std::ops::Range<usize>] {
+        &self.misspellings
+    }
+
+    /// The folds of `view_id`, which may be nested.
+    pub fn folds(&self, view_id: ViewId) -> &[Fold] {
+        self.folds.get(&view_id).map_or(&[], |folds| folds)
+    }
+
+    /// Forgets the state of the closed view `view_id`.
+    pub fn remove_view(&mut self, view_id: ViewId) {
+        self.selections.remove(&view_id);
+        self.folds.remove(&view_id);
+        self.highlights.remove(&view_id);
+        if let Some((_, _, cancellation_flag)) = self.pending_highlights.remove(&view_id) {
+            cancellation_flag.store(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn set_folds(&mut self, view_id: ViewId, folds: Vec<Fold>) {
+        if folds.is_empty() {
+            self.folds.remove(&view_id);
+        } else {
+            self.folds.insert(view_id, folds);
+        }
+    }
+
+    /// How `line` differs from the diff base, if it does.
+    pub fn line_diff(&self, line: usize) -> Option<LineDiff> {
+        self.line_diffs
+            .binary_search_by_key(&line, |&(line, _)| line)
+            .ok()
+            .map(|index| self.line_diffs[index].1)
+    }
+}
+
+/// Keeps the language server in sync with the document.
+pub(crate) fn language_server_hook(event: &mut Event) -> anyhow::Result<()> {
+    match event {
+        Event::DocumentDidOpen { doc } => {
+            if let (Some(language_server), Some(url)) = (&doc.language_server, doc.url()) {
+                let language_id = doc
+                    .language()
+                    .and_then(|s| s.split('.').last()) // source.rust
+                    .map(ToOwned::to_owned)
+                    .unwrap_or_default();
+
+                tokio::spawn(language_server.text_document_did_open(
+                    url,
+                    doc.version(),
+                    doc.text(),
+                    language_id,
+                ));
+            }
+        }
+        Event::DocumentDidChange {
+            doc,
+            old_text,
+            changes,
+        } => {
+            if let Some(language_server) = &doc.language_server {
+                let notify = language_server.text_document_did_change(
+                    doc.versioned_identifier(),
+                    old_text,
+                    doc.text(),
+                    changes,
+                );
+                if let Some(notify) = notify {
+                    tokio::spawn(notify);
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Loads the version of the file in git to diff against, and marks the diff outdated on changes.
+pub(crate) fn diff_hook(event: &mut Event) -> anyhow::Result<()> {
+    match event {
+        Event::DocumentDidOpen { doc } => {
+            let diff_base = doc.path().and_then(|path| crate::git::head_contents(path));
+            doc.set_diff_base(diff_base);
+        }
+        Event::DocumentDidChange { doc, .. } => doc.diff_outdated = doc.diff_base.is_some(),
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Keeps track of the text changed since the spelling was checked, in the current text.
+pub(crate) fn spelling_hook(event: &mut Event) -> anyhow::Result<()> {
+    if let Event::DocumentDidChange { doc, changes, .. } = event {
+        for range in &mut doc.spelling_changes {
+            let start = changes.map_pos(range.start, Assoc::Before);
+            let end = changes.map_pos(range.end, Assoc::After);
+            *range = start..end;
+        }
+        let mut pos = 0;
+        for operation in changes.changes() {
+            match operation {
+                Operation::Retain(len) => pos += len,
+                Operation::Delete(_) => doc.spelling_changes.push(pos..pos),
+                Operation::Insert(text) => {
+                    let len = text.chars().count();
+                    doc.spelling_changes.push(pos..pos + len);
+                    pos += len;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Formats the document before saving, if its language is formatted on save. A failing formatter
+/// doesn't prevent saving, see [`Document::take_format_error`].
+pub(crate) fn auto_format_hook(event: &mut Event) -> anyhow::Result<()> {
+    if let Event::DocumentWillSave { doc, view_id, .. } = event {
+        doc.format_error = None;
+        if let Some(formatting) = doc.auto_format() {
+            match helix_lsp::block_on(formatting) {
+                Ok(formatting) => {
+                    doc.apply(&Transaction::from(formatting), *view_id);
+                    doc.append_changes_to_history(*view_id);
+                }
+                Err(err) => {
+                    log::warn!("formatting {:?} failed: {}", doc.path(), err);
+                    doc.format_error = Some(err.to_string());
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Trims trailing whitespace and adds the final line ending before saving. The `.editorconfig`
+/// files take precedence over the language setting, which takes precedence over the editor's.
+pub(crate) fn trim_trailing_whitespace_hook(event: &mut Event) -> anyhow::Result<()> {
+    if let Event::DocumentWillSave {
+        doc,
+        view_id,
+        config,
+    } = event
+    {
+        let trim = doc
+            .editor_config
+            .trim_trailing_whitespace
+            .or_else(|| {
+                doc.language_config()
+                    .and_then(|language| language.trim_trailing_whitespace)
+            })
+            .unwrap_or(config.trim_trailing_whitespace);
+        // trimming always added the final line ending
+        let final_line_ending = doc.editor_config.insert_final_newline.unwrap_or(trim);
+        doc.clean_up_whitespace(*view_id, trim, final_line_ending);
+    }
+    Ok(())
 }
 
 impl Default for Document {
@@ -1098,6 +1955,22 @@ impl Default for Document {
 mod test {
     use super::*;
 
+    #[test]
+    fn external_formatting_keeps_the_cursor() {
+        let mut doc = Document::from(Rope::from("fn main(){\n}\n"), None);
+        let view = ViewId::default();
+        // on the closing brace
+        doc.set_selection(view, Selection::point(11));
+
+        let formatting = Formatting::External {
+            doc: doc.text().clone(),
+            formatted: Rope::from("fn main() {\n}\n"),
+        };
+        doc.apply(&formatting.into(), view);
+        assert_eq!(doc.text(), "fn main() {\n}\n");
+        assert_eq!(doc.selection(view).cursor(), 12);
+    }
+
     #[test]
     fn changeset_to_changes() {
         use helix_lsp::{lsp, Client, OffsetEncoding};
@@ -1212,6 +2085,382 @@ mod test {
         );
     }
 
+    /// A document of `text` whose language is formatted on save with `sh -c script`.
+    fn formatted_document(text: &str, script: &str) -> Document {
+        let mut language: LanguageConfiguration = toml::from_str(
+            r#"
+            name = "formatted"
+            scope = "source.formatted"
+            file-types = []
+            roots = []
+            auto-format = true
+            formatter = { command = "sh" }
+            "#,
+        )
+        .unwrap();
+        let formatter = language.formatter.as_mut().unwrap();
+        formatter.args = vec!["-c".to_owned(), script.to_owned(), "{path}".to_owned()];
+        let mut doc = Document::from(Rope::from(text), None);
+        doc.language = Some(Arc::new(language));
+        doc.set_selection(ViewId::default(), Selection::point(0));
+        doc
+    }
+
+    fn will_save(doc: &mut Document) {
+        crate::events::dispatch(Event::DocumentWillSave {
+            doc,
+            view_id: ViewId::default(),
+            config: &crate::editor::Config::default(),
+        })
+        .unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn auto_format_before_saving() {
+        let mut doc = formatted_document("hello\n", "tr a-z A-Z");
+        will_save(&mut doc);
+        assert_eq!(doc.text().to_string(), "HELLO\n");
+        assert_eq!(doc.take_format_error(), None);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn format_in_document_directory() {
+        // `$0` is the path given in place of `{path}`
+        let mut doc = formatted_document("", r#"pwd; echo "$0""#);
+        let dir = std::env::temp_dir().canonicalize().unwrap();
+        doc.path = Some(dir.join("formatted.txt"));
+
+        let formatting = doc.format().unwrap().await.unwrap();
+        doc.apply(&Transaction::from(formatting), ViewId::default());
+        assert_eq!(
+            doc.text().to_string(),
+            format!(
+                "{}\n{}\n",
+                dir.display(),
+                dir.join("formatted.txt").display()
+            )
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn failed_formatting() {
+        let doc = formatted_document("hello\n", "echo oops >&2; exit 3");
+        let err = doc.format().unwrap().await.unwrap_err().to_string();
+        assert!(err.contains("oops"), "{}", err);
+
+        let mut doc = formatted_document("hello\n", r"cat >/dev/null; printf '\377'");
+        let err = doc.format().unwrap().await.unwrap_err().to_string();
+        assert!(err.contains("invalid UTF-8"), "{}", err);
+
+        // the document is saved as is, and the error reported
+        will_save(&mut doc);
+        assert_eq!(doc.text().to_string(), "hello\n");
+        assert!(doc.take_format_error().is_some());
+        assert_eq!(doc.take_format_error(), None);
+    }
+
+    #[test]
+    fn utf16_round_trip() {
+        let text = "hello wörld\n";
+        let mut input = vec![0xFF, 0xFE];
+        input.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+
+        let (rope, encoding) = from_reader(&mut &input[..], None).unwrap();
+        assert_eq!(encoding, encoding_rs::UTF_16LE);
+        assert_eq!(rope.to_string(), text);
+
+        let mut output: Vec<u8> = Vec::new();
+        helix_lsp::block_on(to_writer(&mut output, encoding, &rope)).unwrap();
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn detect_indent_style() {
+        let mut doc = Document::from(Rope::from("fn main() {\n\tfoo();\n\tbar();\n}\n"), None);
+        doc.detect_indent_style();
+        assert_eq!(doc.indent_style, IndentStyle::Tabs);
+
+        let mut doc = Document::from(Rope::from("a:\n  b:\n    c: 1\n  d: 2\n"), None);
+        doc.detect_indent_style();
+        assert_eq!(doc.indent_style, IndentStyle::Spaces(2));
+    }
+
+    #[test]
+    fn detect_or_enforce_language_indent() {
+        let document = |text: &str, enforce: bool| {
+            let language: LanguageConfiguration = toml::from_str(&format!(
+                r#"
+                name = "indented"
+                scope = "source.indented"
+                file-types = []
+                roots = []
+                indent = {{ tab-width = 4, unit = "    ", enforce = {} }}
+                "#,
+                enforce
+            ))
+            .unwrap();
+            let mut doc = Document::from(Rope::from(text), None);
+            doc.language = Some(Arc::new(language));
+            doc.detect_indent_style();
+            doc.indent_style
+        };
+        let tabs = "fn main() {\n\tfoo();\n\tbar();\n}\n";
+
+        // the indentation of the file wins, unless the language enforces its own
+        assert_eq!(document(tabs, false), IndentStyle::Tabs);
+        assert_eq!(document(tabs, true), IndentStyle::Spaces(4));
+        // files without indentation get the one of the language either way
+        assert_eq!(document("a\nb\n", false), IndentStyle::Spaces(4));
+        assert_eq!(document("a\nb\n", true), IndentStyle::Spaces(4));
+    }
+
+    #[test]
+    fn update_spelling_of_changed_lines() {
+        let mut dictionary = Dictionary::new();
+        for word in ["the", "quick", "fox"] {
+            dictionary.insert(word);
+        }
+        let mut doc = Document::from(Rope::from("the qiuck fox\nteh fox\nthe fox\n"), None);
+        let view = ViewId::default();
+        doc.set_selection(view, Selection::point(0));
+        assert!(doc.update_spelling(&dictionary));
+        assert_eq!(doc.misspellings(), &[4..9, 14..17]);
+        assert!(!doc.update_spelling(&dictionary));
+
+        // only the changed lines are checked again, with the new words
+        dictionary.insert("qiuck");
+        dictionary.insert("box");
+        let edit = |doc: &mut Document, from, to, text: &str| {
+            let transaction =
+                Transaction::change(doc.text(), std::iter::once((from, to, Some(text.into()))));
+            doc.apply(&transaction, view);
+        };
+        edit(&mut doc, 14, 17, "the");
+        edit(&mut doc, 26, 29, "bx");
+        assert!(doc.update_spelling(&dictionary));
+        assert_eq!(doc.misspellings(), &[4..9, 26..28]);
+        assert!(!doc.update_spelling(&dictionary));
+
+        // deleting a line break joins the words of both lines
+        edit(&mut doc, 21, 22, "");
+        assert!(doc.update_spelling(&dictionary));
+        assert_eq!(doc.misspellings(), &[4..9, 18..24, 25..27]);
+        // a full check sees the new words everywhere
+        doc.check_spelling(&dictionary);
+        assert_eq!(doc.misspellings(), &[18..24, 25..27]);
+    }
+
+    #[test]
+    fn set_line_ending() {
+        let text = Rope::from("hello\r\nworld\nfoo");
+        let mut doc = Document::from(text, None);
+        let view = ViewId::default();
+        doc.set_selection(view, Selection::single(0, 0));
+
+        doc.set_line_ending(LineEnding::Crlf, view);
+        assert_eq!(doc.text().to_string(), "hello\r\nworld\r\nfoo");
+
+        doc.set_line_ending(LineEnding::LF, view);
+        assert_eq!(doc.text().to_string(), "hello\nworld\nfoo");
+        assert_eq!(doc.line_ending, LineEnding::LF);
+    }
+
+    #[test]
+    fn from_stream() {
+        let doc = Document::from_stream(&mut "one\r\ntwo".as_bytes()).unwrap();
+        assert_eq!(doc.text().to_string(), "one\r\ntwo\r\n");
+        assert_eq!(doc.line_ending, LineEnding::Crlf);
+        assert!(doc.path().is_none());
+    }
+
+    #[test]
+    fn readonly_refuses_edits() {
+        let mut doc = Document::from(Rope::from("hello"), None);
+        let view = ViewId::default();
+        doc.set_selection(view, Selection::single(0, 0));
+        doc.readonly = true;
+
+        let insert = Transaction::insert(doc.text(), doc.selection(view), "oh, ".into());
+        assert!(!doc.apply(&insert, view));
+        doc.set_mode(Mode::Insert);
+        assert_eq!(doc.text().to_string(), "hello");
+        assert_eq!(doc.mode(), Mode::Normal);
+        assert!(doc.take_refused_edit());
+        assert!(!doc.take_refused_edit());
+
+        // moving the selection isn't an edit
+        let select = Transaction::new(doc.text()).with_selection(Selection::single(1, 3));
+        assert!(doc.apply(&select, view));
+        assert_eq!(doc.selection(view), &Selection::single(1, 3));
+        assert!(!doc.take_refused_edit());
+    }
+
+    #[test]
+    fn undo_redo_restore_selections() {
+        use helix_core::history::UndoKind;
+
+        let mut doc = Document::from(Rope::from("hello world"), None);
+        let view = ViewId::default();
+        doc.set_selection(view, Selection::single(6, 11));
+
+        let delete = Transaction::change_by_selection(doc.text(), doc.selection(view), |range| {
+            (range.from(), range.to(), None)
+        });
+        doc.apply(&delete, view);
+        doc.set_selection(view, Selection::point(5));
+        doc.append_changes_to_history(view);
+        assert_eq!(doc.text().to_string(), "hello ");
+
+        doc.set_selection(view, Selection::point(0));
+        doc.undo(view);
+        assert_eq!(doc.text().to_string(), "hello world");
+        assert_eq!(doc.selection(view), &Selection::single(6, 11));
+
+        doc.set_selection(view, Selection::point(0));
+        doc.redo(view);
+        assert_eq!(doc.text().to_string(), "hello ");
+        assert_eq!(doc.selection(view), &Selection::point(5));
+
+        doc.earlier(view, UndoKind::Steps(1));
+        assert_eq!(doc.selection(view), &Selection::single(6, 11));
+        // editing after jumping through the history
+        let insert = Transaction::insert(doc.text(), doc.selection(view), "!".into());
+        doc.apply(&insert, view);
+        doc.append_changes_to_history(view);
+        assert_eq!(doc.text().to_string(), "hello world!");
+        assert!(!doc.redo(view));
+        assert!(doc.undo(view));
+        assert!(!doc.undo(view));
+    }
+
+    #[test]
+    fn map_other_views_selections() {
+        let mut views = slotmap::SlotMap::<ViewId, ()>::with_key();
+        let (view, other) = (views.insert(()), views.insert(()));
+
+        let mut doc = Document::from(Rope::from("hello world"), None);
+        doc.set_selection(view, Selection::point(0));
+        doc.set_selection(other, Selection::single(6, 11));
+
+        let insert = Transaction::insert(doc.text(), doc.selection(view), "oh, ".into());
+        doc.apply(&insert, view);
+        assert_eq!(doc.text().to_string(), "oh, hello world");
+        assert_eq!(doc.selection(view), &Selection::point(4));
+        assert_eq!(doc.selection(other), &Selection::single(10, 15));
+
+        // undoing from the other view moves the first one back
+        doc.append_changes_to_history(view);
+        doc.undo(other);
+        assert_eq!(doc.text().to_string(), "hello world");
+        assert_eq!(doc.selection(view), &Selection::point(0));
+    }
+
+    #[test]
+    fn selections_open_folds() {
+        let mut doc = Document::from(Rope::from("a\nb\nc\nd\ne\n"), None);
+        let view = ViewId::default();
+        doc.set_selection(view, Selection::point(0));
+        doc.set_folds(view, vec![0..=2, 3..=4]);
+
+        // on the first line of a fold, it stays closed
+        doc.set_selection(view, Selection::point(6));
+        assert_eq!(doc.folds(view), &[0..=2, 3..=4]);
+        // a search or a jump to a hidden line opens its fold
+        doc.set_selection(view, Selection::point(8));
+        assert_eq!(doc.folds(view), &[0..=2]);
+
+        doc.remove_view(view);
+        assert!(doc.folds(view).is_empty());
+        assert!(!doc.selections().contains_key(&view));
+    }
+
+    #[test]
+    fn highlights_of_each_view() {
+        let mut doc = Document::from(Rope::from("a\nb\nc\n"), None);
+        let mut views = slotmap::SlotMap::<ViewId, ()>::with_key();
+        let (first, second) = (views.insert(()), views.insert(()));
+        let highlights = |version, range: Range<usize>| Highlights {
+            version,
+            range,
+            events: Arc::new(Vec::new()),
+        };
+        let pending = |doc: &mut Document, view, version, range| {
+            let flag = Arc::new(AtomicUsize::new(0));
+            doc.pending_highlights
+                .insert(view, (version, range, flag.clone()));
+            flag
+        };
+
+        // each view highlights its own part without cancelling the other
+        pending(&mut doc, first, 0, 0..2);
+        pending(&mut doc, second, 0, 4..6);
+        doc.set_highlights(second, highlights(0, 4..6));
+        doc.set_highlights(first, highlights(0, 0..2));
+        assert_eq!(doc.highlights(first).unwrap().range, 0..2);
+        assert_eq!(doc.highlights(second).unwrap().range, 4..6);
+
+        // highlights of an older version aren't drawn, or kept once they arrive
+        doc.set_selection(first, Selection::point(0));
+        let transaction = Transaction::insert(doc.text(), &Selection::point(0), "x".into());
+        doc.apply(&transaction, first);
+        assert!(doc.highlights(first).is_none());
+        pending(&mut doc, first, 0, 0..4);
+        doc.set_highlights(first, highlights(0, 0..4));
+        assert!(doc.highlights(first).is_none());
+        // nor are superseded ones
+        pending(&mut doc, first, 1, 0..4);
+        doc.set_highlights(first, highlights(1, 0..2));
+        assert!(doc.highlights(first).is_none());
+
+        let flag = pending(&mut doc, second, 1, 0..4);
+        doc.remove_view(second);
+        assert_eq!(flag.load(Ordering::Relaxed), 1);
+        doc.set_highlights(second, highlights(1, 0..4));
+        assert!(doc.highlights(second).is_none());
+    }
+
+    #[test]
+    fn apply_editor_config() {
+        use helix_core::editorconfig::IndentStyle as Style;
+
+        let mut doc = Document::from(Rope::from("a:\n  b: 1\n"), None);
+        doc.detect_indent_style();
+        doc.apply_editor_config(EditorConfig {
+            indent_size: Some(4),
+            line_ending: Some(LineEnding::Crlf),
+            ..Default::default()
+        });
+        assert_eq!(doc.indent_style, IndentStyle::Spaces(4));
+        assert_eq!(doc.tab_width(), 4);
+        assert_eq!(doc.line_ending, LineEnding::Crlf);
+
+        doc.apply_editor_config(EditorConfig {
+            indent_style: Some(Style::Tab),
+            tab_width: Some(8),
+            ..Default::default()
+        });
+        assert_eq!(doc.indent_style, IndentStyle::Tabs);
+        assert_eq!(doc.tab_width(), 8);
+    }
+
+    #[test]
+    fn trim_trailing_whitespace() {
+        let text = Rope::from("hello  \nworld\t\n  \nfoo ");
+        let mut doc = Document::from(text, None);
+        let view = ViewId::default();
+        doc.set_selection(view, Selection::single(0, 0));
+
+        doc.clean_up_whitespace(view, true, true);
+        assert_eq!(
+            doc.text().to_string(),
+            format!("hello\nworld\n\nfoo{}", DEFAULT_LINE_ENDING.as_str())
+        );
+        assert!(doc.is_modified());
+
+        doc.undo(view);
+        assert_eq!(doc.text().to_string(), "hello  \nworld\t\n  \nfoo ");
+    }
+
     #[test]
     fn test_line_ending() {
         if cfg!(windows) {
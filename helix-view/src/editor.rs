@@ -1,38 +1,456 @@
 use crate::{
     clipboard::{get_clipboard_provider, ClipboardProvider},
+    document::Mode,
+    events::{self, Event},
     graphics::{CursorKind, Rect},
+    gutter::{self, GutterType, LineNumber},
     theme::{self, Theme},
-    tree::Tree,
+    tree::{self, Tree},
     Document, DocumentId, RegisterSelection, View, ViewId,
 };
 
-use futures_util::future;
-use std::{path::PathBuf, sync::Arc, time::Duration};
+use futures_util::{future, stream::select_all::SelectAll};
+use std::{collections::HashMap, path::PathBuf, sync::Arc, time::Duration};
+use tokio_stream::wrappers::UnboundedReceiverStream;
 
 use slotmap::SlotMap;
 
-use anyhow::Error;
+use anyhow::{anyhow, bail, Error};
 
 pub use helix_core::diagnostic::Severity;
+use helix_core::regex::{self, Regex, RegexBuilder};
 pub use helix_core::register::Registers;
+use helix_core::spelling::{self, Dictionary};
 use helix_core::syntax;
-use helix_core::Position;
+use helix_core::wrap::SoftWrap;
+use helix_core::{find_root, Position, Selection};
+use helix_lsp::{lsp, OffsetEncoding};
+
+use serde::Deserialize;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct Config {
+    /// How lines longer than the view width are displayed. Defaults to `none`.
+    pub soft_wrap: SoftWrap,
+    /// Capture mouse events to place the cursor, select and scroll. Defaults to `true`.
+    pub mouse: bool,
+    pub whitespace: WhitespaceConfig,
+    pub indent_guides: IndentGuidesConfig,
+    /// Columns at which to draw rulers, starting at 1. Can be overridden per language.
+    pub rulers: Vec<u16>,
+    pub cursor_shape: CursorShapeConfig,
+    /// Highlight the line of the primary cursor. Defaults to `false`.
+    pub cursorline: bool,
+    /// Highlight the column of the primary cursor. Defaults to `false`.
+    pub cursorcolumn: bool,
+    /// Highlight the other occurrences of the symbol under the cursor once typing stops, found by
+    /// the language server or else by matching the word. Defaults to `true`.
+    pub symbol_highlights: bool,
+    /// How the `line-numbers` gutter numbers lines, `absolute` or `relative` to the primary
+    /// cursor. Defaults to `absolute`.
+    pub line_number: LineNumber,
+    /// Close brackets and quotes when typing the opening one. Defaults to `true`.
+    pub auto_pairs: bool,
+    /// The columns left of the text, in order. Defaults to `["diagnostics", "line-numbers",
+    /// "diff"]`.
+    pub gutters: Vec<GutterType>,
+    /// List the open documents on the top row. Defaults to `false`.
+    pub bufferline: bool,
+    /// Files or directories marking the root of a project, by priority: the root is the closest
+    /// directory containing the first marker found above the current document. Defaults to
+    /// `[".git", "Cargo.toml"]`.
+    pub root_markers: Vec<String>,
+    /// Strip trailing whitespace and ensure a final line ending before saving. Can be overridden
+    /// per language. Defaults to `false`.
+    pub trim_trailing_whitespace: bool,
+    /// Maximum line width used by `reflow`. Can be overridden per language. Defaults to `80`.
+    pub text_width: usize,
+    pub search: SearchConfig,
+    pub file_picker: FilePickerConfig,
+    pub spelling: SpellingConfig,
+    pub inline_diagnostics: InlineDiagnosticsConfig,
+    /// Show the keys following a pending prefix key, like `g` or `space`, with the commands they
+    /// run. Defaults to `true`.
+    pub auto_info: bool,
+    /// Milliseconds to wait for the next key before showing them. Defaults to `300`.
+    pub auto_info_delay: u64,
+    /// Copy to the clipboard of the terminal with OSC 52 escape sequences when no clipboard
+    /// program is found, which works over SSH. Defaults to `true`.
+    pub osc52_clipboard: bool,
+    /// Largest text, in bytes, copied with OSC 52. Defaults to `75000`.
+    pub osc52_max_size: usize,
+    /// External programs run as typable commands or on events, in the order they're run on
+    /// events.
+    pub plugins: Vec<PluginConfig>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            soft_wrap: SoftWrap::default(),
+            mouse: true,
+            whitespace: WhitespaceConfig::default(),
+            indent_guides: IndentGuidesConfig::default(),
+            rulers: Vec::new(),
+            cursor_shape: CursorShapeConfig::default(),
+            cursorline: false,
+            cursorcolumn: false,
+            symbol_highlights: true,
+            line_number: LineNumber::default(),
+            auto_pairs: true,
+            gutters: gutter::DEFAULT_GUTTERS.to_vec(),
+            bufferline: false,
+            root_markers: vec![".git".to_owned(), "Cargo.toml".to_owned()],
+            trim_trailing_whitespace: false,
+            text_width: 80,
+            search: SearchConfig::default(),
+            file_picker: FilePickerConfig::default(),
+            spelling: SpellingConfig::default(),
+            inline_diagnostics: InlineDiagnosticsConfig::default(),
+            auto_info: true,
+            auto_info_delay: 300,
+            osc52_clipboard: true,
+            osc52_max_size: 75_000,
+            plugins: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Options that can be changed while running with `:set` and `:toggle`, named like in
+    /// `config.toml`.
+    pub const OPTIONS: &'static [&'static str] = &[
+        "soft-wrap",
+        "line-number",
+        "auto-pairs",
+        "whitespace.render",
+        "indent-guides.render",
+        "rulers",
+        "cursorline",
+        "cursorcolumn",
+        "symbol-highlights",
+        "bufferline",
+        "trim-trailing-whitespace",
+        "text-width",
+        "search.smart-case",
+        "search.wrap-around",
+        "file-picker.hidden",
+        "file-picker.git-ignore",
+        "inline-diagnostics.enable",
+        "inline-diagnostics.min-severity",
+        "auto-info",
+        "auto-info-delay",
+    ];
+
+    /// Sets the option `key`, one of [`Self::OPTIONS`], to `value` written like in `config.toml`.
+    /// Strings don't need quotes.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), Error> {
+        fn parse<T: serde::de::DeserializeOwned>(value: toml::Value) -> Result<T, Error> {
+            Ok(value.try_into()?)
+        }
+
+        if !Self::OPTIONS.contains(&key) {
+            bail!("unknown option: {}", key);
+        }
+        let value = match format!("value = {}", value).parse::<toml::Value>() {
+            Ok(toml::Value::Table(mut table)) => table.remove("value").unwrap(),
+            _ => toml::Value::String(value.to_owned()),
+        };
+        match key {
+            "soft-wrap" => self.soft_wrap = parse(value)?,
+            "line-number" => self.line_number = parse(value)?,
+            "auto-pairs" => self.auto_pairs = parse(value)?,
+            "whitespace.render" => self.whitespace.render = parse(value)?,
+            "indent-guides.render" => self.indent_guides.render = parse(value)?,
+            "rulers" => self.rulers = parse(value)?,
+            "cursorline" => self.cursorline = parse(value)?,
+            "cursorcolumn" => self.cursorcolumn = parse(value)?,
+            "symbol-highlights" => self.symbol_highlights = parse(value)?,
+            "bufferline" => self.bufferline = parse(value)?,
+            "trim-trailing-whitespace" => self.trim_trailing_whitespace = parse(value)?,
+            "text-width" => self.text_width = parse(value)?,
+            "search.smart-case" => self.search.smart_case = parse(value)?,
+            "search.wrap-around" => self.search.wrap_around = parse(value)?,
+            "file-picker.hidden" => self.file_picker.hidden = parse(value)?,
+            "file-picker.git-ignore" => self.file_picker.git_ignore = parse(value)?,
+            "inline-diagnostics.enable" => self.inline_diagnostics.enable = parse(value)?,
+            "inline-diagnostics.min-severity" => {
+                self.inline_diagnostics.min_severity = parse(value)?
+            }
+            "auto-info" => self.auto_info = parse(value)?,
+            "auto-info-delay" => self.auto_info_delay = parse(value)?,
+            _ => unreachable!("{} is missing", key),
+        }
+        Ok(())
+    }
+
+    /// Flips the boolean option `key`, returning its new value.
+    pub fn toggle(&mut self, key: &str) -> Result<bool, Error> {
+        if !Self::OPTIONS.contains(&key) {
+            bail!("unknown option: {}", key);
+        }
+        let option = match key {
+            "auto-pairs" => &mut self.auto_pairs,
+            "whitespace.render" => &mut self.whitespace.render,
+            "indent-guides.render" => &mut self.indent_guides.render,
+            "cursorline" => &mut self.cursorline,
+            "cursorcolumn" => &mut self.cursorcolumn,
+            "symbol-highlights" => &mut self.symbol_highlights,
+            "bufferline" => &mut self.bufferline,
+            "trim-trailing-whitespace" => &mut self.trim_trailing_whitespace,
+            "search.smart-case" => &mut self.search.smart_case,
+            "search.wrap-around" => &mut self.search.wrap_around,
+            "file-picker.hidden" => &mut self.file_picker.hidden,
+            "file-picker.git-ignore" => &mut self.file_picker.git_ignore,
+            "inline-diagnostics.enable" => &mut self.inline_diagnostics.enable,
+            "auto-info" => &mut self.auto_info,
+            _ => bail!("{} isn't a boolean option", key),
+        };
+        *option = !*option;
+        Ok(*option)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct SearchConfig {
+    /// Search case-insensitively unless the pattern contains an uppercase character. Defaults to
+    /// `true`.
+    pub smart_case: bool,
+    /// Continue from the other end of the document when there are no more matches. Defaults to
+    /// `true`.
+    pub wrap_around: bool,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            smart_case: true,
+            wrap_around: true,
+        }
+    }
+}
+
+impl SearchConfig {
+    /// Compiles a search `pattern`, honoring `smart-case`.
+    pub fn build_regex(&self, pattern: &str) -> Result<Regex, regex::Error> {
+        let case_insensitive = self.smart_case && !pattern.chars().any(char::is_uppercase);
+        RegexBuilder::new(pattern)
+            .case_insensitive(case_insensitive)
+            .build()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct FilePickerConfig {
+    /// Leave out hidden files. Defaults to `true`.
+    pub hidden: bool,
+    /// Leave out the files ignored by `.gitignore`, `.ignore` and the global git excludes.
+    /// Defaults to `true`.
+    pub git_ignore: bool,
+}
+
+impl Default for FilePickerConfig {
+    fn default() -> Self {
+        Self {
+            hidden: true,
+            git_ignore: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct SpellingConfig {
+    /// Check the spelling of comments and strings, and of text without syntax highlighting.
+    /// Defaults to `false`.
+    pub enable: bool,
+    /// File listing the correctly spelled words, one per line. Defaults to
+    /// `/usr/share/dict/words`.
+    pub dictionary: PathBuf,
+}
+
+impl Default for SpellingConfig {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            dictionary: PathBuf::from("/usr/share/dict/words"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct InlineDiagnosticsConfig {
+    /// Show the first diagnostic of a line after its end. Defaults to `true`.
+    pub enable: bool,
+    /// Least severe diagnostics shown. Defaults to `warning`.
+    pub min_severity: Severity,
+}
+
+impl Default for InlineDiagnosticsConfig {
+    fn default() -> Self {
+        Self {
+            enable: true,
+            min_severity: Severity::Warning,
+        }
+    }
+}
+
+/// An external program run as a typable command, like `:name args`, or on `events`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PluginConfig {
+    pub name: String,
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Shown in the command prompt and palette.
+    #[serde(default)]
+    pub doc: String,
+    #[serde(default)]
+    pub events: Vec<PluginEvent>,
+    /// Seconds to wait for the program before it's killed. Defaults to `5`. Plugins are run on
+    /// the UI thread, so the editor doesn't respond to input while waiting, each time the command
+    /// is run or the document is saved with a hook.
+    #[serde(default = "default_plugin_timeout")]
+    pub timeout: u64,
+}
+
+fn default_plugin_timeout() -> u64 {
+    5
+}
+
+/// Events plugins can be run on, on the document they concern.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PluginEvent {
+    /// Before the document is written, so the changes of the plugin are saved too.
+    Save,
+}
+
+/// Shape of the primary cursor in each mode.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct CursorShapeConfig {
+    pub normal: CursorKind,
+    pub insert: CursorKind,
+    pub select: CursorKind,
+}
+
+impl CursorShapeConfig {
+    pub fn from_mode(&self, mode: Mode) -> CursorKind {
+        match mode {
+            Mode::Normal => self.normal,
+            Mode::Insert => self.insert,
+            Mode::Select => self.select,
+        }
+    }
+}
+
+impl Default for CursorShapeConfig {
+    fn default() -> Self {
+        Self {
+            normal: CursorKind::Block,
+            insert: CursorKind::Bar,
+            select: CursorKind::Underline,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct WhitespaceConfig {
+    /// Render tabs, trailing spaces and non-breaking spaces as symbols. Defaults to `false`.
+    pub render: bool,
+    pub characters: WhitespaceCharacters,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct WhitespaceCharacters {
+    pub tab: char,
+    pub space: char,
+    pub nbsp: char,
+}
+
+impl Default for WhitespaceCharacters {
+    fn default() -> Self {
+        Self {
+            tab: '→',
+            space: '·',
+            nbsp: '⍽',
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct IndentGuidesConfig {
+    /// Draw a guide at each indent level of the leading whitespace. Defaults to `false`.
+    pub render: bool,
+    /// Defaults to `│`.
+    pub character: char,
+    /// Leave out the guide of the first level, at the start of the line. Defaults to `false`.
+    pub skip_first_level: bool,
+}
+
+impl Default for IndentGuidesConfig {
+    fn default() -> Self {
+        Self {
+            render: false,
+            character: '│',
+            skip_first_level: false,
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct Editor {
     pub tree: Tree,
     pub documents: SlotMap<DocumentId, Document>,
+    /// The open documents in the order they were opened, since the slot map reuses the keys of
+    /// closed ones.
+    document_order: Vec<DocumentId>,
     pub count: Option<std::num::NonZeroUsize>,
     pub selected_register: RegisterSelection,
     pub registers: Registers,
     pub theme: Theme,
     pub language_servers: helix_lsp::Registry,
+    /// The debug adapter of the running debug session.
+    pub debugger: Option<helix_dap::Client>,
+    pub debugger_events: SelectAll<UnboundedReceiverStream<(usize, helix_dap::Payload)>>,
+    /// The dictionary to check spelling with, if spell checking is enabled.
+    pub dictionary: Option<Dictionary>,
+    /// Breakpoints by file, kept across debug sessions.
+    pub breakpoints: HashMap<PathBuf, Vec<helix_dap::SourceBreakpoint>>,
     pub clipboard_provider: Box<dyn ClipboardProvider>,
 
     pub syn_loader: Arc<syntax::Loader>,
     pub theme_loader: Arc<theme::Loader>,
 
     pub status_msg: Option<(String, Severity)>,
+    /// Highlight the matches of the last search, until cleared with `:nohl`.
+    pub search_highlight: bool,
+    /// The last search highlighted, whether it was compiled with `smart-case` and its regex, so
+    /// it's only compiled again once it changes.
+    search_regex: Option<(String, bool, Option<Regex>)>,
+
+    pub config: Config,
+}
+
+/// Loads the configured dictionary and the words added to it.
+fn load_dictionary(config: &SpellingConfig) -> Option<Dictionary> {
+    let mut dictionary = Dictionary::new();
+    if let Err(err) = dictionary.load(&config.dictionary) {
+        log::error!("failed to load dictionary {:?}: {}", config.dictionary, err);
+        return None;
+    }
+    // the user dictionary only exists once a word is added
+    let _ = dictionary.load(&spelling::user_dictionary());
+    Some(dictionary)
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -48,6 +466,7 @@ impl Editor {
         mut area: Rect,
         themes: Arc<theme::Loader>,
         config_loader: Arc<syntax::Loader>,
+        config: Config,
     ) -> Self {
         let language_servers = helix_lsp::Registry::new();
 
@@ -57,15 +476,27 @@ impl Editor {
         Self {
             tree: Tree::new(area),
             documents: SlotMap::with_key(),
+            document_order: Vec::new(),
             count: None,
             selected_register: RegisterSelection::default(),
             theme: themes.default(),
             language_servers,
+            debugger: None,
+            debugger_events: SelectAll::new(),
+            breakpoints: HashMap::new(),
+            dictionary: config
+                .spelling
+                .enable
+                .then(|| load_dictionary(&config.spelling))
+                .flatten(),
             syn_loader: config_loader,
             theme_loader: themes,
             registers: Registers::default(),
-            clipboard_provider: get_clipboard_provider(),
+            clipboard_provider: get_clipboard_provider(&config),
             status_msg: None,
+            search_highlight: false,
+            search_regex: None,
+            config,
         }
     }
 
@@ -73,14 +504,50 @@ impl Editor {
         self.status_msg = None;
     }
 
+    /// Shows `status` in the bottom line until the next key press.
     pub fn set_status(&mut self, status: String) {
         self.status_msg = Some((status, Severity::Info));
     }
 
+    /// Like [`Editor::set_status`], styled as a warning.
+    pub fn set_warning(&mut self, warning: String) {
+        log::warn!("{}", warning);
+        self.status_msg = Some((warning, Severity::Warning));
+    }
+
+    /// Like [`Editor::set_status`], styled as an error.
     pub fn set_error(&mut self, error: String) {
+        log::error!("{}", error);
         self.status_msg = Some((error, Severity::Error));
     }
 
+    /// Adds `word` to the dictionary and saves it in the user dictionary, then checks the
+    /// spelling of every document again.
+    pub fn add_to_dictionary(&mut self, word: &str) -> anyhow::Result<()> {
+        use std::io::Write;
+
+        let dictionary = match &mut self.dictionary {
+            Some(dictionary) => dictionary,
+            None => anyhow::bail!("Spell checking is disabled"),
+        };
+
+        let path = spelling::user_dictionary();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)?;
+        writeln!(file, "{}", word)?;
+
+        dictionary.insert(word);
+        for doc in self.documents.values_mut() {
+            doc.check_spelling(dictionary);
+        }
+        Ok(())
+    }
+
     pub fn set_theme(&mut self, theme: Theme) {
         let scopes = theme.scopes();
         for config in self
@@ -90,6 +557,9 @@ impl Editor {
         {
             config.reconfigure(scopes);
         }
+        for doc in self.documents.values_mut() {
+            doc.reset_highlights();
+        }
 
         self.theme = theme;
         self._refresh();
@@ -152,19 +622,32 @@ impl Editor {
             Action::Load => {
                 return;
             }
-            Action::HorizontalSplit => {
-                let view = View::new(id);
-                let view_id = self.tree.split(view, Layout::Horizontal);
-                // initialize selection for view
-                let doc = &mut self.documents[id];
-                doc.selections.insert(view_id, Selection::point(0));
-            }
-            Action::VerticalSplit => {
-                let view = View::new(id);
-                let view_id = self.tree.split(view, Layout::Vertical);
+            Action::HorizontalSplit | Action::VerticalSplit => {
+                let layout = match action {
+                    Action::HorizontalSplit => Layout::Horizontal,
+                    _ => Layout::Vertical,
+                };
+                let mut view = View::new(id);
+                view.soft_wrap = self.config.soft_wrap;
+                view.gutters = self.config.gutters.clone();
+
+                // a split of the same document starts with the selection and scroll of the focused
+                // view, then keeps its own
+                let selection = match self
+                    .tree
+                    .views()
+                    .find(|(view, focused)| *focused && view.doc == id)
+                {
+                    Some((focused, _)) => {
+                        view.first_line = focused.first_line;
+                        view.first_col = focused.first_col;
+                        self.documents[id].selection(focused.id).clone()
+                    }
+                    None => Selection::point(0),
+                };
+                let view_id = self.tree.split(view, layout);
                 // initialize selection for view
-                let doc = &mut self.documents[id];
-                doc.selections.insert(view_id, Selection::point(0));
+                self.documents[id].selections.insert(view_id, selection);
             }
         }
 
@@ -172,9 +655,14 @@ impl Editor {
     }
 
     pub fn new_file(&mut self, action: Action) -> DocumentId {
-        let doc = Document::default();
+        self.new_file_from_document(action, Document::default())
+    }
+
+    /// Adds `doc` as a new scratch buffer and shows it.
+    pub fn new_file_from_document(&mut self, action: Action, doc: Document) -> DocumentId {
         let id = self.documents.insert(doc);
         self.documents[id].id = id;
+        self.document_order.push(id);
         self.switch(id, action);
         id
     }
@@ -191,32 +679,38 @@ impl Editor {
             id
         } else {
             let mut doc = Document::open(path, None, Some(&self.theme), Some(&self.syn_loader))?;
-
             // try to find a language server based on the language name
-            let language_server = doc
-                .language
-                .as_ref()
-                .and_then(|language| self.language_servers.get(language).ok());
+            let language_server = doc.language.as_ref().and_then(|language| {
+                // the roots of the language come after the ones of the editor
+                let markers: Vec<_> = self
+                    .config
+                    .root_markers
+                    .iter()
+                    .chain(&language.roots)
+                    .cloned()
+                    .collect();
+                let root = find_root(doc.path().and_then(|path| path.parent()), &markers);
+                self.language_servers
+                    .get(language, root.as_deref())
+                    .map_err(|err| {
+                        log::error!(
+                            "failed to start the language server for {}: {}",
+                            language.scope,
+                            err
+                        )
+                    })
+                    .ok()
+            });
+            doc.set_language_server(language_server);
 
-            if let Some(language_server) = language_server {
-                doc.set_language_server(Some(language_server.clone()));
-
-                let language_id = doc
-                    .language()
-                    .and_then(|s| s.split('.').last()) // source.rust
-                    .map(ToOwned::to_owned)
-                    .unwrap_or_default();
-
-                tokio::spawn(language_server.text_document_did_open(
-                    doc.url().unwrap(),
-                    doc.version(),
-                    doc.text(),
-                    language_id,
-                ));
+            events::notify(Event::DocumentDidOpen { doc: &mut doc });
+            if let Some(dictionary) = &self.dictionary {
+                doc.check_spelling(dictionary);
             }
 
             let id = self.documents.insert(doc);
             self.documents[id].id = id;
+            self.document_order.push(id);
             id
         };
 
@@ -224,24 +718,107 @@ impl Editor {
         Ok(id)
     }
 
+    /// Applies `workspace_edit`, which may span several documents, opening the ones that aren't
+    /// open yet without showing them. Nothing is applied if a document can't be opened, is
+    /// read-only or changed since the edit was computed, and the documents opened for it are
+    /// closed again. Each document gets its own undo step and is left unsaved. Returns the paths of
+    /// the documents changed.
+    pub fn apply_workspace_edit(
+        &mut self,
+        offset_encoding: OffsetEncoding,
+        workspace_edit: &lsp::WorkspaceEdit,
+    ) -> Result<Vec<PathBuf>, Error> {
+        let mut opened = Vec::new();
+        let edits = self.open_edited_documents(workspace_edit, &mut opened);
+        let edits = match edits {
+            Ok(edits) => edits,
+            Err(err) => {
+                for id in opened {
+                    self.close_document(id);
+                }
+                return Err(err);
+            }
+        };
+
+        let view_id = self.tree.focus;
+        let mut paths: Vec<PathBuf> = Vec::new();
+        for (id, path, text_edits) in edits {
+            let doc = &mut self.documents[id];
+            // documents opened for the edit aren't in any view yet
+            if !doc.selections().contains_key(&view_id) {
+                doc.set_selection(view_id, Selection::point(0));
+            }
+            let transaction = helix_lsp::util::generate_transaction_from_edits(
+                doc.text(),
+                text_edits,
+                offset_encoding,
+            );
+            doc.apply(&transaction, view_id);
+            doc.append_changes_to_history(view_id);
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+        Ok(paths)
+    }
+
+    /// Opens the documents `workspace_edit` changes and checks they can be edited, adding the ones
+    /// that weren't open to `opened`.
+    fn open_edited_documents(
+        &mut self,
+        workspace_edit: &lsp::WorkspaceEdit,
+        opened: &mut Vec<DocumentId>,
+    ) -> Result<Vec<(DocumentId, PathBuf, Vec<lsp::TextEdit>)>, Error> {
+        let mut edits = Vec::new();
+        for (url, version, text_edits) in document_edits(workspace_edit)? {
+            let path = url
+                .to_file_path()
+                .map_err(|_| anyhow!("{} isn't a local file", url))?;
+            let open_documents = self.documents.len();
+            let id = self.open(path.clone(), Action::Load)?;
+            if self.documents.len() > open_documents {
+                opened.push(id);
+            }
+            let doc = &self.documents[id];
+            if doc.readonly {
+                bail!("{} is read-only", path.display());
+            }
+            if matches!(version, Some(version) if version != doc.version()) {
+                bail!("{} changed since the edit was computed", path.display());
+            }
+            edits.push((id, path, text_edits));
+        }
+        Ok(edits)
+    }
+
+    /// Closes the document `id`, which no view shows.
+    fn close_document(&mut self, id: DocumentId) {
+        if let Some(doc) = self.documents.remove(id) {
+            self.document_order.retain(|&open| open != id);
+            if let Some(language_server) = doc.language_server() {
+                tokio::spawn(language_server.text_document_did_close(doc.identifier()));
+            }
+        }
+    }
+
     pub fn close(&mut self, id: ViewId, close_buffer: bool) {
-        let view = self.tree.get(self.tree.focus);
+        let view = self.tree.get(id);
         // remove selection
-        self.documents[view.doc].selections.remove(&id);
+        self.documents[view.doc].remove_view(id);
 
         if close_buffer {
-            // get around borrowck issues
-            let language_servers = &mut self.language_servers;
             let doc = &self.documents[view.doc];
 
+            // closing a document shouldn't start its language server
             let language_server = doc
                 .language
                 .as_ref()
-                .and_then(|language| language_servers.get(language).ok());
+                .and_then(|language| self.language_servers.get_running(language));
             if let Some(language_server) = language_server {
                 tokio::spawn(language_server.text_document_did_close(doc.identifier()));
             }
             self.documents.remove(view.doc);
+            self.document_order.retain(|&open| open != view.doc);
         }
 
         self.tree.remove(id);
@@ -254,10 +831,41 @@ impl Editor {
         };
     }
 
+    /// The root of the project of the current document, or of the working directory for scratch
+    /// buffers. Outside of any project, the working directory.
+    pub fn workspace_root(&self) -> PathBuf {
+        let doc = &self.documents[view!(self).doc];
+        find_root(
+            doc.path().and_then(|path| path.parent()),
+            &self.config.root_markers,
+        )
+        .or_else(|| std::env::current_dir().ok())
+        .unwrap_or_else(|| PathBuf::from("./"))
+    }
+
     pub fn focus_next(&mut self) {
         self.tree.focus_next();
     }
 
+    pub fn focus_direction(&mut self, direction: tree::Direction) {
+        self.tree.focus_direction(direction);
+    }
+
+    pub fn swap_split_in_direction(&mut self, direction: tree::Direction) {
+        self.tree.swap_split_in_direction(direction);
+    }
+
+    /// Makes the focused split `cells` wider, or taller with `vertically`, or smaller when
+    /// negative.
+    pub fn grow_split(&mut self, vertically: bool, cells: i32) {
+        let layout = if vertically {
+            tree::Layout::Horizontal
+        } else {
+            tree::Layout::Vertical
+        };
+        self.tree.grow_split(self.tree.focus, layout, cells);
+    }
+
     pub fn should_close(&self) -> bool {
         self.tree.is_empty()
     }
@@ -280,6 +888,32 @@ impl Editor {
         self.documents.iter().map(|(_id, doc)| doc)
     }
 
+    /// The regex of the last search while its matches are highlighted, unless it's invalid.
+    pub fn search_highlight_regex(&mut self) -> Option<Regex> {
+        if !self.search_highlight {
+            return None;
+        }
+        let query = self.registers.read('\\')?.last()?;
+        let smart_case = self.config.search.smart_case;
+        match &self.search_regex {
+            Some((cached, cached_smart_case, regex))
+                if cached == query && *cached_smart_case == smart_case =>
+            {
+                regex.clone()
+            }
+            _ => {
+                let regex = self.config.search.build_regex(query).ok();
+                self.search_regex = Some((query.clone(), smart_case, regex.clone()));
+                regex
+            }
+        }
+    }
+
+    /// The ids of the open documents, in the order they were opened.
+    pub fn document_order(&self) -> &[DocumentId] {
+        &self.document_order
+    }
+
     pub fn documents_mut(&mut self) -> impl Iterator<Item = &mut Document> {
         self.documents.iter_mut().map(|(_id, doc)| doc)
     }
@@ -290,14 +924,18 @@ impl Editor {
     // }
 
     pub fn cursor(&self) -> (Option<Position>, CursorKind) {
-        const OFFSET: u16 = 7; // 1 diagnostic + 5 linenr + 1 gutter
         let view = view!(self);
         let doc = &self.documents[view.doc];
         let cursor = doc.selection(view.id).cursor();
         if let Some(mut pos) = view.screen_coords_at_pos(doc, doc.text().slice(..), cursor) {
-            pos.col += view.area.x as usize + OFFSET as usize;
+            pos.col += (view.area.x + view.gutter_offset()) as usize;
             pos.row += view.area.y as usize;
-            (Some(pos), CursorKind::Hidden)
+            let kind = match self.config.cursor_shape.from_mode(doc.mode()) {
+                // block cursors are drawn by the renderer so that they can be themed
+                CursorKind::Block => CursorKind::Hidden,
+                kind => kind,
+            };
+            (Some(pos), kind)
         } else {
             (None, CursorKind::Hidden)
         }
@@ -321,3 +959,271 @@ impl Editor {
         .map(|_| ())
     }
 }
+
+/// The text edits of a workspace edit for one document, and the version of the document they were
+/// computed for if the server said.
+type DocumentEdits = (lsp::Url, Option<i32>, Vec<lsp::TextEdit>);
+
+/// The text edits of `workspace_edit` by document, in the order they're to be applied. File
+/// operations, like renaming a file, aren't supported.
+fn document_edits(workspace_edit: &lsp::WorkspaceEdit) -> Result<Vec<DocumentEdits>, Error> {
+    let text_document_edits = |edit: &lsp::TextDocumentEdit| {
+        let text_edits = edit
+            .edits
+            .iter()
+            .map(|edit| match edit {
+                lsp::OneOf::Left(text_edit) => text_edit.clone(),
+                lsp::OneOf::Right(annotated) => annotated.text_edit.clone(),
+            })
+            .collect();
+        (
+            edit.text_document.uri.clone(),
+            edit.text_document.version,
+            text_edits,
+        )
+    };
+
+    // servers send `document_changes` when the client supports it, `changes` otherwise
+    match (&workspace_edit.document_changes, &workspace_edit.changes) {
+        (Some(lsp::DocumentChanges::Edits(edits)), _) => {
+            Ok(edits.iter().map(text_document_edits).collect())
+        }
+        (Some(lsp::DocumentChanges::Operations(operations)), _) => operations
+            .iter()
+            .map(|operation| match operation {
+                lsp::DocumentChangeOperation::Edit(edit) => Ok(text_document_edits(edit)),
+                lsp::DocumentChangeOperation::Op(_) => {
+                    bail!("creating, renaming and deleting files isn't supported")
+                }
+            })
+            .collect(),
+        (None, Some(changes)) => {
+            let mut edits: Vec<_> = changes
+                .iter()
+                .map(|(url, text_edits)| (url.clone(), None, text_edits.clone()))
+                .collect();
+            // the order of a map is arbitrary, sort to report the files in a stable one
+            edits.sort_by(|a, b| a.0.as_str().cmp(b.0.as_str()));
+            Ok(edits)
+        }
+        (None, None) => Ok(Vec::new()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn url(name: &str) -> lsp::Url {
+        lsp::Url::parse(&format!("file:///src/{}", name)).unwrap()
+    }
+
+    fn text_edit(text: &str) -> lsp::TextEdit {
+        let position = lsp::Position::new(0, 0);
+        lsp::TextEdit::new(lsp::Range::new(position, position), text.to_owned())
+    }
+
+    fn text_document_edit(name: &str, version: Option<i32>, text: &str) -> lsp::TextDocumentEdit {
+        lsp::TextDocumentEdit {
+            text_document: lsp::OptionalVersionedTextDocumentIdentifier {
+                uri: url(name),
+                version,
+            },
+            edits: vec![lsp::OneOf::Left(text_edit(text))],
+        }
+    }
+
+    #[test]
+    fn test_document_edits() {
+        // changes by file, in a stable order
+        let changes = vec![
+            (url("b.rs"), vec![text_edit("b")]),
+            (url("a.rs"), vec![text_edit("a"), text_edit("A")]),
+        ];
+        let edit = lsp::WorkspaceEdit::new(changes.into_iter().collect());
+        assert_eq!(
+            document_edits(&edit).unwrap(),
+            vec![
+                (url("a.rs"), None, vec![text_edit("a"), text_edit("A")]),
+                (url("b.rs"), None, vec![text_edit("b")]),
+            ]
+        );
+
+        // versioned document changes, in the order they're given
+        let edit = lsp::WorkspaceEdit {
+            document_changes: Some(lsp::DocumentChanges::Edits(vec![
+                text_document_edit("b.rs", Some(3), "b"),
+                text_document_edit("a.rs", None, "a"),
+            ])),
+            ..Default::default()
+        };
+        assert_eq!(
+            document_edits(&edit).unwrap(),
+            vec![
+                (url("b.rs"), Some(3), vec![text_edit("b")]),
+                (url("a.rs"), None, vec![text_edit("a")]),
+            ]
+        );
+
+        let operations = |operation| lsp::WorkspaceEdit {
+            document_changes: Some(lsp::DocumentChanges::Operations(vec![
+                lsp::DocumentChangeOperation::Edit(text_document_edit("a.rs", Some(1), "a")),
+                operation,
+            ])),
+            ..Default::default()
+        };
+        let edit = operations(lsp::DocumentChangeOperation::Edit(text_document_edit(
+            "b.rs", None, "b",
+        )));
+        assert_eq!(document_edits(&edit).unwrap().len(), 2);
+        // file operations aren't supported
+        let edit = operations(lsp::DocumentChangeOperation::Op(lsp::ResourceOp::Delete(
+            lsp::DeleteFile {
+                uri: url("b.rs"),
+                options: None,
+            },
+        )));
+        assert!(document_edits(&edit).is_err());
+
+        assert!(document_edits(&lsp::WorkspaceEdit::default())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_set_every_option() {
+        let values = [
+            ("soft-wrap", "word"),
+            ("line-number", "relative"),
+            ("auto-pairs", "false"),
+            ("whitespace.render", "true"),
+            ("indent-guides.render", "true"),
+            ("rulers", "[80, 100]"),
+            ("cursorline", "true"),
+            ("cursorcolumn", "true"),
+            ("symbol-highlights", "false"),
+            ("bufferline", "true"),
+            ("trim-trailing-whitespace", "true"),
+            ("text-width", "72"),
+            ("search.smart-case", "false"),
+            ("search.wrap-around", "false"),
+            ("file-picker.hidden", "false"),
+            ("file-picker.git-ignore", "false"),
+            ("inline-diagnostics.enable", "false"),
+            ("inline-diagnostics.min-severity", "error"),
+            ("auto-info", "false"),
+            ("auto-info-delay", "100"),
+        ];
+        for option in Config::OPTIONS {
+            let (_, value) = values
+                .iter()
+                .find(|(name, _)| name == option)
+                .unwrap_or_else(|| panic!("no value to set {} to", option));
+            let mut config = Config::default();
+            config.set(option, value).unwrap();
+            assert_ne!(config, Config::default(), "{} wasn't set", option);
+        }
+        assert_eq!(values.len(), Config::OPTIONS.len());
+
+        let mut config = Config::default();
+        config.set("line-number", "relative").unwrap();
+        assert_eq!(config.line_number, LineNumber::Relative);
+        config.set("auto-pairs", "false").unwrap();
+        assert!(!config.auto_pairs);
+        assert!(config.set("line-number", "sideways").is_err());
+        assert!(config.set("auto-pairs", "maybe").is_err());
+        assert!(config.set("line-numbers", "relative").is_err());
+    }
+
+    #[test]
+    fn test_toggle_every_option() {
+        let mut config = Config::default();
+        for option in Config::OPTIONS {
+            match config.toggle(option) {
+                Ok(value) => {
+                    assert_ne!(config, Config::default(), "{} wasn't toggled", option);
+                    // toggling is the same as setting the flipped value
+                    let mut set = Config::default();
+                    set.set(option, &value.to_string()).unwrap();
+                    assert_eq!(config, set);
+                    assert_eq!(config.toggle(option).unwrap(), !value);
+                    assert_eq!(config, Config::default());
+                }
+                Err(err) => assert_eq!(
+                    err.to_string(),
+                    format!("{} isn't a boolean option", option)
+                ),
+            }
+        }
+        assert!(config.toggle("auto-pairs").is_ok());
+        assert!(!config.auto_pairs);
+        assert!(config.toggle("line-number").is_err());
+        assert!(config.toggle("unknown").is_err());
+    }
+
+    fn editor() -> Editor {
+        let runtime = helix_core::runtime_dir();
+        Editor::new(
+            Rect::new(0, 0, 80, 24),
+            Arc::new(theme::Loader::new(&runtime, &runtime)),
+            Arc::new(syntax::Loader::new(syntax::Configuration {
+                language: Vec::new(),
+            })),
+            Config::default(),
+        )
+    }
+
+    #[test]
+    fn test_search_highlight_regex() {
+        let mut editor = editor();
+        editor.registers.write('\\', vec!["ab".to_owned()]);
+        assert!(editor.search_highlight_regex().is_none());
+
+        editor.search_highlight = true;
+        let regex = editor.search_highlight_regex().unwrap();
+        assert!(regex.is_match("AB"));
+        // compiled once per query
+        let cached = |editor: &Editor| {
+            editor
+                .search_regex
+                .clone()
+                .map(|(query, smart_case, _)| (query, smart_case))
+        };
+        assert_eq!(cached(&editor), Some(("ab".to_owned(), true)));
+        assert!(editor.search_highlight_regex().unwrap().is_match("AB"));
+
+        editor.config.search.smart_case = false;
+        assert!(!editor.search_highlight_regex().unwrap().is_match("AB"));
+        assert_eq!(cached(&editor), Some(("ab".to_owned(), false)));
+
+        editor
+            .registers
+            .write('\\', vec!["ab".to_owned(), "(".to_owned()]);
+        assert!(editor.search_highlight_regex().is_none());
+        assert_eq!(cached(&editor), Some(("(".to_owned(), false)));
+    }
+
+    #[test]
+    fn test_close_without_starting_language_server() {
+        let marker = std::env::temp_dir().join(format!("helix-view-test-{}", std::process::id()));
+        let language: syntax::LanguageConfiguration = toml::from_str(&format!(
+            r#"
+            name = "served"
+            scope = "source.served"
+            file-types = []
+            roots = []
+            language-server = {{ command = "sh", args = ["-c", "touch {}"] }}
+            "#,
+            marker.display()
+        ))
+        .unwrap();
+        assert!(editor().language_servers.get_running(&language).is_none());
+
+        let mut editor = editor();
+        let id = editor.new_file(Action::VerticalSplit);
+        editor.documents[id].language = Some(Arc::new(language));
+        editor.close(editor.tree.focus, true);
+        assert!(editor.documents.is_empty());
+        assert!(!marker.exists());
+    }
+}
@@ -0,0 +1,103 @@
+//! Events of the editor, and the hooks reacting to them.
+//!
+//! Features like syncing documents with their language server or the diff gutter subscribe to
+//! events instead of being called from every place a document changes. The core hooks always run
+//! first, then the ones added with [`register`], in order.
+
+use crate::{document::Mode, editor::Config, Document, ViewId};
+use helix_core::{ChangeSet, Rope};
+use once_cell::sync::Lazy;
+use std::sync::{Arc, RwLock};
+
+pub enum Event<'a> {
+    /// A document was opened from a file.
+    DocumentDidOpen { doc: &'a mut Document },
+    /// The text of a document changed from `old_text` by `changes`.
+    DocumentDidChange {
+        doc: &'a mut Document,
+        old_text: &'a Rope,
+        changes: &'a ChangeSet,
+    },
+    /// A document is about to be written, with the selections of `view_id`. Hooks failing cancel
+    /// the save.
+    DocumentWillSave {
+        doc: &'a mut Document,
+        view_id: ViewId,
+        config: &'a Config,
+    },
+    ModeChanged {
+        doc: &'a mut Document,
+        old_mode: Mode,
+        new_mode: Mode,
+    },
+    SelectionChanged {
+        doc: &'a mut Document,
+        view_id: ViewId,
+    },
+}
+
+pub type Hook = fn(&mut Event) -> anyhow::Result<()>;
+
+const CORE_HOOKS: &[Hook] = &[
+    crate::document::language_server_hook,
+    crate::document::diff_hook,
+    crate::document::spelling_hook,
+    crate::document::auto_format_hook,
+    crate::document::trim_trailing_whitespace_hook,
+];
+
+/// The registered hooks, replaced as a whole when one is added so that dispatching only clones
+/// the `Arc`.
+static HOOKS: Lazy<RwLock<Arc<Vec<Hook>>>> = Lazy::new(Default::default);
+
+/// Adds `hook`, run on every event after the ones added before.
+pub fn register(hook: Hook) {
+    Arc::make_mut(&mut HOOKS.write().unwrap()).push(hook);
+}
+
+/// Runs the hooks on `event`, stopping at the first one failing.
+pub fn dispatch(mut event: Event) -> anyhow::Result<()> {
+    // hooks may dispatch events themselves, so the lock isn't held while they run
+    let hooks = HOOKS.read().unwrap().clone();
+    for hook in CORE_HOOKS.iter().chain(hooks.iter()) {
+        hook(&mut event)?;
+    }
+    Ok(())
+}
+
+/// Like [`dispatch`], for events that can't be cancelled: failing hooks are logged.
+pub fn notify(event: Event) {
+    if let Err(err) = dispatch(event) {
+        log::error!("hook failed: {}", err);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static INSERT_MODE_CHANGES: AtomicUsize = AtomicUsize::new(0);
+
+    fn count_insert_mode_changes(event: &mut Event) -> anyhow::Result<()> {
+        if let Event::ModeChanged {
+            new_mode: Mode::Insert,
+            ..
+        } = event
+        {
+            INSERT_MODE_CHANGES.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_registered_hooks_run() {
+        register(count_insert_mode_changes);
+        let mut doc = Document::default();
+        doc.set_mode(Mode::Insert);
+        // staying in the same mode isn't a change
+        doc.set_mode(Mode::Insert);
+        doc.set_mode(Mode::Normal);
+        assert_eq!(INSERT_MODE_CHANGES.load(Ordering::Relaxed), 1);
+    }
+}
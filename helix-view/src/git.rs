@@ -0,0 +1,29 @@
+//! The contents of files at the git `HEAD`, which documents are compared to for the diff gutter.
+
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use helix_core::Rope;
+
+/// Returns the contents of the file at `path` as of the `HEAD` commit of the repository it's in,
+/// or `None` if it isn't tracked by git.
+pub fn head_contents(path: &Path) -> Option<Rope> {
+    let dir = path.parent()?;
+    let name = path.file_name()?.to_str()?;
+
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .arg("show")
+        .arg(format!("HEAD:./{}", name))
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let contents = String::from_utf8(output.stdout).ok()?;
+    Some(Rope::from(contents))
+}
@@ -1,7 +1,9 @@
 use bitflags::bitflags;
+use serde::Deserialize;
 use std::cmp::{max, min};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
 /// UNSTABLE
 pub enum CursorKind {
     /// █
@@ -0,0 +1,253 @@
+//! The columns drawn left of the text of a view, in the order of the `gutters` option.
+//!
+//! Each column implements [`Gutter`], so new ones only need a [`GutterType`] naming them.
+
+use crate::{editor::Config, graphics::Style, Document, Theme, View};
+use helix_core::diagnostic::Severity;
+use helix_core::diff::LineDiff;
+use serde::Deserialize;
+
+/// A column of the gutter, drawn line by line.
+pub trait Gutter {
+    /// Width of the column, in cells.
+    fn width(&self) -> u16;
+
+    /// The text drawn on the first row of `line`, if any. `selected` is set on the lines having a
+    /// cursor in the focused view.
+    fn render_line(
+        &self,
+        doc: &Document,
+        view: &View,
+        theme: &Theme,
+        config: &Config,
+        line: usize,
+        selected: bool,
+    ) -> Option<(String, Style)>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GutterType {
+    /// A dot colored by the most recent diagnostic of the line.
+    Diagnostics,
+    /// Absolute or relative, following the `line-number` option.
+    LineNumbers,
+    /// How the line changed since the version in git.
+    Diff,
+    /// A blank column.
+    Spacer,
+    /// Marks the first line of folds.
+    Folds,
+}
+
+/// How the `line-numbers` gutter numbers lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LineNumber {
+    /// The number of each line.
+    Absolute,
+    /// The distance to the line of the primary cursor, whose own number is shown as is.
+    Relative,
+}
+
+impl Default for LineNumber {
+    fn default() -> Self {
+        Self::Absolute
+    }
+}
+
+/// The gutter of views when the `gutters` option isn't set.
+pub const DEFAULT_GUTTERS: &[GutterType] = &[
+    GutterType::Diagnostics,
+    GutterType::LineNumbers,
+    GutterType::Diff,
+];
+
+impl GutterType {
+    pub fn gutter(self) -> &'static dyn Gutter {
+        match self {
+            Self::Diagnostics => &Diagnostics,
+            Self::LineNumbers => &LineNumbers,
+            Self::Diff => &Diff,
+            Self::Spacer => &Spacer,
+            Self::Folds => &Folds,
+        }
+    }
+}
+
+/// Total width of the `gutters`.
+pub fn width(gutters: &[GutterType]) -> u16 {
+    gutters.iter().map(|gutter| gutter.gutter().width()).sum()
+}
+
+struct Diagnostics;
+
+impl Gutter for Diagnostics {
+    fn width(&self) -> u16 {
+        1
+    }
+
+    fn render_line(
+        &self,
+        doc: &Document,
+        _view: &View,
+        theme: &Theme,
+        _config: &Config,
+        line: usize,
+        _selected: bool,
+    ) -> Option<(String, Style)> {
+        let diagnostic = doc.diagnostics().iter().find(|d| d.line == line)?;
+        let style = match diagnostic.severity {
+            Some(Severity::Error) => theme.get("error"),
+            Some(Severity::Warning) | None => theme.get("warning"),
+            Some(Severity::Info) => theme.get("info"),
+            Some(Severity::Hint) => theme.get("hint"),
+        };
+        Some(("●".to_owned(), style))
+    }
+}
+
+struct LineNumbers;
+
+impl Gutter for LineNumbers {
+    fn width(&self) -> u16 {
+        5
+    }
+
+    fn render_line(
+        &self,
+        doc: &Document,
+        view: &View,
+        theme: &Theme,
+        config: &Config,
+        line: usize,
+        selected: bool,
+    ) -> Option<(String, Style)> {
+        // line numbers having selections are rendered differently
+        let style = if selected {
+            theme
+                .try_get("ui.linenr.selected")
+                .unwrap_or_else(|| theme.get("ui.linenr"))
+        } else {
+            theme.get("ui.linenr")
+        };
+        let number = match config.line_number {
+            LineNumber::Absolute => line + 1,
+            LineNumber::Relative => {
+                let cursor = doc.selection(view.id).cursor();
+                match doc.text().char_to_line(cursor) {
+                    current if current == line => line + 1,
+                    current => current.abs_diff(line),
+                }
+            }
+        };
+        Some((format!("{:>5}", number), style))
+    }
+}
+
+struct Diff;
+
+impl Gutter for Diff {
+    fn width(&self) -> u16 {
+        1
+    }
+
+    fn render_line(
+        &self,
+        doc: &Document,
+        _view: &View,
+        theme: &Theme,
+        _config: &Config,
+        line: usize,
+        _selected: bool,
+    ) -> Option<(String, Style)> {
+        let (symbol, scope) = match doc.line_diff(line)? {
+            LineDiff::Added => ("▍", "diff.plus"),
+            LineDiff::Modified => ("▍", "diff.delta"),
+            LineDiff::Removed => ("▔", "diff.minus"),
+        };
+        Some((symbol.to_owned(), theme.get(scope)))
+    }
+}
+
+struct Spacer;
+
+impl Gutter for Spacer {
+    fn width(&self) -> u16 {
+        1
+    }
+
+    fn render_line(
+        &self,
+        _doc: &Document,
+        _view: &View,
+        _theme: &Theme,
+        _config: &Config,
+        _line: usize,
+        _selected: bool,
+    ) -> Option<(String, Style)> {
+        None
+    }
+}
+
+struct Folds;
+
+impl Gutter for Folds {
+    fn width(&self) -> u16 {
+        1
+    }
+
+    fn render_line(
+        &self,
+        doc: &Document,
+        view: &View,
+        theme: &Theme,
+        _config: &Config,
+        line: usize,
+        _selected: bool,
+    ) -> Option<(String, Style)> {
+        doc.folds(view.id)
+            .iter()
+            .find(|fold| *fold.start() == line)?;
+        Some(("▸".to_owned(), theme.get("ui.linenr")))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_width() {
+        // the default layout: 1 diagnostic + 5 linenr + 1 diff
+        assert_eq!(width(DEFAULT_GUTTERS), 7);
+        assert_eq!(width(&[GutterType::Spacer, GutterType::LineNumbers]), 6);
+        assert_eq!(width(&[]), 0);
+    }
+
+    #[test]
+    fn test_line_numbers() {
+        use crate::{theme::DEFAULT_THEME, DocumentId};
+        use helix_core::{Rope, Selection};
+
+        let mut doc = Document::from(Rope::from("a\nb\nc\nd\n"), None);
+        let view = View::new(DocumentId::default());
+        doc.set_selection(view.id, Selection::point(4));
+        let mut config = Config::default();
+        let numbers = |config: &Config| {
+            (0..4)
+                .map(|line| {
+                    let (number, _) = LineNumbers
+                        .render_line(&doc, &view, &DEFAULT_THEME, config, line, false)
+                        .unwrap();
+                    number.trim().to_owned()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(numbers(&config), ["1", "2", "3", "4"]);
+        // the line of the cursor keeps its number
+        config.line_number = LineNumber::Relative;
+        assert_eq!(numbers(&config), ["2", "1", "3", "1"]);
+    }
+}
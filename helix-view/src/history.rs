@@ -0,0 +1,51 @@
+//! Prompt history: what was entered in the `:` command, search and shell prompts is kept in a
+//! register for each, which are saved in the state directory across sessions.
+
+use std::path::PathBuf;
+
+use helix_core::register::Registers;
+
+/// The history registers and the names of their files.
+const HISTORY: [(char, &str); 3] = [(':', "command"), ('\\', "search"), ('|', "shell")];
+
+/// Number of entries kept in each history file.
+const MAX_ENTRIES: usize = 100;
+
+pub fn history_dir() -> PathBuf {
+    helix_core::state_dir().join("history")
+}
+
+/// Fills the history registers from the history files. Missing files are skipped.
+pub fn load(registers: &mut Registers) {
+    for (register, name) in HISTORY {
+        if let Ok(contents) = std::fs::read_to_string(history_dir().join(name)) {
+            let entries = contents.lines().map(String::from).collect();
+            registers.write(register, entries);
+        }
+    }
+}
+
+/// Writes the last entries of each history register to its file. Entries that span several
+/// lines are left out.
+pub fn save(registers: &Registers) -> std::io::Result<()> {
+    std::fs::create_dir_all(history_dir())?;
+
+    for (register, name) in HISTORY {
+        let entries: Vec<_> = registers
+            .read(register)
+            .unwrap_or_default()
+            .iter()
+            .filter(|entry| !entry.contains('\n'))
+            .collect();
+        let start = entries.len().saturating_sub(MAX_ENTRIES);
+
+        let mut contents = String::new();
+        for entry in &entries[start..] {
+            contents.push_str(entry);
+            contents.push('\n');
+        }
+        std::fs::write(history_dir().join(name), contents)?;
+    }
+
+    Ok(())
+}
@@ -221,7 +221,9 @@ impl<'de> Deserialize<'de> for KeyEvent {
 #[cfg(feature = "term")]
 impl From<crossterm::event::KeyEvent> for KeyEvent {
     fn from(
-        crossterm::event::KeyEvent { code, modifiers }: crossterm::event::KeyEvent,
+        crossterm::event::KeyEvent {
+            code, modifiers, ..
+        }: crossterm::event::KeyEvent,
     ) -> KeyEvent {
         KeyEvent {
             code: code.into(),
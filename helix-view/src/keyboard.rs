@@ -151,6 +151,8 @@ impl From<crossterm::event::KeyCode> for KeyCode {
             CKeyCode::Char(character) => KeyCode::Char(character),
             CKeyCode::Null => KeyCode::Null,
             CKeyCode::Esc => KeyCode::Esc,
+            // lock, media and modifier keys, only reported with keyboard enhancements
+            _ => KeyCode::Null,
         }
     }
 }
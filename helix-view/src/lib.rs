@@ -4,10 +4,15 @@ pub mod macros;
 pub mod clipboard;
 pub mod document;
 pub mod editor;
+pub mod events;
+pub mod git;
 pub mod graphics;
+pub mod gutter;
+pub mod history;
 pub mod info;
 pub mod input;
 pub mod keyboard;
+pub mod recovery;
 pub mod register_selection;
 pub mod theme;
 pub mod tree;
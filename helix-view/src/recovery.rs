@@ -0,0 +1,172 @@
+//! Crash recovery: the contents of modified documents are periodically written to the cache
+//! directory, so they can be restored if the editor doesn't exit cleanly.
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use helix_core::{diff::compare_ropes, Rope};
+
+use crate::{Document, Editor, ViewId};
+
+pub fn recovery_dir() -> PathBuf {
+    helix_core::cache_dir().join("recovery")
+}
+
+/// Path of the backup in `dir` of the document at `path`, the separators of which are replaced
+/// by `%`.
+fn backup_path(dir: &Path, path: &Path) -> PathBuf {
+    let name = path
+        .to_string_lossy()
+        .replace(std::path::MAIN_SEPARATOR, "%");
+    dir.join(name)
+}
+
+/// Writes the contents of all modified documents that have a path, and removes the backups of
+/// documents that were saved since.
+pub fn write_backups(editor: &Editor) -> std::io::Result<()> {
+    write_backups_in(&recovery_dir(), editor)
+}
+
+fn write_backups_in(dir: &Path, editor: &Editor) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+
+    for doc in editor.documents() {
+        let path = match doc.path() {
+            Some(path) => path,
+            None => continue,
+        };
+        let backup = backup_path(dir, path);
+
+        if doc.is_modified() {
+            let mut file = BufWriter::new(File::create(&backup)?);
+            doc.text().write_to(&mut file)?;
+        } else if backup.exists() {
+            std::fs::remove_file(&backup)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes the backups of all open documents, once the editor exits cleanly.
+pub fn remove_backups(editor: &Editor) {
+    remove_backups_in(&recovery_dir(), editor)
+}
+
+fn remove_backups_in(dir: &Path, editor: &Editor) {
+    for path in editor.documents().filter_map(Document::path) {
+        let backup = backup_path(dir, path);
+        if backup.exists() {
+            if let Err(err) = std::fs::remove_file(&backup) {
+                log::error!("failed to remove backup {:?}: {}", backup, err);
+            }
+        }
+    }
+}
+
+/// Returns the contents of `doc` that were backed up but never saved, if any.
+pub fn find_backup(doc: &Document) -> Option<Rope> {
+    find_backup_in(&recovery_dir(), doc)
+}
+
+fn find_backup_in(dir: &Path, doc: &Document) -> Option<Rope> {
+    let file = File::open(backup_path(dir, doc.path()?)).ok()?;
+    let rope = Rope::from_reader(BufReader::new(file)).ok()?;
+
+    if rope == *doc.text() {
+        None
+    } else {
+        Some(rope)
+    }
+}
+
+/// Replaces the contents of `doc` with its backup as a single change that can be undone.
+pub fn restore(doc: &mut Document, view_id: ViewId, backup: &Rope) {
+    let transaction = compare_ropes(doc.text(), backup);
+    doc.apply(&transaction, view_id);
+    doc.append_changes_to_history(view_id);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{editor::Action, graphics::Rect, theme, Editor};
+    use helix_core::{syntax, Selection, Transaction};
+    use std::sync::Arc;
+
+    fn editor() -> Editor {
+        let runtime = helix_core::runtime_dir();
+        Editor::new(
+            Rect::new(0, 0, 80, 24),
+            Arc::new(theme::Loader::new(&runtime, &runtime)),
+            Arc::new(syntax::Loader::new(syntax::Configuration {
+                language: Vec::new(),
+            })),
+            crate::editor::Config::default(),
+        )
+    }
+
+    fn insert(doc: &mut Document, view_id: ViewId, text: &str) {
+        let transaction = Transaction::insert(doc.text(), &Selection::point(0), text.into());
+        doc.apply(&transaction, view_id);
+        doc.append_changes_to_history(view_id);
+    }
+
+    #[test]
+    fn backup_round_trip() {
+        let dir = std::env::temp_dir().join(format!("helix-recovery-test-{}", std::process::id()));
+        let file = dir.join("file.txt");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&file, "saved\n").unwrap();
+        let backups = dir.join("recovery");
+
+        let mut editor = editor();
+        let id = editor.open(file, Action::VerticalSplit).unwrap();
+        let view_id = editor.tree.focus;
+        let backup = backup_path(&backups, editor.documents[id].path().unwrap());
+
+        // unmodified documents aren't backed up
+        write_backups_in(&backups, &editor).unwrap();
+        assert!(!backup.exists());
+        assert_eq!(find_backup_in(&backups, &editor.documents[id]), None);
+
+        let doc = editor.documents.get_mut(id).unwrap();
+        insert(doc, view_id, "unsaved ");
+        write_backups_in(&backups, &editor).unwrap();
+        assert!(backup.exists());
+
+        // the backup is found once the document is opened again as it was saved
+        let mut editor_after_crash = self::editor();
+        let restored = editor_after_crash
+            .open(dir.join("file.txt"), Action::VerticalSplit)
+            .unwrap();
+        let view_id = editor_after_crash.tree.focus;
+        let doc = editor_after_crash.documents.get_mut(restored).unwrap();
+        let contents = find_backup_in(&backups, doc).unwrap();
+        assert_eq!(contents, "unsaved saved\n");
+
+        restore(doc, view_id, &contents);
+        assert_eq!(doc.text(), &contents);
+        assert!(doc.is_modified());
+        // a backup identical to the document isn't offered
+        assert_eq!(find_backup_in(&backups, doc), None);
+        // the restore is a single change that can be undone
+        assert!(doc.undo(view_id));
+        assert_eq!(doc.text(), "saved\n");
+
+        // exiting cleanly removes the backup
+        remove_backups_in(&backups, &editor_after_crash);
+        assert!(!backup.exists());
+
+        // as does saving the document before the next backup
+        write_backups_in(&backups, &editor).unwrap();
+        assert!(backup.exists());
+        let doc = editor.documents.get_mut(id).unwrap();
+        doc.reset_modified();
+        write_backups_in(&backups, &editor).unwrap();
+        assert!(!backup.exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -21,6 +21,9 @@ pub struct Tree {
 pub struct Node {
     parent: ViewId,
     content: Content,
+    /// Share of the container taken by the node, relative to its siblings. Kept across terminal
+    /// resizes.
+    weight: f32,
 }
 
 #[derive(Debug)]
@@ -34,6 +37,7 @@ impl Node {
         Self {
             parent: ViewId::default(),
             content: Content::Container(Box::new(Container::new(layout))),
+            weight: 1.0,
         }
     }
 
@@ -41,19 +45,34 @@ impl Node {
         Self {
             parent: ViewId::default(),
             content: Content::View(Box::new(view)),
+            weight: 1.0,
         }
     }
 }
 
 // TODO: screen coord to container + container coordinate helpers
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Layout {
     Horizontal,
     Vertical,
     // could explore stacked/tabbed
 }
 
+/// Smallest height, with the statusline, a split is resized to.
+const MIN_SPLIT_HEIGHT: f32 = 2.0;
+/// Smallest width a split is resized to.
+const MIN_SPLIT_WIDTH: f32 = 10.0;
+
+/// Where a split is relative to another, on screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
 #[derive(Debug)]
 pub struct Container {
     layout: Layout,
@@ -166,8 +185,10 @@ impl Tree {
             container.children.insert(pos, node);
             self.nodes[node].parent = parent;
         } else {
+            // the new container takes the place of the focused view, and its share
             let mut split = Node::container(layout);
             split.parent = parent;
+            split.weight = std::mem::replace(&mut self.nodes[focus].weight, 1.0);
             let split = self.nodes.insert(split);
 
             let container = match &mut self.nodes[split] {
@@ -318,6 +339,17 @@ impl Tree {
         // b) node is container, calculate areas for each child and push them on the stack
 
         while let Some((key, area)) = self.stack.pop() {
+            // the shares of the children, if the node is a container
+            let weights: Vec<f32> = match &self.nodes[key].content {
+                Content::Container(container) => container
+                    .children
+                    .iter()
+                    .map(|child| self.nodes[*child].weight)
+                    .collect(),
+                Content::View(_) => Vec::new(),
+            };
+            let total: f32 = weights.iter().sum();
+
             let node = &mut self.nodes[key];
 
             match &mut node.content {
@@ -333,11 +365,10 @@ impl Tree {
                         Layout::Horizontal => {
                             let len = container.children.len();
 
-                            let height = area.height / len as u16;
-
                             let mut child_y = area.y;
 
                             for (i, child) in container.children.iter().enumerate() {
+                                let height = (area.height as f32 * weights[i] / total) as u16;
                                 let mut area = Rect::new(
                                     container.area.x,
                                     child_y,
@@ -349,7 +380,8 @@ impl Tree {
                                 // last child takes the remaining width because we can get uneven
                                 // space from rounding
                                 if i == len - 1 {
-                                    area.height = container.area.y + container.area.height - area.y;
+                                    area.height = (container.area.y + container.area.height)
+                                        .saturating_sub(area.y);
                                 }
 
                                 self.stack.push((*child, area));
@@ -358,18 +390,21 @@ impl Tree {
                         Layout::Vertical => {
                             let len = container.children.len();
 
-                            let width = area.width / len as u16;
-
                             let inner_gap = 1u16;
-                            // let total_gap = inner_gap * (len as u16 - 1);
+                            let total_gap = inner_gap * (len as u16 - 1);
+                            let available = area.width.saturating_sub(total_gap);
 
                             let mut child_x = area.x;
 
                             for (i, child) in container.children.iter().enumerate() {
+                                let width = (available as f32 * weights[i] / total) as u16;
+                                // children are squeezed into the last column when there isn't
+                                // enough room for the gaps
+                                let right = container.area.x + container.area.width;
                                 let mut area = Rect::new(
-                                    child_x,
+                                    child_x.min(right),
                                     container.area.y,
-                                    width,
+                                    width.min(right.saturating_sub(child_x)),
                                     container.area.height,
                                 );
                                 child_x += width + inner_gap;
@@ -377,7 +412,7 @@ impl Tree {
                                 // last child takes the remaining width because we can get uneven
                                 // space from rounding
                                 if i == len - 1 {
-                                    area.width = container.area.x + container.area.width - area.x;
+                                    area.width = right - area.x;
                                 }
 
                                 self.stack.push((*child, area));
@@ -434,6 +469,206 @@ impl Tree {
         }
     }
 
+    /// The view bordering `id` on the side `direction`. Of several, the one beside the top left
+    /// corner of `id` is preferred, then the closest to it.
+    pub fn find_split_in_direction(&self, id: ViewId, direction: Direction) -> Option<ViewId> {
+        let current = self.get(id).area;
+        let overlaps = |a: (u16, u16), b: (u16, u16)| a.0 < b.1 && b.0 < a.1;
+        self.views()
+            .map(|(view, _)| (view.id, view.area))
+            .filter(|(other, area)| {
+                *other != id
+                    && match direction {
+                        Direction::Left | Direction::Right => {
+                            overlaps((area.y, area.bottom()), (current.y, current.bottom()))
+                        }
+                        Direction::Up | Direction::Down => {
+                            overlaps((area.x, area.right()), (current.x, current.right()))
+                        }
+                    }
+            })
+            .filter_map(|(other, area)| {
+                let distance = match direction {
+                    Direction::Left => current.x.checked_sub(area.right())?,
+                    Direction::Right => area.x.checked_sub(current.right())?,
+                    Direction::Up => current.y.checked_sub(area.bottom())?,
+                    Direction::Down => area.y.checked_sub(current.bottom())?,
+                };
+                let (start, end, corner) = match direction {
+                    Direction::Left | Direction::Right => (area.y, area.bottom(), current.y),
+                    Direction::Up | Direction::Down => (area.x, area.right(), current.x),
+                };
+                let offset = if (start..end).contains(&corner) {
+                    0
+                } else {
+                    start.abs_diff(corner)
+                };
+                Some((other, (distance, offset)))
+            })
+            .min_by_key(|(_, key)| *key)
+            .map(|(other, _)| other)
+    }
+
+    /// Focuses the view next to the focused one in `direction`, if there is one.
+    pub fn focus_direction(&mut self, direction: Direction) {
+        if let Some(id) = self.find_split_in_direction(self.focus, direction) {
+            self.focus = id;
+        }
+    }
+
+    /// Swaps the places of the focused view and the one next to it in `direction`, keeping the
+    /// focus on the same view.
+    pub fn swap_split_in_direction(&mut self, direction: Direction) {
+        let focus = self.focus;
+        let other = match self.find_split_in_direction(focus, direction) {
+            Some(other) => other,
+            None => return,
+        };
+        let (focus_parent, other_parent) = (self.nodes[focus].parent, self.nodes[other].parent);
+        let position = |container: &Container, id: ViewId| {
+            container
+                .children
+                .iter()
+                .position(|&child| child == id)
+                .unwrap()
+        };
+        if focus_parent == other_parent {
+            let container = self.container_mut(focus_parent);
+            let (i, j) = (position(container, focus), position(container, other));
+            container.children.swap(i, j);
+        } else {
+            let container = self.container_mut(focus_parent);
+            let i = position(container, focus);
+            container.children[i] = other;
+            let container = self.container_mut(other_parent);
+            let j = position(container, other);
+            container.children[j] = focus;
+        }
+        self.nodes[focus].parent = other_parent;
+        self.nodes[other].parent = focus_parent;
+        self.recalculate();
+    }
+
+    /// Rotates the splits of the container of the focused view forward: each takes the place of
+    /// the next one and the last one becomes the first.
+    pub fn rotate_splits(&mut self) {
+        let parent = self.nodes[self.focus].parent;
+        self.container_mut(parent).children.rotate_right(1);
+        self.recalculate();
+    }
+
+    /// Makes the split of `id` bigger by `cells`, or smaller when negative, within the closest
+    /// container laid out as `layout` around it: widths change for [`Layout::Vertical`], heights
+    /// for [`Layout::Horizontal`]. The space comes from the next split, or the previous one for the
+    /// last.
+    pub fn grow_split(&mut self, id: ViewId, layout: Layout, cells: i32) {
+        if let Some((container, index)) = self.split_around(id, &layout, false) {
+            let len = self.container(container).children.len();
+            let other = if index + 1 < len {
+                index + 1
+            } else {
+                index - 1
+            };
+            self.transfer_size(container, other, index, cells);
+        }
+    }
+
+    /// Moves the border after the split of `id`, right of it for [`Layout::Vertical`] or below it
+    /// for [`Layout::Horizontal`], by `cells` towards the end.
+    pub fn move_split_border(&mut self, id: ViewId, layout: Layout, cells: i32) {
+        if let Some((container, index)) = self.split_around(id, &layout, true) {
+            self.transfer_size(container, index + 1, index, cells);
+        }
+    }
+
+    /// Gives every split the same share of its container again.
+    pub fn equalize_splits(&mut self) {
+        for (_, node) in self.nodes.iter_mut() {
+            node.weight = 1.0;
+        }
+        self.recalculate();
+    }
+
+    /// The closest container laid out as `layout` around `id` with other splits, and the index of
+    /// the child containing `id`. With `before_last`, that child must have a next sibling.
+    fn split_around(
+        &self,
+        id: ViewId,
+        layout: &Layout,
+        before_last: bool,
+    ) -> Option<(ViewId, usize)> {
+        let mut child = id;
+        loop {
+            let parent = self.nodes[child].parent;
+            let container = self.container(parent);
+            let index = container.children.iter().position(|&c| c == child)?;
+            let len = container.children.len();
+            if container.layout == *layout && len > 1 && (!before_last || index + 1 < len) {
+                return Some((parent, index));
+            }
+            if parent == self.root {
+                return None;
+            }
+            child = parent;
+        }
+    }
+
+    /// Moves `cells` of the container `id` from its child `from` to its child `to`, as far as both
+    /// keep a usable size.
+    fn transfer_size(&mut self, id: ViewId, from: usize, to: usize, cells: i32) {
+        let container = self.container(id);
+        let (extent, min) = match container.layout {
+            Layout::Horizontal => (container.area.height, MIN_SPLIT_HEIGHT),
+            // without the gaps between the splits
+            Layout::Vertical => (
+                container
+                    .area
+                    .width
+                    .saturating_sub(container.children.len() as u16 - 1),
+                MIN_SPLIT_WIDTH,
+            ),
+        };
+        let (from, to) = (container.children[from], container.children[to]);
+        let total: f32 = container
+            .children
+            .iter()
+            .map(|child| self.nodes[*child].weight)
+            .sum();
+        // weight of a cell
+        let cell = total / extent.max(1) as f32;
+        let (from_cells, to_cells) = (self.nodes[from].weight / cell, self.nodes[to].weight / cell);
+
+        // neither gets smaller than the minimum, nor bigger when already below it
+        let cells = if cells > 0 {
+            (cells as f32).min(from_cells - min).max(0.0)
+        } else {
+            (cells as f32).max(min - to_cells).min(0.0)
+        };
+        self.nodes[from].weight -= cells * cell;
+        self.nodes[to].weight += cells * cell;
+        self.recalculate();
+    }
+
+    fn container(&self, id: ViewId) -> &Container {
+        match &self.nodes[id] {
+            Node {
+                content: Content::Container(container),
+                ..
+            } => container,
+            _ => unreachable!(),
+        }
+    }
+
+    fn container_mut(&mut self, id: ViewId) -> &mut Container {
+        match &mut self.nodes[id] {
+            Node {
+                content: Content::Container(container),
+                ..
+            } => container,
+            _ => unreachable!(),
+        }
+    }
+
     pub fn area(&self) -> Rect {
         self.area
     }
@@ -472,3 +707,123 @@ impl<'a> Iterator for Traverse<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::DocumentId;
+
+    #[test]
+    fn find_split_in_direction() {
+        let mut tree = Tree::new(Rect::new(0, 0, 180, 80));
+        let left = tree.insert(View::new(DocumentId::default()));
+        let right = tree.split(View::new(DocumentId::default()), Layout::Vertical);
+        let bottom_right = tree.split(View::new(DocumentId::default()), Layout::Horizontal);
+        // | left | right        |
+        // |      | bottom_right |
+
+        assert_eq!(
+            tree.find_split_in_direction(left, Direction::Right),
+            Some(right)
+        );
+        assert_eq!(tree.find_split_in_direction(left, Direction::Up), None);
+        assert_eq!(
+            tree.find_split_in_direction(bottom_right, Direction::Up),
+            Some(right)
+        );
+        assert_eq!(
+            tree.find_split_in_direction(bottom_right, Direction::Left),
+            Some(left)
+        );
+        assert_eq!(
+            tree.find_split_in_direction(right, Direction::Down),
+            Some(bottom_right)
+        );
+
+        tree.focus_direction(Direction::Left);
+        assert_eq!(tree.focus, left);
+
+        // swapping keeps the focus on the moved view
+        tree.swap_split_in_direction(Direction::Right);
+        assert_eq!(tree.focus, left);
+        assert_eq!(
+            tree.find_split_in_direction(left, Direction::Down),
+            Some(bottom_right)
+        );
+        assert_eq!(
+            tree.find_split_in_direction(right, Direction::Right),
+            Some(left)
+        );
+    }
+
+    #[test]
+    fn resize_splits() {
+        let mut tree = Tree::new(Rect::new(0, 0, 181, 80));
+        let left = tree.insert(View::new(DocumentId::default()));
+        let right = tree.split(View::new(DocumentId::default()), Layout::Vertical);
+        assert_eq!(tree.get(left).area.width, 90);
+
+        tree.grow_split(left, Layout::Vertical, 10);
+        assert_eq!(tree.get(left).area.width, 100);
+        // the last split takes from the previous one
+        tree.grow_split(right, Layout::Vertical, 20);
+        assert_eq!(tree.get(left).area.width, 80);
+        // there's no horizontal split to resize
+        tree.grow_split(right, Layout::Horizontal, 5);
+        assert_eq!(tree.get(right).area.height, 80);
+
+        // the shares are kept when the terminal is resized
+        tree.resize(Rect::new(0, 0, 91, 40));
+        assert_eq!(tree.get(left).area.width, 40);
+
+        // splits don't get smaller than the minimum
+        tree.move_split_border(left, Layout::Vertical, 100);
+        assert_eq!(tree.get(right).area.width, MIN_SPLIT_WIDTH as u16);
+        // the border after the last split is the one of the parent, if any
+        tree.move_split_border(right, Layout::Vertical, -10);
+        assert_eq!(tree.get(right).area.width, MIN_SPLIT_WIDTH as u16);
+
+        tree.equalize_splits();
+        assert_eq!(tree.get(left).area.width, 45);
+    }
+
+    #[test]
+    fn swap_splits_left_and_up() {
+        let mut tree = Tree::new(Rect::new(0, 0, 180, 80));
+        let left = tree.insert(View::new(DocumentId::default()));
+        let right = tree.split(View::new(DocumentId::default()), Layout::Vertical);
+
+        tree.swap_split_in_direction(Direction::Left);
+        assert_eq!(tree.focus, right);
+        let order: Vec<_> = tree.traverse().map(|(id, _)| id).collect();
+        assert_eq!(order, vec![right, left]);
+        assert_eq!(
+            tree.find_split_in_direction(right, Direction::Right),
+            Some(left)
+        );
+
+        let mut tree = Tree::new(Rect::new(0, 0, 180, 80));
+        let top = tree.insert(View::new(DocumentId::default()));
+        let bottom = tree.split(View::new(DocumentId::default()), Layout::Horizontal);
+
+        tree.swap_split_in_direction(Direction::Up);
+        assert_eq!(tree.focus, bottom);
+        assert_eq!(tree.get(bottom).area.y, 0);
+        assert_eq!(
+            tree.find_split_in_direction(bottom, Direction::Down),
+            Some(top)
+        );
+    }
+
+    #[test]
+    fn rotate_splits() {
+        let mut tree = Tree::new(Rect::new(0, 0, 180, 80));
+        let first = tree.insert(View::new(DocumentId::default()));
+        let second = tree.split(View::new(DocumentId::default()), Layout::Vertical);
+        let third = tree.split(View::new(DocumentId::default()), Layout::Vertical);
+
+        tree.rotate_splits();
+        let order: Vec<_> = tree.traverse().map(|(id, _)| id).collect();
+        assert_eq!(order, vec![third, first, second]);
+    }
+}
@@ -1,9 +1,13 @@
-use std::borrow::Cow;
+use crate::{
+    graphics::Rect,
+    gutter::{self, GutterType},
+    Document, DocumentId, ViewId,
+};
+use std::collections::VecDeque;
 
-use crate::{graphics::Rect, Document, DocumentId, ViewId};
 use helix_core::{
-    coords_at_pos,
-    graphemes::{grapheme_width, RopeGraphemes},
+    fold, visual_coords_at_pos,
+    wrap::{self, SoftWrap},
     Position, RopeSlice, Selection,
 };
 
@@ -58,6 +62,81 @@ impl JumpList {
     }
 }
 
+/// How many past selections each view remembers.
+const SELECTION_HISTORY_LEN: usize = 100;
+
+/// A selection of a document, at a version of its text.
+type SelectionState = (DocumentId, i32, Selection);
+
+/// The selections of a view before the commands that changed them, so a misfired command doesn't
+/// lose a carefully built set of selections. Selections from before an edit of the text are
+/// forgotten, the undo history restores those.
+#[derive(Debug, Default)]
+pub struct SelectionHistory {
+    /// The selection when last recorded.
+    last: Option<SelectionState>,
+    undo: VecDeque<SelectionState>,
+    redo: Vec<SelectionState>,
+}
+
+impl SelectionHistory {
+    /// Notes the `current` selection, remembering the previous one if only the selection changed
+    /// since the last call.
+    pub fn record(&mut self, current: SelectionState) {
+        let (doc, version, _) = current;
+        let last = match self.last.replace(current) {
+            Some(last) => last,
+            None => return,
+        };
+        if last.0 != doc || last.1 != version || Some(&last) == self.last.as_ref() {
+            return;
+        }
+        self.redo.clear();
+        if self.undo.len() == SELECTION_HISTORY_LEN {
+            self.undo.pop_front();
+        }
+        self.undo.push_back(last);
+    }
+
+    /// The latest selection before `current` of the same document and text. The ones skipped,
+    /// of other documents or older texts, are dropped.
+    pub fn undo(&mut self, current: SelectionState) -> Option<Selection> {
+        while let Some((doc, version, selection)) = self.undo.pop_back() {
+            if doc == current.0 && version == current.1 {
+                self.redo.push(current);
+                // restoring isn't a change to remember
+                self.last = Some((doc, version, selection.clone()));
+                return Some(selection);
+            }
+        }
+        None
+    }
+
+    /// The selection undone last, back from `current`.
+    pub fn redo(&mut self, current: SelectionState) -> Option<Selection> {
+        while let Some((doc, version, selection)) = self.redo.pop() {
+            if doc == current.0 && version == current.1 {
+                self.undo.push_back(current);
+                self.last = Some((doc, version, selection.clone()));
+                return Some(selection);
+            }
+        }
+        None
+    }
+}
+
+/// Other occurrences of the symbol under the cursor, shown until the cursor moves or the text
+/// changes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolHighlights {
+    /// The document, its version and the cursor the occurrences were found for.
+    pub doc: DocumentId,
+    pub version: i32,
+    pub cursor: usize,
+    /// Char ranges of the occurrences.
+    pub ranges: Vec<std::ops::Range<usize>>,
+}
+
 #[derive(Debug)]
 pub struct View {
     pub id: ViewId,
@@ -68,6 +147,15 @@ pub struct View {
     pub jumps: JumpList,
     /// the last accessed file before the current one
     pub last_accessed_doc: Option<DocumentId>,
+    pub soft_wrap: SoftWrap,
+    /// The columns left of the text, in order.
+    pub gutters: Vec<GutterType>,
+    pub selection_history: SelectionHistory,
+    /// Selections before each `expand_selection`, restored in turn by `shrink_selection`.
+    pub object_selections: Vec<Selection>,
+    /// Char ranges highlighted as matches, such as the pending replacements of `:replace`.
+    pub match_highlights: Vec<std::ops::Range<usize>>,
+    pub symbol_highlights: Option<SymbolHighlights>,
 }
 
 impl View {
@@ -80,12 +168,63 @@ impl View {
             area: Rect::default(), // will get calculated upon inserting into tree
             jumps: JumpList::new((doc, Selection::point(0))), // TODO: use actual sel
             last_accessed_doc: None,
+            soft_wrap: SoftWrap::None,
+            gutters: gutter::DEFAULT_GUTTERS.to_vec(),
+            selection_history: SelectionHistory::default(),
+            object_selections: Vec::new(),
+            match_highlights: Vec::new(),
+            symbol_highlights: None,
+        }
+    }
+
+    /// The occurrences of the symbol under the cursor, unless they are out of date.
+    pub fn symbol_highlights(&self, doc: &Document) -> Option<&SymbolHighlights> {
+        self.symbol_highlights.as_ref().filter(|highlights| {
+            highlights.doc == doc.id()
+                && highlights.version == doc.version()
+                && highlights.cursor == doc.selection(self.id).cursor()
+        })
+    }
+
+    /// Width of the gutter, left of the text.
+    pub fn gutter_offset(&self) -> u16 {
+        gutter::width(&self.gutters)
+    }
+
+    /// Width of the text area, excluding the gutter.
+    pub fn inner_width(&self) -> usize {
+        self.area.width.saturating_sub(self.gutter_offset()) as usize
+    }
+
+    /// Char offsets (relative to the line start) at which the rows of a soft wrapped line start.
+    pub fn line_breaks(&self, doc: &Document, line: usize) -> Vec<usize> {
+        wrap::line_breaks(
+            doc.text().line(line),
+            self.inner_width(),
+            doc.tab_width(),
+            self.soft_wrap,
+        )
+    }
+
+    /// Number of screen rows `line` takes up, none if it's folded.
+    pub fn line_height(&self, doc: &Document, line: usize) -> usize {
+        if fold::is_hidden(doc.folds(self.id), line) {
+            return 0;
         }
+        match self.soft_wrap {
+            SoftWrap::None => 1,
+            _ => self.line_breaks(doc, line).len(),
+        }
+    }
+
+    /// Whether lines may take up other than one row, so rows have to be counted line by line.
+    fn has_varying_rows(&self, doc: &Document) -> bool {
+        self.soft_wrap != SoftWrap::None || !doc.folds(self.id).is_empty()
     }
 
     pub fn ensure_cursor_in_view(&mut self, doc: &Document) {
         let cursor = doc.selection(self.id).cursor();
-        let pos = coords_at_pos(doc.text().slice(..), cursor);
+        let pos = visual_coords_at_pos(doc.text().slice(..), cursor, doc.tab_width());
         let line = pos.row;
         let col = pos.col;
         let height = self.area.height.saturating_sub(1); // - 1 for statusline
@@ -93,11 +232,17 @@ impl View {
 
         let scrolloff = PADDING.min(self.area.height as usize / 2); // TODO: user pref
 
-        // TODO: not ideal
-        const OFFSET: usize = 7; // 1 diagnostic + 5 linenr + 1 gutter
-        let last_col = self.first_col + (self.area.width as usize - OFFSET);
+        if self.soft_wrap != SoftWrap::None {
+            self.first_col = 0;
+            self.ensure_cursor_in_wrapped_view(doc, line, scrolloff);
+            return;
+        }
+
+        let last_col = self.first_col + self.inner_width();
 
-        if line > last_line.saturating_sub(scrolloff) {
+        if self.has_varying_rows(doc) {
+            self.ensure_cursor_in_wrapped_view(doc, line, scrolloff);
+        } else if line > last_line.saturating_sub(scrolloff) {
             // scroll down
             self.first_line += line - (last_line.saturating_sub(scrolloff));
         } else if line < self.first_line + scrolloff {
@@ -114,14 +259,61 @@ impl View {
         }
     }
 
+    /// Scrolls vertically so that the cursor row of a soft wrapped or folded view is at least
+    /// `scrolloff` rows away from the edges.
+    fn ensure_cursor_in_wrapped_view(&mut self, doc: &Document, line: usize, scrolloff: usize) {
+        let height = self.area.height.saturating_sub(1) as usize; // - 1 for statusline
+
+        if line < self.first_line + scrolloff {
+            // scroll up
+            self.first_line = line.saturating_sub(scrolloff);
+            return;
+        }
+
+        let text = doc.text().slice(..);
+        let cursor = doc.selection(self.id).cursor();
+        let line_start = text.line_to_char(line);
+        let breaks = self.line_breaks(doc, line);
+        let cursor_row = wrap::visual_coords_at_pos(
+            text.line(line),
+            &breaks,
+            cursor - line_start,
+            doc.tab_width(),
+        )
+        .row;
+
+        // rows between the top of the view and the cursor, inclusive
+        let mut rows: usize = (self.first_line..line)
+            .map(|line| self.line_height(doc, line))
+            .sum::<usize>()
+            + cursor_row
+            + 1;
+        let max_rows = height.saturating_sub(scrolloff).max(1);
+
+        // scroll down
+        while rows > max_rows && self.first_line < line {
+            rows -= self.line_height(doc, self.first_line);
+            self.first_line += 1;
+        }
+    }
+
     /// Calculates the last visible line on screen
     #[inline]
     pub fn last_line(&self, doc: &Document) -> usize {
-        let height = self.area.height.saturating_sub(1); // - 1 for statusline
-        std::cmp::min(
-            self.first_line + height as usize,
-            doc.text().len_lines() - 1,
-        )
+        let height = self.area.height.saturating_sub(1) as usize; // - 1 for statusline
+        let last_doc_line = doc.text().len_lines() - 1;
+
+        if !self.has_varying_rows(doc) {
+            return std::cmp::min(self.first_line + height, last_doc_line);
+        }
+
+        let mut rows = 0;
+        let mut line = self.first_line;
+        while line < last_doc_line && rows < height {
+            rows += self.line_height(doc, line);
+            line += 1;
+        }
+        line
     }
 
     /// Translates a document position to an absolute position in the terminal.
@@ -135,25 +327,37 @@ impl View {
     ) -> Option<Position> {
         let line = text.char_to_line(pos);
 
-        if line < self.first_line || line > self.last_line(doc) {
+        if line < self.first_line
+            || line > self.last_line(doc)
+            || fold::is_hidden(doc.folds(self.id), line)
+        {
             // Line is not visible on screen
             return None;
         }
 
-        let line_start = text.line_to_char(line);
-        let line_slice = text.slice(line_start..pos);
-        let mut col = 0;
-        let tab_width = doc.tab_width();
-
-        for grapheme in RopeGraphemes::new(line_slice) {
-            if grapheme == "\t" {
-                col += tab_width;
-            } else {
-                let grapheme = Cow::from(grapheme);
-                col += grapheme_width(&grapheme);
+        if self.has_varying_rows(doc) {
+            let line_start = text.line_to_char(line);
+            let breaks = self.line_breaks(doc, line);
+            let pos = wrap::visual_coords_at_pos(
+                text.line(line),
+                &breaks,
+                pos - line_start,
+                doc.tab_width(),
+            );
+            let row = (self.first_line..line)
+                .map(|line| self.line_height(doc, line))
+                .sum::<usize>()
+                + pos.row;
+
+            if row >= self.area.height.saturating_sub(1) as usize {
+                return None;
             }
+            // only views that aren't soft wrapped scroll horizontally
+            return Some(Position::new(row, pos.col.saturating_sub(self.first_col)));
         }
 
+        let col = visual_coords_at_pos(text, pos, doc.tab_width()).col;
+
         // It is possible for underflow to occur if the buffer length is larger than the terminal width.
         let row = line.saturating_sub(self.first_line);
         let col = col.saturating_sub(self.first_col);
@@ -161,6 +365,44 @@ impl View {
         Some(Position::new(row, col))
     }
 
+    /// Translates an absolute position in the terminal to a document position. Returns `None`
+    /// if the position is outside of the view's text area.
+    pub fn pos_at_screen_coords(&self, doc: &Document, row: u16, column: u16) -> Option<usize> {
+        let height = self.area.height.saturating_sub(1); // - 1 for statusline
+        if row < self.area.y
+            || row >= self.area.y + height
+            || column < self.area.x + self.gutter_offset()
+            || column >= self.area.right()
+        {
+            return None;
+        }
+
+        let text = doc.text().slice(..);
+        let last_doc_line = text.len_lines() - 1;
+        let mut row = (row - self.area.y) as usize;
+        let col = (column - self.area.x - self.gutter_offset()) as usize + self.first_col;
+
+        // find the line that is rendered on the given row
+        let mut line = self.first_line;
+        loop {
+            let line_height = self.line_height(doc, line);
+            if row < line_height || line >= last_doc_line {
+                break;
+            }
+            row -= line_height;
+            line += 1;
+        }
+
+        let breaks = self.line_breaks(doc, line);
+        let pos = wrap::pos_at_visual_coords(
+            text.line(line),
+            &breaks,
+            Position::new(row, col),
+            doc.tab_width(),
+        );
+        Some(text.line_to_char(line) + pos)
+    }
+
     // pub fn traverse<F>(&self, text: RopeSlice, start: usize, end: usize, fun: F)
     // where
     //     F: Fn(usize, usize),
@@ -182,3 +424,51 @@ impl View {
     //     }
     // }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_selection_history() {
+        let doc = DocumentId::default();
+        let mut history = SelectionHistory::default();
+        for pos in 1..=3 {
+            history.record((doc, 0, Selection::point(pos)));
+        }
+
+        assert_eq!(
+            history.undo((doc, 0, Selection::point(3))),
+            Some(Selection::point(2))
+        );
+        history.record((doc, 0, Selection::point(2)));
+        assert_eq!(
+            history.redo((doc, 0, Selection::point(2))),
+            Some(Selection::point(3))
+        );
+
+        // selections of older texts don't apply anymore
+        history.record((doc, 1, Selection::point(4)));
+        assert_eq!(history.undo((doc, 1, Selection::point(4))), None);
+    }
+
+    #[test]
+    fn test_folded_lines() {
+        let mut doc = Document::from(helix_core::Rope::from("a\nb\nc\nd\n"), None);
+        let mut view = View::new(doc.id());
+        view.area = Rect::new(0, 0, 40, 10);
+        doc.set_selection(view.id, Selection::point(0));
+        doc.set_folds(view.id, vec![0..=2]);
+        let text = doc.text().clone();
+        let text = text.slice(..);
+
+        assert_eq!(view.line_height(&doc, 1), 0);
+        assert_eq!(view.screen_coords_at_pos(&doc, text, 2), None);
+        assert_eq!(
+            view.screen_coords_at_pos(&doc, text, 6),
+            Some(Position::new(1, 0))
+        );
+        let gutter = view.gutter_offset();
+        assert_eq!(view.pos_at_screen_coords(&doc, 1, gutter), Some(6));
+    }
+}
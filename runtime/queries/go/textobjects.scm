@@ -0,0 +1,9 @@
+(function_declaration
+  body: (block)? @function.inside) @function.around
+
+(method_declaration
+  body: (block)? @function.inside) @function.around
+
+(type_declaration
+  (type_spec
+    type: (_) @class.inside)) @class.around